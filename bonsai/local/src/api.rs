@@ -0,0 +1,466 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routes and handlers for the Bonsai REST API.
+//!
+//! The hosted service hands out presigned URLs for uploads and downloads. Here those URLs point
+//! back at the `/objects` routes of this server, which read and write the [ObjectStore].
+//!
+//! [ObjectStore]: crate::store::ObjectStore
+
+use std::sync::{atomic::Ordering, mpsc::Sender, Arc};
+
+use anyhow::anyhow;
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Request, State},
+    http::{header::HOST, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use bonsai_sdk::{
+    responses::{
+        CreateSessRes, ImgUploadRes, ProofReq, Quotas, ReceiptDownload, SessionStats,
+        SessionStatusRes, SnarkReceipt, SnarkReq, SnarkStatusRes, UploadRes, VersionInfo,
+    },
+    API_KEY_HEADER,
+};
+use risc0_zkvm::compute_image_id;
+use uuid::Uuid;
+
+use crate::{
+    store::Bucket,
+    worker::{Job, SessionRecord, SessionRequest, SnarkRecord, Status},
+    ServerState,
+};
+
+#[derive(Clone)]
+struct ApiState {
+    state: Arc<ServerState>,
+    jobs: Sender<Job>,
+}
+
+impl ApiState {
+    fn submit(&self, job: Job) -> ApiResult<()> {
+        self.jobs
+            .send(job)
+            .map_err(|_| anyhow!("job queue is closed"))?;
+        Ok(())
+    }
+}
+
+pub(crate) fn router(state: Arc<ServerState>, jobs: Sender<Job>) -> Router {
+    let state = ApiState { state, jobs };
+    Router::new()
+        .route("/images/upload/:image_id", get(image_upload))
+        .route("/images/:image_id", delete(image_delete))
+        .route("/inputs/upload", get(input_upload))
+        .route("/inputs/:input_id", delete(input_delete))
+        .route("/receipts/upload", get(receipt_upload))
+        .route("/receipts/:session_id", get(receipt_download))
+        .route("/objects/:bucket/:key", get(object_get).put(object_put))
+        .route("/sessions/create", post(session_create))
+        .route("/sessions/status/:session_id", get(session_status))
+        .route("/sessions/logs/:session_id", get(session_logs))
+        .route("/sessions/stop/:session_id", get(session_stop))
+        .route("/snark/create", post(snark_create))
+        .route("/snark/status/:snark_id", get(snark_status))
+        .route("/version", get(version))
+        .route("/user/quotas", get(quotas))
+        .layer(middleware::from_fn_with_state(state.clone(), check_api_key))
+        // Images and inputs are routinely larger than the default body limit.
+        .layer(DefaultBodyLimit::disable())
+        .with_state(state)
+}
+
+/// An error response, with the message as the body.
+///
+/// The SDK reports the body of any unsuccessful response as an
+/// [InternalServerErr](bonsai_sdk::SdkErr::InternalServerErr).
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("{err:#}"),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, self.message).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Run blocking file I/O or hashing off the async runtime.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> ApiResult<T> + Send + 'static,
+) -> ApiResult<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(anyhow::Error::from)?
+}
+
+async fn check_api_key(State(api): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(api_key) = &api.state.config.api_key {
+        let provided = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if provided != Some(api_key.as_str()) {
+            return (StatusCode::UNAUTHORIZED, "invalid API key").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// URL at which the given object can be uploaded or downloaded, as seen by the client.
+fn object_url(headers: &HeaderMap, bucket: Bucket, key: &str) -> ApiResult<String> {
+    let host = headers
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::bad_request("missing host header"))?;
+    Ok(format!("http://{host}/objects/{}/{key}", bucket.as_str()))
+}
+
+fn parse_bucket(name: &str) -> ApiResult<Bucket> {
+    Bucket::from_name(name).ok_or_else(|| ApiError::not_found(format!("no bucket {name}")))
+}
+
+// - /images
+
+async fn image_upload(
+    State(api): State<ApiState>,
+    Path(image_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if api.state.store.exists(Bucket::Images, &image_id) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let url = object_url(&headers, Bucket::Images, &image_id)?;
+    Ok(Json(ImgUploadRes { url }).into_response())
+}
+
+async fn image_delete(State(api): State<ApiState>, Path(image_id): Path<String>) -> ApiResult<()> {
+    delete_object(api, Bucket::Images, image_id).await
+}
+
+// - /inputs and /receipts
+
+fn new_upload(headers: &HeaderMap, bucket: Bucket) -> ApiResult<Json<UploadRes>> {
+    let uuid = Uuid::new_v4().to_string();
+    let url = object_url(headers, bucket, &uuid)?;
+    Ok(Json(UploadRes { url, uuid }))
+}
+
+async fn input_upload(headers: HeaderMap) -> ApiResult<Json<UploadRes>> {
+    new_upload(&headers, Bucket::Inputs)
+}
+
+async fn input_delete(State(api): State<ApiState>, Path(input_id): Path<String>) -> ApiResult<()> {
+    delete_object(api, Bucket::Inputs, input_id).await
+}
+
+async fn receipt_upload(headers: HeaderMap) -> ApiResult<Json<UploadRes>> {
+    new_upload(&headers, Bucket::Receipts)
+}
+
+async fn receipt_download(
+    State(api): State<ApiState>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Json<ReceiptDownload>> {
+    if !api.state.store.exists(Bucket::Receipts, &session_id) {
+        return Err(ApiError::not_found(format!(
+            "no receipt for session {session_id}"
+        )));
+    }
+    let url = object_url(&headers, Bucket::Receipts, &session_id)?;
+    Ok(Json(ReceiptDownload { url }))
+}
+
+// - /objects, the targets of the upload and download URLs
+
+async fn object_get(
+    State(api): State<ApiState>,
+    Path((bucket, key)): Path<(String, String)>,
+) -> ApiResult<Vec<u8>> {
+    let bucket = parse_bucket(&bucket)?;
+    if !api.state.store.exists(bucket, &key) {
+        return Err(ApiError::not_found(format!("{bucket:?} {key} not found")));
+    }
+    blocking(move || Ok(api.state.store.read(bucket, &key)?)).await
+}
+
+async fn object_put(
+    State(api): State<ApiState>,
+    Path((bucket, key)): Path<(String, String)>,
+    body: Bytes,
+) -> ApiResult<()> {
+    let bucket = parse_bucket(&bucket)?;
+    blocking(move || {
+        if bucket == Bucket::Images {
+            // Sessions refer to images by ID, so check that it matches the uploaded ELF.
+            let image_id = compute_image_id(&body).map_err(|err| {
+                ApiError::bad_request(format!("image is not a valid ELF: {err:#}"))
+            })?;
+            if hex::encode(image_id) != key {
+                return Err(ApiError::bad_request(format!(
+                    "image ID mismatch: uploaded as {key}, but the ELF has image ID {}",
+                    hex::encode(image_id)
+                )));
+            }
+        }
+        if bucket == Bucket::Receipts {
+            // Receipts are keyed by session ID when written by the prover, so only new keys may
+            // be uploaded, and never the ID of a session.
+            let is_session = api.state.sessions.lock().unwrap().contains_key(&key);
+            if is_session || api.state.store.exists(bucket, &key) {
+                return Err(ApiError::forbidden(format!(
+                    "receipt {key} already exists or belongs to a session"
+                )));
+            }
+        }
+        Ok(api.state.store.write(bucket, &key, &body)?)
+    })
+    .await
+}
+
+async fn delete_object(api: ApiState, bucket: Bucket, key: String) -> ApiResult<()> {
+    if !api.state.store.exists(bucket, &key) {
+        return Err(ApiError::not_found(format!("{bucket:?} {key} not found")));
+    }
+    blocking(move || Ok(api.state.store.delete(bucket, &key)?)).await
+}
+
+// - /sessions
+
+async fn session_create(
+    State(api): State<ApiState>,
+    Json(req): Json<ProofReq>,
+) -> ApiResult<Json<CreateSessRes>> {
+    api.state.prune();
+    let store = &api.state.store;
+    if !store.exists(Bucket::Images, &req.img) {
+        return Err(ApiError::bad_request(format!(
+            "image {} has not been uploaded",
+            req.img
+        )));
+    }
+    if !store.exists(Bucket::Inputs, &req.input) {
+        return Err(ApiError::bad_request(format!(
+            "input {} has not been uploaded",
+            req.input
+        )));
+    }
+    if let Some(receipt_id) = req
+        .assumptions
+        .iter()
+        .find(|receipt_id| !store.exists(Bucket::Receipts, receipt_id))
+    {
+        return Err(ApiError::bad_request(format!(
+            "receipt {receipt_id} has not been uploaded"
+        )));
+    }
+
+    let uuid = Uuid::new_v4().to_string();
+    let request = SessionRequest {
+        img: req.img,
+        input: req.input,
+        assumptions: req.assumptions,
+        execute_only: req.execute_only,
+    };
+    api.state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(uuid.clone(), SessionRecord::new(request));
+    api.submit(Job::Session(uuid.clone()))?;
+    tracing::info!("session {uuid}: queued");
+    Ok(Json(CreateSessRes { uuid }))
+}
+
+async fn session_status(
+    State(api): State<ApiState>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Json<SessionStatusRes>> {
+    let sessions = api.state.sessions.lock().unwrap();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| ApiError::not_found(format!("session {session_id} not found")))?;
+    let receipt_url = match session.status {
+        Status::Succeeded if !session.request.execute_only => {
+            Some(object_url(&headers, Bucket::Receipts, &session_id)?)
+        }
+        _ => None,
+    };
+    Ok(Json(SessionStatusRes {
+        status: session.status.as_str().to_string(),
+        receipt_url,
+        error_msg: session.error_msg.clone(),
+        state: session.state.clone(),
+        elapsed_time: Some(session.elapsed().as_secs_f64()),
+        stats: session.stats.as_ref().map(|stats| SessionStats {
+            segments: stats.segments,
+            total_cycles: stats.total_cycles,
+            cycles: stats.cycles,
+        }),
+    }))
+}
+
+async fn session_logs(
+    State(api): State<ApiState>,
+    Path(session_id): Path<String>,
+) -> ApiResult<String> {
+    if !api.state.sessions.lock().unwrap().contains_key(&session_id) {
+        return Err(ApiError::not_found(format!(
+            "session {session_id} not found"
+        )));
+    }
+    blocking(move || {
+        let path = api.state.store.log_path(&session_id)?;
+        // The log is created when the session starts executing.
+        if !path.exists() {
+            return Ok(String::new());
+        }
+        let log = std::fs::read(path).map_err(anyhow::Error::from)?;
+        Ok(String::from_utf8_lossy(&log).into_owned())
+    })
+    .await
+}
+
+async fn session_stop(
+    State(api): State<ApiState>,
+    Path(session_id): Path<String>,
+) -> ApiResult<()> {
+    let mut sessions = api.state.sessions.lock().unwrap();
+    let session = sessions
+        .get_mut(&session_id)
+        .ok_or_else(|| ApiError::not_found(format!("session {session_id} not found")))?;
+    if session.abort() {
+        tracing::info!("session {session_id}: stopped");
+    }
+    Ok(())
+}
+
+// - /snark
+
+async fn snark_create(
+    State(api): State<ApiState>,
+    Json(req): Json<SnarkReq>,
+) -> ApiResult<Json<CreateSessRes>> {
+    api.state.prune();
+    let status = api
+        .state
+        .sessions
+        .lock()
+        .unwrap()
+        .get(&req.session_id)
+        .map(|session| session.status);
+    // Receipts outlive the in-memory session table, so a receipt from before a restart can still
+    // be converted.
+    let has_receipt = matches!(status, None | Some(Status::Succeeded))
+        && api.state.store.exists(Bucket::Receipts, &req.session_id);
+    if !has_receipt {
+        return Err(ApiError::bad_request(format!(
+            "session {} has no receipt to convert",
+            req.session_id
+        )));
+    }
+
+    let uuid = Uuid::new_v4().to_string();
+    api.state
+        .snarks
+        .lock()
+        .unwrap()
+        .insert(uuid.clone(), SnarkRecord::new(req.session_id));
+    api.submit(Job::Snark(uuid.clone()))?;
+    tracing::info!("snark {uuid}: queued");
+    Ok(Json(CreateSessRes { uuid }))
+}
+
+async fn snark_status(
+    State(api): State<ApiState>,
+    Path(snark_id): Path<String>,
+) -> ApiResult<Json<SnarkStatusRes>> {
+    let snarks = api.state.snarks.lock().unwrap();
+    let snark = snarks
+        .get(&snark_id)
+        .ok_or_else(|| ApiError::not_found(format!("snark {snark_id} not found")))?;
+    Ok(Json(SnarkStatusRes {
+        status: snark.status.as_str().to_string(),
+        output: snark.output.as_ref().map(|output| SnarkReceipt {
+            snark: output.snark.clone(),
+            post_state_digest: output.post_state_digest.clone(),
+            journal: output.journal.clone(),
+        }),
+        error_msg: snark.error_msg.clone(),
+    }))
+}
+
+// - /version and /user
+
+async fn version() -> Json<VersionInfo> {
+    Json(VersionInfo {
+        risc0_zkvm: vec![risc0_zkvm::VERSION.to_string()],
+    })
+}
+
+async fn quotas(State(api): State<ApiState>) -> Json<Quotas> {
+    let config = &api.state.config;
+    let cycle_usage = api.state.cycle_usage.load(Ordering::Relaxed);
+    Json(Quotas {
+        exec_cycle_limit: config
+            .exec_cycle_limit
+            .map_or(i64::MAX, |limit| limit.try_into().unwrap_or(i64::MAX)),
+        concurrent_proofs: config.workers as i64,
+        cycle_budget: i64::MAX,
+        cycle_usage: cycle_usage.try_into().unwrap_or(i64::MAX),
+        dedicated_executor: 0,
+        dedicated_gpu: 0,
+    })
+}
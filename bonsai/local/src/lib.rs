@@ -0,0 +1,243 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![deny(missing_docs)]
+
+//! A self-hosted server implementing the Bonsai REST API on top of the [LocalProver].
+//!
+//! `bonsai-local` accepts the same requests as the hosted Bonsai service, so that
+//! [bonsai_sdk] clients, the [BonsaiProver](risc0_zkvm::BonsaiProver), and
+//! `cargo risczero deploy` can be used unchanged against a prover running in CI or on an
+//! on-premises cluster:
+//!
+//! ```sh
+//! bonsai-local --bind 127.0.0.1:8081 --data-dir ./bonsai-data &
+//! export BONSAI_API_URL=http://127.0.0.1:8081
+//! export BONSAI_API_KEY=unused
+//! ```
+//!
+//! Sessions are queued, and each worker proves one session at a time. Uploaded images, inputs
+//! and receipts are kept in the data directory, along with a log of the guest's stdout and stderr
+//! for each session. The status of sessions and SNARK conversions is held in memory, and is
+//! forgotten once it is older than `--status-ttl` or when the server restarts, but receipts
+//! remain available for download. Receipts written by the prover cannot be overwritten by
+//! clients.
+//!
+//! There are a few differences from the hosted service:
+//! * Only ELF images are accepted, and their image ID is checked on upload.
+//! * Stopping a session that is already being proven marks it as aborted, but the prover runs to
+//!   completion before the worker takes the next job.
+//! * There is no cycle budget. The executor cycle limit can be set with `--exec-cycle-limit`.
+//!
+//! [LocalProver]: risc0_zkvm::LocalProver
+
+mod api;
+mod store;
+mod worker;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::AtomicU64,
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use clap::Parser;
+use tokio::net::TcpListener;
+
+use self::{
+    store::ObjectStore,
+    worker::{Job, SessionRecord, SnarkRecord},
+};
+
+/// Configuration for a [Server].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Config {
+    /// Directory in which uploaded objects, receipts and session logs are stored.
+    pub data_dir: PathBuf,
+
+    /// API key that clients must provide. Any key is accepted when this is [None].
+    pub api_key: Option<String>,
+
+    /// Number of jobs to run concurrently.
+    pub workers: usize,
+
+    /// Maximum number of cycles to execute in each session, in millions of cycles.
+    pub exec_cycle_limit: Option<u64>,
+
+    /// How long the status of a finished session or SNARK conversion is kept.
+    pub status_ttl: Duration,
+}
+
+impl Config {
+    /// Construct a [Config] that stores data in the given directory, accepts any API key, and
+    /// runs one job at a time.
+    pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            data_dir: data_dir.into(),
+            api_key: None,
+            workers: 1,
+            exec_cycle_limit: None,
+            status_ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Return [Config] with the required API key set.
+    pub fn with_api_key(self, api_key: &str) -> Self {
+        Self {
+            api_key: Some(api_key.to_string()),
+            ..self
+        }
+    }
+
+    /// Return [Config] with the number of workers set.
+    pub fn with_workers(self, workers: usize) -> Self {
+        Self { workers, ..self }
+    }
+
+    /// Return [Config] with the executor cycle limit set, in millions of cycles.
+    pub fn with_exec_cycle_limit(self, exec_cycle_limit: u64) -> Self {
+        Self {
+            exec_cycle_limit: Some(exec_cycle_limit),
+            ..self
+        }
+    }
+
+    /// Return [Config] with the time to keep the status of finished jobs set.
+    pub fn with_status_ttl(self, status_ttl: Duration) -> Self {
+        Self { status_ttl, ..self }
+    }
+}
+
+/// State shared between the request handlers and the worker threads.
+pub(crate) struct ServerState {
+    pub(crate) config: Config,
+    pub(crate) store: ObjectStore,
+    pub(crate) sessions: Mutex<HashMap<String, SessionRecord>>,
+    pub(crate) snarks: Mutex<HashMap<String, SnarkRecord>>,
+    pub(crate) cycle_usage: AtomicU64,
+}
+
+impl ServerState {
+    /// Forget sessions and SNARK conversions that finished longer ago than the status TTL.
+    pub(crate) fn prune(&self) {
+        let ttl = self.config.status_ttl;
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| !session.expired(ttl));
+        self.snarks
+            .lock()
+            .unwrap()
+            .retain(|_, snark| !snark.expired(ttl));
+    }
+}
+
+/// A Bonsai REST API server backed by the [LocalProver](risc0_zkvm::LocalProver).
+pub struct Server {
+    state: Arc<ServerState>,
+    jobs: Sender<Job>,
+}
+
+impl Server {
+    /// Construct a [Server], opening the data directory and starting the worker threads.
+    ///
+    /// The worker threads exit once the [Server] is dropped, or has finished serving.
+    pub fn new(config: Config) -> Result<Self> {
+        let state = Arc::new(ServerState {
+            store: ObjectStore::open(&config.data_dir)?,
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            snarks: Mutex::new(HashMap::new()),
+            cycle_usage: AtomicU64::new(0),
+        });
+
+        let (jobs, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for idx in 0..state.config.workers.max(1) {
+            let state = state.clone();
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("bonsai-local-worker-{idx}"))
+                .spawn(move || worker::run(state, receiver))?;
+        }
+
+        Ok(Self { state, jobs })
+    }
+
+    /// Serve the Bonsai REST API on the given listener.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        axum::serve(listener, api::router(self.state, self.jobs)).await?;
+        Ok(())
+    }
+}
+
+/// Runs a Bonsai REST API server backed by the local prover.
+#[derive(Parser)]
+#[command(about, version, author)]
+struct Cli {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8081")]
+    bind: SocketAddr,
+
+    /// Directory in which uploaded objects, receipts and session logs are stored.
+    #[arg(long, default_value = "bonsai-local")]
+    data_dir: PathBuf,
+
+    /// API key that clients must provide. Any key is accepted if this is not set.
+    #[arg(long, env = "BONSAI_LOCAL_API_KEY")]
+    api_key: Option<String>,
+
+    /// Number of jobs to run concurrently.
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+
+    /// Maximum number of cycles to execute in each session, in millions of cycles.
+    #[arg(long)]
+    exec_cycle_limit: Option<u64>,
+
+    /// How long to keep the status of a finished session or SNARK conversion, in seconds.
+    #[arg(long, default_value_t = 24 * 60 * 60)]
+    status_ttl: u64,
+}
+
+#[doc(hidden)]
+pub fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
+        .init();
+
+    let args = Cli::parse();
+    let config = Config {
+        data_dir: args.data_dir,
+        api_key: args.api_key,
+        workers: args.workers,
+        exec_cycle_limit: args.exec_cycle_limit,
+        status_ttl: Duration::from_secs(args.status_ttl),
+    };
+    let server = Server::new(config)?;
+
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = TcpListener::bind(args.bind).await?;
+        tracing::info!("listening on http://{}", listener.local_addr()?);
+        server.serve(listener).await
+    })
+}
@@ -0,0 +1,160 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-disk object store for uploaded images, inputs and receipts, and session logs.
+
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use uuid::Uuid;
+
+/// The kinds of object that can be uploaded to and downloaded from the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Bucket {
+    Images,
+    Inputs,
+    Receipts,
+}
+
+impl Bucket {
+    const ALL: [Bucket; 3] = [Bucket::Images, Bucket::Inputs, Bucket::Receipts];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Bucket::Images => "images",
+            Bucket::Inputs => "inputs",
+            Bucket::Receipts => "receipts",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|bucket| bucket.as_str() == name)
+    }
+}
+
+/// A directory holding one subdirectory per [Bucket], plus a directory of session logs.
+pub(crate) struct ObjectStore {
+    root: PathBuf,
+}
+
+impl ObjectStore {
+    /// Open the store rooted at the given directory, creating it if needed.
+    pub(crate) fn open(root: &Path) -> Result<Self> {
+        for bucket in Bucket::ALL {
+            fs::create_dir_all(root.join(bucket.as_str()))?;
+        }
+        fs::create_dir_all(root.join("logs"))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+
+    fn path(&self, bucket: Bucket, key: &str) -> Result<PathBuf> {
+        check_key(key)?;
+        Ok(self.root.join(bucket.as_str()).join(key))
+    }
+
+    pub(crate) fn exists(&self, bucket: Bucket, key: &str) -> bool {
+        self.path(bucket, key)
+            .map(|path| path.is_file())
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn read(&self, bucket: Bucket, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(bucket, key)?;
+        fs::read(&path).with_context(|| format!("{} {key} not found", bucket.as_str()))
+    }
+
+    /// Write an object, replacing any existing object with the same key.
+    ///
+    /// The data is written to a temporary file first, so that readers never observe a partially
+    /// written object.
+    pub(crate) fn write(&self, bucket: Bucket, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(bucket, key)?;
+        let tmp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    pub(crate) fn delete(&self, bucket: Bucket, key: &str) -> Result<()> {
+        let path = self.path(bucket, key)?;
+        fs::remove_file(&path).with_context(|| format!("{} {key} not found", bucket.as_str()))
+    }
+
+    /// Path of the log file that captures the guest's stdout and stderr for a session.
+    pub(crate) fn log_path(&self, session_id: &str) -> Result<PathBuf> {
+        check_key(session_id)?;
+        Ok(self.root.join("logs").join(format!("{session_id}.log")))
+    }
+}
+
+// Keys are image IDs and UUIDs. Reject anything else so that a key cannot escape its directory.
+fn check_key(key: &str) -> Result<()> {
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("invalid object key: {key:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket, ObjectStore};
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ObjectStore::open(dir.path()).unwrap();
+
+        assert!(!store.exists(Bucket::Inputs, "abc"));
+        store.write(Bucket::Inputs, "abc", b"hello").unwrap();
+        assert!(store.exists(Bucket::Inputs, "abc"));
+        assert!(!store.exists(Bucket::Receipts, "abc"));
+        assert_eq!(store.read(Bucket::Inputs, "abc").unwrap(), b"hello");
+
+        store.delete(Bucket::Inputs, "abc").unwrap();
+        assert!(!store.exists(Bucket::Inputs, "abc"));
+        assert!(store.read(Bucket::Inputs, "abc").is_err());
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ObjectStore::open(dir.path()).unwrap();
+
+        for key in ["", "..", "../inputs/abc", "a/b", "a.b"] {
+            assert!(store.write(Bucket::Images, key, b"").is_err(), "{key}");
+            assert!(!store.exists(Bucket::Images, key), "{key}");
+        }
+        assert!(store.log_path("../escape").is_err());
+    }
+
+    #[test]
+    fn bucket_names() {
+        for bucket in [Bucket::Images, Bucket::Inputs, Bucket::Receipts] {
+            assert_eq!(Bucket::from_name(bucket.as_str()), Some(bucket));
+        }
+        assert_eq!(Bucket::from_name("logs"), None);
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Job queue that runs proving sessions and SNARK conversions on the [LocalProver].
+
+use std::{
+    fs::File,
+    sync::{atomic::Ordering, mpsc::Receiver, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bonsai_sdk::responses::{SessionStats, SnarkReceipt};
+use risc0_groth16::Seal;
+use risc0_zkvm::{
+    sha::Digestible, Executor, ExecutorEnv, LocalProver, Prover, ProverOpts, Receipt,
+    VerifierContext,
+};
+
+use crate::{store::Bucket, ServerState};
+
+const PROVER_NAME: &str = "bonsai-local";
+
+/// A unit of work for the worker threads, identified by session or SNARK UUID.
+pub(crate) enum Job {
+    Session(String),
+    Snark(String),
+}
+
+/// Status of a session or SNARK conversion, as reported by the Bonsai API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Status {
+    Running,
+    Succeeded,
+    Failed,
+    Aborted,
+}
+
+impl Status {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Status::Running => "RUNNING",
+            Status::Succeeded => "SUCCEEDED",
+            Status::Failed => "FAILED",
+            Status::Aborted => "ABORTED",
+        }
+    }
+}
+
+/// The parameters of a proof request, as given to `/sessions/create`.
+#[derive(Clone)]
+pub(crate) struct SessionRequest {
+    pub(crate) img: String,
+    pub(crate) input: String,
+    pub(crate) assumptions: Vec<String>,
+    pub(crate) execute_only: bool,
+}
+
+pub(crate) struct SessionRecord {
+    pub(crate) request: SessionRequest,
+    pub(crate) status: Status,
+    pub(crate) state: Option<String>,
+    pub(crate) error_msg: Option<String>,
+    pub(crate) stats: Option<SessionStats>,
+    created: Instant,
+    finished: Option<Instant>,
+}
+
+impl SessionRecord {
+    pub(crate) fn new(request: SessionRequest) -> Self {
+        Self {
+            request,
+            status: Status::Running,
+            state: Some("Setup".to_string()),
+            error_msg: None,
+            stats: None,
+            created: Instant::now(),
+            finished: None,
+        }
+    }
+
+    /// Time since the session was created, or that it took to finish.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.finished.unwrap_or_else(Instant::now) - self.created
+    }
+
+    /// Whether the session finished longer ago than the given TTL.
+    pub(crate) fn expired(&self, ttl: Duration) -> bool {
+        self.finished
+            .is_some_and(|finished| finished.elapsed() > ttl)
+    }
+
+    /// Mark the session as stopped. Returns false if the session had already finished.
+    pub(crate) fn abort(&mut self) -> bool {
+        if self.status != Status::Running {
+            return false;
+        }
+        self.status = Status::Aborted;
+        self.state = None;
+        self.error_msg = Some("session stopped by request".to_string());
+        self.finished = Some(Instant::now());
+        true
+    }
+
+    fn finish(&mut self, result: Result<SessionStats>) {
+        self.state = None;
+        self.finished = Some(Instant::now());
+        match result {
+            Ok(stats) => {
+                self.status = Status::Succeeded;
+                self.stats = Some(stats);
+            }
+            Err(err) => {
+                self.status = Status::Failed;
+                self.error_msg = Some(format!("{err:#}"));
+            }
+        }
+    }
+}
+
+pub(crate) struct SnarkRecord {
+    pub(crate) session_id: String,
+    pub(crate) status: Status,
+    pub(crate) error_msg: Option<String>,
+    pub(crate) output: Option<SnarkReceipt>,
+    finished: Option<Instant>,
+}
+
+impl SnarkRecord {
+    pub(crate) fn new(session_id: String) -> Self {
+        Self {
+            session_id,
+            status: Status::Running,
+            error_msg: None,
+            output: None,
+            finished: None,
+        }
+    }
+
+    /// Whether the conversion finished longer ago than the given TTL.
+    pub(crate) fn expired(&self, ttl: Duration) -> bool {
+        self.finished
+            .is_some_and(|finished| finished.elapsed() > ttl)
+    }
+}
+
+/// Take jobs from the queue and run them until the queue is closed.
+pub(crate) fn run(state: Arc<ServerState>, jobs: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        // The lock is only held while waiting for the next job, so idle workers take turns.
+        let job = jobs.lock().unwrap().recv();
+        match job {
+            Ok(Job::Session(session_id)) => run_session(&state, &session_id),
+            Ok(Job::Snark(snark_id)) => run_snark(&state, &snark_id),
+            Err(_) => return,
+        }
+    }
+}
+
+fn run_session(state: &ServerState, session_id: &str) {
+    let request = {
+        let mut sessions = state.sessions.lock().unwrap();
+        let Some(session) = sessions.get_mut(session_id) else {
+            return;
+        };
+        // The session may have been stopped while it was queued.
+        if session.status != Status::Running {
+            return;
+        }
+        let stage = if session.request.execute_only {
+            "Executor"
+        } else {
+            "InProgress"
+        };
+        session.state = Some(stage.to_string());
+        session.request.clone()
+    };
+
+    tracing::info!("session {session_id}: started");
+    let result = prove_session(state, session_id, &request);
+
+    let mut sessions = state.sessions.lock().unwrap();
+    let Some(session) = sessions.get_mut(session_id) else {
+        return;
+    };
+    // A session that was stopped while running keeps its aborted status, and the result of the
+    // prover is discarded without writing its receipt.
+    if session.status != Status::Running {
+        tracing::info!("session {session_id}: discarded result of stopped session");
+        return;
+    }
+    let result = result.and_then(|(stats, receipt)| {
+        if let Some(receipt) = receipt {
+            state
+                .store
+                .write(Bucket::Receipts, session_id, &bincode::serialize(&receipt)?)?;
+        }
+        Ok(stats)
+    });
+    match &result {
+        Ok(stats) => {
+            tracing::info!("session {session_id}: succeeded");
+            state
+                .cycle_usage
+                .fetch_add(stats.total_cycles, Ordering::Relaxed);
+        }
+        Err(err) => tracing::warn!("session {session_id}: failed: {err:#}"),
+    }
+    session.finish(result);
+}
+
+fn prove_session(
+    state: &ServerState,
+    session_id: &str,
+    request: &SessionRequest,
+) -> Result<(SessionStats, Option<Receipt>)> {
+    let elf = state.store.read(Bucket::Images, &request.img)?;
+    let input = state.store.read(Bucket::Inputs, &request.input)?;
+    let log = File::create(state.store.log_path(session_id)?)?;
+
+    let mut env = ExecutorEnv::builder();
+    env.write_slice(&input)
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .session_limit(
+            state
+                .config
+                .exec_cycle_limit
+                .map(|limit| limit.saturating_mul(1_000_000)),
+        );
+    for receipt_id in request.assumptions.iter() {
+        let receipt: Receipt =
+            bincode::deserialize(&state.store.read(Bucket::Receipts, receipt_id)?)?;
+        env.add_assumption(receipt);
+    }
+    let env = env.build()?;
+
+    let prover = LocalProver::new(PROVER_NAME);
+    if request.execute_only {
+        let info = prover.execute(env, &elf)?;
+        let stats = SessionStats {
+            segments: info.segments.len(),
+            total_cycles: info.segments.iter().map(|s| 1u64 << s.po2).sum(),
+            cycles: info.segments.iter().map(|s| s.cycles as u64).sum(),
+        };
+        return Ok((stats, None));
+    }
+
+    let info = prover.prove_with_ctx(
+        env,
+        &VerifierContext::default(),
+        &elf,
+        &ProverOpts::succinct(),
+    )?;
+    let stats = SessionStats {
+        segments: info.stats.segments,
+        total_cycles: info.stats.total_cycles,
+        cycles: info.stats.user_cycles,
+    };
+    Ok((stats, Some(info.receipt)))
+}
+
+fn run_snark(state: &ServerState, snark_id: &str) {
+    let Some(session_id) = state
+        .snarks
+        .lock()
+        .unwrap()
+        .get(snark_id)
+        .map(|snark| snark.session_id.clone())
+    else {
+        return;
+    };
+
+    tracing::info!("snark {snark_id}: started for session {session_id}");
+    let result = prove_snark(state, &session_id);
+
+    let mut snarks = state.snarks.lock().unwrap();
+    let Some(snark) = snarks.get_mut(snark_id) else {
+        return;
+    };
+    snark.finished = Some(Instant::now());
+    match result {
+        Ok(output) => {
+            tracing::info!("snark {snark_id}: succeeded");
+            snark.status = Status::Succeeded;
+            snark.output = Some(output);
+        }
+        Err(err) => {
+            tracing::warn!("snark {snark_id}: failed: {err:#}");
+            snark.status = Status::Failed;
+            snark.error_msg = Some(format!("{err:#}"));
+        }
+    }
+}
+
+fn prove_snark(state: &ServerState, session_id: &str) -> Result<SnarkReceipt> {
+    let receipt: Receipt = bincode::deserialize(&state.store.read(Bucket::Receipts, session_id)?)?;
+    let receipt = LocalProver::new(PROVER_NAME).compress(&ProverOpts::groth16(), &receipt)?;
+    let seal = Seal::from_vec(&receipt.inner.groth16()?.seal)?;
+    let post_state_digest = receipt.claim()?.as_value()?.post.digest();
+    Ok(SnarkReceipt {
+        snark: seal,
+        post_state_digest: post_state_digest.as_bytes().to_vec(),
+        journal: receipt.journal.bytes,
+    })
+}
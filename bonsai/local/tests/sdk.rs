@@ -0,0 +1,227 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runs the Bonsai SDK and the BonsaiProver against a bonsai-local server.
+
+use std::time::Duration;
+
+use bonsai_local::{Config, Server};
+use bonsai_sdk::blocking::{Client, SessionId};
+use risc0_zkvm::{serde::to_vec, BonsaiProver, ExecutorEnv, Prover};
+use risc0_zkvm_methods::{multi_test::MultiTestSpec, MULTI_TEST_ELF, MULTI_TEST_ID};
+use tempfile::TempDir;
+
+const API_KEY: &str = "test-key";
+
+// Start a server on an unused port, returning its URL and the data directory.
+fn start_server(config: impl FnOnce(Config) -> Config) -> (String, TempDir) {
+    let data_dir = tempfile::tempdir().unwrap();
+    let server = Server::new(config(Config::new(data_dir.path()))).unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            server.serve(listener).await.unwrap();
+        })
+    });
+    (url, data_dir)
+}
+
+fn client(url: &str) -> Client {
+    Client::from_parts(url.to_string(), API_KEY.to_string(), risc0_zkvm::VERSION).unwrap()
+}
+
+fn wait_for_session(
+    client: &Client,
+    session: &SessionId,
+) -> bonsai_sdk::responses::SessionStatusRes {
+    loop {
+        let res = session.status(client).unwrap();
+        if res.status != "RUNNING" {
+            return res;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Upload the multi-test guest, and input consisting of the spec followed by the given bytes.
+fn upload_multi_test(client: &Client, spec: &MultiTestSpec, stdin: &[u8]) -> (String, String) {
+    let image_id = hex::encode(risc0_zkvm::sha::Digest::from(MULTI_TEST_ID));
+    client
+        .upload_img(&image_id, MULTI_TEST_ELF.to_vec())
+        .unwrap();
+    let mut input = bytemuck::cast_slice(&to_vec(spec).unwrap()).to_vec();
+    input.extend_from_slice(stdin);
+    let input_id = client.upload_input(input).unwrap();
+    (image_id, input_id)
+}
+
+#[test_log::test]
+fn execute_only_session() {
+    let (url, _data_dir) = start_server(|config| config);
+    let client = client(&url);
+
+    let (image_id, input_id) = upload_multi_test(&client, &MultiTestSpec::DoNothing, &[]);
+    // A second upload of the same image is skipped.
+    assert!(client
+        .upload_img(&image_id, MULTI_TEST_ELF.to_vec())
+        .unwrap());
+
+    let session = client
+        .create_session(image_id, input_id, vec![], true)
+        .unwrap();
+    let res = wait_for_session(&client, &session);
+    assert_eq!(res.status, "SUCCEEDED", "{:?}", res.error_msg);
+    assert!(res.receipt_url.is_none());
+    let stats = res.stats.unwrap();
+    assert!(stats.segments > 0);
+    assert!(stats.total_cycles >= stats.cycles);
+    assert!(client.quotas().unwrap().cycle_usage > 0);
+
+    // Execute-only sessions do not produce a receipt.
+    assert!(matches!(
+        client.receipt_download(&session),
+        Err(bonsai_sdk::SdkErr::ReceiptNotFound)
+    ));
+}
+
+#[test_log::test]
+fn session_logs() {
+    let (url, _data_dir) = start_server(|config| config);
+    let client = client(&url);
+
+    // The guest copies the rest of its input to stdout.
+    let spec = MultiTestSpec::EchoStdout { nbytes: 7, fd: 0 };
+    let (image_id, input_id) = upload_multi_test(&client, &spec, b"hello from the guest");
+    let session = client
+        .create_session(image_id, input_id, vec![], true)
+        .unwrap();
+    assert_eq!(wait_for_session(&client, &session).status, "SUCCEEDED");
+    assert!(session
+        .logs(&client)
+        .unwrap()
+        .contains("hello from the guest"));
+}
+
+#[test_log::test]
+fn failed_session() {
+    let (url, _data_dir) = start_server(|config| config);
+    let client = client(&url);
+
+    let (image_id, input_id) = upload_multi_test(&client, &MultiTestSpec::Panic, &[]);
+    let session = client
+        .create_session(image_id, input_id, vec![], true)
+        .unwrap();
+    let res = wait_for_session(&client, &session);
+    assert_eq!(res.status, "FAILED");
+    assert!(res.error_msg.is_some());
+}
+
+#[test_log::test]
+fn rejects_bad_requests() {
+    let (url, _data_dir) = start_server(|config| config);
+    let client = client(&url);
+
+    // The image ID must match the uploaded ELF.
+    assert!(client
+        .upload_img(&"00".repeat(32), MULTI_TEST_ELF.to_vec())
+        .is_err());
+
+    // Sessions can only be created from uploaded objects.
+    let input_id = client.upload_input(vec![]).unwrap();
+    assert!(client
+        .create_session("00".repeat(32), input_id, vec![], false)
+        .is_err());
+
+    assert!(SessionId::new("unknown".to_string())
+        .status(&client)
+        .is_err());
+    assert!(client.create_snark("unknown".to_string()).is_err());
+}
+
+#[test_log::test]
+fn receipts_cannot_be_overwritten() {
+    let (url, _data_dir) = start_server(|config| config);
+    let client = client(&url);
+    let put_receipt = |key: &str| {
+        reqwest::blocking::Client::new()
+            .put(format!("{url}/objects/receipts/{key}"))
+            .body(vec![1, 2, 3])
+            .send()
+            .unwrap()
+            .status()
+    };
+
+    // An uploaded receipt cannot be replaced.
+    let receipt_id = client.upload_receipt(vec![4, 5, 6]).unwrap();
+    assert_eq!(put_receipt(&receipt_id), reqwest::StatusCode::FORBIDDEN);
+
+    // Nor can a receipt be written for a session, which the prover owns.
+    let (image_id, input_id) = upload_multi_test(&client, &MultiTestSpec::DoNothing, &[]);
+    let session = client
+        .create_session(image_id, input_id, vec![], true)
+        .unwrap();
+    assert_eq!(put_receipt(&session.uuid), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[test_log::test]
+fn expired_status_is_forgotten() {
+    let (url, _data_dir) = start_server(|config| config.with_status_ttl(Duration::ZERO));
+    let client = client(&url);
+
+    let (image_id, input_id) = upload_multi_test(&client, &MultiTestSpec::DoNothing, &[]);
+    let session = client
+        .create_session(image_id.clone(), input_id.clone(), vec![], true)
+        .unwrap();
+    assert_eq!(wait_for_session(&client, &session).status, "SUCCEEDED");
+
+    // Finished sessions are pruned when the next one is created.
+    client
+        .create_session(image_id, input_id, vec![], true)
+        .unwrap();
+    assert!(session.status(&client).is_err());
+}
+
+#[test_log::test]
+fn requires_api_key() {
+    let (url, _data_dir) = start_server(|config| config.with_api_key(API_KEY));
+    client(&url).upload_input(vec![1, 2, 3]).unwrap();
+
+    let other = Client::from_parts(url, "other-key".to_string(), risc0_zkvm::VERSION).unwrap();
+    assert!(other.upload_input(vec![1, 2, 3]).is_err());
+}
+
+#[test_log::test]
+fn bonsai_prover() {
+    let (url, _data_dir) = start_server(|config| config);
+
+    // Proving is done in dev mode, so that the test is fast and the result is a fake receipt.
+    let receipt = temp_env::with_var("RISC0_DEV_MODE", Some("1"), || {
+        let env = ExecutorEnv::builder()
+            .write(&MultiTestSpec::DoNothing)
+            .unwrap()
+            .build()
+            .unwrap();
+        BonsaiProver::from_parts("bonsai-local", url.clone(), API_KEY.to_string())
+            .prove(env, MULTI_TEST_ELF)
+            .unwrap()
+            .receipt
+    });
+    temp_env::with_var("RISC0_DEV_MODE", Some("1"), || {
+        receipt.verify(MULTI_TEST_ID).unwrap();
+    });
+}
@@ -0,0 +1,30 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::{guest::env, ReceiptClaim};
+
+risc0_zkvm::entry!(main);
+
+fn main() {
+    // Verify each of the claims, and commit the root of the aggregation set as the journal.
+    let claims: Vec<ReceiptClaim> = env::read();
+    env::aggregate(&claims).unwrap();
+}
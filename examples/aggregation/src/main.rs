@@ -0,0 +1,55 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use aggregation_example_methods::{AGGREGATE_ELF, AGGREGATE_ID};
+use hello_world_methods::MULTIPLY_ELF;
+use risc0_zkvm::{default_prover, Digest, ExecutorEnv, ProverOpts};
+
+fn main() {
+    // Prove a few multiplications, each with its own succinct receipt.
+    let opts = ProverOpts::succinct();
+    let receipts: Vec<_> = [(17u64, 23u64), (3, 5), (7, 11)]
+        .into_iter()
+        .map(|(a, b)| {
+            let env = ExecutorEnv::builder()
+                .write(&a)
+                .unwrap()
+                .write(&b)
+                .unwrap()
+                .build()
+                .unwrap();
+            let receipt = default_prover()
+                .prove_with_opts(env, MULTIPLY_ELF, &opts)
+                .unwrap()
+                .receipt;
+            receipt.inner.succinct().unwrap().clone()
+        })
+        .collect();
+
+    // Aggregate the receipts into one. The aggregation guest verifies each of them, and commits
+    // the root of the set of their claims as its journal.
+    let (aggregate, proofs) = default_prover()
+        .aggregate(AGGREGATE_ELF, &receipts, &opts)
+        .unwrap();
+    aggregate.verify(AGGREGATE_ID).unwrap();
+
+    // Anybody holding the aggregate receipt can check that any one of the claims is included
+    // using only its inclusion proof, without the other claims.
+    let root = Digest::try_from(aggregate.journal.bytes.as_slice()).unwrap();
+    for (receipt, proof) in receipts.iter().zip(&proofs) {
+        proof.verify(&receipt.claim.digest(), &root).unwrap();
+    }
+
+    println!("{} receipts aggregated under root {root}", receipts.len());
+}
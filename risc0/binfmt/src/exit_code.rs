@@ -53,6 +53,15 @@ pub enum ExitCode {
     /// NOTE: The current version of the RISC Zero zkVM will never exit with an exit code of SessionLimit.
     /// This is because the system cannot currently prove that the session limit as been reached.
     SessionLimit,
+
+    /// This indicates that the guest faulted, e.g. by panicking, and execution cannot continue.
+    ///
+    /// The executor reports the cause of the fault alongside the session, so that the host can
+    /// handle it without the session being lost.
+    ///
+    /// NOTE: The current version of the RISC Zero zkVM cannot prove that the guest faulted, so a
+    /// session that exits with an exit code of Fault cannot be proven.
+    Fault,
 }
 
 impl ExitCode {
@@ -64,16 +73,18 @@ impl ExitCode {
             ExitCode::Paused(user_exit) => (1, user_exit),
             ExitCode::SystemSplit => (2, 0),
             ExitCode::SessionLimit => (2, 2),
+            ExitCode::Fault => (2, 1),
         }
     }
 
     /// Convert this [ExitCode] from its pair representation, where the first number is the "system"
     /// part, and the second is the "user" part. E.g. (0, 255) -> Halted(255)
     pub fn from_pair(sys_exit: u32, user_exit: u32) -> Result<ExitCode, InvalidExitCodeError> {
-        match sys_exit {
-            0 => Ok(ExitCode::Halted(user_exit)),
-            1 => Ok(ExitCode::Paused(user_exit)),
-            2 => Ok(ExitCode::SystemSplit),
+        match (sys_exit, user_exit) {
+            (0, _) => Ok(ExitCode::Halted(user_exit)),
+            (1, _) => Ok(ExitCode::Paused(user_exit)),
+            (2, 1) => Ok(ExitCode::Fault),
+            (2, _) => Ok(ExitCode::SystemSplit),
             _ => Err(InvalidExitCodeError(sys_exit, user_exit)),
         }
     }
@@ -84,7 +95,7 @@ impl ExitCode {
     pub fn expects_output(&self) -> bool {
         match self {
             ExitCode::Halted(_) | ExitCode::Paused(_) => true,
-            ExitCode::SystemSplit | ExitCode::SessionLimit => false,
+            ExitCode::SystemSplit | ExitCode::SessionLimit | ExitCode::Fault => false,
         }
     }
 
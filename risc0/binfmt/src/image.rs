@@ -14,7 +14,8 @@
 
 extern crate alloc;
 
-use alloc::{collections::BTreeMap, vec, vec::Vec};
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use core::ops::Range;
 
 use anyhow::{ensure, Result};
 use risc0_zkp::core::{
@@ -27,23 +28,44 @@ use risc0_zkvm_platform::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{elf::Program, Digestible, SystemState};
+use crate::{
+    elf::Program,
+    page_store::{Page, PageStore},
+    Digestible, SystemState,
+};
 
 /// An image of a zkVM guest's memory
 ///
 /// This is an image of the full memory state of the zkVM, including the data,
 /// text, inputs, page table, and system memory. In addition to the memory image
 /// proper, this includes some metadata about the page table.
+///
+/// Pages are copy-on-write, so cloning an image is cheap, and the clone shares
+/// storage for every page that neither of them modifies.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct MemoryImage {
     /// Sparse memory image as a map from page index to page.
-    pub pages: BTreeMap<u32, Vec<u8>>,
+    pages: PageStore,
 
     /// Metadata about the structure of the page table
     pub info: PageTableInfo,
 
     /// Program Counter from [Program] entry point
     pub pc: u32,
+
+    /// Pages modified since their page table entry was last updated.
+    ///
+    /// This is serialized along with the pages, so that a deserialized image
+    /// still updates their entries on the next call to
+    /// [MemoryImage::hash_pages].
+    dirty: BTreeSet<u32>,
+
+    /// The guard region below the guest's stack, if it was linked with one.
+    ///
+    /// The executor reports any access to it as a stack overflow. It is not
+    /// part of the image ID.
+    #[serde(default)]
+    pub stack_guard: Option<Range<u32>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -190,9 +212,11 @@ impl MemoryImage {
         // Compute the page table hashes except for the very last root hash.
         let info = PageTableInfo::new(PAGE_TABLE.start() as u32, page_size)?;
         let mut img = Self {
-            pages: BTreeMap::new(),
+            pages: PageStore::new(),
             info,
             pc: program.entry,
+            dirty: BTreeSet::new(),
+            stack_guard: None,
         };
 
         // Load the ELF into the memory image.
@@ -203,33 +227,72 @@ impl MemoryImage {
             img.store_region_in_page(addr, &data.to_le_bytes());
         }
 
-        img.hash_pages();
+        img.build_page_table();
         Ok(img)
     }
 
+    /// Return a sparse copy of this image containing only the given pages.
+    ///
+    /// The pages are shared with this image rather than copied.
+    pub fn subset(&self, page_indices: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            pages: page_indices
+                .into_iter()
+                .map(|page_idx| (page_idx, self.load_page(page_idx)))
+                .collect(),
+            info: self.info.clone(),
+            pc: self.pc,
+            dirty: BTreeSet::new(),
+            stack_guard: self.stack_guard.clone(),
+        }
+    }
+
+    /// Sparse memory image as a map from page index to page.
+    ///
+    /// Pages are modified through [MemoryImage::store_page] and
+    /// [MemoryImage::store_region_in_page], which track the pages whose page
+    /// table entries need updating.
+    pub fn pages(&self) -> &PageStore {
+        &self.pages
+    }
+
     /// Load a page specified by page_idx. If no page is found, a zero page is
     /// returned.
-    pub fn load_page(&self, page_idx: u32) -> Vec<u8> {
+    pub fn load_page(&self, page_idx: u32) -> Page {
         self.pages
-            .get(&page_idx)
+            .get(page_idx)
             .cloned()
-            .unwrap_or_else(|| vec![0; self.info.page_size as usize])
+            .unwrap_or_else(|| Page::zeroed(self.info.page_size as usize))
+    }
+
+    /// Replace the page specified by page_idx.
+    ///
+    /// The page table entry for the page is updated by the next call to
+    /// [MemoryImage::hash_pages].
+    pub fn store_page(&mut self, page_idx: u32, page: Page) {
+        self.pages.insert(page_idx, page);
+        self.dirty.insert(page_idx);
     }
 
     /// Writes the given byte array in this memory image at the given
     /// address.  The caller is responsible for ensuring the bytes do
     /// not overlap a page boundary.
+    ///
+    /// The page table entry for the page is updated by the next call to
+    /// [MemoryImage::hash_pages].
     pub fn store_region_in_page(&mut self, addr: u32, bytes: &[u8]) {
         let page_idx = self.info.get_page_index(addr);
-        let page = self.pages.entry(page_idx).or_insert_with(|| {
+        let page_size = self.info.page_size as usize;
+        let page = self.pages.get_or_insert_with(page_idx, || {
             if addr as usize >= MEM_SIZE {
                 panic!("address {addr:08X} outside MEM_SIZE")
             }
-            vec![0_u8; self.info.page_size as usize]
+            Page::zeroed(page_size)
         });
         let page_start = self.info.get_page_addr(page_idx);
-        page[(addr - page_start) as usize..(addr - page_start) as usize + bytes.len()]
+        page.make_mut()[(addr - page_start) as usize..(addr - page_start) as usize + bytes.len()]
             .clone_from_slice(bytes);
+        self.dirty.insert(page_idx);
     }
 
     /// Reads the given byte array in this memory image at the given
@@ -239,7 +302,7 @@ impl MemoryImage {
         let page_idx = self.info.get_page_index(addr);
         let page_start = self.info.get_page_addr(page_idx);
 
-        if let Some(page) = self.pages.get(&page_idx) {
+        if let Some(page) = self.pages.get(page_idx) {
             bytes.clone_from_slice(
                 &page[(addr - page_start) as usize..(addr - page_start) as usize + bytes.len()],
             );
@@ -254,9 +317,20 @@ impl MemoryImage {
         Ok(())
     }
 
-    /// Calculate and update the image merkle tree within this image.
+    /// Update the image merkle tree within this image for the pages modified
+    /// since it was last updated.
+    ///
+    /// Only the page table entries on the path from each modified page to the
+    /// root are rehashed.
     pub fn hash_pages(&mut self) {
-        self.hash_pages_iter(0..self.info.num_pages)
+        // The entry for a page is always stored in a page with a greater index,
+        // so visiting pages in order updates each page table page after all of
+        // the entries within it.
+        while let Some(page_idx) = self.dirty.pop_first() {
+            if page_idx < self.info.root_idx {
+                self.update_page(page_idx);
+            }
+        }
     }
 
     /// Calculate and update the image merkle tree within this image based on
@@ -273,16 +347,77 @@ impl MemoryImage {
         let digest = self.hash_page(page_idx);
         let entry_addr = self.info.get_page_entry_addr(page_idx);
         self.store_region_in_page(entry_addr, digest.as_bytes());
+        self.dirty.remove(&page_idx);
     }
 
     fn hash_page(&self, page_idx: u32) -> Digest {
-        if let Some(page) = self.pages.get(&page_idx) {
+        if let Some(page) = self.pages.get(page_idx) {
             hash_page_bytes(page)
         } else {
             self.info.zero_page_hash
         }
     }
 
+    /// Build the page table for a newly loaded image.
+    ///
+    /// Each page table page is filled with the entries of the pages below it,
+    /// in order of page index. Most of memory is unused, and a page table page
+    /// whose entries are all the same is shared with every other such page,
+    /// rather than being stored and hashed separately.
+    fn build_page_table(&mut self) {
+        let info = self.info.clone();
+        let entries_per_page = info.page_size / DIGEST_BYTES as u32;
+        let mut hashes = Vec::with_capacity(info.root_idx as usize);
+        let mut uniform_pages: Vec<(Digest, Page, Digest)> = Vec::new();
+
+        for page_idx in 0..=info.root_idx {
+            let page_addr = info.get_page_addr(page_idx);
+            if page_addr + info.page_size <= info.page_table_addr {
+                hashes.push(self.hash_page(page_idx));
+                continue;
+            }
+
+            // The range of pages whose entries fall within this page.
+            let first = page_addr.saturating_sub(info.page_table_addr) / DIGEST_BYTES as u32;
+            let end = (first + entries_per_page).min(info.num_pages);
+            let entries = &hashes[first as usize..end as usize];
+
+            let is_uniform = page_addr >= info.page_table_addr
+                && end - first == entries_per_page
+                && !self.pages.contains(page_idx)
+                && entries.iter().all(|entry| *entry == entries[0]);
+            let shared = is_uniform
+                .then(|| {
+                    uniform_pages
+                        .iter()
+                        .find(|(entry, ..)| *entry == entries[0])
+                })
+                .flatten();
+            let hash = if let Some((_, page, hash)) = shared {
+                self.pages.insert(page_idx, page.clone());
+                *hash
+            } else {
+                let mut page = self.load_page(page_idx);
+                let offset = (info.get_page_entry_addr(first) - page_addr) as usize;
+                for (i, entry) in entries.iter().enumerate() {
+                    let entry_offset = offset + i * DIGEST_BYTES;
+                    page.make_mut()[entry_offset..entry_offset + DIGEST_BYTES]
+                        .copy_from_slice(entry.as_bytes());
+                }
+                let hash = hash_page_bytes(&page);
+                if is_uniform {
+                    uniform_pages.push((entries[0], page.clone(), hash));
+                }
+                self.pages.insert(page_idx, page);
+                hash
+            };
+            if page_idx < info.root_idx {
+                hashes.push(hash);
+            }
+        }
+        self.dirty.clear();
+    }
+
     /// Verify the integrity of the MemoryImage.
     ///
     /// Confirms that the page table is a valid Merkle tree with the expected
@@ -324,7 +459,7 @@ impl MemoryImage {
     pub fn compute_root_hash(&self) -> Digest {
         let root_page = self
             .pages
-            .get(&self.info.root_idx)
+            .get(self.info.root_idx)
             .expect("Missing root page?");
         hash_page_bytes(&root_page[..(self.info.root_addr - self.info.root_page_addr) as usize])
     }
@@ -388,6 +523,66 @@ mod tests {
         image.check(image.info.root_page_addr).unwrap();
     }
 
+    #[test]
+    fn page_table_matches_full_rehash() {
+        const PAGE_SIZE: u32 = 1024;
+        let program = Program::load_elf(MULTI_TEST_ELF, GUEST_MAX_MEM as u32).unwrap();
+        let image = MemoryImage::new(&program, PAGE_SIZE).unwrap();
+
+        let mut expected = image.clone();
+        expected.hash_pages_iter(0..expected.info.num_pages);
+        assert_eq!(image.compute_id(), expected.compute_id());
+    }
+
+    #[test]
+    fn lazy_rehash() {
+        const PAGE_SIZE: u32 = 1024;
+        let program = Program::load_elf(MULTI_TEST_ELF, GUEST_MAX_MEM as u32).unwrap();
+        let image = MemoryImage::new(&program, PAGE_SIZE).unwrap();
+        let original_id = image.compute_id();
+
+        let mut modified = image.clone();
+        modified.store_region_in_page(TEXT_START, &[1, 2, 3, 4]);
+        modified.store_region_in_page(STACK_TOP - 4, &[5, 6, 7, 8]);
+        modified.hash_pages();
+        modified.check(TEXT_START).unwrap();
+        modified.check(STACK_TOP - 4).unwrap();
+
+        let mut expected = modified.clone();
+        expected.hash_pages_iter(0..expected.info.num_pages);
+        assert_eq!(modified.compute_id(), expected.compute_id());
+
+        // The original is unchanged, and shares every page that was not modified.
+        assert_eq!(image.compute_id(), original_id);
+        assert_ne!(modified.compute_id(), original_id);
+        let text_idx = image.info.get_page_index(TEXT_START);
+        assert!(!image
+            .load_page(text_idx)
+            .ptr_eq(&modified.load_page(text_idx)));
+        let unused_idx = image.info.get_page_index(0x0400_0000);
+        let table_idx = image
+            .info
+            .get_page_index(image.info.get_page_entry_addr(unused_idx));
+        assert!(image
+            .load_page(table_idx)
+            .ptr_eq(&modified.load_page(table_idx)));
+    }
+
+    #[test]
+    fn deserialized_image_keeps_dirty_pages() {
+        const PAGE_SIZE: u32 = 1024;
+        let program = Program::load_elf(MULTI_TEST_ELF, GUEST_MAX_MEM as u32).unwrap();
+        let mut image = MemoryImage::new(&program, PAGE_SIZE).unwrap();
+        image.store_region_in_page(TEXT_START, &[1, 2, 3, 4]);
+
+        let mut decoded: MemoryImage =
+            bincode::deserialize(&bincode::serialize(&image).unwrap()).unwrap();
+        decoded.hash_pages();
+        image.hash_pages();
+        assert_eq!(decoded.compute_id(), image.compute_id());
+        decoded.check(TEXT_START).unwrap();
+    }
+
     #[test]
     fn page_table_info() {
         const PAGE_SIZE_1K: u32 = 1024;
@@ -24,10 +24,15 @@ mod exit_code;
 mod hash;
 #[cfg(not(target_os = "zkvm"))]
 mod image;
+#[cfg(not(target_os = "zkvm"))]
+mod page_store;
 mod sys_state;
 
 #[cfg(not(target_os = "zkvm"))]
-pub use self::image::{MemoryImage, PageTableInfo};
+pub use self::{
+    image::{MemoryImage, PageTableInfo},
+    page_store::{Page, PageIter, PageStore},
+};
 pub use crate::{
     elf::Program,
     exit_code::{ExitCode, InvalidExitCodeError},
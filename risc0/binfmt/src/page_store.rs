@@ -0,0 +1,345 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::ops::Deref;
+
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of bits of the page index consumed at each level of the trie.
+const LEVEL_BITS: u32 = 8;
+
+/// Number of children of each branch of the trie.
+const FANOUT: usize = 1 << LEVEL_BITS;
+
+/// Number of levels in the trie, enough to cover any `u32` page index.
+const LEVELS: u32 = u32::BITS / LEVEL_BITS;
+
+/// The contents of a page of memory.
+///
+/// Pages are reference counted, so cloning a page is cheap. The contents are copied the first
+/// time a shared page is modified.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Page(Arc<Vec<u8>>);
+
+impl Page {
+    /// Construct a page of the given size filled with zeros.
+    pub fn zeroed(size: usize) -> Self {
+        Self(Arc::new(vec![0; size]))
+    }
+
+    /// Return a mutable reference to the contents of this page, copying them first if they are
+    /// shared with another page.
+    pub fn make_mut(&mut self) -> &mut [u8] {
+        Arc::make_mut(&mut self.0).as_mut_slice()
+    }
+
+    /// Returns true if the two pages share the same storage.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for Page {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Page {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(Arc::new(bytes))
+    }
+}
+
+#[derive(Clone)]
+enum Slot {
+    Empty,
+    Branch(Arc<Vec<Slot>>),
+    Page(Page),
+}
+
+fn empty_branch() -> Arc<Vec<Slot>> {
+    Arc::new(vec![Slot::Empty; FANOUT])
+}
+
+/// Index into the branch at the given level for the given page index.
+fn slot_index(page_idx: u32, level: u32) -> usize {
+    ((page_idx >> ((LEVELS - 1 - level) * LEVEL_BITS)) as usize) & (FANOUT - 1)
+}
+
+/// A sparse, persistent map from page index to [Page].
+///
+/// The map is a trie of reference-counted nodes, so cloning it is cheap and the clone shares all
+/// of its pages and branches with the original. Modifying either copies only the branches on the
+/// path to the modified page, so a sequence of images that each differ in a few pages share
+/// storage for all the rest.
+#[derive(Clone)]
+pub struct PageStore {
+    root: Arc<Vec<Slot>>,
+    len: usize,
+}
+
+impl PageStore {
+    /// Construct an empty [PageStore].
+    pub fn new() -> Self {
+        Self {
+            root: empty_branch(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of pages in the store.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the store contains no pages.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the page at the given index, if present.
+    pub fn get(&self, page_idx: u32) -> Option<&Page> {
+        let mut branch = &self.root;
+        for level in 0..LEVELS - 1 {
+            match &branch[slot_index(page_idx, level)] {
+                Slot::Branch(next) => branch = next,
+                _ => return None,
+            }
+        }
+        match &branch[slot_index(page_idx, LEVELS - 1)] {
+            Slot::Page(page) => Some(page),
+            _ => None,
+        }
+    }
+
+    /// Returns true if a page is present at the given index.
+    pub fn contains(&self, page_idx: u32) -> bool {
+        self.get(page_idx).is_some()
+    }
+
+    /// Returns a mutable reference to the page at the given index, if present.
+    pub fn get_mut(&mut self, page_idx: u32) -> Option<&mut Page> {
+        if !self.contains(page_idx) {
+            return None;
+        }
+        match self.slot_mut(page_idx) {
+            Slot::Page(page) => Some(page),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns a mutable reference to the page at the given index, inserting the result of `f`
+    /// if the page is not present.
+    pub fn get_or_insert_with(&mut self, page_idx: u32, f: impl FnOnce() -> Page) -> &mut Page {
+        if !self.contains(page_idx) {
+            self.len += 1;
+        }
+        let slot = self.slot_mut(page_idx);
+        if let Slot::Empty = slot {
+            *slot = Slot::Page(f());
+        }
+        match slot {
+            Slot::Page(page) => page,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Insert a page at the given index, returning the page it replaced, if any.
+    pub fn insert(&mut self, page_idx: u32, page: Page) -> Option<Page> {
+        let old = match core::mem::replace(self.slot_mut(page_idx), Slot::Page(page)) {
+            Slot::Page(old) => Some(old),
+            _ => None,
+        };
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Returns an iterator over the pages in the store, in order of their index.
+    pub fn iter(&self) -> PageIter<'_> {
+        PageIter {
+            stack: vec![(self.root.as_slice(), 0, 0)],
+        }
+    }
+
+    // Returns the leaf slot for the given page index, copying any shared branches on the path to
+    // it and creating any missing ones.
+    fn slot_mut(&mut self, page_idx: u32) -> &mut Slot {
+        let mut branch = Arc::make_mut(&mut self.root);
+        for level in 0..LEVELS - 1 {
+            let slot = &mut branch[slot_index(page_idx, level)];
+            if let Slot::Empty = slot {
+                *slot = Slot::Branch(empty_branch());
+            }
+            branch = match slot {
+                Slot::Branch(next) => Arc::make_mut(next),
+                _ => unreachable!(),
+            };
+        }
+        &mut branch[slot_index(page_idx, LEVELS - 1)]
+    }
+}
+
+impl Default for PageStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<(u32, Page)> for PageStore {
+    fn from_iter<I: IntoIterator<Item = (u32, Page)>>(iter: I) -> Self {
+        let mut store = Self::new();
+        for (page_idx, page) in iter {
+            store.insert(page_idx, page);
+        }
+        store
+    }
+}
+
+/// An iterator over the pages of a [PageStore], in order of their index.
+pub struct PageIter<'a> {
+    // Branches being visited, along with the position of the next slot to visit and the prefix of
+    // the page indices below that branch.
+    stack: Vec<(&'a [Slot], usize, u32)>,
+}
+
+impl<'a> Iterator for PageIter<'a> {
+    type Item = (u32, &'a Page);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let &mut (branch, ref mut pos, prefix) = self.stack.last_mut()?;
+            let Some(slot) = branch.get(*pos) else {
+                self.stack.pop();
+                continue;
+            };
+            let page_idx = (prefix << LEVEL_BITS) | *pos as u32;
+            *pos += 1;
+            match slot {
+                Slot::Empty => {}
+                Slot::Page(page) => return Some((page_idx, page)),
+                Slot::Branch(next) => self.stack.push((next.as_slice(), 0, page_idx)),
+            }
+        }
+    }
+}
+
+// Serialized in the same form as a `BTreeMap<u32, Vec<u8>>`.
+impl Serialize for PageStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len))?;
+        for (page_idx, page) in self.iter() {
+            map.serialize_entry(&page_idx, &**page)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PageStore {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pages = BTreeMap::<u32, Vec<u8>>::deserialize(deserializer)?;
+        Ok(pages
+            .into_iter()
+            .map(|(page_idx, page)| (page_idx, page.into()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{Page, PageStore};
+
+    #[test]
+    fn insert_and_get() {
+        let mut store = PageStore::new();
+        assert!(store.is_empty());
+        for page_idx in [0, 1, 255, 256, 0x1234_5678, u32::MAX] {
+            assert!(store
+                .insert(page_idx, vec![page_idx as u8; 4].into())
+                .is_none());
+        }
+        assert_eq!(store.len(), 6);
+        assert_eq!(&**store.get(0x1234_5678).unwrap(), &[0x78; 4]);
+        assert!(store.get(2).is_none());
+        assert!(store.get(0x1234_5679).is_none());
+
+        let old = store.insert(1, vec![9; 4].into()).unwrap();
+        assert_eq!(&*old, &[1; 4]);
+        assert_eq!(store.len(), 6);
+    }
+
+    #[test]
+    fn iter_in_order() {
+        let indices = [u32::MAX, 7, 0x100, 0, 0x0001_0000, 0xff];
+        let store: PageStore = indices
+            .iter()
+            .map(|&page_idx| (page_idx, Page::zeroed(4)))
+            .collect();
+        let mut expected = indices.to_vec();
+        expected.sort();
+        let actual: Vec<u32> = store.iter().map(|(page_idx, _)| page_idx).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let mut store = PageStore::new();
+        store.insert(3, Page::zeroed(4));
+        store.insert(0x1_0000, Page::zeroed(4));
+
+        let mut copy = store.clone();
+        copy.get_mut(3).unwrap().make_mut()[0] = 1;
+        copy.insert(4, Page::zeroed(4));
+
+        // The original is unchanged, and untouched pages are still shared.
+        assert_eq!(&**store.get(3).unwrap(), &[0; 4]);
+        assert_eq!(&**copy.get(3).unwrap(), &[1, 0, 0, 0]);
+        assert!(!store.contains(4));
+        assert_eq!(store.len(), 2);
+        assert_eq!(copy.len(), 3);
+        assert!(store
+            .get(0x1_0000)
+            .unwrap()
+            .ptr_eq(copy.get(0x1_0000).unwrap()));
+    }
+
+    #[test]
+    fn serde_matches_btree_map() {
+        let map = BTreeMap::from([(1u32, vec![1u8, 2]), (0x1_0000, vec![3, 4])]);
+        let store: PageStore = map
+            .iter()
+            .map(|(&page_idx, page)| (page_idx, page.clone().into()))
+            .collect();
+
+        let encoded = bincode::serialize(&store).unwrap();
+        assert_eq!(encoded, bincode::serialize(&map).unwrap());
+        let decoded: PageStore = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(
+            decoded
+                .iter()
+                .map(|(page_idx, page)| (page_idx, page.to_vec()))
+                .collect::<BTreeMap<_, _>>(),
+            map
+        );
+    }
+}
@@ -24,7 +24,7 @@ use risc0_zkvm_platform::{
 };
 use tempfile::tempdir;
 
-use crate::get_env_var;
+use crate::{get_env_var, FRAME_POINTER_FLAGS};
 
 const DOCKER_IGNORE: &str = r#"
 **/Dockerfile
@@ -37,6 +37,15 @@ const DOCKER_IGNORE: &str = r#"
 /// The target directory for the ELF binaries.
 pub const TARGET_DIR: &str = "target/riscv-guest/riscv32im-risc0-zkvm-elf/docker";
 
+/// The version of the risc0 toolchain in the Docker image.
+pub(crate) const RISC0_TOOLCHAIN_VERSION: &str = "r0.1.78.0";
+
+/// Where the Docker context is copied to in the Docker image.
+pub(crate) const DOCKER_SRC_DIR: &str = "/src";
+
+/// The CARGO_HOME of the Docker image.
+pub(crate) const DOCKER_CARGO_HOME: &str = "/root/.cargo";
+
 /// Indicates weather the build was successful or skipped.
 pub enum BuildStatus {
     /// The build was successful.
@@ -50,6 +59,17 @@ pub fn docker_build(
     manifest_path: &Path,
     src_dir: &Path,
     features: &[String],
+) -> Result<BuildStatus> {
+    docker_build_guest(manifest_path, src_dir, features, false)
+}
+
+/// Build as [docker_build], keeping frame pointers in the guest if
+/// `frame_pointers` is set, see [crate::GuestOptions::frame_pointers].
+pub(crate) fn docker_build_guest(
+    manifest_path: &Path,
+    src_dir: &Path,
+    features: &[String],
+    frame_pointers: bool,
 ) -> Result<BuildStatus> {
     if !get_env_var("RISC0_SKIP_BUILD").is_empty() {
         eprintln!("Skipping build because RISC0_SKIP_BUILD is set");
@@ -88,7 +108,13 @@ pub fn docker_build(
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path();
         let rel_manifest_path = manifest_path.strip_prefix(&src_dir)?;
-        create_dockerfile(rel_manifest_path, temp_path, pkg_name.as_str(), features)?;
+        create_dockerfile(
+            rel_manifest_path,
+            temp_path,
+            pkg_name.as_str(),
+            features,
+            frame_pointers,
+        )?;
         build(&src_dir, temp_path)?;
     }
     println!("ELFs ready at:");
@@ -112,11 +138,10 @@ fn create_dockerfile(
     temp_dir: &Path,
     pkg_name: &str,
     features: &[String],
+    frame_pointers: bool,
 ) -> Result<()> {
     let manifest_env = &[("CARGO_MANIFEST_PATH", manifest_path.to_str().unwrap())];
-    let rustflags = format!(
-        "-C passes=loweratomic -C link-arg=-Ttext=0x{TEXT_START:08X} -C link-arg=--fatal-warnings",
-    );
+    let rustflags = guest_rustflags(frame_pointers).join(" ");
     let rustflags_env = &[("RUSTFLAGS", rustflags.as_str())];
 
     let common_args = vec![
@@ -144,9 +169,10 @@ fn create_dockerfile(
     .concat()
     .join(" ");
 
+    let builder_image = format!("risczero/risc0-guest-builder:{RISC0_TOOLCHAIN_VERSION}");
     let build = DockerFile::new()
-        .from_alias("build", "risczero/risc0-guest-builder:r0.1.78.0")
-        .workdir("/src")
+        .from_alias("build", &builder_image)
+        .workdir(DOCKER_SRC_DIR)
         .copy(".", ".")
         .env(manifest_env)
         .env(rustflags_env)
@@ -173,6 +199,24 @@ fn create_dockerfile(
     Ok(())
 }
 
+/// The rustflags that guests are built with in the Docker image.
+pub(crate) fn guest_rustflags(frame_pointers: bool) -> Vec<String> {
+    let mut rustflags = [
+        "-C",
+        "passes=loweratomic",
+        "-C",
+        &format!("link-arg=-Ttext=0x{TEXT_START:08X}"),
+        "-C",
+        "link-arg=--fatal-warnings",
+    ]
+    .map(str::to_string)
+    .to_vec();
+    if frame_pointers {
+        rustflags.extend(FRAME_POINTER_FLAGS.map(str::to_string));
+    }
+    rustflags
+}
+
 /// Build the dockerfile and outputs the ELF.
 ///
 /// Overwrites if an ELF with the same name already exists.
@@ -208,7 +252,7 @@ fn check_cargo_lock(manifest_path: &Path) -> Result<()> {
 }
 
 /// Compute the image ID for a given ELF.
-fn compute_image_id(elf_path: &Path) -> Result<String> {
+pub(crate) fn compute_image_id(elf_path: &Path) -> Result<String> {
     let elf = fs::read(elf_path)?;
     let program = Program::load_elf(&elf, GUEST_MAX_MEM as u32).context("unable to load elf")?;
     let image =
@@ -0,0 +1,348 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reproducible guest builds with the locally installed toolchain.
+//!
+//! A hermetic build recreates the environment of [docker_build] without
+//! Docker: it checks that the installed `risc0` toolchain is the one in the
+//! Docker image, builds offline from dependencies vendored from `Cargo.lock`,
+//! ignores any rustflags set in the environment or cargo config, and remaps
+//! the paths embedded in the ELF to the ones they have inside the Docker
+//! image. Guests whose dependencies all come from crates.io or from paths
+//! within the build context get the same image IDs either way, which
+//! [verify_hermetic_build] checks. Other dependencies, such as git
+//! dependencies, are rejected, since their paths can't be remapped to the
+//! ones cargo uses inside the Docker image.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use cargo_metadata::{MetadataCommand, Package};
+use tempfile::tempdir;
+
+use crate::{
+    docker::{
+        compute_image_id, docker_build, guest_rustflags, DOCKER_CARGO_HOME, DOCKER_SRC_DIR,
+        RISC0_TOOLCHAIN_VERSION,
+    },
+    get_env_var, sanitized_cmd, BuildStatus, TARGET_DIR,
+};
+
+/// The target directory for hermetic builds.
+pub const HERMETIC_TARGET_DIR: &str = "target/riscv-guest/hermetic";
+
+/// Build the package in the manifest path reproducibly, without Docker.
+///
+/// The ELF binaries are written to
+/// `HERMETIC_TARGET_DIR/riscv32im-risc0-zkvm-elf/release` within `src_dir`,
+/// which plays the role of the Docker context.
+pub fn hermetic_build(
+    manifest_path: &Path,
+    src_dir: &Path,
+    features: &[String],
+) -> Result<BuildStatus> {
+    hermetic_build_guest(manifest_path, src_dir, features, false)
+}
+
+/// Build as [hermetic_build], keeping frame pointers in the guest if
+/// `frame_pointers` is set, see [crate::GuestOptions::frame_pointers].
+pub(crate) fn hermetic_build_guest(
+    manifest_path: &Path,
+    src_dir: &Path,
+    features: &[String],
+    frame_pointers: bool,
+) -> Result<BuildStatus> {
+    if !get_env_var("RISC0_SKIP_BUILD").is_empty() {
+        eprintln!("Skipping build because RISC0_SKIP_BUILD is set");
+        return Ok(BuildStatus::Skipped);
+    }
+
+    let manifest_path = manifest_path
+        .canonicalize()
+        .context(format!("manifest_path: {manifest_path:?}"))?;
+    let src_dir = src_dir.canonicalize().context("src_dir")?;
+    let rel_manifest_path = manifest_path.strip_prefix(&src_dir)?;
+    check_toolchain()?;
+    // Use the risc0 toolchain's cargo, which unpacks crates.io packages to
+    // the same directory as the one in the Docker image.
+    let meta = MetadataCommand::new()
+        .cargo_path("cargo")
+        .env("RUSTUP_TOOLCHAIN", "risc0")
+        .manifest_path(&manifest_path)
+        .exec()
+        .context("Manifest not found")?;
+    let root_pkg = meta.root_package().context("Failed to parse Cargo.toml")?;
+    let registry_src_dir = registry_src_dir(&src_dir, &meta.packages)?;
+
+    eprintln!("Hermetic build context: {src_dir:?}");
+    eprintln!(
+        "Building ELF binaries in {} for riscv32im-risc0-zkvm-elf target...",
+        root_pkg.name
+    );
+
+    let vendor = tempdir()?;
+    let vendor_dir = vendor.path().join("vendor");
+    let vendor_config = vendor.path().join("config.toml");
+    vendor_dependencies(&manifest_path, &vendor_dir, &vendor_config)?;
+
+    let target_dir = src_dir.join(HERMETIC_TARGET_DIR);
+    let remaps = path_remaps(
+        &src_dir,
+        &target_dir,
+        &vendor_dir,
+        registry_src_dir.as_deref(),
+    );
+    let mut rustflags = guest_rustflags(frame_pointers);
+    for (from, to) in remaps.iter() {
+        rustflags.push(format!(
+            "--remap-path-prefix={}={}",
+            from.display(),
+            to.display()
+        ));
+    }
+
+    let mut cmd = sanitized_cmd("cargo");
+    cmd.args(["+risc0", "build", "--release", "--locked", "--offline"])
+        .args(["--target", "riscv32im-risc0-zkvm-elf"])
+        .arg("--manifest-path")
+        .arg(rel_manifest_path)
+        .arg("--config")
+        .arg(&vendor_config)
+        .current_dir(&src_dir)
+        .env_remove("RUSTFLAGS")
+        .env("CARGO_ENCODED_RUSTFLAGS", rustflags.join("\x1f"))
+        .env("CARGO_TARGET_DIR", &target_dir);
+    if !features.is_empty() {
+        cmd.arg("--features").arg(features.join(","));
+    }
+    if !cmd.status().context("cargo failed to execute")?.success() {
+        bail!("hermetic build failed");
+    }
+
+    println!("ELFs ready at:");
+    for target in root_pkg.targets.iter().filter(|t| t.is_bin()) {
+        let rel_elf_path = elf_dir(Path::new(HERMETIC_TARGET_DIR)).join(&target.name);
+        let image_id = compute_image_id(&src_dir.join(&rel_elf_path))?;
+        println!("ImageID: {} - {:?}", image_id, rel_elf_path);
+    }
+
+    Ok(BuildStatus::Success)
+}
+
+/// Build the package in the manifest path both with [hermetic_build] and with
+/// [docker_build], and check that each binary has the same image ID.
+pub fn verify_hermetic_build(
+    manifest_path: &Path,
+    src_dir: &Path,
+    features: &[String],
+) -> Result<()> {
+    if let BuildStatus::Skipped = hermetic_build(manifest_path, src_dir, features)? {
+        return Ok(());
+    }
+    docker_build(manifest_path, src_dir, features)?;
+
+    let meta = MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .exec()
+        .context("Manifest not found")?;
+    let root_pkg = meta.root_package().context("Failed to parse Cargo.toml")?;
+    let docker_dir = src_dir
+        .join(TARGET_DIR)
+        .join(root_pkg.name.replace('-', "_"));
+    let hermetic_dir = elf_dir(&src_dir.join(HERMETIC_TARGET_DIR));
+
+    let mut mismatches = String::new();
+    for target in root_pkg.targets.iter().filter(|t| t.is_bin()) {
+        let docker_id = compute_image_id(&docker_dir.join(&target.name))?;
+        let hermetic_id = compute_image_id(&hermetic_dir.join(&target.name))?;
+        if docker_id != hermetic_id {
+            mismatches += &format!(
+                "\n  {}: docker {docker_id}, hermetic {hermetic_id}",
+                target.name
+            );
+        }
+    }
+    ensure!(
+        mismatches.is_empty(),
+        "hermetic build differs from the Docker build:{mismatches}"
+    );
+    println!("Hermetic build matches the Docker build");
+    Ok(())
+}
+
+// Returns the path prefixes to remap to the paths they have inside the Docker image, given the
+// directory where cargo unpacks crates.io packages, relative to CARGO_HOME. rustc uses the last
+// matching prefix, so more specific paths come later.
+fn path_remaps(
+    src_dir: &Path,
+    target_dir: &Path,
+    vendor_dir: &Path,
+    registry_src_dir: Option<&Path>,
+) -> Vec<(PathBuf, PathBuf)> {
+    let docker_src_dir = Path::new(DOCKER_SRC_DIR);
+    let mut remaps = vec![
+        (src_dir.to_path_buf(), docker_src_dir.to_path_buf()),
+        (target_dir.to_path_buf(), docker_src_dir.join("target")),
+    ];
+    if let Some(registry_src_dir) = registry_src_dir {
+        remaps.push((
+            vendor_dir.to_path_buf(),
+            Path::new(DOCKER_CARGO_HOME).join(registry_src_dir),
+        ));
+    }
+    remaps
+}
+
+// Checks that every package is either from crates.io or a path within src_dir, which are the
+// only ones whose paths can be remapped to the ones in the Docker image. Returns the directory
+// where cargo unpacks crates.io packages, relative to CARGO_HOME, if there are any.
+fn registry_src_dir(src_dir: &Path, packages: &[Package]) -> Result<Option<PathBuf>> {
+    let mut registry_src_dir = None;
+    for pkg in packages {
+        let manifest_path = pkg.manifest_path.as_std_path();
+        match &pkg.source {
+            None => ensure!(
+                manifest_path.starts_with(src_dir),
+                "hermetic builds require path dependencies to be within {}, \
+                but {} is at {}",
+                src_dir.display(),
+                pkg.name,
+                manifest_path.display()
+            ),
+            Some(source) if source.is_crates_io() => {
+                registry_src_dir = Some(crates_io_src_dir(manifest_path)?);
+            }
+            Some(source) => bail!(
+                "hermetic builds only support crates.io and path dependencies, \
+                but {} comes from {source}",
+                pkg.name
+            ),
+        }
+    }
+    Ok(registry_src_dir)
+}
+
+// Returns the directory, relative to CARGO_HOME, of the crates.io package with the given
+// manifest, which cargo unpacks to `registry/src/<index>/<name>-<version>/Cargo.toml`.
+fn crates_io_src_dir(manifest_path: &Path) -> Result<PathBuf> {
+    let index = manifest_path
+        .parent()
+        .and_then(Path::parent)
+        .filter(|dir| {
+            dir.parent()
+                .is_some_and(|dir| dir.ends_with("registry/src"))
+        })
+        .and_then(Path::file_name)
+        .with_context(|| format!("unexpected crates.io package path: {manifest_path:?}"))?;
+    Ok(Path::new("registry/src").join(index))
+}
+
+fn elf_dir(target_dir: &Path) -> PathBuf {
+    target_dir.join("riscv32im-risc0-zkvm-elf").join("release")
+}
+
+// Checks that the installed risc0 toolchain is the one used by the Docker image.
+fn check_toolchain() -> Result<()> {
+    let output = sanitized_cmd("rustc")
+        .args(["+risc0", "--version"])
+        .output()
+        .context("rustc failed to execute")?;
+    ensure!(output.status.success(), "risc0 toolchain not found");
+
+    let version = String::from_utf8(output.stdout)?;
+    let expected = RISC0_TOOLCHAIN_VERSION.trim_start_matches("r0.");
+    ensure!(
+        version.contains(expected),
+        "hermetic builds require the {RISC0_TOOLCHAIN_VERSION} toolchain, found {}. \
+        Install it with `cargo risczero install --version {RISC0_TOOLCHAIN_VERSION}`.",
+        version.trim()
+    );
+    Ok(())
+}
+
+// Vendors the dependencies in Cargo.lock, writing the cargo config to build with them.
+fn vendor_dependencies(manifest_path: &Path, vendor_dir: &Path, config: &Path) -> Result<()> {
+    let output = sanitized_cmd("cargo")
+        .args(["+risc0", "vendor", "--locked", "--versioned-dirs"])
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .arg(vendor_dir)
+        .output()
+        .context("cargo vendor failed to execute")?;
+    if !output.status.success() {
+        bail!(
+            "cargo vendor failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    fs::write(config, output.stdout)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{crates_io_src_dir, path_remaps};
+
+    #[test]
+    fn remaps() {
+        let registry_src_dir = crates_io_src_dir(Path::new(
+            "/home/user/.cargo/registry/src/index-1a2b/serde-1.0.0/Cargo.toml",
+        ))
+        .unwrap();
+        assert_eq!(registry_src_dir, Path::new("registry/src/index-1a2b"));
+        assert!(crates_io_src_dir(Path::new("/home/user/serde-1.0.0/Cargo.toml")).is_err());
+
+        let remaps = path_remaps(
+            Path::new("/work"),
+            Path::new("/work/target/riscv-guest/hermetic"),
+            Path::new("/tmp/vendor"),
+            Some(&registry_src_dir),
+        );
+        let expected: Vec<(PathBuf, PathBuf)> = [
+            ("/work", "/src"),
+            ("/work/target/riscv-guest/hermetic", "/src/target"),
+            ("/tmp/vendor", "/root/.cargo/registry/src/index-1a2b"),
+        ]
+        .into_iter()
+        .map(|(from, to)| (from.into(), to.into()))
+        .collect();
+        assert_eq!(remaps, expected);
+
+        let remaps = path_remaps(
+            Path::new("/work"),
+            Path::new("/work/target/riscv-guest/hermetic"),
+            Path::new("/tmp/vendor"),
+            None,
+        );
+        assert_eq!(remaps, expected[..2]);
+    }
+
+    // requires Docker to be installed
+    #[cfg(feature = "docker")]
+    #[test]
+    fn test_hermetic_matches_docker() {
+        use super::verify_hermetic_build;
+
+        const SRC_DIR: &str = "../..";
+
+        let manifest_path = Path::new("../../risc0/zkvm/methods/guest/Cargo.toml");
+        verify_hermetic_build(manifest_path, Path::new(SRC_DIR), &[]).unwrap();
+    }
+}
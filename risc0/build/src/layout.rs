@@ -0,0 +1,141 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Custom placement of the guest's stack and heap in memory.
+//!
+//! By default, the stack grows down from `STACK_TOP` to the bottom of guest
+//! memory, and a stack overflow silently corrupts whatever it runs into. A
+//! [MemoryLayout] instead places a guard region below a stack of a chosen
+//! size, followed by the program and then the heap. The layout is passed to
+//! the linker as symbols that the guest runtime reads at startup, and that the
+//! executor uses to report accesses to the guard region as a stack overflow.
+//! Such guests start at an entry point of their own, so that guests with the
+//! default layout keep the same `_start`, and with it the same image ID.
+
+use anyhow::{ensure, Result};
+use risc0_zkvm_platform::{
+    memory::{
+        GUEST_MIN_MEM, HEAP_END_SYMBOL, STACK_BOTTOM_SYMBOL, STACK_GUARD_SYMBOL, STACK_TOP,
+        STACK_TOP_SYMBOL, START_WITH_STACK_TOP_SYMBOL, SYSTEM, TEXT_START,
+    },
+    PAGE_SIZE,
+};
+
+/// Default size of the guard region below the stack, in bytes.
+pub const DEFAULT_STACK_GUARD_SIZE: usize = 4 * 1024;
+
+/// The layout of a guest's stack and heap, set in [crate::GuestOptions].
+#[derive(Clone, Debug)]
+pub struct MemoryLayout {
+    /// Size of the stack, in bytes. Rounded up to a whole number of pages.
+    pub stack_size: usize,
+
+    /// Size of the guard region below the stack, in bytes. Rounded up to a
+    /// whole number of pages. Any load or store in this region ends execution
+    /// with a stack overflow fault. A size of zero disables the guard.
+    pub stack_guard_size: usize,
+
+    /// Maximum size of the heap, in bytes, or `None` to let the heap grow
+    /// until it reaches system memory. Allocating beyond the limit panics.
+    pub heap_limit: Option<usize>,
+}
+
+impl Default for MemoryLayout {
+    /// The same amount of stack as the default layout, with a guard region
+    /// below it.
+    fn default() -> Self {
+        Self {
+            stack_size: STACK_TOP as usize - GUEST_MIN_MEM,
+            stack_guard_size: DEFAULT_STACK_GUARD_SIZE,
+            heap_limit: None,
+        }
+    }
+}
+
+impl MemoryLayout {
+    /// Returns the rustflags that link a guest with this layout.
+    pub(crate) fn rustflags(&self) -> Result<Vec<String>> {
+        let guard_start = GUEST_MIN_MEM;
+        let stack_bottom = guard_start + align_up(self.stack_guard_size);
+        let stack_top = stack_bottom + align_up(self.stack_size);
+        // Leave the same gap between the stack and the program as the default layout.
+        let text_start = stack_top + (TEXT_START - STACK_TOP) as usize;
+        ensure!(self.stack_size > 0, "stack size must not be zero");
+        ensure!(
+            text_start < SYSTEM.start(),
+            "stack of {} bytes does not fit in guest memory",
+            self.stack_size
+        );
+
+        let mut link_args = vec![
+            format!("--entry={START_WITH_STACK_TOP_SYMBOL}"),
+            format!("-Ttext=0x{text_start:08X}"),
+            format!("--defsym={STACK_GUARD_SYMBOL}=0x{guard_start:08X}"),
+            format!("--defsym={STACK_BOTTOM_SYMBOL}=0x{stack_bottom:08X}"),
+            format!("--defsym={STACK_TOP_SYMBOL}=0x{stack_top:08X}"),
+        ];
+        if let Some(heap_limit) = self.heap_limit {
+            // The heap starts at `_end`, the end of the program's sections.
+            link_args.push(format!("--defsym={HEAP_END_SYMBOL}=_end+0x{heap_limit:X}"));
+        }
+        Ok(link_args
+            .into_iter()
+            .flat_map(|arg| ["-C".to_string(), format!("link-arg={arg}")])
+            .collect())
+    }
+}
+
+fn align_up(size: usize) -> usize {
+    size.next_multiple_of(PAGE_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryLayout;
+
+    #[test]
+    fn layout_rustflags() {
+        let layout = MemoryLayout {
+            stack_size: 64 * 1024 - 1,
+            stack_guard_size: 1024,
+            heap_limit: Some(0x10000),
+        };
+        assert_eq!(
+            layout.rustflags().unwrap(),
+            [
+                "-C",
+                "link-arg=--entry=_start_with_stack_top",
+                "-C",
+                "link-arg=-Ttext=0x00010C00",
+                "-C",
+                "link-arg=--defsym=__risc0_stack_guard=0x00000400",
+                "-C",
+                "link-arg=--defsym=__risc0_stack_bottom=0x00000800",
+                "-C",
+                "link-arg=--defsym=__risc0_stack_top=0x00010800",
+                "-C",
+                "link-arg=--defsym=__risc0_heap_end=_end+0x10000",
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_too_large() {
+        let layout = MemoryLayout {
+            stack_size: 1 << 30,
+            ..Default::default()
+        };
+        assert!(layout.rustflags().is_err());
+    }
+}
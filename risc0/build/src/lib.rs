@@ -18,6 +18,9 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 mod docker;
+mod hermetic;
+mod layout;
+mod lock;
 
 use std::{
     borrow::Cow,
@@ -33,11 +36,17 @@ use std::{
 use anyhow::{Context, Result};
 use cargo_metadata::{Message, MetadataCommand, Package};
 use risc0_binfmt::compute_image_id;
-use risc0_zkp::core::digest::DIGEST_WORDS;
+use risc0_zkp::core::digest::{Digest, DIGEST_WORDS};
 use risc0_zkvm_platform::memory;
 use serde::Deserialize;
 
 pub use docker::{docker_build, BuildStatus, TARGET_DIR};
+pub use hermetic::{hermetic_build, verify_hermetic_build, HERMETIC_TARGET_DIR};
+
+use docker::docker_build_guest;
+use hermetic::hermetic_build_guest;
+pub use layout::{MemoryLayout, DEFAULT_STACK_GUARD_SIZE};
+pub use lock::{METHODS_LOCK, UPDATE_METHODS_LOCK_ENV};
 
 const RUSTUP_TOOLCHAIN_NAME: &str = "risc0";
 
@@ -248,6 +257,28 @@ where
         .collect()
 }
 
+/// Returns all methods associated with the given guest crate, built by [hermetic_build].
+fn guest_methods_hermetic(pkg: &Package, src_dir: &Path) -> Vec<GuestListEntry> {
+    pkg.targets
+        .iter()
+        .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+        .map(|target| {
+            GuestListEntry::build(
+                &target.name,
+                src_dir
+                    .join(HERMETIC_TARGET_DIR)
+                    .join("riscv32im-risc0-zkvm-elf")
+                    .join("release")
+                    .join(&target.name)
+                    .to_str()
+                    .context("elf path contains invalid unicode")
+                    .unwrap(),
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
 fn get_env_var(name: &str) -> String {
     println!("cargo:rerun-if-env-changed={name}");
     env::var(name).unwrap_or_default()
@@ -264,9 +295,23 @@ fn sanitized_cmd(tool: &str) -> Command {
     cmd
 }
 
+/// The rustflags that keep frame pointers in a guest, so that the executor
+/// can recover the guest's stack when it panics.
+const FRAME_POINTER_FLAGS: [&str; 2] = ["-C", "force-frame-pointers=yes"];
+
 /// Creates a std::process::Command to execute the given cargo
 /// command in an environment suitable for targeting the zkvm guest.
 pub fn cargo_command(subcmd: &str, rust_flags: &[&str]) -> Command {
+    guest_cargo_command(subcmd, rust_flags, None)
+}
+
+// Creates the cargo command for [cargo_command], linking the guest with the
+// given memory layout if any.
+fn guest_cargo_command(
+    subcmd: &str,
+    rust_flags: &[&str],
+    memory_layout: Option<&[String]>,
+) -> Command {
     let rustc = sanitized_cmd("rustup")
         .args(["+risc0", "which", "rustc"])
         .output()
@@ -295,19 +340,25 @@ pub fn cargo_command(subcmd: &str, rust_flags: &[&str]) -> Command {
 
     println!("Building guest package: cargo {}", args.join(" "));
 
+    let text_start = format!("link-arg=-Ttext=0x{:08X}", memory::TEXT_START);
+    let layout_flags: Vec<&str> = match memory_layout {
+        // The layout specifies where the program is loaded, after the stack.
+        Some(flags) => flags.iter().map(String::as_str).collect(),
+        // Specify where to start loading the program in
+        // memory.  The clang linker understands the same
+        // command line arguments as the GNU linker does; see
+        // https://ftp.gnu.org/old-gnu/Manuals/ld-2.9.1/html_mono/ld.html#SEC3
+        // for details.
+        None => vec!["-C", &text_start],
+    };
+
     let rustflags_envvar = [
         rust_flags,
+        &layout_flags,
         &[
             // Replace atomic ops with nonatomic versions since the guest is single threaded.
             "-C",
             "passes=loweratomic",
-            // Specify where to start loading the program in
-            // memory.  The clang linker understands the same
-            // command line arguments as the GNU linker does; see
-            // https://ftp.gnu.org/old-gnu/Manuals/ld-2.9.1/html_mono/ld.html#SEC3
-            // for details.
-            "-C",
-            &format!("link-arg=-Ttext=0x{:08X}", memory::TEXT_START),
             // Apparently not having an entry point is only a linker warning(!), so
             // error out in this case.
             "-C",
@@ -427,11 +478,22 @@ fn build_guest_package<P>(
 
     fs::create_dir_all(target_dir.as_ref()).unwrap();
 
-    let mut cmd = if let Some(lib) = runtime_lib {
-        cargo_command("build", &["-C", &format!("link_arg={}", lib)])
-    } else {
-        cargo_command("build", &[])
-    };
+    let memory_layout = guest_opts.memory_layout.as_ref().map(|layout| {
+        layout
+            .rustflags()
+            .with_context(|| format!("invalid memory layout for {}", pkg.name))
+            .unwrap()
+    });
+    let memory_layout = memory_layout.as_deref();
+    let link_arg = runtime_lib.map(|lib| format!("link_arg={}", lib));
+    let mut rust_flags = Vec::new();
+    if let Some(link_arg) = link_arg.as_deref() {
+        rust_flags.extend(["-C", link_arg]);
+    }
+    if guest_opts.frame_pointers {
+        rust_flags.extend(FRAME_POINTER_FLAGS);
+    }
+    let mut cmd = guest_cargo_command("build", &rust_flags, memory_layout);
 
     let features_str = guest_opts.features.join(",");
     if !features_str.is_empty() {
@@ -522,6 +584,16 @@ pub struct DockerOptions {
     pub root_dir: Option<PathBuf>,
 }
 
+/// Options for configuring a hermetic build, see [hermetic_build].
+#[derive(Clone)]
+pub struct HermeticOptions {
+    /// Specify the root directory for hermetic builds, which corresponds to
+    /// the root directory of docker builds.
+    ///
+    /// The current working directory is used if `None` is specified.
+    pub root_dir: Option<PathBuf>,
+}
+
 /// Options defining how to embed a guest package in
 /// [`embed_methods_with_options`].
 #[derive(Default)]
@@ -531,6 +603,24 @@ pub struct GuestOptions {
 
     /// Use a docker environment for building.
     pub use_docker: Option<DockerOptions>,
+
+    /// Build reproducibly with the installed toolchain, as with docker but
+    /// without it. Ignored if `use_docker` is set.
+    pub use_hermetic: Option<HermeticOptions>,
+
+    /// Place the stack and heap according to this layout, rather than the
+    /// default of a stack below the program without a guard region. Only used
+    /// by local builds; docker and hermetic builds use the default layout so
+    /// that their image IDs can be reproduced.
+    pub memory_layout: Option<MemoryLayout>,
+
+    /// Keep frame pointers in the guest, so that the backtrace of a guest
+    /// panic includes the callers of the function that panicked.
+    ///
+    /// This applies to every kind of build, but changes the image ID of the
+    /// guest and adds a few cycles to each function call, so it is off by
+    /// default.
+    pub frame_pointers: bool,
 }
 
 fn get_guest_dir() -> PathBuf {
@@ -578,6 +668,7 @@ pub fn embed_methods_with_options(
     detect_toolchain(RUSTUP_TOOLCHAIN_NAME);
 
     let mut guest_list = vec![];
+    let mut locked_methods = vec![];
     for guest_pkg in guest_packages {
         println!("Building guest package {}.{}", pkg.name, guest_pkg.name);
 
@@ -585,23 +676,42 @@ pub fn embed_methods_with_options(
             .remove(guest_pkg.name.as_str())
             .unwrap_or_default();
 
-        let methods = if let Some(docker_opts) = guest_opts.use_docker {
+        let methods = if let Some(docker_opts) = &guest_opts.use_docker {
             let src_dir = docker_opts
                 .root_dir
+                .clone()
                 .unwrap_or_else(|| std::env::current_dir().unwrap());
-            docker_build(
+            docker_build_guest(
                 guest_pkg.manifest_path.as_std_path(),
                 &src_dir,
                 &guest_opts.features,
+                guest_opts.frame_pointers,
             )
             .unwrap();
             guest_methods_docker(&guest_pkg, &guest_dir)
+        } else if let Some(hermetic_opts) = &guest_opts.use_hermetic {
+            let src_dir = hermetic_opts
+                .root_dir
+                .clone()
+                .unwrap_or_else(|| std::env::current_dir().unwrap());
+            hermetic_build_guest(
+                guest_pkg.manifest_path.as_std_path(),
+                &src_dir,
+                &guest_opts.features,
+                guest_opts.frame_pointers,
+            )
+            .unwrap();
+            guest_methods_hermetic(&guest_pkg, &src_dir)
         } else {
             build_guest_package(&guest_pkg, &guest_dir, &guest_opts, None);
             guest_methods(&guest_pkg, &guest_dir)
         };
 
         for method in methods {
+            locked_methods.push((
+                format!("{}/{}", guest_pkg.name, method.name),
+                Digest::from(method.image_id).to_string(),
+            ));
             methods_file
                 .write_all(method.codegen_consts().as_bytes())
                 .unwrap();
@@ -623,6 +733,12 @@ pub fn embed_methods_with_options(
         )
         .unwrap();
 
+    lock::check_methods_lock(
+        pkg.manifest_path.parent().unwrap().as_std_path(),
+        locked_methods,
+    )
+    .unwrap();
+
     // HACK: It's not particularly practical to figure out all the
     // files that all the guest crates transitively depend on.  So, we
     // want to run the guest "cargo build" command each time we build.
@@ -0,0 +1,157 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::get_env_var;
+
+/// The name of the file recording the image ID of each guest method.
+pub const METHODS_LOCK: &str = "methods.lock";
+
+/// Set this environment variable to `1` to create or update a `methods.lock`.
+pub const UPDATE_METHODS_LOCK_ENV: &str = "RISC0_UPDATE_METHODS_LOCK";
+
+const HEADER: &str = "\
+# This file is generated by risc0-build and records the image ID of each guest
+# method, by package and binary name. The build fails if the methods or their
+# image IDs change, unless it is run with RISC0_UPDATE_METHODS_LOCK=1 to update
+# this file.
+";
+
+/// Checks the image IDs of the given methods, keyed by `package/bin`, against
+/// the `methods.lock` in `dir`, or creates or updates it if
+/// [UPDATE_METHODS_LOCK_ENV] is set.
+///
+/// Checking is opt-in: nothing is checked until a `methods.lock` exists. A
+/// method that is added, removed or whose image ID changed is an error, which
+/// lists the changes. The file is only written when updating, since this runs
+/// in build scripts, which should otherwise leave the source tree alone.
+pub(crate) fn check_methods_lock(
+    dir: &Path,
+    methods: impl IntoIterator<Item = (String, String)>,
+) -> Result<()> {
+    let path = dir.join(METHODS_LOCK);
+    let update = get_env_var(UPDATE_METHODS_LOCK_ENV) == "1";
+    let actual: BTreeMap<String, String> = methods.into_iter().collect();
+
+    if update {
+        let contents = format(&actual);
+        if fs::read_to_string(&path).ok().as_deref() != Some(contents.as_str()) {
+            fs::write(&path, contents).with_context(|| format!("{}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let expected = parse(&contents).with_context(|| format!("{}", path.display()))?;
+    let diff = diff(&expected, &actual);
+    if !diff.is_empty() {
+        bail!(
+            "image IDs differ from {}:\n{diff}\n\
+            If these changes are expected, build with {UPDATE_METHODS_LOCK_ENV}=1 to update it.",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn parse(contents: &str) -> Result<BTreeMap<String, String>> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, image_id) = line
+                .split_once('=')
+                .with_context(|| format!("invalid line: {line}"))?;
+            Ok((
+                name.trim().trim_matches('"').to_string(),
+                image_id.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn format(methods: &BTreeMap<String, String>) -> String {
+    let mut contents = HEADER.to_string();
+    for (name, image_id) in methods {
+        writeln!(contents, "\"{name}\" = \"{image_id}\"").unwrap();
+    }
+    contents
+}
+
+// Lists the methods which were added, removed or whose image ID changed, in the style of a
+// unified diff.
+fn diff(expected: &BTreeMap<String, String>, actual: &BTreeMap<String, String>) -> String {
+    let mut diff = String::new();
+    let names: BTreeSet<_> = expected.keys().chain(actual.keys()).collect();
+    for name in names {
+        match (expected.get(name), actual.get(name)) {
+            (Some(locked), Some(image_id)) if image_id == locked => (),
+            (locked, image_id) => {
+                if let Some(locked) = locked {
+                    writeln!(diff, "-{name} = \"{locked}\"").unwrap();
+                }
+                if let Some(image_id) = image_id {
+                    writeln!(diff, "+{name} = \"{image_id}\"").unwrap();
+                }
+            }
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{diff, format, parse};
+
+    #[test]
+    fn roundtrip() {
+        let methods = BTreeMap::from([
+            ("methods-guest/multiply".to_string(), "00aa".to_string()),
+            ("other-guest/hello".to_string(), "11bb".to_string()),
+        ]);
+        assert_eq!(parse(&format(&methods)).unwrap(), methods);
+    }
+
+    #[test]
+    fn changes() {
+        let expected = BTreeMap::from([
+            ("multiply".to_string(), "00aa".to_string()),
+            ("removed".to_string(), "11bb".to_string()),
+            ("same".to_string(), "22cc".to_string()),
+        ]);
+        let actual = BTreeMap::from([
+            ("added".to_string(), "33dd".to_string()),
+            ("multiply".to_string(), "44ee".to_string()),
+            ("same".to_string(), "22cc".to_string()),
+        ]);
+        assert_eq!(
+            diff(&expected, &actual),
+            "+added = \"33dd\"\n-multiply = \"00aa\"\n+multiply = \"44ee\"\n-removed = \"11bb\"\n"
+        );
+        assert!(diff(&expected, &expected).is_empty());
+    }
+}
@@ -32,9 +32,13 @@ fn main() -> Result<()> {
         RisczeroCmd::New(cmd) => cmd.run(),
         RisczeroCmd::Deploy(cmd) => cmd.run(),
         RisczeroCmd::Verify(cmd) => cmd.run(),
+        #[cfg(feature = "r0vm")]
+        RisczeroCmd::Run(cmd) => cmd.run(),
+        #[cfg(feature = "r0vm")]
+        RisczeroCmd::Estimate(cmd) => cmd.run(),
+        #[cfg(feature = "r0vm")]
+        RisczeroCmd::Test(cmd) => cmd.run(),
         #[cfg(feature = "experimental")]
         RisczeroCmd::BuildCrate(build) => build.run(BuildSubcommand::Build),
-        #[cfg(feature = "experimental")]
-        RisczeroCmd::Test(build) => build.run(BuildSubcommand::Test),
     }
 }
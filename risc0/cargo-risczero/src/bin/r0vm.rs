@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     #[cfg(feature = "r0vm")]
-    risc0_r0vm::main()
+    risc0_r0vm::main()?;
+    Ok(())
 }
@@ -20,7 +20,8 @@ use risc0_build::BuildStatus;
 
 /// `cargo risczero build`
 ///
-/// NOTE: Requires Docker to be installed and running.
+/// NOTE: Requires Docker to be installed and running, unless `--hermetic` is
+/// given.
 #[derive(Parser)]
 pub struct BuildGuest {
     /// Location of the Cargo.toml for the guest code.
@@ -32,11 +33,27 @@ pub struct BuildGuest {
     /// Feature flags passed to cargo.
     #[arg(long, value_delimiter = ',')]
     pub features: Vec<String>,
+
+    /// Build reproducibly with the installed toolchain instead of Docker.
+    #[arg(long)]
+    pub hermetic: bool,
+
+    /// Build both with Docker and hermetically, and check that the image IDs
+    /// match.
+    #[arg(long, requires = "hermetic")]
+    pub verify: bool,
 }
 
 impl BuildGuest {
     pub fn run(&self) -> Result<()> {
-        build(&self.manifest_path, &self.features)?;
+        let src_dir = std::env::current_dir()?;
+        if self.verify {
+            risc0_build::verify_hermetic_build(&self.manifest_path, &src_dir, &self.features)?;
+        } else if self.hermetic {
+            risc0_build::hermetic_build(&self.manifest_path, &src_dir, &self.features)?;
+        } else {
+            build(&self.manifest_path, &self.features)?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,99 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use risc0_zkvm::{CostEstimate, HardwareProfile};
+
+use super::run::{print_session, RunCommand};
+
+/// `cargo risczero estimate`
+///
+/// Executes a guest without proving it, and estimates the cost of proving it
+/// on a given machine.
+#[derive(Parser)]
+pub struct EstimateCommand {
+    /// The hardware profile of the machine to estimate for, as written to
+    /// `target/hotbench/calibrate/profile.json` by
+    /// `cargo bench -p risc0-zkvm --bench calibrate`.
+    #[arg(long)]
+    pub profile: PathBuf,
+
+    #[command(flatten)]
+    pub run: RunCommand,
+}
+
+impl EstimateCommand {
+    pub fn run(&self) -> Result<()> {
+        let profile = HardwareProfile::load(&self.profile)?;
+        let (session, paging) = self.run.execute()?;
+        print_session(&session, paging);
+        if let Some(panic) = &session.panic {
+            bail!("{panic}");
+        }
+        if let Some(fault) = &session.fault {
+            bail!("{fault}");
+        }
+
+        println!();
+        println!("Estimates for {}:", profile.name);
+        println!(
+            "{:<12} {:>14} {:>14} {:>14}",
+            "Receipt", "Proving time", "Peak memory", "Receipt size"
+        );
+        for estimate in session.estimate(&profile)? {
+            print_estimate(&estimate);
+        }
+        Ok(())
+    }
+}
+
+fn print_estimate(estimate: &CostEstimate) {
+    println!(
+        "{:<12} {:>14} {:>14} {:>14}",
+        format!("{:?}", estimate.receipt_kind),
+        format!("{:.2?}", estimate.proving_time),
+        format_bytes(estimate.peak_memory),
+        format_bytes(estimate.receipt_size),
+    );
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_bytes;
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(256), "256 B");
+        assert_eq!(format_bytes(217 * 1024), "217.0 KiB");
+        assert_eq!(format_bytes(3 << 30), "3.0 GiB");
+    }
+}
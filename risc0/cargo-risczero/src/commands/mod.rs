@@ -17,6 +17,12 @@ pub mod build;
 pub mod build_guest;
 pub mod build_toolchain;
 pub mod deploy;
+#[cfg(feature = "r0vm")]
+pub mod estimate;
 pub mod install;
 pub mod new;
+#[cfg(feature = "r0vm")]
+pub mod run;
+#[cfg(feature = "r0vm")]
+pub mod test;
 pub mod verify;
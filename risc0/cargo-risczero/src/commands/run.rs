@@ -0,0 +1,291 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::Cell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use risc0_build::{get_package, TARGET_DIR};
+use risc0_zkvm::{ExecutorEnv, ExecutorEnvBuilder, ExecutorImpl, Session, TraceEvent};
+use serde::Deserialize;
+
+use super::build_guest;
+
+/// `cargo risczero run`
+///
+/// Executes a guest without proving it, and reports its cycles.
+#[derive(Parser)]
+pub struct RunCommand {
+    /// The guest to run: an ELF binary, or the Cargo.toml of a guest package
+    /// to build with Docker first.
+    pub guest: PathBuf,
+
+    /// The binary to run, if the guest package has more than one.
+    #[arg(long)]
+    pub bin: Option<String>,
+
+    /// Feature flags passed to cargo when building a guest package.
+    #[arg(long, value_delimiter = ',')]
+    pub features: Vec<String>,
+
+    /// File to read the guest's stdin from, or `-` to read from this
+    /// process's stdin.
+    #[arg(long)]
+    pub stdin: Option<PathBuf>,
+
+    /// JSON file holding an array of typed values, such as `{"u32": 7}` or
+    /// `{"string": "hello"}`, which the guest reads in order with
+    /// `env::read`.
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+
+    /// Add environment variables in the form of NAME=value.
+    #[arg(long, action = clap::ArgAction::Append)]
+    pub env: Vec<String>,
+
+    /// Set a segment limit, specified in powers of 2 cycles.
+    #[arg(long)]
+    pub segment_limit_po2: Option<u32>,
+
+    /// Directory to save the segments to, for proving later.
+    #[arg(long)]
+    pub segments_out: Option<PathBuf>,
+
+    /// Arguments passed to the guest.
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+/// A value written to the guest's input, tagged with its type so that it is
+/// serialized as the guest expects.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Input {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    /// Hex-encoded bytes, read by the guest as a `Vec<u8>`.
+    Bytes(String),
+    /// Words, read by the guest as a `Vec<u32>`.
+    Words(Vec<u32>),
+    /// Hex-encoded bytes, written as is and read by the guest with
+    /// `env::read_slice`.
+    Raw(String),
+}
+
+impl Input {
+    fn write_to(&self, builder: &mut ExecutorEnvBuilder<'_>) -> Result<()> {
+        match self {
+            Input::Bool(value) => builder.write(value)?,
+            Input::U8(value) => builder.write(value)?,
+            Input::U16(value) => builder.write(value)?,
+            Input::U32(value) => builder.write(value)?,
+            Input::U64(value) => builder.write(value)?,
+            Input::I8(value) => builder.write(value)?,
+            Input::I16(value) => builder.write(value)?,
+            Input::I32(value) => builder.write(value)?,
+            Input::I64(value) => builder.write(value)?,
+            Input::F32(value) => builder.write(value)?,
+            Input::F64(value) => builder.write(value)?,
+            Input::String(value) => builder.write(value)?,
+            Input::Bytes(value) => builder.write(&hex::decode(value)?)?,
+            Input::Words(value) => builder.write(value)?,
+            Input::Raw(value) => builder.write_slice(&hex::decode(value)?),
+        };
+        Ok(())
+    }
+}
+
+impl RunCommand {
+    pub fn run(&self) -> Result<()> {
+        let (session, paging) = self.execute()?;
+        print_session(&session, paging);
+        if let Some(panic) = &session.panic {
+            bail!("{panic}");
+        }
+        if let Some(fault) = &session.fault {
+            bail!("{fault}");
+        }
+        Ok(())
+    }
+
+    /// Executes the guest, building it first if needed. Returns the session,
+    /// and the number of instructions that paged memory along with the cycles
+    /// they spent paging.
+    pub(crate) fn execute(&self) -> Result<(Session, (u64, u64))> {
+        let elf = fs::read(self.elf_path()?)?;
+
+        // Count the cycles spent paging, which are reported by trace events.
+        let paging = Rc::new(Cell::new((0u64, 0u64)));
+        let env = self.build_env(paging.clone())?;
+        let session = ExecutorImpl::from_elf(env, &elf)?.run()?;
+        Ok((session, paging.get()))
+    }
+
+    fn build_env<'a>(&self, paging: Rc<Cell<(u64, u64)>>) -> Result<ExecutorEnv<'a>> {
+        let mut builder = ExecutorEnv::builder();
+        builder.args(&self.args);
+        for var in self.env.iter() {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| anyhow!("environment variables should be of the form NAME=value"))?;
+            builder.env_var(name, value);
+        }
+
+        match &self.stdin {
+            Some(path) if path == Path::new("-") => {
+                builder.stdin(std::io::stdin());
+            }
+            Some(path) => {
+                builder.stdin(fs::File::open(path).context("failed to open stdin file")?);
+            }
+            None => (),
+        }
+
+        if let Some(path) = &self.input {
+            let inputs: Vec<Input> = serde_json::from_slice(&fs::read(path)?)
+                .with_context(|| format!("failed to parse inputs in {}", path.display()))?;
+            for input in inputs.iter() {
+                input.write_to(&mut builder)?;
+            }
+        }
+
+        if let Some(po2) = self.segment_limit_po2 {
+            builder.segment_limit_po2(po2);
+        }
+
+        if let Some(path) = &self.segments_out {
+            fs::create_dir_all(path)?;
+            builder.segment_path(path);
+        }
+
+        builder.trace_callback(move |event| {
+            if let TraceEvent::Paging { cycles } = event {
+                let (count, total) = paging.get();
+                paging.set((count + 1, total + cycles));
+            }
+            Ok(())
+        });
+
+        builder.build()
+    }
+
+    // Returns the path of the ELF to run, building the guest package first if needed.
+    fn elf_path(&self) -> Result<PathBuf> {
+        let manifest_path = if self.guest.is_dir() {
+            self.guest.join("Cargo.toml")
+        } else if self
+            .guest
+            .file_name()
+            .is_some_and(|name| name == "Cargo.toml")
+        {
+            self.guest.clone()
+        } else {
+            return Ok(self.guest.clone());
+        };
+
+        build_guest::build(&manifest_path, &self.features)?;
+
+        let manifest_dir = manifest_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let pkg = get_package(manifest_dir);
+        let bins: Vec<_> = pkg
+            .targets
+            .iter()
+            .filter(|target| target.is_bin())
+            .collect();
+        let bin = match (&self.bin, bins.as_slice()) {
+            (Some(name), _) => bins
+                .iter()
+                .find(|target| &target.name == name)
+                .ok_or_else(|| anyhow!("no binary named {name} in {}", pkg.name))?,
+            (None, [bin]) => bin,
+            (None, _) => bail!(
+                "{} has more than one binary, use --bin to choose one of: {}",
+                pkg.name,
+                bins.iter()
+                    .map(|target| target.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        Ok(std::env::current_dir()?
+            .join(TARGET_DIR)
+            .join(pkg.name.replace('-', "_"))
+            .join(&bin.name))
+    }
+}
+
+pub(crate) fn print_session(session: &Session, (paging_count, paging_cycles): (u64, u64)) {
+    println!("Exit code: {:?}", session.exit_code);
+    if let Some(journal) = &session.journal {
+        println!(
+            "Journal ({} bytes): {}",
+            journal.bytes.len(),
+            hex::encode(&journal.bytes)
+        );
+    }
+    println!("Segments: {}", session.segments.len());
+    println!("Total cycles: {}", session.total_cycles);
+    println!("User cycles: {}", session.user_cycles);
+    println!("Paging cycles: {paging_cycles} ({paging_count} instructions paged memory)");
+    println!(
+        "Reserved and padding cycles: {}",
+        session
+            .total_cycles
+            .saturating_sub(session.user_cycles + paging_cycles)
+    );
+    if let Some(report) = &session.profile_report {
+        println!("{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+
+    #[test]
+    fn parse_inputs() {
+        let inputs: Vec<Input> = serde_json::from_str(
+            r#"[{"u32": 7}, {"string": "hello"}, {"bytes": "00ff"}, {"words": [1, 2]}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inputs,
+            [
+                Input::U32(7),
+                Input::String("hello".into()),
+                Input::Bytes("00ff".into()),
+                Input::Words(vec![1, 2]),
+            ]
+        );
+    }
+}
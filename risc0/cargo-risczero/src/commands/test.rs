@@ -0,0 +1,764 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    rc::Rc,
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use cargo_metadata::{Artifact, ArtifactProfile, Message, Target};
+use clap::Parser;
+use risc0_build::cargo_command;
+use risc0_zkvm::{Bytes, ExecutorEnv, ExecutorEnvBuilder, ExecutorImpl, ExitCode, Session};
+use syn::{Attribute, Expr, ExprLit, Item, Lit, Meta};
+
+/// The default number of cycles after which a test fails.
+pub const DEFAULT_CYCLE_LIMIT: u64 = 1 << 28;
+
+/// `cargo risczero test`
+///
+/// Builds the tests of a guest crate and runs each test in the executor.
+#[derive(Parser)]
+pub struct TestCommand {
+    /// Path to the Cargo.toml file for the crate to be tested.
+    #[arg(long, default_value = "./Cargo.toml")]
+    pub manifest_path: PathBuf,
+
+    /// Only run tests whose names contain this string.
+    pub filter: Option<String>,
+
+    /// Only run the test whose name is exactly the filter.
+    #[arg(long)]
+    pub exact: bool,
+
+    /// Only run ignored tests.
+    #[arg(long, conflicts_with = "include_ignored")]
+    pub ignored: bool,
+
+    /// Run ignored tests along with the others.
+    #[arg(long)]
+    pub include_ignored: bool,
+
+    /// Fail a test after it runs for this many cycles.
+    #[arg(long, default_value_t = DEFAULT_CYCLE_LIMIT)]
+    pub cycle_limit: u64,
+
+    /// Reply to a host syscall made by the guest with the contents of a file,
+    /// in the form of NAME=PATH.
+    #[arg(long = "mock-syscall", action = clap::ArgAction::Append)]
+    pub mock_syscalls: Vec<String>,
+
+    /// Additional arguments to pass to "cargo test --no-run".
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+impl TestCommand {
+    /// Execute this command.
+    pub fn run(&self) -> Result<()> {
+        let mut harness = TestHarness::new().cycle_limit(self.cycle_limit);
+        for mock in self.mock_syscalls.iter() {
+            let (name, path) = mock
+                .split_once('=')
+                .ok_or_else(|| anyhow!("mock syscalls should be of the form NAME=PATH"))?;
+            let reply = Bytes::from(fs::read(path).with_context(|| format!("mock {name}"))?);
+            harness = harness.mock_syscall(name, move |_| Ok(reply.clone()));
+        }
+
+        let filter = TestFilter {
+            filter: self.filter.clone(),
+            exact: self.exact,
+            ignored: self.ignored,
+            include_ignored: self.include_ignored,
+        };
+
+        let mut failed = false;
+        for test_bin in build_tests(&self.manifest_path, &self.args)? {
+            eprintln!("     Running {}", test_bin.path.display());
+            let elf = fs::read(&test_bin.path)?;
+            failed |= !harness
+                .run(
+                    &test_bin.path.to_string_lossy(),
+                    &elf,
+                    &filter,
+                    &test_bin.expected_panics,
+                )?
+                .success();
+        }
+        if failed {
+            bail!("test failed");
+        }
+        Ok(())
+    }
+}
+
+/// Selects the tests to run, as with the arguments of a libtest binary.
+#[derive(Clone, Debug, Default)]
+pub struct TestFilter {
+    /// Only run tests whose names contain this string.
+    pub filter: Option<String>,
+
+    /// Only run the test whose name is exactly the filter.
+    pub exact: bool,
+
+    /// Only run ignored tests.
+    pub ignored: bool,
+
+    /// Run ignored tests along with the others.
+    pub include_ignored: bool,
+}
+
+impl TestFilter {
+    fn matches(&self, name: &str) -> bool {
+        match &self.filter {
+            Some(filter) if self.exact => name == filter,
+            Some(filter) => name.contains(filter.as_str()),
+            None => true,
+        }
+    }
+}
+
+/// The tests that are expected to panic, marked with `#[should_panic]`, by
+/// name. Each is mapped to the substring that its panic message is expected to
+/// contain, if any.
+pub type ExpectedPanics = HashMap<String, Option<String>>;
+
+/// Finds the tests that are expected to panic in the crate with the given root
+/// source file, by reading the `#[should_panic]` attributes of its `#[test]`
+/// functions.
+///
+/// Modules are followed as the compiler does, but attributes generated by
+/// macros are not seen.
+pub fn expected_panics(src_path: &Path) -> Result<ExpectedPanics> {
+    let mut panics = ExpectedPanics::new();
+    let dir = src_path.parent().context("invalid source path")?;
+    collect_expected_panics(src_path, dir, "", &mut panics)?;
+    Ok(panics)
+}
+
+// Collects the expected panics of the module in the given file, whose child
+// modules are in `dir`.
+fn collect_expected_panics(
+    path: &Path,
+    dir: &Path,
+    prefix: &str,
+    panics: &mut ExpectedPanics,
+) -> Result<()> {
+    let src =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let file =
+        syn::parse_file(&src).with_context(|| format!("failed to parse {}", path.display()))?;
+    collect_items(&file.items, path, dir, prefix, panics)
+}
+
+fn collect_items(
+    items: &[Item],
+    path: &Path,
+    dir: &Path,
+    prefix: &str,
+    panics: &mut ExpectedPanics,
+) -> Result<()> {
+    for item in items {
+        match item {
+            Item::Fn(func) => {
+                let is_test = func.attrs.iter().any(|attr| {
+                    attr.path()
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "test")
+                });
+                let should_panic = func
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path().is_ident("should_panic"));
+                if let (true, Some(attr)) = (is_test, should_panic) {
+                    panics.insert(
+                        format!("{prefix}{}", func.sig.ident),
+                        expected_message(attr)?,
+                    );
+                }
+            }
+            Item::Mod(module) => {
+                let name = module.ident.to_string();
+                let prefix = format!("{prefix}{name}::");
+                let mod_path = module.attrs.iter().find_map(path_attr);
+                let child_dir = dir.join(&name);
+                match (&module.content, mod_path) {
+                    (Some((_, items)), _) => {
+                        collect_items(items, path, &child_dir, &prefix, panics)?
+                    }
+                    (None, Some(mod_path)) => {
+                        let mod_path = path.parent().unwrap_or(dir).join(mod_path);
+                        let mod_dir = mod_path.parent().unwrap_or(dir).to_path_buf();
+                        collect_expected_panics(&mod_path, &mod_dir, &prefix, panics)?
+                    }
+                    (None, None) => {
+                        // Modules that are configured out may have no file.
+                        let file = [dir.join(format!("{name}.rs")), child_dir.join("mod.rs")]
+                            .into_iter()
+                            .find(|file| file.exists());
+                        if let Some(file) = file {
+                            collect_expected_panics(&file, &child_dir, &prefix, panics)?
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+// Returns the substring expected by `#[should_panic(expected = "...")]` or
+// `#[should_panic = "..."]`, if any.
+fn expected_message(attr: &Attribute) -> Result<Option<String>> {
+    let expected = match &attr.meta {
+        Meta::Path(_) => None,
+        Meta::NameValue(meta) => Some(meta.value.clone()),
+        Meta::List(_) => {
+            let meta: syn::MetaNameValue = attr.parse_args()?;
+            ensure!(
+                meta.path.is_ident("expected"),
+                "unsupported should_panic attribute"
+            );
+            Some(meta.value)
+        }
+    };
+    match expected {
+        None => Ok(None),
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        })) => Ok(Some(lit.value())),
+        Some(_) => bail!("unsupported should_panic attribute"),
+    }
+}
+
+// Returns the file given by a `#[path = "..."]` attribute.
+fn path_attr(attr: &Attribute) -> Option<PathBuf> {
+    match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => Some(lit.value().into()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The outcome of a test run in the executor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    /// The test passed after running for this many user cycles.
+    Passed {
+        /// User cycles used by the test
+        cycles: u64,
+    },
+
+    /// The test failed.
+    Failed {
+        /// User cycles used by the test, if execution completed.
+        cycles: Option<u64>,
+
+        /// Why the test failed, along with its output.
+        reason: String,
+    },
+
+    /// The test is ignored.
+    Ignored,
+}
+
+/// The results of running the tests in a test binary.
+#[derive(Clone, Debug, Default)]
+pub struct TestReport {
+    /// The outcome of each test that was selected, by name.
+    pub tests: Vec<(String, TestOutcome)>,
+
+    /// The number of tests that did not match the filter.
+    pub filtered_out: usize,
+}
+
+impl TestReport {
+    /// Returns true if no test failed.
+    pub fn success(&self) -> bool {
+        !self
+            .tests
+            .iter()
+            .any(|(_, outcome)| matches!(outcome, TestOutcome::Failed { .. }))
+    }
+}
+
+type SyscallMock = Rc<dyn Fn(Bytes) -> Result<Bytes>>;
+
+/// Runs the `#[test]` functions of a test binary built for the zkVM, each in its own execution.
+///
+/// Since guests abort on panic, running each test separately lets the remaining tests run after
+/// one fails. A panicking test is reported with the message the guest sent through `sys_panic`.
+pub struct TestHarness {
+    cycle_limit: u64,
+    mocks: Vec<(String, SyscallMock)>,
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHarness {
+    /// Construct a [TestHarness] with the default cycle limit and no mock syscalls.
+    pub fn new() -> Self {
+        Self {
+            cycle_limit: DEFAULT_CYCLE_LIMIT,
+            mocks: Vec::new(),
+        }
+    }
+
+    /// Fail a test after it runs for this many cycles.
+    pub fn cycle_limit(mut self, cycle_limit: u64) -> Self {
+        self.cycle_limit = cycle_limit;
+        self
+    }
+
+    /// Handle the host syscall with the given name with a mock, as registered with
+    /// [ExecutorEnvBuilder::io_callback].
+    pub fn mock_syscall(
+        mut self,
+        name: &str,
+        mock: impl Fn(Bytes) -> Result<Bytes> + 'static,
+    ) -> Self {
+        self.mocks.push((name.to_string(), Rc::new(mock)));
+        self
+    }
+
+    /// Run the tests in the given test binary that match the filter, printing
+    /// the results in the same format as libtest.
+    ///
+    /// A test in `expected_panics` passes only if it panics with the expected
+    /// message, as found by [expected_panics].
+    pub fn run(
+        &self,
+        name: &str,
+        elf: &[u8],
+        filter: &TestFilter,
+        expected_panics: &ExpectedPanics,
+    ) -> Result<TestReport> {
+        let start = Instant::now();
+        let all = self.list(name, elf, false)?;
+        let ignored = self.list(name, elf, true)?;
+
+        let mut report = TestReport::default();
+        let selected: Vec<_> = all
+            .into_iter()
+            .filter(|test| {
+                let matches = filter.matches(test) && (!filter.ignored || ignored.contains(test));
+                report.filtered_out += !matches as usize;
+                matches
+            })
+            .collect();
+
+        println!();
+        println!("running {} tests", selected.len());
+        for test in selected {
+            let is_ignored = ignored.contains(&test);
+            let outcome = if is_ignored && !filter.ignored && !filter.include_ignored {
+                TestOutcome::Ignored
+            } else {
+                let expected_panic = expected_panics.get(&test);
+                self.run_test(name, elf, &test, is_ignored, expected_panic)
+            };
+            match &outcome {
+                TestOutcome::Passed { cycles } => println!("test {test} ... ok ({cycles} cycles)"),
+                TestOutcome::Failed {
+                    cycles: Some(cycles),
+                    ..
+                } => println!("test {test} ... FAILED ({cycles} cycles)"),
+                TestOutcome::Failed { cycles: None, .. } => println!("test {test} ... FAILED"),
+                TestOutcome::Ignored => println!("test {test} ... ignored"),
+            }
+            report.tests.push((test, outcome));
+        }
+
+        print_summary(&report, start.elapsed().as_secs_f64());
+        Ok(report)
+    }
+
+    // Lists the tests in the binary, or only the ignored tests.
+    fn list(&self, name: &str, elf: &[u8], ignored: bool) -> Result<Vec<String>> {
+        let mut args = vec![name, "--list", "--format", "terse"];
+        if ignored {
+            args.push("--ignored");
+        }
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let env = self
+            .env_builder(&args, output.clone())
+            .build()
+            .context("failed to list tests")?;
+        let session = ExecutorImpl::from_elf(env, elf)?.run()?;
+        if session.exit_code != ExitCode::Halted(0) {
+            bail!(
+                "listing tests exited with code {:?}: {}",
+                session.exit_code,
+                String::from_utf8_lossy(&output.borrow())
+            );
+        }
+        let output = String::from_utf8(output.take())?;
+        Ok(parse_list(&output))
+    }
+
+    fn run_test(
+        &self,
+        name: &str,
+        elf: &[u8],
+        test: &str,
+        ignored: bool,
+        expected_panic: Option<&Option<String>>,
+    ) -> TestOutcome {
+        let mut args = vec![name, test, "--exact", "--nocapture", "--test-threads=1"];
+        if ignored {
+            args.push("--ignored");
+        }
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let session = self
+            .env_builder(&args, output.clone())
+            .build()
+            .and_then(|env| ExecutorImpl::from_elf(env, elf)?.run());
+        let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+        let session = match session {
+            Ok(session) => session,
+            Err(err) => {
+                return TestOutcome::Failed {
+                    cycles: None,
+                    reason: format!("{output}{err:#}"),
+                }
+            }
+        };
+        let result = match expected_panic {
+            Some(expected) => check_expected_panic(&session, expected.as_deref()),
+            None if session.exit_code == ExitCode::Halted(0) => Ok(()),
+            None => Err(failure_reason(&session)),
+        };
+        match result {
+            Ok(()) => TestOutcome::Passed {
+                cycles: session.user_cycles,
+            },
+            Err(reason) => TestOutcome::Failed {
+                cycles: Some(session.user_cycles),
+                reason: format!("{output}{reason}"),
+            },
+        }
+    }
+
+    fn env_builder<'a>(
+        &self,
+        args: &[&str],
+        output: Rc<RefCell<Vec<u8>>>,
+    ) -> ExecutorEnvBuilder<'a> {
+        let mut builder = ExecutorEnv::builder();
+        builder
+            .args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+            .session_limit(Some(self.cycle_limit))
+            .stdout(SharedWriter(output.clone()))
+            .stderr(SharedWriter(output));
+        for (name, mock) in self.mocks.iter() {
+            let mock = mock.clone();
+            builder.io_callback(name, move |from_guest| mock(from_guest));
+        }
+        builder
+    }
+}
+
+// Describes why a test that was not expected to panic failed.
+fn failure_reason(session: &Session) -> String {
+    match (&session.panic, &session.fault) {
+        (Some(panic), _) => panic.to_string(),
+        (None, Some(fault)) => fault.to_string(),
+        (None, None) => format!("test exited with code {:?}", session.exit_code),
+    }
+}
+
+// Checks that a `#[should_panic]` test panicked with the expected message, with
+// the same failure messages as libtest.
+fn check_expected_panic(session: &Session, expected: Option<&str>) -> Result<(), String> {
+    let Some(panic) = &session.panic else {
+        return Err(match session.exit_code {
+            ExitCode::Halted(0) => "note: test did not panic as expected".to_string(),
+            _ => failure_reason(session),
+        });
+    };
+    match expected {
+        Some(expected) if !panic.message.contains(expected) => Err(format!(
+            "note: panic did not contain expected string\n      panic message: `{:?}`,\n expected substring: `{expected:?}`",
+            panic.message
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Collects the stdout and stderr of a test, in the order they are written.
+struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Parses the output of `--list --format terse`, one `name: test` line per test.
+fn parse_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(str::to_string)
+        .collect()
+}
+
+fn print_summary(report: &TestReport, elapsed: f64) {
+    let failures: Vec<_> = report
+        .tests
+        .iter()
+        .filter_map(|(test, outcome)| match outcome {
+            TestOutcome::Failed { reason, .. } => Some((test, reason)),
+            _ => None,
+        })
+        .collect();
+    if !failures.is_empty() {
+        println!();
+        println!("failures:");
+        for (test, reason) in failures.iter() {
+            println!();
+            println!("---- {test} stdout ----");
+            println!("{reason}");
+        }
+        println!();
+        println!("failures:");
+        for (test, _) in failures.iter() {
+            println!("    {test}");
+        }
+    }
+
+    let count = |f: fn(&TestOutcome) -> bool| {
+        report
+            .tests
+            .iter()
+            .filter(|(_, outcome)| f(outcome))
+            .count()
+    };
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed; {} ignored; 0 measured; {} filtered out; finished in {elapsed:.2}s",
+        if report.success() { "ok" } else { "FAILED" },
+        count(|outcome| matches!(outcome, TestOutcome::Passed { .. })),
+        failures.len(),
+        count(|outcome| matches!(outcome, TestOutcome::Ignored)),
+        report.filtered_out,
+    );
+    println!();
+}
+
+// A test binary built for the zkVM.
+struct TestBinary {
+    path: PathBuf,
+    expected_panics: ExpectedPanics,
+}
+
+// Builds the tests of the crate for the zkVM, returning the test binaries.
+fn build_tests(manifest_path: &Path, args: &[String]) -> Result<Vec<TestBinary>> {
+    let manifest_path = fs::canonicalize(manifest_path).with_context(|| {
+        format!(
+            "failed to resolve manifest path `{}`",
+            manifest_path.display()
+        )
+    })?;
+    let target_dir = risc0_build::get_target_dir(&manifest_path);
+
+    let mut cmd = cargo_command("test", &[]);
+    cmd.args(["--no-run", "--message-format=json", "--manifest-path"])
+        .arg(&manifest_path)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(args);
+
+    let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+    let reader = BufReader::new(
+        child
+            .stdout
+            .take()
+            .ok_or(anyhow!("failed to read from cmd stdout"))?,
+    );
+    let mut tests = Vec::new();
+    for message in Message::parse_stream(reader) {
+        match message? {
+            Message::CompilerArtifact(Artifact {
+                executable: Some(exec_path),
+                profile: ArtifactProfile { test: true, .. },
+                target: Target { src_path, .. },
+                ..
+            }) => tests.push(TestBinary {
+                path: exec_path.into(),
+                expected_panics: expected_panics(src_path.as_std_path())?,
+            }),
+            Message::CompilerMessage(msg) => eprint!("{msg}"),
+            _ => (),
+        }
+    }
+
+    if !child
+        .wait()
+        .context("couldn't get cargo's exit status")?
+        .success()
+    {
+        bail!("failed to build tests")
+    }
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use tempfile::tempdir;
+
+    use super::{
+        build_tests, expected_panics, parse_list, ExpectedPanics, TestFilter, TestHarness,
+        TestOutcome,
+    };
+
+    #[test]
+    fn list() {
+        let output = "tests::add: test\ntests::bench: benchmark\nmod::sub: test\n";
+        assert_eq!(parse_list(output), ["tests::add", "mod::sub"]);
+    }
+
+    #[test]
+    fn filter() {
+        let filter = TestFilter {
+            filter: Some("add".into()),
+            ..Default::default()
+        };
+        assert!(filter.matches("tests::add"));
+        assert!(!filter.matches("tests::sub"));
+
+        let filter = TestFilter {
+            filter: Some("add".into()),
+            exact: true,
+            ..Default::default()
+        };
+        assert!(!filter.matches("tests::add"));
+        assert!(filter.matches("add"));
+    }
+
+    #[test]
+    fn should_panic_attributes() {
+        let dir = tempdir().unwrap();
+        let src = dir.path();
+        fs::create_dir_all(src.join("outer")).unwrap();
+        fs::write(
+            src.join("lib.rs"),
+            r#"
+            mod outer;
+            #[cfg(feature = "missing")]
+            mod missing;
+            #[test]
+            #[should_panic]
+            fn root() {}
+            #[test]
+            fn not_expected() {}
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            src.join("outer.rs"),
+            r#"
+            mod inner {
+                #[test]
+                #[should_panic(expected = "boom")]
+                fn with_message() {}
+            }
+            mod file;
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            src.join("outer/file.rs"),
+            r#"
+            #[test_log::test]
+            #[should_panic = "bang"]
+            fn name_value() {}
+            #[should_panic]
+            fn not_a_test() {}
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            expected_panics(&src.join("lib.rs")).unwrap(),
+            ExpectedPanics::from([
+                ("root".to_string(), None),
+                (
+                    "outer::inner::with_message".to_string(),
+                    Some("boom".to_string())
+                ),
+                (
+                    "outer::file::name_value".to_string(),
+                    Some("bang".to_string())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn guest_tests() {
+        let manifest_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/guest-tests/Cargo.toml");
+        let mut outcomes = HashMap::new();
+        for test_bin in build_tests(&manifest_path, &[]).unwrap() {
+            let elf = fs::read(&test_bin.path).unwrap();
+            let report = TestHarness::new()
+                .run(
+                    &test_bin.path.to_string_lossy(),
+                    &elf,
+                    &TestFilter::default(),
+                    &test_bin.expected_panics,
+                )
+                .unwrap();
+            outcomes.extend(report.tests);
+        }
+
+        let passed = |test: &str| matches!(outcomes[test], TestOutcome::Passed { .. });
+        let failure = |test: &str| match &outcomes[test] {
+            TestOutcome::Failed { reason, .. } => reason.clone(),
+            outcome => panic!("{test} did not fail: {outcome:?}"),
+        };
+        assert!(passed("tests::passes"));
+        assert!(passed("tests::panics"));
+        assert!(passed("tests::panics_with_message"));
+        assert!(failure("tests::fails").contains("bad arithmetic"));
+        assert!(failure("tests::panics_with_other_message")
+            .contains("panic did not contain expected string"));
+        assert!(failure("tests::does_not_panic").contains("test did not panic as expected"));
+        assert_eq!(outcomes["tests::ignored"], TestOutcome::Ignored);
+    }
+}
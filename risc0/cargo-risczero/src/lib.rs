@@ -23,6 +23,10 @@ mod utils;
 
 #[cfg(feature = "experimental")]
 pub use self::commands::build::BuildSubcommand;
+#[cfg(feature = "r0vm")]
+pub use self::commands::test::{
+    expected_panics, ExpectedPanics, TestFilter, TestHarness, TestOutcome, TestReport,
+};
 
 use clap::{Parser, Subcommand};
 
@@ -32,6 +36,8 @@ use self::commands::{
     build_guest::BuildGuest, build_toolchain::BuildToolchain, deploy::DeployCommand,
     install::Install, new::NewCommand, verify::VerifyCommand,
 };
+#[cfg(feature = "r0vm")]
+use self::commands::{estimate::EstimateCommand, run::RunCommand, test::TestCommand};
 
 #[derive(Parser)]
 #[command(name = "cargo", bin_name = "cargo")]
@@ -65,12 +71,18 @@ pub enum RisczeroCmd {
     Deploy(DeployCommand),
     /// Verifies if a receipt is valid.
     Verify(VerifyCommand),
+    /// Executes a guest without proving it, and reports its cycles.
+    #[cfg(feature = "r0vm")]
+    Run(RunCommand),
+    /// Executes a guest and estimates the cost of proving it on a given machine.
+    #[cfg(feature = "r0vm")]
+    Estimate(EstimateCommand),
     /// Build a crate for RISC Zero.
     #[cfg(feature = "experimental")]
     BuildCrate(BuildCommand),
-    /// Build and test a crate for RISC Zero.
-    #[cfg(feature = "experimental")]
-    Test(BuildCommand),
+    /// Build the tests of a crate and run each test in the executor.
+    #[cfg(feature = "r0vm")]
+    Test(TestCommand),
 }
 
 #[cfg(test)]
@@ -0,0 +1,57 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests with every outcome, run by the tests of `cargo risczero test`.
+
+// Link the zkVM runtime into the test binary.
+extern crate risc0_zkvm;
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn passes() {
+        assert_eq!(1 + 1, 2);
+    }
+
+    #[test]
+    fn fails() {
+        assert_eq!(1 + 1, 3, "bad arithmetic");
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics() {
+        panic!("as expected");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected")]
+    fn panics_with_message() {
+        panic!("as expected");
+    }
+
+    #[test]
+    #[should_panic(expected = "something else")]
+    fn panics_with_other_message() {
+        panic!("as expected");
+    }
+
+    #[test]
+    #[should_panic]
+    fn does_not_panic() {}
+
+    #[test]
+    #[ignore]
+    fn ignored() {}
+}
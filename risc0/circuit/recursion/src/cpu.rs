@@ -22,7 +22,10 @@ use risc0_zkp::{
         baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
         map_pow, Elem, ExtElem, RootsOfUnity,
     },
-    hal::{cpu::CpuBuffer, CircuitHal, Hal},
+    hal::{
+        cpu::{CpuBuffer, ROWS_PER_PAGE},
+        CircuitHal, Hal,
+    },
     prove::accum::{Accum, Handler},
     INV_RATE, ZK_CYCLES,
 };
@@ -85,22 +88,27 @@ where
 
         let args: &[&[BabyBearElem]] = &[&code, &out, &data, &mix, &accum];
 
-        (0..domain).into_par_iter().for_each(|cycle| {
-            let tot = self.circuit.poly_fp(cycle, domain, poly_mix_pows, args);
-            let x = BabyBearElem::ROU_FWD[po2 + EXP_PO2].pow(cycle);
-            // TODO: what is this magic number 3?
-            let y = (BabyBearElem::new(3) * x).pow(1 << po2);
-            let ret = tot * (y - BabyBearElem::new(1)).inv();
+        // Each task covers a page's worth of cycles so that columns kept in
+        // temporary files are read a page at a time.
+        (0..domain)
+            .into_par_iter()
+            .with_min_len(ROWS_PER_PAGE)
+            .for_each(|cycle| {
+                let tot = self.circuit.poly_fp(cycle, domain, poly_mix_pows, args);
+                let x = BabyBearElem::ROU_FWD[po2 + EXP_PO2].pow(cycle);
+                // TODO: what is this magic number 3?
+                let y = (BabyBearElem::new(3) * x).pow(1 << po2);
+                let ret = tot * (y - BabyBearElem::new(1)).inv();
 
-            // SAFETY: This conversion is to make the check slice mutable, which should be
-            // safe because each thread access will not overlap with each other.
-            let check = unsafe {
-                std::slice::from_raw_parts_mut(check.as_ptr() as *mut BabyBearElem, check.len())
-            };
-            for i in 0..BabyBearExtElem::EXT_SIZE {
-                check[i * domain + cycle] = ret.elems()[i];
-            }
-        });
+                // SAFETY: This conversion is to make the check slice mutable, which should be
+                // safe because each thread access will not overlap with each other.
+                let check = unsafe {
+                    std::slice::from_raw_parts_mut(check.as_ptr() as *mut BabyBearElem, check.len())
+                };
+                for i in 0..BabyBearExtElem::EXT_SIZE {
+                    check[i * domain + cycle] = ret.elems()[i];
+                }
+            });
     }
 
     fn accumulate(
@@ -49,9 +49,10 @@ pub mod zkr;
 
 use risc0_core::field::baby_bear::{BabyBearElem, BabyBearExtElem};
 use risc0_zkp::{
-    adapter::{CircuitCoreDef, TapsProvider},
+    adapter::{CircuitCoreDef, CircuitInfo, PolyExtDef, PolyExtStepDef, TapsProvider},
     field::baby_bear::BabyBear,
     taps::TapSet,
+    verify::ScratchSize,
 };
 
 pub const REGISTER_GROUP_ACCUM: usize = 0;
@@ -81,8 +82,21 @@ impl TapsProvider for CircuitImpl {
     }
 }
 
+impl PolyExtDef for CircuitImpl {
+    fn poly_ext_def(&self) -> &'static PolyExtStepDef {
+        &poly_ext::DEF
+    }
+}
+
 impl CircuitCoreDef<BabyBear> for CircuitImpl {}
 
+/// Scratch memory needed to verify a seal of this circuit without allocating.
+pub const SCRATCH_SIZE: ScratchSize = ScratchSize::from_defs::<BabyBear>(
+    taps::TAPSET,
+    &poly_ext::DEF,
+    <CircuitImpl as CircuitInfo>::MIX_SIZE,
+);
+
 // Values for micro inst "opcode"
 pub mod micro_op {
     pub const CONST: u32 = 0;
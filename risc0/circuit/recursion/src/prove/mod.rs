@@ -77,6 +77,7 @@ pub struct Prover {
     input: VecDeque<u32>,
     split_points: Vec<usize>,
     output: Vec<u32>,
+    memory_budget: Option<usize>,
 }
 
 #[cfg(feature = "cuda")]
@@ -122,32 +123,38 @@ mod metal {
 }
 
 mod cpu {
-    use risc0_zkp::core::hash::{poseidon_254::Poseidon254HashSuite, sha::Sha256HashSuite};
+    use risc0_zkp::core::hash::{
+        poseidon_254::Poseidon254HashSuite, sha::Sha256HashSuite, HashSuite,
+    };
 
     use super::{
         BabyBear, CircuitImpl, CpuCircuitHal, CpuHal, HalPair, Poseidon2HashSuite, Rc, CIRCUIT,
     };
 
-    #[allow(dead_code)]
-    pub fn sha256_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-        let hal = Rc::new(CpuHal::new(Sha256HashSuite::new_suite()));
+    type CpuHalPair = HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>>;
+
+    fn hal_pair(suite: HashSuite<BabyBear>, memory_budget: Option<usize>) -> CpuHalPair {
+        let hal = Rc::new(match memory_budget {
+            Some(memory_budget) => CpuHal::with_memory_budget(suite, memory_budget),
+            None => CpuHal::new(suite),
+        });
         let circuit_hal = Rc::new(CpuCircuitHal::new(&CIRCUIT));
         HalPair { hal, circuit_hal }
     }
 
     #[allow(dead_code)]
-    pub fn poseidon2_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-        let hal = Rc::new(CpuHal::new(Poseidon2HashSuite::new_suite()));
-        let circuit_hal = Rc::new(CpuCircuitHal::new(&CIRCUIT));
-        HalPair { hal, circuit_hal }
+    pub fn sha256_hal_pair(memory_budget: Option<usize>) -> CpuHalPair {
+        hal_pair(Sha256HashSuite::new_suite(), memory_budget)
     }
 
     #[allow(dead_code)]
-    pub fn poseidon254_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>>
-    {
-        let hal = Rc::new(CpuHal::new(Poseidon254HashSuite::new_suite()));
-        let circuit_hal = Rc::new(CpuCircuitHal::new(&CIRCUIT));
-        HalPair { hal, circuit_hal }
+    pub fn poseidon2_hal_pair(memory_budget: Option<usize>) -> CpuHalPair {
+        hal_pair(Poseidon2HashSuite::new_suite(), memory_budget)
+    }
+
+    #[allow(dead_code)]
+    pub fn poseidon254_hal_pair(memory_budget: Option<usize>) -> CpuHalPair {
+        hal_pair(Poseidon254HashSuite::new_suite(), memory_budget)
     }
 }
 
@@ -168,7 +175,7 @@ cfg_if::cfg_if! {
         /// TODO
         #[allow(dead_code)]
         pub fn poseidon254_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-            cpu::poseidon254_hal_pair()
+            cpu::poseidon254_hal_pair(None)
         }
     } else if #[cfg(feature = "metal")] {
         /// TODO
@@ -186,25 +193,25 @@ cfg_if::cfg_if! {
         /// TODO
         #[allow(dead_code)]
         pub fn poseidon254_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-            cpu::poseidon254_hal_pair()
+            cpu::poseidon254_hal_pair(None)
         }
     } else {
         /// TODO
         #[allow(dead_code)]
         pub fn sha256_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-            cpu::sha256_hal_pair()
+            cpu::sha256_hal_pair(None)
         }
 
         /// TODO
         #[allow(dead_code)]
         pub fn poseidon2_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-            cpu::poseidon2_hal_pair()
+            cpu::poseidon2_hal_pair(None)
         }
 
         /// TODO
         #[allow(dead_code)]
         pub fn poseidon254_hal_pair() -> HalPair<CpuHal<BabyBear>, CpuCircuitHal<'static, CircuitImpl>> {
-            cpu::poseidon254_hal_pair()
+            cpu::poseidon254_hal_pair(None)
         }
     }
 }
@@ -223,12 +230,24 @@ impl Prover {
     /// is a low-level interface; for the zkVM, prefer to use
     /// risc0_zkvm::host::recursion::prove::Prover.
     pub fn new(program: Program, hashfn: &str) -> Self {
+        Self::new_with_memory_budget(program, hashfn, None)
+    }
+
+    /// Creates a new prover with the given recursion program that keeps at
+    /// most `memory_budget` bytes of buffers in memory when proving on the
+    /// CPU. GPU provers ignore the budget.
+    pub fn new_with_memory_budget(
+        program: Program,
+        hashfn: &str,
+        memory_budget: Option<usize>,
+    ) -> Self {
         Self {
             program,
             hashfn: hashfn.to_string(),
             input: VecDeque::new(),
             split_points: Vec::new(),
             output: Vec::new(),
+            memory_budget,
         }
     }
 
@@ -260,14 +279,25 @@ impl Prover {
     #[tracing::instrument(skip_all)]
     pub fn run(&mut self) -> Result<RecursionReceipt> {
         // NOTE: Code is repeated across match arms to satisfy generics.
+        let cpu_only = !cfg!(any(feature = "cuda", feature = "metal"));
         match self.hashfn.as_ref() {
+            "poseidon2" if cpu_only => {
+                let hal_pair = cpu::poseidon2_hal_pair(self.memory_budget);
+                let (hal, circuit_hal) = (hal_pair.hal.as_ref(), hal_pair.circuit_hal.as_ref());
+                self.run_with_hal(hal, circuit_hal)
+            }
             "poseidon2" => {
                 let hal_pair = poseidon2_hal_pair();
                 let (hal, circuit_hal) = (hal_pair.hal.as_ref(), hal_pair.circuit_hal.as_ref());
                 self.run_with_hal(hal, circuit_hal)
             }
             "poseidon_254" => {
-                let hal_pair = poseidon254_hal_pair();
+                let hal_pair = cpu::poseidon254_hal_pair(self.memory_budget);
+                let (hal, circuit_hal) = (hal_pair.hal.as_ref(), hal_pair.circuit_hal.as_ref());
+                self.run_with_hal(hal, circuit_hal)
+            }
+            "sha-256" if cpu_only => {
+                let hal_pair = cpu::sha256_hal_pair(self.memory_budget);
                 let (hal, circuit_hal) = (hal_pair.hal.as_ref(), hal_pair.circuit_hal.as_ref());
                 self.run_with_hal(hal, circuit_hal)
             }
@@ -298,7 +328,7 @@ impl Prover {
 
         let mut adapter = ProveAdapter::new(&mut executor.executor);
         let mut prover = risc0_zkp::prove::Prover::new(hal, CIRCUIT.get_taps());
-        let hashfn = Rc::clone(&hal.get_hash_suite().hashfn);
+        let hashfn = hal.get_hash_suite().hashfn.clone();
 
         // At the start of the protocol, seed the Fiat-Shamir transcript with context information
         // about the proof system and circuit.
@@ -25,7 +25,7 @@ mod taps;
 pub mod trace;
 
 use risc0_zkp::{
-    adapter::{CircuitCoreDef, TapsProvider},
+    adapter::{CircuitCoreDef, PolyExtDef, PolyExtStepDef, TapsProvider},
     field::baby_bear::BabyBear,
     taps::TapSet,
 };
@@ -53,4 +53,10 @@ impl TapsProvider for CircuitImpl {
     }
 }
 
+impl PolyExtDef for CircuitImpl {
+    fn poly_ext_def(&self) -> &'static PolyExtStepDef {
+        &poly_ext::DEF
+    }
+}
+
 impl CircuitCoreDef<BabyBear> for CircuitImpl {}
@@ -15,7 +15,7 @@
 #[cfg(test)]
 mod tests;
 
-use std::{array, cell::RefCell, collections::BTreeSet, mem, rc::Rc};
+use std::{array, cell::RefCell, collections::BTreeSet, fmt, mem, ops::Range, rc::Rc};
 
 use anyhow::{bail, ensure, Result};
 use crypto_bigint::{CheckedMul as _, Encoding as _, NonZero, U256, U512};
@@ -66,6 +66,12 @@ pub trait Syscall {
         ctx: &mut dyn SyscallContext,
         into_guest: &mut [u32],
     ) -> Result<(u32, u32)>;
+
+    /// Returns the input digest to provide to the guest, if it is determined by the system calls
+    /// made so far rather than being fixed when execution starts.
+    fn input_digest(&self) -> Option<Digest> {
+        None
+    }
 }
 
 /// Access to memory and machine state for syscalls.
@@ -90,6 +96,9 @@ pub trait SyscallContext {
 
     /// Returns the current cycle count.
     fn get_cycle(&self) -> u64;
+
+    /// Returns the address of the `ecall` instruction that invoked the system call.
+    fn peek_pc(&self) -> ByteAddr;
 }
 
 pub struct ExecutorResult {
@@ -101,6 +110,9 @@ pub struct ExecutorResult {
     pub pre_state: SystemState,
     pub post_state: SystemState,
     pub output_digest: Option<Digest>,
+    /// The trap that ended execution with [ExitCode::Fault], if the guest trapped rather than
+    /// faulting in a system call.
+    pub trap: Option<TrapError>,
 }
 
 #[derive(Default)]
@@ -125,6 +137,42 @@ struct PendingState {
     events: BTreeSet<TraceEvent>,
 }
 
+/// A trap raised by the guest, e.g. by accessing memory outside of the guest's address space.
+///
+/// The trap ends execution with [ExitCode::Fault] at the trapping instruction, which does not
+/// complete, and is reported in [ExecutorResult::trap].
+#[derive(Clone, Debug)]
+pub struct TrapError {
+    /// The cause of the trap.
+    pub cause: TrapCause,
+
+    /// The address of the instruction that trapped.
+    pub pc: ByteAddr,
+}
+
+impl fmt::Display for TrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Trap: {:08x?}, pc: {:?}", self.cause, self.pc)
+    }
+}
+
+impl std::error::Error for TrapError {}
+
+/// The error returned by a [Syscall] to end execution with [ExitCode::Fault], rather than
+/// aborting it, e.g. when the guest panics.
+///
+/// The system call does not complete, and the final segment ends at the `ecall` instruction.
+#[derive(Clone, Debug)]
+pub struct SyscallFault;
+
+impl fmt::Display for SyscallFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Guest faulted in a system call")
+    }
+}
+
+impl std::error::Error for SyscallFault {}
+
 pub struct Executor<'a, 'b, S: Syscall> {
     pc: ByteAddr,
     insn_cycles: usize,
@@ -137,6 +185,7 @@ pub struct Executor<'a, 'b, S: Syscall> {
     pending: PendingState,
     trace: Vec<Rc<RefCell<dyn TraceCallback + 'b>>>,
     cycles: SessionCycles,
+    stack_guard: Option<Range<u32>>,
 }
 
 impl PendingState {
@@ -177,9 +226,19 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
             },
             trace,
             cycles: SessionCycles::default(),
+            stack_guard: None,
         }
     }
 
+    /// Report data accesses to addresses in `guard` as a [TrapCause::StackOverflow].
+    ///
+    /// The guard region sits just below the guest's stack, so that accesses to it are the
+    /// result of the stack growing past its bottom. This is only checked by the executor.
+    pub fn with_stack_guard(mut self, guard: Range<u32>) -> Self {
+        self.stack_guard = Some(guard);
+        self
+    }
+
     pub fn run<F: FnMut(Segment) -> Result<()>>(
         &mut self,
         segment_po2: usize,
@@ -200,6 +259,7 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
         let mut emu = Emulator::new();
         let mut segments = 0;
         let initial_state = self.pager.image.get_system_state();
+        let mut trap = None;
 
         loop {
             if self.exit_code.is_some() {
@@ -212,11 +272,24 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
                 }
             }
 
-            emu.step(self)?;
+            let pager_cycles = self.pager.cycles;
+            if let Err(err) = emu.step(self) {
+                trap = match err.downcast::<TrapError>() {
+                    Ok(err) => Some(err),
+                    Err(err) if err.is::<SyscallFault>() => None,
+                    Err(err) => return Err(err),
+                };
+                // Discard the partially executed instruction and end the session at it.
+                self.pager.undo();
+                self.pending.reset(self.pc);
+                self.pending.events.clear();
+                self.exit_code = Some(ExitCode::Fault);
+                break;
+            }
 
             let segment_cycles = self.insn_cycles + self.pager.cycles + self.pending.cycles;
             if segment_cycles < segment_limit {
-                self.advance()?;
+                self.advance(self.pager.cycles - pager_cycles)?;
             } else if self.insn_cycles == 0 {
                 bail!(
                     "segment limit ({segment_limit}) too small for instruction at pc: {:?}",
@@ -296,10 +369,11 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
             pre_state: initial_state,
             post_state,
             output_digest: self.output_digest,
+            trap,
         })
     }
 
-    fn advance(&mut self) -> Result<()> {
+    fn advance(&mut self, paging_cycles: usize) -> Result<()> {
         for trace in &self.trace {
             trace
                 .borrow_mut()
@@ -312,6 +386,12 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
             for event in &self.pending.events {
                 trace.borrow_mut().trace_callback(event.clone()).unwrap();
             }
+
+            if paging_cycles > 0 {
+                trace.borrow_mut().trace_callback(TraceEvent::Paging {
+                    cycles: paging_cycles as u64,
+                })?;
+            }
         }
 
         self.pc = self.pending.pc;
@@ -366,7 +446,11 @@ impl<'a, 'b, S: Syscall> Executor<'a, 'b, S> {
         tracing::debug!("[{}] ecall_input", self.insn_cycles);
         let a0 = self.load_register(REG_A0)? as usize;
         ensure!(a0 < DIGEST_WORDS, "sys_input index out of range");
-        let word = self.input_digest.as_words()[a0];
+        let input_digest = self
+            .syscall_handler
+            .input_digest()
+            .unwrap_or(self.input_digest);
+        let word = input_digest.as_words()[a0];
         self.store_register(REG_A0, word)?;
 
         self.pending.cycles += 1;
@@ -640,9 +724,9 @@ impl<'a, 'b, S: Syscall> EmuContext for Executor<'a, 'b, S> {
     }
 
     fn trap(&self, cause: TrapCause) -> Result<bool> {
-        let msg = format!("Trap: {cause:08x?}, pc: {:?}", self.pc);
-        tracing::info!("{msg}");
-        bail!("{msg}");
+        let trap = TrapError { cause, pc: self.pc };
+        tracing::info!("{trap}");
+        Err(trap.into())
     }
 
     fn check_data_load(&self, addr: ByteAddr) -> bool {
@@ -657,6 +741,12 @@ impl<'a, 'b, S: Syscall> EmuContext for Executor<'a, 'b, S> {
         is_guest_memory(addr.0)
     }
 
+    fn check_stack_guard(&self, addr: ByteAddr) -> bool {
+        self.stack_guard
+            .as_ref()
+            .map_or(true, |guard| !guard.contains(&addr.0))
+    }
+
     fn on_insn_decoded(&self, insn: &Instruction, _decoded: &DecodedInstruction) {
         tracing::trace!("{:?}> {:?}", self.pc, insn.kind);
     }
@@ -715,6 +805,10 @@ impl<'a, 'b, S: Syscall> SyscallContext for Executor<'a, 'b, S> {
         self.cycles.user as u64
     }
 
+    fn peek_pc(&self) -> ByteAddr {
+        self.pc
+    }
+
     fn peek_register(&mut self, idx: usize) -> Result<u32> {
         if idx >= REG_MAX {
             bail!("invalid register: x{idx}");
@@ -23,7 +23,7 @@ use risc0_zkvm_platform::{
 };
 use test_log::test;
 
-use super::{Syscall, SyscallContext};
+use super::{Syscall, SyscallContext, SyscallFault};
 use crate::prove::emu::{
     addr::ByteAddr,
     exec::DEFAULT_SEGMENT_LIMIT_PO2,
@@ -129,3 +129,39 @@ fn system_split() {
         segments[0].post_state.digest::<ShaImpl>()
     );
 }
+
+struct FaultSyscall;
+
+impl Syscall for FaultSyscall {
+    fn syscall(
+        &self,
+        syscall: &str,
+        ctx: &mut dyn SyscallContext,
+        _guest_buf: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        assert_eq!(syscall, "flt");
+        assert_eq!(ctx.peek_pc(), ByteAddr(0x400c));
+        Err(SyscallFault.into())
+    }
+}
+
+#[test]
+fn syscall_fault() {
+    let program = testutil::software_ecall();
+    let image = MemoryImage::new(&program, PAGE_SIZE as u32).unwrap();
+
+    let result = super::execute(
+        image,
+        DEFAULT_SEGMENT_LIMIT_PO2,
+        DEFAULT_SESSION_LIMIT,
+        &FaultSyscall,
+        None,
+    )
+    .unwrap();
+
+    let segments = result.segments;
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].exit_code, ExitCode::Fault);
+    assert!(segments[0].syscalls.is_empty());
+    assert_eq!(segments[0].post_state.pc, 0x400c);
+}
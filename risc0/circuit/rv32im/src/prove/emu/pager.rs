@@ -18,7 +18,7 @@ use std::{
 };
 
 use anyhow::Result;
-use risc0_binfmt::{MemoryImage, SystemState};
+use risc0_binfmt::{MemoryImage, Page, SystemState};
 use risc0_zkp::core::hash::sha::BLOCK_BYTES;
 use risc0_zkvm_platform::{PAGE_SIZE, WORD_SIZE};
 
@@ -40,8 +40,6 @@ const fn cycles_per_page(blocks_per_page: usize) -> usize {
     1 + SHA_INIT + (SHA_LOAD + SHA_MAIN) * blocks_per_page
 }
 
-struct Page(Vec<u8>);
-
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 enum PageState {
     Loaded,
@@ -61,10 +59,45 @@ enum Action {
     Store(WordAddr, u32),
 }
 
+/// A page held by the pager, which shares storage with the image until it is
+/// first stored to.
+enum CachedPage {
+    Shared(Page),
+    Owned(Vec<u8>),
+}
+
+impl CachedPage {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            CachedPage::Shared(page) => page,
+            CachedPage::Owned(bytes) => bytes,
+        }
+    }
+
+    // The page is copied once, on the first store to it, so that later stores
+    // do not need to check whether its storage is shared.
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        if let CachedPage::Shared(page) = self {
+            *self = CachedPage::Owned(page.to_vec());
+        }
+        match self {
+            CachedPage::Owned(bytes) => bytes,
+            CachedPage::Shared(_) => unreachable!(),
+        }
+    }
+
+    fn to_page(&self) -> Page {
+        match self {
+            CachedPage::Shared(page) => page.clone(),
+            CachedPage::Owned(bytes) => Page::from(bytes.clone()),
+        }
+    }
+}
+
 pub struct PagedMemory {
     pub image: MemoryImage,
     page_table: Vec<u32>,
-    page_cache: Vec<Page>,
+    page_cache: Vec<CachedPage>,
     page_states: BTreeMap<u32, PageState>,
     pub cycles: usize,
     pending_actions: Vec<Action>,
@@ -101,7 +134,7 @@ impl PagedMemory {
         if idx == INVALID_IDX {
             self.pre_peek(addr)
         } else {
-            Ok(self.page_cache[idx as usize].load(addr))
+            Ok(load_word(self.page_cache[idx as usize].bytes(), addr))
         }
     }
 
@@ -113,7 +146,7 @@ impl PagedMemory {
             self.load_page(page_idx);
             idx = self.page_table[page_idx as usize];
         }
-        self.page_cache[idx as usize].load(addr)
+        load_word(self.page_cache[idx as usize].bytes(), addr)
     }
 
     pub fn store(&mut self, addr: WordAddr, data: u32) -> Result<()> {
@@ -132,47 +165,34 @@ impl PagedMemory {
         }
 
         let idx = self.page_table[page_idx as usize] as usize;
-        let page = self.page_cache.get_mut(idx).unwrap();
-        let old = page.load(addr);
+        let page = self.page_cache[idx].bytes_mut();
+        let old = load_word(page, addr);
         self.pending_actions.push(Action::Store(addr, old));
-        page.store(addr, data);
+        store_word(page, addr, data);
 
         Ok(())
     }
 
     pub fn commit(&mut self, pc: ByteAddr) -> (SystemState, MemoryImage, SystemState) {
         let pre_state = self.image.get_system_state();
-        let info = &self.image.info;
 
-        let mut image = MemoryImage {
-            pages: BTreeMap::new(),
-            info: info.clone(),
-            pc: pre_state.pc,
-        };
+        // The 'original' version of all pages, this is just the subset of
+        // pages for the previous segment. These share storage with the image
+        // that accumulates over segments.
+        let image = self.image.subset(self.page_states.keys().copied());
 
-        for (page_idx, page_state) in &self.page_states {
-            // Copy 'original' version of all pages, this is just the subset of
-            // pages for the previous segment.
-            image
-                .pages
-                .insert(*page_idx, self.image.load_page(*page_idx));
-
-            // Update all 'dirty' pages into the image that accumulates over
-            // segments.
-            if *page_state == PageState::Dirty {
-                let idx = self.page_table[*page_idx as usize] as usize;
-                let page = &self.page_cache[idx];
-                self.image.pages.insert(*page_idx, page.0.clone());
-            }
-        }
-
-        // Update the merkle tree
+        // Update all 'dirty' pages into the image that accumulates over
+        // segments, and then rehash just those pages and their ancestors in
+        // the merkle tree.
         for (page_idx, page_state) in &self.page_states {
             if *page_state == PageState::Dirty {
                 tracing::trace!("dirty: 0x{page_idx:05x}");
-                self.image.update_page(*page_idx);
+                let idx = self.page_table[*page_idx as usize] as usize;
+                self.image
+                    .store_page(*page_idx, self.page_cache[idx].to_page());
             }
         }
+        self.image.hash_pages();
         self.image.pc = pc.0;
         let post_state = self.image.get_system_state();
 
@@ -198,7 +218,7 @@ impl PagedMemory {
                 }
                 Action::Store(addr, data) => {
                     let idx = self.page_table[addr.page_idx() as usize] as usize;
-                    self.page_cache[idx].store(*addr, *data);
+                    store_word(self.page_cache[idx].bytes_mut(), *addr, *data);
                 }
             }
         }
@@ -231,7 +251,7 @@ impl PagedMemory {
         tracing::trace!("load_page: 0x{page_idx:05x}");
         let page = self.image.load_page(page_idx);
         self.page_table[page_idx as usize] = self.page_cache.len() as u32;
-        self.page_cache.push(Page(page));
+        self.page_cache.push(CachedPage::Shared(page));
         self.update(page_idx, PageState::Loaded);
         self.page_changed(page_idx, PageState::Loaded);
     }
@@ -250,7 +270,7 @@ impl PagedMemory {
             } else {
                 let page = self.image.load_page(parent_idx);
                 self.page_table[parent_idx as usize] = self.page_cache.len() as u32;
-                self.page_cache.push(Page(page));
+                self.page_cache.push(CachedPage::Shared(page));
                 self.page_changed(parent_idx, goal);
             }
 
@@ -280,21 +300,19 @@ impl PagedMemory {
     }
 }
 
-impl Page {
-    fn load(&self, addr: WordAddr) -> u32 {
-        let word_addr = (addr.0 % PAGE_WORDS as u32) as usize;
-        let byte_addr = word_addr * WORD_SIZE;
-        let mut bytes = [0u8; WORD_SIZE];
-        bytes.clone_from_slice(&self.0[byte_addr..byte_addr + WORD_SIZE]);
-        //let data = u32::from_le_bytes(bytes);
-        // tracing::trace!("load({addr:?}) -> 0x{data:08x}");
-        u32::from_le_bytes(bytes)
-    }
+fn load_word(page: &[u8], addr: WordAddr) -> u32 {
+    let word_addr = (addr.0 % PAGE_WORDS as u32) as usize;
+    let byte_addr = word_addr * WORD_SIZE;
+    let mut bytes = [0u8; WORD_SIZE];
+    bytes.clone_from_slice(&page[byte_addr..byte_addr + WORD_SIZE]);
+    //let data = u32::from_le_bytes(bytes);
+    // tracing::trace!("load({addr:?}) -> 0x{data:08x}");
+    u32::from_le_bytes(bytes)
+}
 
-    fn store(&mut self, addr: WordAddr, data: u32) {
-        let word_addr = (addr.0 % PAGE_WORDS as u32) as usize;
-        let byte_addr = word_addr * WORD_SIZE;
-        // tracing::trace!("store({addr:?}, 0x{data:08x})");
-        self.0[byte_addr..byte_addr + WORD_SIZE].clone_from_slice(&data.to_le_bytes());
-    }
+fn store_word(page: &mut [u8], addr: WordAddr, data: u32) {
+    let word_addr = (addr.0 % PAGE_WORDS as u32) as usize;
+    let byte_addr = word_addr * WORD_SIZE;
+    // tracing::trace!("store({addr:?}, 0x{data:08x})");
+    page[byte_addr..byte_addr + WORD_SIZE].clone_from_slice(&data.to_le_bytes());
 }
@@ -65,6 +65,11 @@ pub trait EmuContext {
     fn check_data_store(&self, _addr: ByteAddr) -> bool {
         true
     }
+
+    // Check that a data access is not within the guard region below the stack
+    fn check_stack_guard(&self, _addr: ByteAddr) -> bool {
+        true
+    }
 }
 
 #[derive(Default)]
@@ -72,7 +77,12 @@ pub struct Emulator {
     table: FastDecodeTable,
 }
 
-#[derive(Debug)]
+/// The reason the emulator trapped.
+///
+/// This is `#[non_exhaustive]` so that causes can be added without breaking
+/// matches on it.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum TrapCause {
     InstructionAddressMisaligned,
     InstructionAccessFault,
@@ -83,6 +93,7 @@ pub enum TrapCause {
     StoreAddressMisaligned(ByteAddr),
     StoreAccessFault,
     EnvironmentCallFromUserMode,
+    StackOverflow(ByteAddr),
 }
 
 #[derive(Clone, Debug, Default)]
@@ -523,6 +534,9 @@ impl Emulator {
         if !ctx.check_data_load(addr) {
             return ctx.trap(TrapCause::LoadAccessFault);
         }
+        if !ctx.check_stack_guard(addr) {
+            return ctx.trap(TrapCause::StackOverflow(addr));
+        }
         let data = ctx.load_memory(addr.waddr())?;
         let shift = 8 * (addr.0 & 3);
         let out = match kind {
@@ -576,6 +590,9 @@ impl Emulator {
         if !ctx.check_data_store(addr) {
             return ctx.trap(TrapCause::StoreAccessFault);
         }
+        if !ctx.check_stack_guard(addr) {
+            return ctx.trap(TrapCause::StackOverflow(addr));
+        }
         let mut data = ctx.load_memory(addr.waddr())?;
         match kind {
             InsnKind::SB => {
@@ -93,6 +93,19 @@ pub fn simple_loop() -> Program {
     )
 }
 
+pub fn software_ecall() -> Program {
+    program_from_instructions(
+        0x4000,
+        [
+            0x00200293, // li t0, 2
+            0x00004637, // lui a2, 0x4
+            0x01060613, // addi a2, a2, 16
+            0x00000073, // ecall(software)
+            0x00746c66, // "flt"
+        ],
+    )
+}
+
 pub fn large_text() -> Program {
     let iter = (0..2500).map(|_| {
         0x1234b137 // lui x2, 0x1234b000
@@ -30,7 +30,7 @@ use risc0_zkp::{
     },
     field::baby_bear::BabyBear,
     hal::{
-        cpu::{CpuBuffer, CpuHal},
+        cpu::{CpuBuffer, CpuHal, ROWS_PER_PAGE},
         CircuitHal, Hal,
     },
     INV_RATE, ZK_CYCLES,
@@ -135,22 +135,27 @@ where
 
         let args: &[&[BabyBearElem]] = &[ctrl, out, data, mix, accum];
 
-        (0..domain).into_par_iter().for_each(|cycle| {
-            let tot = CIRCUIT.poly_fp(cycle, domain, poly_mix_pows, args);
-            let x = BabyBearElem::ROU_FWD[po2 + EXP_PO2].pow(cycle);
-            // TODO: what is this magic number 3?
-            let y = (BabyBearElem::new(3) * x).pow(1 << po2);
-            let ret = tot * (y - BabyBearElem::new(1)).inv();
-
-            // SAFETY: This conversion is to make the check slice mutable, which should be
-            // safe because each thread access will not overlap with each other.
-            let check = unsafe {
-                std::slice::from_raw_parts_mut(check.as_ptr() as *mut BabyBearElem, check.len())
-            };
-            for i in 0..BabyBearExtElem::EXT_SIZE {
-                check[i * domain + cycle] = ret.elems()[i];
-            }
-        });
+        // Each task covers a page's worth of cycles so that columns kept in
+        // temporary files are read a page at a time.
+        (0..domain)
+            .into_par_iter()
+            .with_min_len(ROWS_PER_PAGE)
+            .for_each(|cycle| {
+                let tot = CIRCUIT.poly_fp(cycle, domain, poly_mix_pows, args);
+                let x = BabyBearElem::ROU_FWD[po2 + EXP_PO2].pow(cycle);
+                // TODO: what is this magic number 3?
+                let y = (BabyBearElem::new(3) * x).pow(1 << po2);
+                let ret = tot * (y - BabyBearElem::new(1)).inv();
+
+                // SAFETY: This conversion is to make the check slice mutable, which should be
+                // safe because each thread access will not overlap with each other.
+                let check = unsafe {
+                    std::slice::from_raw_parts_mut(check.as_ptr() as *mut BabyBearElem, check.len())
+                };
+                for i in 0..BabyBearExtElem::EXT_SIZE {
+                    check[i * domain + cycle] = ret.elems()[i];
+                }
+            });
     }
 
     #[tracing::instrument(skip_all)]
@@ -204,14 +209,20 @@ where
     }
 }
 
-pub fn segment_prover(hashfn: &str) -> Result<Box<dyn SegmentProver>> {
+pub fn segment_prover(
+    hashfn: &str,
+    memory_budget: Option<usize>,
+) -> Result<Box<dyn SegmentProver>> {
     let suite = match hashfn {
         "sha-256" => Sha256HashSuite::new_suite(),
         "poseidon2" => Poseidon2HashSuite::new_suite(),
         _ => bail!("Unsupported hashfn: {hashfn}"),
     };
 
-    let hal = Rc::new(CpuHal::new(suite));
+    let hal = Rc::new(match memory_budget {
+        Some(memory_budget) => CpuHal::with_memory_budget(suite, memory_budget),
+        None => CpuHal::new(suite),
+    });
     let circuit_hal = Rc::new(CpuCircuitHal::new());
     Ok(Box::new(SegmentProverImpl::new(hal, circuit_hal)))
 }
@@ -29,13 +29,25 @@ pub trait SegmentProver {
 }
 
 pub fn segment_prover(hashfn: &str) -> Result<Box<dyn SegmentProver>> {
+    segment_prover_with_memory_budget(hashfn, None)
+}
+
+/// Returns a [SegmentProver] that keeps at most `memory_budget` bytes of
+/// buffers in memory when proving on the CPU, and stores the rest in temporary
+/// files. GPU provers ignore the budget.
+pub fn segment_prover_with_memory_budget(
+    hashfn: &str,
+    memory_budget: Option<usize>,
+) -> Result<Box<dyn SegmentProver>> {
     cfg_if! {
         if #[cfg(feature = "cuda")] {
+            let _ = memory_budget;
             self::hal::cuda::segment_prover(hashfn)
         } else if #[cfg(feature = "metal")] {
+            let _ = memory_budget;
             self::hal::metal::segment_prover(hashfn)
         } else {
-            self::hal::cpu::segment_prover(hashfn)
+            self::hal::cpu::segment_prover(hashfn, memory_budget)
         }
     }
 }
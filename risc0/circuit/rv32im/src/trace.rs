@@ -20,7 +20,10 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 /// An event traced from the running VM.
+///
+/// New kinds of events may be added, so matches on this enum need a wildcard arm.
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum TraceEvent {
     /// An instruction has started at the given program counter
     InstructionStart {
@@ -47,6 +50,13 @@ pub enum TraceEvent {
         /// Data that's been written
         region: Vec<u8>,
     },
+
+    /// Memory has been paged in or out to execute the instruction that last started, costing
+    /// cycles in addition to those of the instruction itself
+    Paging {
+        /// Number of cycles spent paging
+        cycles: u64,
+    },
 }
 
 /// A callback used to collect [TraceEvent]s.
@@ -70,6 +80,7 @@ impl core::fmt::Debug for TraceEvent {
             Self::MemorySet { addr, region } => {
                 write!(f, "MemorySet(0x{addr:08X}, {region:#04X?})")
             }
+            Self::Paging { cycles } => write!(f, "Paging({cycles})"),
         }
     }
 }
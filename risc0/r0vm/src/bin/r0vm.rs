@@ -12,6 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     risc0_r0vm::main()
 }
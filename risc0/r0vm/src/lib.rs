@@ -12,12 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fs, io, path::PathBuf, rc::Rc};
+use std::{fs, io, path::PathBuf, rc::Rc, time::Duration};
 
+use anyhow::{Context, Result};
 use clap::{Args, Parser, ValueEnum};
 use risc0_zkvm::{
-    get_prover_server, ApiServer, ExecutorEnv, ExecutorImpl, ProverOpts, ProverServer,
-    VerifierContext,
+    get_prover_server, ApiDaemon, ApiServer, ExecutorEnv, ExecutorImpl, ProverOpts, ProverServer,
+    ResourcePolicy, VerifierContext,
 };
 
 /// Runs a RISC-V ELF binary within the RISC Zero ZKVM.
@@ -67,6 +68,36 @@ struct Cli {
     /// The receipt kind produced by the r0vm prover
     #[arg(long, value_enum, default_value_t = ReceiptKind::Composite)]
     receipt_kind: ReceiptKind,
+
+    /// The number of requests handled concurrently by a daemon.
+    #[arg(long, default_value_t = 1, requires = "daemon")]
+    workers: usize,
+
+    /// The number of connections waiting for a daemon worker, after which
+    /// clients wait to be accepted.
+    #[arg(long, default_value_t = 64, requires = "daemon")]
+    queue_size: usize,
+
+    /// The longest time, in seconds, that a daemon spends executing a guest.
+    #[arg(long, requires = "daemon")]
+    max_wall_clock_secs: Option<u64>,
+
+    /// The most pages of memory that a guest executed by a daemon may touch.
+    #[arg(long, requires = "daemon")]
+    max_pages: Option<u64>,
+
+    /// The largest journal, in bytes, that a guest executed by a daemon may
+    /// write.
+    #[arg(long, requires = "daemon")]
+    max_journal_bytes: Option<u64>,
+
+    /// The most bytes that a guest executed by a daemon may read.
+    #[arg(long, requires = "daemon")]
+    max_input_bytes: Option<u64>,
+
+    /// The most receipts that a guest executed by a daemon may verify.
+    #[arg(long, requires = "daemon")]
+    max_verify_calls: Option<u64>,
 }
 
 #[derive(Args)]
@@ -75,6 +106,16 @@ struct Mode {
     #[arg(long)]
     port: Option<u16>,
 
+    /// Serve requests from a DistributedProver listening on this address,
+    /// until it stops listening.
+    #[arg(long)]
+    worker: Option<String>,
+
+    /// Listen on this address and serve requests from many clients, until
+    /// interrupted.
+    #[arg(long)]
+    daemon: Option<String>,
+
     /// The ELF to execute
     #[arg(long)]
     elf: Option<PathBuf>,
@@ -102,7 +143,7 @@ enum ReceiptKind {
     Groth16,
 }
 
-pub fn main() {
+pub fn main() -> Result<()> {
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
         .init();
@@ -110,7 +151,23 @@ pub fn main() {
     let args = Cli::parse();
     if let Some(port) = args.mode.port {
         run_server(port);
-        return;
+        return Ok(());
+    }
+
+    if let Some(addr) = args.mode.worker {
+        run_worker(&addr);
+        return Ok(());
+    }
+
+    if let Some(addr) = args.mode.daemon {
+        let mut policy = ResourcePolicy::new();
+        policy.wall_clock = args.max_wall_clock_secs.map(Duration::from_secs);
+        policy.pages = args.max_pages;
+        policy.journal_bytes = args.max_journal_bytes;
+        policy.input_bytes = args.max_input_bytes;
+        policy.verify_calls = args.max_verify_calls;
+        run_daemon(&addr, args.workers, args.queue_size, policy);
+        return Ok(());
     }
 
     let env = {
@@ -124,7 +181,10 @@ pub fn main() {
         }
 
         if let Some(input) = args.initial_input.as_ref() {
-            builder.stdin(fs::File::open(input).unwrap());
+            builder.stdin(
+                fs::File::open(input)
+                    .with_context(|| format!("failed to open {}", input.display()))?,
+            );
         } else {
             builder.stdin(io::stdin());
         }
@@ -133,31 +193,40 @@ pub fn main() {
             builder.enable_profiler(pprof_out);
         }
 
-        builder.build().unwrap()
+        builder.build()?
     };
 
     let session = {
         let mut exec = if let Some(ref elf_path) = args.mode.elf {
-            let elf_contents = fs::read(elf_path).unwrap();
-            ExecutorImpl::from_elf(env, &elf_contents).unwrap()
+            let elf_contents = fs::read(elf_path)
+                .with_context(|| format!("failed to read {}", elf_path.display()))?;
+            ExecutorImpl::from_elf(env, &elf_contents)?
         } else if let Some(ref image_path) = args.mode.image {
-            let image_contents = fs::read(image_path).unwrap();
-            let image = bincode::deserialize(&image_contents).unwrap();
-            ExecutorImpl::new(env, image).unwrap()
+            let image_contents = fs::read(image_path)
+                .with_context(|| format!("failed to read {}", image_path.display()))?;
+            let image = bincode::deserialize(&image_contents)?;
+            ExecutorImpl::new(env, image)?
         } else {
             unreachable!()
         };
-        exec.run().unwrap()
+        exec.run()?
     };
 
-    let prover = args.get_prover();
+    if let Some(report) = &session.profile_report {
+        eprintln!("{report}");
+    }
+
+    // A session that ended in a guest fault is only proven with --prove-guest-errors; otherwise
+    // the prover returns an error that describes the fault.
+    let prover = args.get_prover()?;
     let ctx = VerifierContext::default();
-    let receipt = prover.prove_session(&ctx, &session).unwrap().receipt;
+    let receipt = prover.prove_session(&ctx, &session)?.receipt;
 
-    let receipt_data = bincode::serialize(&receipt).unwrap();
+    let receipt_data = bincode::serialize(&receipt)?;
     let receipt_bytes = bytemuck::cast_slice(&receipt_data);
     if let Some(receipt_file) = args.receipt.as_ref() {
-        fs::write(receipt_file, receipt_bytes).expect("Unable to write receipt file");
+        fs::write(receipt_file, receipt_bytes)
+            .with_context(|| format!("failed to write {}", receipt_file.display()))?;
         if args.verbose > 0 {
             eprintln!(
                 "Wrote {} bytes of receipt to {}",
@@ -166,10 +235,12 @@ pub fn main() {
             );
         }
     }
+
+    Ok(())
 }
 
 impl Cli {
-    fn get_prover(&self) -> Rc<dyn ProverServer> {
+    fn get_prover(&self) -> Result<Rc<dyn ProverServer>> {
         let hashfn = match self.hashfn {
             HashFn::Sha256 => "sha-256",
             HashFn::Poseidon2 => "poseidon2",
@@ -182,7 +253,7 @@ impl Cli {
                 ReceiptKind::Succinct => risc0_zkvm::ReceiptKind::Succinct,
                 ReceiptKind::Groth16 => risc0_zkvm::ReceiptKind::Groth16,
             });
-        get_prover_server(&opts).unwrap()
+        get_prover_server(&opts)
     }
 }
 
@@ -191,3 +262,38 @@ fn run_server(port: u16) {
     let server = ApiServer::new_tcp(addr);
     server.run().unwrap()
 }
+
+fn run_worker(addr: &str) {
+    // Each connection serves a single request, so reconnect after each one. A
+    // refused connection means that the prover is no longer listening.
+    loop {
+        let server = ApiServer::new_tcp(addr);
+        if let Err(err) = server.run() {
+            let refused = err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::ConnectionRefused);
+            if refused {
+                tracing::info!("{addr} is no longer listening");
+                return;
+            }
+            tracing::warn!("request failed: {err:#}");
+        }
+    }
+}
+
+fn run_daemon(addr: &str, workers: usize, queue_size: usize, policy: ResourcePolicy) {
+    let daemon = ApiDaemon::bind(addr)
+        .unwrap()
+        .workers(workers)
+        .queue_size(queue_size)
+        .resource_policy(policy);
+
+    // Finish the requests in progress when interrupted or terminated.
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let handle = daemon.handle();
+        // SAFETY: the action only stores to an atomic, which is async-signal-safe.
+        unsafe { signal_hook_registry::register(signal, move || handle.shutdown()) }.unwrap();
+    }
+
+    daemon.run().unwrap()
+}
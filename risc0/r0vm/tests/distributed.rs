@@ -0,0 +1,98 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    time::Duration,
+};
+
+use assert_cmd::cargo::cargo_bin;
+use risc0_zkvm::{
+    DistributedProver, ExecutorEnv, InnerReceipt, ProveInfo, Prover, ProverOpts, RetryPolicy,
+};
+use risc0_zkvm_methods::{multi_test::MultiTestSpec, MULTI_TEST_ELF, MULTI_TEST_ID};
+
+const WORKERS: usize = 3;
+
+// Prove the multi-test guest with a pool of `r0vm --worker` processes.
+fn prove_distributed(spec: MultiTestSpec, opts: &ProverOpts) -> ProveInfo {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut workers: Vec<Child> = (0..WORKERS)
+        .map(|_| {
+            Command::new(cargo_bin("r0vm"))
+                .arg("--worker")
+                .arg(addr.to_string())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+
+    // A worker that goes away without serving its request, which must be retried elsewhere.
+    drop(TcpStream::connect(addr).unwrap());
+
+    let env = ExecutorEnv::builder()
+        .write(&spec)
+        .unwrap()
+        .segment_limit_po2(16)
+        .build()
+        .unwrap();
+    let prove_info = DistributedProver::new("distributed", listener)
+        .with_workers(WORKERS)
+        .with_retry_policy(
+            RetryPolicy::default().with_backoff(Duration::from_millis(10), Duration::from_secs(1)),
+        )
+        .prove_with_opts(env, MULTI_TEST_ELF, opts)
+        .unwrap();
+
+    // The workers exit once the prover has stopped listening.
+    for worker in workers.iter_mut() {
+        assert!(worker.wait().unwrap().success());
+    }
+    prove_info
+}
+
+#[test_log::test]
+fn composite_proof() {
+    let cycles = 1 << 17;
+    let prove_info =
+        prove_distributed(MultiTestSpec::BusyLoop { cycles }, &ProverOpts::composite());
+    assert!(prove_info.stats.segments > 1);
+    assert_eq!(prove_info.prover.as_deref(), Some("distributed"));
+
+    let receipt = prove_info.receipt;
+    receipt.verify(MULTI_TEST_ID).unwrap();
+    let composite = receipt.inner.composite().unwrap();
+    assert_eq!(composite.segments.len(), prove_info.stats.segments);
+}
+
+#[test_log::test]
+fn succinct_proof() {
+    let cycles = 1 << 17;
+    let prove_info = prove_distributed(MultiTestSpec::BusyLoop { cycles }, &ProverOpts::succinct());
+    assert!(prove_info.stats.segments > 2);
+    prove_info.receipt.verify(MULTI_TEST_ID).unwrap();
+    prove_info.receipt.inner.succinct().unwrap();
+}
+
+#[test_log::test]
+#[cfg(not(feature = "disable-dev-mode"))]
+fn dev_mode() {
+    temp_env::with_var("RISC0_DEV_MODE", Some("1"), || {
+        let prove_info = prove_distributed(MultiTestSpec::DoNothing, &ProverOpts::default());
+        prove_info.receipt.verify(MULTI_TEST_ID).unwrap();
+        assert!(matches!(prove_info.receipt.inner, InnerReceipt::Fake(_)));
+    });
+}
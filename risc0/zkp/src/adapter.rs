@@ -169,8 +169,14 @@ pub trait CircuitInfo {
     const MIX_SIZE: usize;
 }
 
+/// Provides the [PolyExtStepDef] describing a circuit's constraint polynomial, so that it can be
+/// evaluated in caller-provided memory (see [PolyExtStepDef::step_with_scratch]).
+pub trait PolyExtDef {
+    fn poly_ext_def(&self) -> &'static PolyExtStepDef;
+}
+
 /// traits implemented by generated rust code used in both prover and verifier
-pub trait CircuitCoreDef<F: Field>: CircuitInfo + PolyExt<F> + TapsProvider {}
+pub trait CircuitCoreDef<F: Field>: CircuitInfo + PolyExt<F> + PolyExtDef + TapsProvider {}
 
 /// traits implemented by generated rust code used in only the prover
 pub trait CircuitProveDef<F: Field>:
@@ -278,4 +284,89 @@ impl PolyExtStepDef {
         );
         mix_vars[self.ret]
     }
+
+    /// Number of field elements and [MixState] values needed as scratch memory by
+    /// [PolyExtStepDef::step_with_scratch].
+    pub const fn scratch_size(&self) -> (usize, usize) {
+        (self.block.len() - (self.ret + 1), self.ret + 1)
+    }
+
+    /// Evaluate the constraint polynomial, storing intermediate values in the given slices
+    /// rather than allocating.
+    ///
+    /// Panics if the slices are smaller than reported by [PolyExtStepDef::scratch_size].
+    pub fn step_with_scratch<F: Field>(
+        &self,
+        mix: &F::ExtElem,
+        u: &[F::ExtElem],
+        args: &[&[F::Elem]],
+        fp_vars: &mut [F::ExtElem],
+        mix_vars: &mut [MixState<F::ExtElem>],
+    ) -> MixState<F::ExtElem> {
+        let (fp_size, mix_size) = self.scratch_size();
+        assert!(
+            fp_vars.len() >= fp_size && mix_vars.len() >= mix_size,
+            "Insufficient scratch space for poly_ext"
+        );
+        let mut fp_len = 0;
+        let mut mix_len = 0;
+        for op in self.block.iter() {
+            match op {
+                PolyExtStep::True => {
+                    mix_vars[mix_len] = MixState {
+                        tot: F::ExtElem::ZERO,
+                        mul: F::ExtElem::ONE,
+                    };
+                    mix_len += 1;
+                }
+                PolyExtStep::AndEqz(x, val) => {
+                    let x = mix_vars[*x];
+                    let val = fp_vars[*val];
+                    mix_vars[mix_len] = MixState {
+                        tot: x.tot + x.mul * val,
+                        mul: x.mul * *mix,
+                    };
+                    mix_len += 1;
+                }
+                PolyExtStep::AndCond(x, cond, inner) => {
+                    let x = mix_vars[*x];
+                    let cond = fp_vars[*cond];
+                    let inner = mix_vars[*inner];
+                    mix_vars[mix_len] = MixState {
+                        tot: x.tot + cond * inner.tot * x.mul,
+                        mul: x.mul * inner.mul,
+                    };
+                    mix_len += 1;
+                }
+                PolyExtStep::Const(value) => {
+                    let elem = F::Elem::from_u64(*value as u64);
+                    fp_vars[fp_len] = F::ExtElem::from_subfield(&elem);
+                    fp_len += 1;
+                }
+                PolyExtStep::Get(tap) => {
+                    fp_vars[fp_len] = u[*tap];
+                    fp_len += 1;
+                }
+                PolyExtStep::GetGlobal(base, offset) => {
+                    fp_vars[fp_len] = F::ExtElem::from_subfield(&args[*base][*offset]);
+                    fp_len += 1;
+                }
+                PolyExtStep::Add(x1, x2) => {
+                    fp_vars[fp_len] = fp_vars[*x1] + fp_vars[*x2];
+                    fp_len += 1;
+                }
+                PolyExtStep::Sub(x1, x2) => {
+                    fp_vars[fp_len] = fp_vars[*x1] - fp_vars[*x2];
+                    fp_len += 1;
+                }
+                PolyExtStep::Mul(x1, x2) => {
+                    fp_vars[fp_len] = fp_vars[*x1] * fp_vars[*x2];
+                    fp_len += 1;
+                }
+            }
+        }
+        assert_eq!(fp_len, fp_size, "Miscalculated scratch size for fp_vars");
+        assert_eq!(mix_len, mix_size, "Miscalculated scratch size for mix_vars");
+        mix_vars[self.ret]
+    }
 }
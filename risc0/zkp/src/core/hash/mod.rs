@@ -21,7 +21,7 @@ pub mod poseidon2;
 pub mod poseidon_254;
 pub mod sha;
 
-use alloc::{boxed::Box, rc::Rc, string::String};
+use alloc::{boxed::Box, string::String, sync::Arc};
 
 use risc0_core::field::{baby_bear::BabyBear, Field};
 
@@ -41,6 +41,38 @@ pub trait HashFn<F: Field>: Send + Sync {
     fn hash_ext_elem_slice(&self, slice: &[F::ExtElem]) -> Box<Digest>;
 }
 
+/// A hash function as used by the verifier, returning digests by value.
+///
+/// Unlike [HashFn], implementations need not allocate, which allows verification on targets
+/// without a heap. It is implemented for any `dyn HashFn`, and natively by hash functions such
+/// as [poseidon2::Poseidon2HashFn].
+pub trait VerifyHashFn<F: Field> {
+    /// Generate a hash from a pair of [Digest].
+    fn hash_pair(&self, a: &Digest, b: &Digest) -> Digest;
+
+    /// Generate a hash from a slice of field elements.  This may be unpadded so
+    /// this is only safe to used when the size is known.
+    fn hash_elem_slice(&self, slice: &[F::Elem]) -> Digest;
+
+    /// Generate a hash from a slice of extension field element.  This may be
+    /// unpadded so this is only safe to used when the size is known.
+    fn hash_ext_elem_slice(&self, slice: &[F::ExtElem]) -> Digest;
+}
+
+impl<F: Field> VerifyHashFn<F> for dyn HashFn<F> {
+    fn hash_pair(&self, a: &Digest, b: &Digest) -> Digest {
+        *HashFn::hash_pair(self, a, b)
+    }
+
+    fn hash_elem_slice(&self, slice: &[F::Elem]) -> Digest {
+        *HashFn::hash_elem_slice(self, slice)
+    }
+
+    fn hash_ext_elem_slice(&self, slice: &[F::ExtElem]) -> Digest {
+        *HashFn::hash_ext_elem_slice(self, slice)
+    }
+}
+
 /// A trait that sets the PRNG used by Fiat-Shamir.  We allow specialization at
 /// this level rather than at RngCore because some hashes such as Poseidon have
 /// elements distributed uniformly over the field natively.
@@ -59,8 +91,26 @@ pub trait Rng<F: Field> {
     fn random_ext_elem(&mut self) -> F::ExtElem;
 }
 
+impl<F: Field, R: Rng<F> + ?Sized> Rng<F> for Box<R> {
+    fn mix(&mut self, val: &Digest) {
+        (**self).mix(val)
+    }
+
+    fn random_bits(&mut self, bits: usize) -> u32 {
+        (**self).random_bits(bits)
+    }
+
+    fn random_elem(&mut self) -> F::Elem {
+        (**self).random_elem()
+    }
+
+    fn random_ext_elem(&mut self) -> F::ExtElem {
+        (**self).random_ext_elem()
+    }
+}
+
 /// Responsible for constructing new Rngs.
-pub trait RngFactory<F: Field> {
+pub trait RngFactory<F: Field>: Send + Sync {
     /// Construct a new Rng
     fn new_rng(&self) -> Box<dyn Rng<F>>;
 }
@@ -71,10 +121,10 @@ pub struct HashSuite<F: Field> {
     pub name: String,
 
     /// Define the hash used by the HashSuite
-    pub hashfn: Rc<dyn HashFn<F>>,
+    pub hashfn: Arc<dyn HashFn<F>>,
 
     /// Define an RNG factory
-    pub rng: Rc<dyn RngFactory<F>>,
+    pub rng: Arc<dyn RngFactory<F>>,
 }
 
 impl<F: Field> Clone for HashSuite<F> {
@@ -18,7 +18,7 @@
 pub(crate) mod consts;
 mod rng;
 
-use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 
 use risc0_core::field::{
     baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem, Elem},
@@ -84,8 +84,8 @@ impl PoseidonHashSuite {
     pub fn new_suite() -> HashSuite<BabyBear> {
         HashSuite {
             name: "poseidon".into(),
-            hashfn: Rc::new(PoseidonHashFn {}),
-            rng: Rc::new(PoseidonRngFactory {}),
+            hashfn: Arc::new(PoseidonHashFn {}),
+            rng: Arc::new(PoseidonRngFactory {}),
         }
     }
 }
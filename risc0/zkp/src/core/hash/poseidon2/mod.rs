@@ -19,7 +19,7 @@
 pub(crate) mod consts;
 mod rng;
 
-use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use alloc::{boxed::Box, sync::Arc};
 
 use risc0_core::field::{
     baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
@@ -28,7 +28,7 @@ use risc0_core::field::{
 
 use self::consts::{M_INT_DIAG_HZN, ROUNDS_HALF_FULL, ROUNDS_PARTIAL, ROUND_CONSTANTS};
 pub use self::{consts::CELLS, rng::Poseidon2Rng};
-use super::{HashFn, HashSuite, Rng, RngFactory};
+use super::{HashFn, HashSuite, Rng, RngFactory, VerifyHashFn};
 use crate::core::digest::{Digest, DIGEST_WORDS};
 
 /// The 'rate' of the sponge, i.e. how much we can safely add/remove per mixing.
@@ -38,28 +38,37 @@ pub const CELLS_RATE: usize = 16;
 pub const CELLS_OUT: usize = 8;
 
 /// A hash implementation for Poseidon2
-struct Poseidon2HashFn;
+pub struct Poseidon2HashFn;
 
 impl HashFn<BabyBear> for Poseidon2HashFn {
     fn hash_pair(&self, a: &Digest, b: &Digest) -> Box<Digest> {
-        let both: Vec<BabyBearElem> = a
-            .as_words()
-            .iter()
-            .chain(b.as_words())
-            .map(|w| BabyBearElem::new_raw(*w))
-            .collect();
-        assert!(both.len() == DIGEST_WORDS * 2);
-        for elem in &both {
+        Box::new(VerifyHashFn::hash_pair(self, a, b))
+    }
+
+    fn hash_elem_slice(&self, slice: &[BabyBearElem]) -> Box<Digest> {
+        Box::new(VerifyHashFn::hash_elem_slice(self, slice))
+    }
+
+    fn hash_ext_elem_slice(&self, slice: &[BabyBearExtElem]) -> Box<Digest> {
+        Box::new(VerifyHashFn::hash_ext_elem_slice(self, slice))
+    }
+}
+
+impl VerifyHashFn<BabyBear> for Poseidon2HashFn {
+    fn hash_pair(&self, a: &Digest, b: &Digest) -> Digest {
+        let mut both = [BabyBearElem::ZERO; DIGEST_WORDS * 2];
+        for (elem, word) in both.iter_mut().zip(a.as_words().iter().chain(b.as_words())) {
+            *elem = BabyBearElem::new_raw(*word);
             assert!(elem.is_reduced());
         }
         to_digest(unpadded_hash(both.iter()))
     }
 
-    fn hash_elem_slice(&self, slice: &[BabyBearElem]) -> Box<Digest> {
+    fn hash_elem_slice(&self, slice: &[BabyBearElem]) -> Digest {
         to_digest(unpadded_hash(slice.iter()))
     }
 
-    fn hash_ext_elem_slice(&self, slice: &[BabyBearExtElem]) -> Box<Digest> {
+    fn hash_ext_elem_slice(&self, slice: &[BabyBearExtElem]) -> Digest {
         to_digest(unpadded_hash(
             slice.iter().flat_map(|ee| ee.subelems().iter()),
         ))
@@ -82,18 +91,18 @@ impl Poseidon2HashSuite {
     pub fn new_suite() -> HashSuite<BabyBear> {
         HashSuite {
             name: "poseidon2".into(),
-            hashfn: Rc::new(Poseidon2HashFn {}),
-            rng: Rc::new(Poseidon2RngFactory {}),
+            hashfn: Arc::new(Poseidon2HashFn {}),
+            rng: Arc::new(Poseidon2RngFactory {}),
         }
     }
 }
 
-fn to_digest(elems: [BabyBearElem; CELLS_OUT]) -> Box<Digest> {
+fn to_digest(elems: [BabyBearElem; CELLS_OUT]) -> Digest {
     let mut state: [u32; DIGEST_WORDS] = [0; DIGEST_WORDS];
     for i in 0..DIGEST_WORDS {
         state[i] = elems[i].as_u32_montgomery();
     }
-    Box::new(Digest::from(state))
+    Digest::from(state)
 }
 
 fn add_round_constants_full(cells: &mut [BabyBearElem; CELLS], round: usize) {
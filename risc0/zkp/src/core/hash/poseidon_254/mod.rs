@@ -17,7 +17,7 @@
 
 mod consts;
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 use ff::{Field, PrimeField};
 use risc0_core::field::{
@@ -229,8 +229,8 @@ impl Poseidon254HashSuite {
     pub fn new_suite() -> HashSuite<BabyBear> {
         HashSuite {
             name: "poseidon254".into(),
-            hashfn: Rc::new(Poseidon254HashFn {}),
-            rng: Rc::new(PoseidonRngFactory {}),
+            hashfn: Arc::new(Poseidon254HashFn {}),
+            rng: Arc::new(PoseidonRngFactory {}),
         }
     }
 }
@@ -14,8 +14,16 @@
 
 //! CPU implementation of the HAL.
 
-use std::{fmt::Debug, ops::Range, sync::Arc};
+mod disk;
+mod simd;
 
+use std::{
+    fmt::Debug,
+    ops::{Deref, DerefMut, Range},
+    sync::Arc,
+};
+
+use bytemuck::NoUninit;
 use ndarray::{ArrayView, ArrayViewMut, Axis};
 use parking_lot::{
     MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
@@ -23,6 +31,7 @@ use parking_lot::{
 use rayon::prelude::*;
 use risc0_core::field::{Elem, ExtElem, Field};
 
+use self::{disk::DiskVec, simd::Isa};
 use super::{tracker, Buffer, Hal};
 use crate::{
     core::{
@@ -34,13 +43,86 @@ use crate::{
     FRI_FOLD,
 };
 
+// The number of elements given to each task of the vectorized elementwise
+// kernels, and the number of digests given to each task of the hash kernels.
+const ELTWISE_CHUNK: usize = 1 << 12;
+const HASH_CHUNK: usize = 1 << 6;
+
+// Buffers smaller than this many bytes are always kept in memory.
+const SPILL_THRESHOLD: usize = 1 << 20;
+
+// The size of a page of a buffer stored in a temporary file.
+const PAGE_SIZE: usize = 4096;
+
+/// The number of consecutive rows of a column of 32-bit field elements that
+/// fill a page of a buffer stored in a temporary file.
+///
+/// Kernels that read a row from every column of a buffer give each task at
+/// least this many rows, so that each page is read by one task while it is in
+/// memory, rather than by many tasks scattered across the buffer.
+pub const ROWS_PER_PAGE: usize = PAGE_SIZE / std::mem::size_of::<u32>();
+
 pub struct CpuHal<F: Field> {
     suite: HashSuite<F>,
+    memory_budget: Option<usize>,
 }
 
 impl<F: Field> CpuHal<F> {
     pub fn new(suite: HashSuite<F>) -> Self {
-        Self { suite }
+        Self {
+            suite,
+            memory_budget: None,
+        }
+    }
+
+    /// Construct a [CpuHal] that keeps at most `memory_budget` bytes of
+    /// buffers in memory.
+    ///
+    /// Large buffers allocated once the budget is used up are stored in
+    /// temporary files that are mapped into memory, so that the operating
+    /// system can evict their pages, which is slower but lets machines prove
+    /// segments that need more memory than they have. Memory is counted by
+    /// [tracker], across all HALs.
+    pub fn with_memory_budget(suite: HashSuite<F>, memory_budget: usize) -> Self {
+        Self {
+            suite,
+            memory_budget: Some(memory_budget),
+        }
+    }
+
+    // Allocates a buffer in memory, or in a temporary file if it is large and
+    // keeping it in memory would exceed the memory budget.
+    fn alloc<T: NoUninit + Default>(&self, name: &'static str, size: usize) -> CpuBuffer<T> {
+        let bytes = size.saturating_mul(std::mem::size_of::<T>());
+        let spill = self.memory_budget.is_some_and(|budget| {
+            bytes >= SPILL_THRESHOLD
+                && tracker().lock().unwrap().total.saturating_add(bytes) > budget
+        });
+        if spill {
+            tracing::debug!("storing {name} ({bytes} bytes) in a temporary file");
+            match CpuBuffer::new_on_disk(name, size) {
+                Ok(buffer) => return buffer,
+                // The buffer may still fit in memory, so try that rather than fail the proof.
+                Err(err) => tracing::warn!(
+                    "failed to store {name} in a temporary file, keeping it in memory: {err}"
+                ),
+            }
+        }
+        CpuBuffer::new(name, size)
+    }
+
+    fn copy_from<T: NoUninit + Default>(&self, name: &'static str, slice: &[T]) -> CpuBuffer<T> {
+        if self.memory_budget.is_none() {
+            return CpuBuffer::copy_from(name, slice);
+        }
+        let buffer = self.alloc(name, slice.len());
+        buffer.as_slice_mut().copy_from_slice(slice);
+        buffer
+    }
+
+    // The vectorized hash kernels implement Poseidon2 over Baby Bear.
+    fn has_simd_hash(&self) -> bool {
+        self.suite.name == "poseidon2" && simd::is_baby_bear::<F::Elem>()
     }
 }
 
@@ -85,10 +167,36 @@ impl<T> Drop for TrackedVec<T> {
     }
 }
 
+// The contents of a [CpuBuffer], in memory or in a temporary file.
+enum Storage<T> {
+    Memory(TrackedVec<T>),
+    Disk(DiskVec<T>),
+}
+
+impl<T> Deref for Storage<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Storage::Memory(vec) => &vec.0,
+            Storage::Disk(vec) => vec,
+        }
+    }
+}
+
+impl<T> DerefMut for Storage<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Storage::Memory(vec) => &mut vec.0,
+            Storage::Disk(vec) => vec,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CpuBuffer<T> {
     name: &'static str,
-    buf: Arc<RwLock<TrackedVec<T>>>,
+    buf: Arc<RwLock<Storage<T>>>,
     region: Region,
 }
 
@@ -166,7 +274,7 @@ impl<T: Default + Clone> CpuBuffer<T> {
         let buf = vec![T::default(); size];
         CpuBuffer {
             name,
-            buf: Arc::new(RwLock::new(TrackedVec::new(buf))),
+            buf: Arc::new(RwLock::new(Storage::Memory(TrackedVec::new(buf)))),
             region: Region(0, size),
         }
     }
@@ -178,7 +286,9 @@ impl<T: Default + Clone> CpuBuffer<T> {
     fn copy_from(name: &'static str, slice: &[T]) -> Self {
         CpuBuffer {
             name,
-            buf: Arc::new(RwLock::new(TrackedVec::new(slice.to_vec()))),
+            buf: Arc::new(RwLock::new(Storage::Memory(TrackedVec::new(
+                slice.to_vec(),
+            )))),
             region: Region(0, slice.len()),
         }
     }
@@ -190,24 +300,39 @@ impl<T: Default + Clone> CpuBuffer<T> {
         let vec = (0..size).map(f).collect();
         CpuBuffer {
             name,
-            buf: Arc::new(RwLock::new(TrackedVec::new(vec))),
+            buf: Arc::new(RwLock::new(Storage::Memory(TrackedVec::new(vec)))),
             region: Region(0, size),
         }
     }
 
     pub fn as_slice(&self) -> MappedRwLockReadGuard<'_, [T]> {
         let vec = self.buf.read();
-        RwLockReadGuard::map(vec, |vec| &vec.0[self.region.range()])
+        RwLockReadGuard::map(vec, |vec| &vec[self.region.range()])
     }
 
     pub fn as_slice_mut(&self) -> MappedRwLockWriteGuard<'_, [T]> {
         let vec = self.buf.write();
-        RwLockWriteGuard::map(vec, |vec| &mut vec.0[self.region.range()])
+        RwLockWriteGuard::map(vec, |vec| &mut vec[self.region.range()])
     }
 
     pub fn as_slice_sync(&self) -> SyncSlice<'_, T> {
         SyncSlice::new(self.as_slice_mut())
     }
+
+    fn new_on_disk(name: &'static str, size: usize) -> std::io::Result<Self>
+    where
+        T: NoUninit,
+    {
+        Ok(CpuBuffer {
+            name,
+            buf: Arc::new(RwLock::new(Storage::Disk(DiskVec::new(size)?))),
+            region: Region(0, size),
+        })
+    }
+
+    fn is_on_disk(&self) -> bool {
+        matches!(*self.buf.read(), Storage::Disk(_))
+    }
 }
 
 impl<T: Default + Clone> From<Vec<T>> for CpuBuffer<T> {
@@ -215,7 +340,7 @@ impl<T: Default + Clone> From<Vec<T>> for CpuBuffer<T> {
         let size = vec.len();
         CpuBuffer {
             name: "vec",
-            buf: Arc::new(RwLock::new(TrackedVec::new(vec))),
+            buf: Arc::new(RwLock::new(Storage::Memory(TrackedVec::new(vec)))),
             region: Region(0, size),
         }
     }
@@ -242,21 +367,21 @@ impl<T: Clone> Buffer<T> for CpuBuffer<T> {
 
     fn get_at(&self, idx: usize) -> T {
         let buf = self.buf.read();
-        buf.0[idx].clone()
+        buf[idx].clone()
     }
 
     fn view<F: FnOnce(&[T])>(&self, f: F) {
         let buf = self.buf.read();
-        f(&buf.0[self.region.range()]);
+        f(&buf[self.region.range()]);
     }
 
     fn view_mut<F: FnOnce(&mut [T])>(&self, f: F) {
         let mut buf = self.buf.write();
-        f(&mut buf.0[self.region.range()]);
+        f(&mut buf[self.region.range()]);
     }
 
     fn to_vec(&self) -> Vec<T> {
-        self.buf.read().0.clone()
+        self.buf.read().to_vec()
     }
 }
 
@@ -267,15 +392,15 @@ impl<F: Field> Hal for CpuHal<F> {
     type Buffer<T: Clone + Debug + PartialEq> = CpuBuffer<T>;
 
     fn alloc_elem(&self, name: &'static str, size: usize) -> Self::Buffer<Self::Elem> {
-        CpuBuffer::new(name, size)
+        self.alloc(name, size)
     }
 
     fn copy_from_elem(&self, name: &'static str, slice: &[Self::Elem]) -> Self::Buffer<Self::Elem> {
-        CpuBuffer::copy_from(name, slice)
+        self.copy_from(name, slice)
     }
 
     fn alloc_extelem(&self, name: &'static str, size: usize) -> Self::Buffer<Self::ExtElem> {
-        CpuBuffer::new(name, size)
+        self.alloc(name, size)
     }
 
     fn copy_from_extelem(
@@ -283,23 +408,23 @@ impl<F: Field> Hal for CpuHal<F> {
         name: &'static str,
         slice: &[Self::ExtElem],
     ) -> Self::Buffer<Self::ExtElem> {
-        CpuBuffer::copy_from(name, slice)
+        self.copy_from(name, slice)
     }
 
     fn alloc_digest(&self, name: &'static str, size: usize) -> Self::Buffer<Digest> {
-        CpuBuffer::new(name, size)
+        self.alloc(name, size)
     }
 
     fn copy_from_digest(&self, name: &'static str, slice: &[Digest]) -> Self::Buffer<Digest> {
-        CpuBuffer::copy_from(name, slice)
+        self.copy_from(name, slice)
     }
 
     fn alloc_u32(&self, name: &'static str, size: usize) -> Self::Buffer<u32> {
-        CpuBuffer::new(name, size)
+        self.alloc(name, size)
     }
 
     fn copy_from_u32(&self, name: &'static str, slice: &[u32]) -> Self::Buffer<u32> {
-        CpuBuffer::copy_from(name, slice)
+        self.copy_from(name, slice)
     }
 
     #[tracing::instrument(skip_all)]
@@ -310,45 +435,39 @@ impl<F: Field> Hal for CpuHal<F> {
         count: usize,
         expand_bits: usize,
     ) {
-        // batch_expand
-        {
-            let out_size = output.size() / count;
-            let in_size = input.size() / count;
-            let expand_bits = log2_ceil(out_size / in_size);
-            assert_eq!(out_size, in_size * (1 << expand_bits));
-            assert_eq!(out_size * count, output.size());
-            assert_eq!(in_size * count, input.size());
-            output
-                .as_slice_mut()
-                .par_chunks_exact_mut(out_size)
-                .zip(input.as_slice().par_chunks_exact(in_size))
-                .for_each(|(output, input)| {
-                    expand(output, input, expand_bits);
-                });
-        }
-
-        // batch_evaluate_ntt
-        {
-            let row_size = output.size() / count;
-            assert_eq!(row_size * count, output.size());
-            output
-                .as_slice_mut()
-                .par_chunks_exact_mut(row_size)
-                .for_each(|row| {
-                    evaluate_ntt::<Self::Elem, Self::Elem>(row, expand_bits);
-                });
-        }
+        let out_size = output.size() / count;
+        let in_size = input.size() / count;
+        let size_bits = log2_ceil(out_size / in_size);
+        assert_eq!(out_size, in_size * (1 << size_bits));
+        assert_eq!(out_size * count, output.size());
+        assert_eq!(in_size * count, input.size());
+        // Expand and evaluate each polynomial in one task, so that its row of
+        // the output is written and transformed while it is in memory, rather
+        // than in two passes over the whole output, which matters when the
+        // output is stored in a temporary file.
+        output
+            .as_slice_mut()
+            .par_chunks_exact_mut(out_size)
+            .zip(input.as_slice().par_chunks_exact(in_size))
+            .for_each(|(row, input)| {
+                expand(row, input, size_bits);
+                match simd::as_baby_bear_mut(row) {
+                    Some(row) => Isa::detect().evaluate_ntt(row, expand_bits),
+                    None => evaluate_ntt::<Self::Elem, Self::Elem>(row, expand_bits),
+                }
+            });
     }
 
     #[tracing::instrument(skip_all)]
     fn batch_interpolate_ntt(&self, io: &Self::Buffer<Self::Elem>, count: usize) {
         let row_size = io.size() / count;
         assert_eq!(row_size * count, io.size());
-        io.as_slice_mut()
-            .par_chunks_exact_mut(row_size)
-            .for_each(|row| {
-                interpolate_ntt::<Self::Elem, Self::Elem>(row);
-            });
+        io.as_slice_mut().par_chunks_exact_mut(row_size).for_each(
+            |row| match simd::as_baby_bear_mut(row) {
+                Some(row) => Isa::detect().interpolate_ntt(row),
+                None => interpolate_ntt::<Self::Elem, Self::Elem>(row),
+            },
+        );
     }
 
     #[tracing::instrument(skip_all)]
@@ -377,23 +496,36 @@ impl<F: Field> Hal for CpuHal<F> {
         assert_eq!(xs.size(), eval_count);
         assert_eq!(out.size(), eval_count);
         let coeffs = &*coeffs.as_slice();
-        let which = which.as_slice();
-        let xs = xs.as_slice();
-        let mut out = out.as_slice_mut();
-        (&which[..], &xs[..], &mut out[..])
-            .into_par_iter()
-            .for_each(|(id, x, out)| {
-                let mut tot = Self::ExtElem::ZERO;
-                let mut cur = Self::ExtElem::ONE;
-                let id = *id as usize;
-                let count = 1 << po2;
+        let xs = &*xs.as_slice();
+        // Evaluate each polynomial at all of its points in one task, so that
+        // its coefficients are read while they are in memory, rather than by
+        // evaluations scattered across tasks, which matters when they are
+        // stored in a temporary file.
+        let mut evals_by_poly = vec![Vec::new(); poly_count];
+        for (eval, id) in which.as_slice().iter().enumerate() {
+            evals_by_poly[*id as usize].push(eval);
+        }
+        let count = 1 << po2;
+        let results: Vec<(usize, Self::ExtElem)> = evals_by_poly
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(id, evals)| {
                 let local = &coeffs[count * id..count * id + count];
-                for coeff in local {
-                    tot += cur * *coeff;
-                    cur *= *x;
-                }
-                *out = tot;
-            });
+                evals.iter().map(move |&eval| {
+                    let mut tot = Self::ExtElem::ZERO;
+                    let mut cur = Self::ExtElem::ONE;
+                    for coeff in local {
+                        tot += cur * *coeff;
+                        cur *= xs[eval];
+                    }
+                    (eval, tot)
+                })
+            })
+            .collect();
+        let mut out = out.as_slice_mut();
+        for (eval, tot) in results {
+            out[eval] = tot;
+        }
     }
 
     #[tracing::instrument(skip_all)]
@@ -404,6 +536,7 @@ impl<F: Field> Hal for CpuHal<F> {
         let mut io = io.as_slice_mut();
         (&mut io[..], 0..count)
             .into_par_iter()
+            .with_min_len(ELTWISE_CHUNK)
             .for_each(|(io, idx)| {
                 let pos = idx & ((1 << bits) - 1);
                 let rev = bit_rev_32(pos as u32) >> (32 - bits);
@@ -471,6 +604,19 @@ impl<F: Field> Hal for CpuHal<F> {
         let mut output = output.as_slice_mut();
         let input1 = input1.as_slice();
         let input2 = input2.as_slice();
+        if let (Some(output), Some(input1), Some(input2)) = (
+            simd::as_baby_bear_mut(&mut output),
+            simd::as_baby_bear(&input1),
+            simd::as_baby_bear(&input2),
+        ) {
+            let isa = Isa::detect();
+            output
+                .par_chunks_mut(ELTWISE_CHUNK)
+                .zip(input1.par_chunks(ELTWISE_CHUNK))
+                .zip(input2.par_chunks(ELTWISE_CHUNK))
+                .for_each(|((o, a), b)| isa.add(o, a, b));
+            return;
+        }
         (&mut output[..], &input1[..], &input2[..])
             .into_par_iter()
             .for_each(|(o, a, b)| {
@@ -566,19 +712,51 @@ impl<F: Field> Hal for CpuHal<F> {
         let row_size = output.size();
         let col_size = matrix.size() / output.size();
         assert_eq!(matrix.size(), col_size * row_size);
+        // Each column is read from every row of the matrix. If the matrix is
+        // in a temporary file, give each task whole pages of every row, so
+        // that each page is read by one task rather than one per column.
+        let chunk_size = if matrix.is_on_disk() {
+            ROWS_PER_PAGE
+        } else {
+            HASH_CHUNK
+        };
         let mut output = output.as_slice_mut();
         let matrix = &*matrix.as_slice();
+        if let Some(matrix) = simd::as_baby_bear(matrix).filter(|_| self.has_simd_hash()) {
+            let isa = Isa::detect();
+            output
+                .par_chunks_mut(chunk_size)
+                .enumerate()
+                .for_each(|(chunk, output)| {
+                    isa.hash_rows(output, matrix, chunk * chunk_size, row_size)
+                });
+            return;
+        }
         let hashfn = self.suite.hashfn.as_ref();
-        output.par_iter_mut().enumerate().for_each(|(idx, output)| {
-            let column: Vec<Self::Elem> =
-                (0..col_size).map(|i| matrix[i * row_size + idx]).collect();
-            *output = *hashfn.hash_elem_slice(column.as_slice());
-        });
+        output
+            .par_iter_mut()
+            .enumerate()
+            .with_min_len(chunk_size)
+            .for_each(|(idx, output)| {
+                let column: Vec<Self::Elem> =
+                    (0..col_size).map(|i| matrix[i * row_size + idx]).collect();
+                *output = *hashfn.hash_elem_slice(column.as_slice());
+            });
     }
 
     fn hash_fold(&self, io: &Self::Buffer<Digest>, input_size: usize, output_size: usize) {
         assert!(io.size() >= 2 * input_size);
         assert_eq!(input_size, 2 * output_size);
+        if self.has_simd_hash() {
+            let isa = Isa::detect();
+            let mut io = io.as_slice_mut();
+            let (output, input) = io[output_size..2 * input_size].split_at_mut(output_size);
+            output
+                .par_chunks_mut(HASH_CHUNK)
+                .zip(input.par_chunks(2 * HASH_CHUNK))
+                .for_each(|(output, input)| isa.hash_pairs(output, input));
+            return;
+        }
         let io = io.as_slice_sync();
         let output = io.slice(output_size, output_size);
         let input = io.slice(input_size, input_size);
@@ -625,10 +803,13 @@ impl<F: Field> Hal for CpuHal<F> {
 mod tests {
     use hex::FromHex;
     use rand::thread_rng;
-    use risc0_core::field::baby_bear::{BabyBear, BabyBearExtElem};
+    use risc0_core::field::baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem};
 
     use super::*;
-    use crate::core::hash::sha::Sha256HashSuite;
+    use crate::{
+        core::hash::{poseidon2::Poseidon2HashSuite, sha::Sha256HashSuite},
+        hal::testutil,
+    };
 
     #[test]
     #[should_panic]
@@ -704,6 +885,68 @@ mod tests {
         );
     }
 
+    fn check_merkle_tree(hal: &CpuHal<BabyBear>, rows: usize) {
+        const COLS: usize = 17;
+        let hashfn = hal.get_hash_suite().hashfn.clone();
+        let mut rng = thread_rng();
+        let matrix: Vec<_> = (0..rows * COLS)
+            .map(|_| BabyBearElem::random(&mut rng))
+            .collect();
+
+        let mut expected = vec![Digest::ZERO; 2 * rows];
+        for idx in 0..rows {
+            let column: Vec<_> = (0..COLS).map(|i| matrix[i * rows + idx]).collect();
+            expected[rows + idx] = *hashfn.hash_elem_slice(&column);
+        }
+        for idx in (1..rows).rev() {
+            expected[idx] = *hashfn.hash_pair(&expected[2 * idx], &expected[2 * idx + 1]);
+        }
+
+        let matrix = hal.copy_from_elem("matrix", &matrix);
+        let nodes = hal.alloc_digest("nodes", 2 * rows);
+        hal.hash_rows(&nodes.slice(rows, rows), &matrix);
+        let mut size = rows;
+        while size > 1 {
+            hal.hash_fold(&nodes, size, size / 2);
+            size /= 2;
+        }
+        assert_eq!(nodes.to_vec()[1..], expected[1..]);
+    }
+
+    #[test]
+    fn poseidon2_merkle_tree() {
+        let hal: CpuHal<BabyBear> = CpuHal::new(Poseidon2HashSuite::new_suite());
+        check_merkle_tree(&hal, 256);
+    }
+
+    #[test]
+    fn spill_to_disk() {
+        let hal: CpuHal<BabyBear> = CpuHal::with_memory_budget(Poseidon2HashSuite::new_suite(), 0);
+        let small = hal.alloc_elem("small", 16);
+        assert!(!small.is_on_disk());
+        let ones = hal.copy_from_elem("ones", &vec![BabyBearElem::ONE; SPILL_THRESHOLD]);
+        assert!(ones.is_on_disk());
+        let twos = hal.alloc_elem("twos", SPILL_THRESHOLD);
+        hal.eltwise_add_elem(&twos, &ones, &ones);
+        twos.view(|twos| assert!(twos.iter().all(|x| *x == BabyBearElem::new(2))));
+
+        let zeros = hal.alloc_extelem("zeros", SPILL_THRESHOLD);
+        assert!(zeros.is_on_disk());
+        zeros.view(|zeros| assert!(zeros.iter().all(|x| *x == BabyBearExtElem::ZERO)));
+
+        check_merkle_tree(&hal, 1 << 14);
+    }
+
+    // The kernels that read large buffers in an order suited to temporary
+    // files give the same results as when the buffers are in memory.
+    #[test]
+    fn spilled_kernels() {
+        let spilled = || CpuHal::<BabyBear>::with_memory_budget(Poseidon2HashSuite::new_suite(), 0);
+        testutil::batch_expand_into_evaluate_ntt(spilled());
+        testutil::batch_evaluate_any(spilled());
+        testutil::zk_shift(spilled());
+    }
+
     #[test]
     fn prefix_products() {
         let hal: CpuHal<BabyBear> = CpuHal::new(Sha256HashSuite::new_suite());
@@ -0,0 +1,86 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Buffers stored in temporary files.
+//!
+//! A [DiskVec] maps a temporary file into memory, so that under memory
+//! pressure the operating system writes its pages back to the file and evicts
+//! them, rather than the prover running out of memory. Files are created in
+//! [std::env::temp_dir], which is set by `TMPDIR` on Unix, and are deleted
+//! when the buffer is dropped.
+
+use std::{
+    io,
+    marker::PhantomData,
+    mem::size_of,
+    ops::{Deref, DerefMut},
+};
+
+use bytemuck::NoUninit;
+use memmap2::MmapMut;
+
+/// A fixed-size array of `T` stored in a temporary file.
+pub(crate) struct DiskVec<T> {
+    mmap: MmapMut,
+    len: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: NoUninit + Default> DiskVec<T> {
+    /// Create an array of `len` default values.
+    pub(crate) fn new(len: usize) -> io::Result<Self> {
+        let bytes = len
+            .checked_mul(size_of::<T>())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "buffer size overflows"))?;
+        let file = tempfile::tempfile()?;
+        file.set_len(bytes as u64)?;
+        // SAFETY: the file is only accessible through this mapping, because it
+        // is not linked into the file system, or is opened exclusively.
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        // The file is filled with zeros, which is the default for field
+        // elements and digests.
+        if bytemuck::bytes_of(&T::default())
+            .iter()
+            .any(|&byte| byte != 0)
+        {
+            let ptr: *mut T = mmap.as_mut_ptr().cast();
+            for i in 0..len {
+                // SAFETY: the mapping is page aligned and holds `len` values.
+                unsafe { ptr.add(i).write(T::default()) };
+            }
+        }
+        Ok(Self {
+            mmap,
+            len,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<T> Deref for DiskVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the mapping is page aligned and holds `len` values, each of
+        // which was written as a `T`, or is zero when that is the default `T`.
+        unsafe { std::slice::from_raw_parts(self.mmap.as_ptr().cast(), self.len) }
+    }
+}
+
+impl<T> DerefMut for DiskVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for `deref`.
+        unsafe { std::slice::from_raw_parts_mut(self.mmap.as_mut_ptr().cast(), self.len) }
+    }
+}
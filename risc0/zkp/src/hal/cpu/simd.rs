@@ -0,0 +1,718 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Vectorized Baby Bear kernels for the CPU HAL.
+//!
+//! The kernels are written once against [Packed], a vector of Baby Bear
+//! elements in Montgomery form, and compiled for each instruction set by the
+//! `kernels!` macro, which enables the matching target features. The best
+//! instruction set supported by the CPU is detected at runtime. Work that does
+//! not fill a whole vector, such as the first layers of an NTT or the last
+//! rows of a matrix, is done by the scalar implementation, so the results are
+//! identical whichever instruction set is used.
+
+// Only the scalar kernels are used on other architectures.
+#![cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    allow(dead_code)
+)]
+
+use std::{any::TypeId, sync::OnceLock};
+
+use risc0_core::field::{baby_bear::BabyBearElem, Elem, RootsOfUnity};
+
+use crate::core::{
+    digest::{Digest, DIGEST_WORDS},
+    hash::{
+        poseidon2::{
+            consts::{M_INT_DIAG_HZN, ROUNDS_HALF_FULL, ROUNDS_PARTIAL, ROUND_CONSTANTS},
+            Poseidon2HashFn, CELLS, CELLS_OUT, CELLS_RATE,
+        },
+        VerifyHashFn,
+    },
+    log2_ceil, ntt,
+};
+
+/// P^-1 mod 2^32, used for Montgomery reduction.
+const P_INV: u32 = 0x88000001;
+
+/// The largest number of lanes of any [Packed] implementation.
+const MAX_WIDTH: usize = 16;
+
+/// A vector of Baby Bear elements in Montgomery form.
+///
+/// All methods are unsafe because they must only be called from functions
+/// compiled with the target features of the implementation.
+trait Packed: Copy {
+    /// The number of elements in a vector.
+    const WIDTH: usize;
+
+    /// Load `WIDTH` elements, which need not be aligned.
+    unsafe fn load(src: *const BabyBearElem) -> Self;
+
+    /// Store `WIDTH` elements, which need not be aligned.
+    unsafe fn store(self, dst: *mut BabyBearElem);
+
+    unsafe fn splat(value: BabyBearElem) -> Self;
+
+    unsafe fn add(self, rhs: Self) -> Self;
+
+    unsafe fn sub(self, rhs: Self) -> Self;
+
+    unsafe fn mul(self, rhs: Self) -> Self;
+}
+
+// Defines the entry points of an instruction set, which instantiate the
+// generic kernels below for `$packed` with `$feature` enabled. This must be
+// done in functions with the `target_feature` attribute, rather than in the
+// kernels themselves, so that the intrinsics are inlined into the kernels.
+macro_rules! kernels {
+    ($packed:ty, $feature:literal) => {
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn add(out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+            super::add::<$packed>(out, a, b)
+        }
+
+        #[cfg(test)]
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn mul(out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+            super::mul::<$packed>(out, a, b)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn scale(io: &mut [BabyBearElem], factor: BabyBearElem) {
+            super::scale::<$packed>(io, factor)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn evaluate_ntt(io: &mut [BabyBearElem], n: usize, expand_bits: usize) {
+            if n == 0 || n == expand_bits {
+                return;
+            }
+            let half = 1 << (n - 1);
+            evaluate_ntt(&mut io[..half], n - 1, expand_bits);
+            evaluate_ntt(&mut io[half..], n - 1, expand_bits);
+            super::fwd_layer::<$packed>(io, n);
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn interpolate_ntt(io: &mut [BabyBearElem], n: usize) {
+            if n == 0 {
+                return;
+            }
+            let half = 1 << (n - 1);
+            super::rev_layer::<$packed>(io, n);
+            interpolate_ntt(&mut io[..half], n - 1);
+            interpolate_ntt(&mut io[half..], n - 1);
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn hash_rows(
+            output: &mut [Digest],
+            matrix: &[BabyBearElem],
+            offset: usize,
+            row_size: usize,
+        ) {
+            super::hash_rows::<$packed>(output, matrix, offset, row_size)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn hash_pairs(output: &mut [Digest], input: &[Digest]) {
+            super::hash_pairs::<$packed>(output, input)
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+mod avx512;
+#[cfg(target_arch = "aarch64")]
+mod neon;
+
+/// An instruction set that the kernels are compiled for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Isa {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+    Avx512,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl Isa {
+    /// Returns the best instruction set supported by this CPU.
+    pub(crate) fn detect() -> Self {
+        static ISA: OnceLock<Isa> = OnceLock::new();
+        *ISA.get_or_init(|| {
+            let isa = *Self::supported().last().unwrap();
+            tracing::debug!("CPU HAL using {isa:?} kernels");
+            isa
+        })
+    }
+
+    /// Returns every instruction set supported by this CPU, from worst to best.
+    pub(crate) fn supported() -> Vec<Self> {
+        #[allow(unused_mut)]
+        let mut supported = vec![Isa::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            supported.push(Isa::Avx2);
+        }
+        #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+        if is_x86_feature_detected!("avx512f") {
+            supported.push(Isa::Avx512);
+        }
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            supported.push(Isa::Neon);
+        }
+        supported
+    }
+
+    /// Compute `out[i] = a[i] + b[i]`.
+    pub(crate) fn add(self, out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+        assert_eq!(out.len(), a.len());
+        assert_eq!(out.len(), b.len());
+        // SAFETY: the instruction set is supported by this CPU.
+        match self {
+            Isa::Scalar => {
+                for ((out, a), b) in out.iter_mut().zip(a).zip(b) {
+                    *out = *a + *b;
+                }
+            }
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { avx2::add(out, a, b) },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe { avx512::add(out, a, b) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { neon::add(out, a, b) },
+        }
+    }
+
+    /// Compute `out[i] = a[i] * b[i]`.
+    #[cfg(test)]
+    fn mul(self, out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+        assert_eq!(out.len(), a.len());
+        assert_eq!(out.len(), b.len());
+        match self {
+            Isa::Scalar => {
+                for ((out, a), b) in out.iter_mut().zip(a).zip(b) {
+                    *out = *a * *b;
+                }
+            }
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { avx2::mul(out, a, b) },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe { avx512::mul(out, a, b) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { neon::mul(out, a, b) },
+        }
+    }
+
+    /// The same as [ntt::evaluate_ntt] on Baby Bear elements.
+    pub(crate) fn evaluate_ntt(self, io: &mut [BabyBearElem], expand_bits: usize) {
+        let n = log2_ceil(io.len());
+        assert_eq!(1 << n, io.len());
+        // SAFETY: the instruction set is supported by this CPU.
+        match self {
+            Isa::Scalar => ntt::evaluate_ntt::<BabyBearElem, BabyBearElem>(io, expand_bits),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { avx2::evaluate_ntt(io, n, expand_bits) },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe { avx512::evaluate_ntt(io, n, expand_bits) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { neon::evaluate_ntt(io, n, expand_bits) },
+        }
+    }
+
+    /// The same as [ntt::interpolate_ntt] on Baby Bear elements.
+    pub(crate) fn interpolate_ntt(self, io: &mut [BabyBearElem]) {
+        let n = log2_ceil(io.len());
+        assert_eq!(1 << n, io.len());
+        let norm = BabyBearElem::from_u64(io.len() as u64).inv();
+        // SAFETY: the instruction set is supported by this CPU.
+        match self {
+            Isa::Scalar => ntt::interpolate_ntt::<BabyBearElem, BabyBearElem>(io),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe {
+                avx2::interpolate_ntt(io, n);
+                avx2::scale(io, norm);
+            },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe {
+                avx512::interpolate_ntt(io, n);
+                avx512::scale(io, norm);
+            },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe {
+                neon::interpolate_ntt(io, n);
+                neon::scale(io, norm);
+            },
+        }
+    }
+
+    /// Hash the columns `offset..offset + output.len()` of a matrix with
+    /// `row_size` columns using Poseidon2, as `CpuHal::hash_rows` does.
+    pub(crate) fn hash_rows(
+        self,
+        output: &mut [Digest],
+        matrix: &[BabyBearElem],
+        offset: usize,
+        row_size: usize,
+    ) {
+        assert!(offset + output.len() <= row_size);
+        assert_eq!(matrix.len() % row_size, 0);
+        // SAFETY: the instruction set is supported by this CPU, and the loads
+        // of each vector stay within a row of the matrix.
+        match self {
+            Isa::Scalar => scalar_hash_rows(output, matrix, offset, row_size),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { avx2::hash_rows(output, matrix, offset, row_size) },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe { avx512::hash_rows(output, matrix, offset, row_size) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { neon::hash_rows(output, matrix, offset, row_size) },
+        }
+    }
+
+    /// Compute `output[i]` as the Poseidon2 hash of the pair `input[2 * i]`
+    /// and `input[2 * i + 1]`.
+    pub(crate) fn hash_pairs(self, output: &mut [Digest], input: &[Digest]) {
+        assert_eq!(input.len(), 2 * output.len());
+        // SAFETY: the instruction set is supported by this CPU.
+        match self {
+            Isa::Scalar => scalar_hash_pairs(output, input),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => unsafe { avx2::hash_pairs(output, input) },
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Isa::Avx512 => unsafe { avx512::hash_pairs(output, input) },
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => unsafe { neon::hash_pairs(output, input) },
+        }
+    }
+}
+
+/// Returns true if `E` is the Baby Bear base field element.
+pub(crate) fn is_baby_bear<E: 'static>() -> bool {
+    TypeId::of::<E>() == TypeId::of::<BabyBearElem>()
+}
+
+/// Returns `elems` as Baby Bear elements, if that is their type.
+pub(crate) fn as_baby_bear<E: 'static>(elems: &[E]) -> Option<&[BabyBearElem]> {
+    // SAFETY: `E` is `BabyBearElem`.
+    is_baby_bear::<E>()
+        .then(|| unsafe { std::slice::from_raw_parts(elems.as_ptr().cast(), elems.len()) })
+}
+
+/// Returns `elems` as Baby Bear elements, if that is their type.
+pub(crate) fn as_baby_bear_mut<E: 'static>(elems: &mut [E]) -> Option<&mut [BabyBearElem]> {
+    if !is_baby_bear::<E>() {
+        return None;
+    }
+    // SAFETY: `E` is `BabyBearElem`.
+    Some(unsafe { std::slice::from_raw_parts_mut(elems.as_mut_ptr().cast(), elems.len()) })
+}
+
+fn scalar_hash_rows(
+    output: &mut [Digest],
+    matrix: &[BabyBearElem],
+    offset: usize,
+    row_size: usize,
+) {
+    let col_size = matrix.len() / row_size;
+    for (idx, output) in output.iter_mut().enumerate() {
+        let column: Vec<BabyBearElem> = (0..col_size)
+            .map(|i| matrix[i * row_size + offset + idx])
+            .collect();
+        *output = Poseidon2HashFn.hash_elem_slice(&column);
+    }
+}
+
+fn scalar_hash_pairs(output: &mut [Digest], input: &[Digest]) {
+    for (output, pair) in output.iter_mut().zip(input.chunks_exact(2)) {
+        *output = VerifyHashFn::hash_pair(&Poseidon2HashFn, &pair[0], &pair[1]);
+    }
+}
+
+#[inline(always)]
+unsafe fn add<V: Packed>(out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+    let vectors = out.len() / V::WIDTH * V::WIDTH;
+    for i in (0..vectors).step_by(V::WIDTH) {
+        let sum = V::load(a.as_ptr().add(i)).add(V::load(b.as_ptr().add(i)));
+        sum.store(out.as_mut_ptr().add(i));
+    }
+    for i in vectors..out.len() {
+        out[i] = a[i] + b[i];
+    }
+}
+
+#[cfg(test)]
+#[inline(always)]
+unsafe fn mul<V: Packed>(out: &mut [BabyBearElem], a: &[BabyBearElem], b: &[BabyBearElem]) {
+    let vectors = out.len() / V::WIDTH * V::WIDTH;
+    for i in (0..vectors).step_by(V::WIDTH) {
+        let product = V::load(a.as_ptr().add(i)).mul(V::load(b.as_ptr().add(i)));
+        product.store(out.as_mut_ptr().add(i));
+    }
+    for i in vectors..out.len() {
+        out[i] = a[i] * b[i];
+    }
+}
+
+#[inline(always)]
+unsafe fn scale<V: Packed>(io: &mut [BabyBearElem], factor: BabyBearElem) {
+    let vectors = io.len() / V::WIDTH * V::WIDTH;
+    let packed_factor = V::splat(factor);
+    for i in (0..vectors).step_by(V::WIDTH) {
+        let ptr = io.as_mut_ptr().add(i);
+        V::load(ptr).mul(packed_factor).store(ptr);
+    }
+    for x in io[vectors..].iter_mut() {
+        *x *= factor;
+    }
+}
+
+// Returns the powers `step^0..step^WIDTH` as a vector, and `step^WIDTH`
+// splatted, which advances the powers by a vector each time it is multiplied.
+#[inline(always)]
+unsafe fn twiddles<V: Packed>(step: BabyBearElem) -> (V, V) {
+    let mut powers = [BabyBearElem::ONE; MAX_WIDTH];
+    for i in 1..V::WIDTH {
+        powers[i] = powers[i - 1] * step;
+    }
+    (
+        V::load(powers.as_ptr()),
+        V::splat(powers[V::WIDTH - 1] * step),
+    )
+}
+
+// The last layer of the forward butterfly of size 2^n, which combines its two
+// halves.
+#[inline(always)]
+unsafe fn fwd_layer<V: Packed>(io: &mut [BabyBearElem], n: usize) {
+    let half = 1 << (n - 1);
+    let step = BabyBearElem::ROU_FWD[n];
+    if half < V::WIDTH {
+        let mut cur = BabyBearElem::ONE;
+        for i in 0..half {
+            let a = io[i];
+            let b = io[i + half] * cur;
+            io[i] = a + b;
+            io[i + half] = a - b;
+            cur *= step;
+        }
+        return;
+    }
+    let (mut cur, step) = twiddles::<V>(step);
+    let (lo, hi) = io.split_at_mut(half);
+    for i in (0..half).step_by(V::WIDTH) {
+        let a = V::load(lo.as_ptr().add(i));
+        let b = V::load(hi.as_ptr().add(i)).mul(cur);
+        a.add(b).store(lo.as_mut_ptr().add(i));
+        a.sub(b).store(hi.as_mut_ptr().add(i));
+        cur = cur.mul(step);
+    }
+}
+
+// The first layer of the reverse butterfly of size 2^n, which splits it into
+// two halves.
+#[inline(always)]
+unsafe fn rev_layer<V: Packed>(io: &mut [BabyBearElem], n: usize) {
+    let half = 1 << (n - 1);
+    let step = BabyBearElem::ROU_REV[n];
+    if half < V::WIDTH {
+        let mut cur = BabyBearElem::ONE;
+        for i in 0..half {
+            let a = io[i];
+            let b = io[i + half];
+            io[i] = a + b;
+            io[i + half] = (a - b) * cur;
+            cur *= step;
+        }
+        return;
+    }
+    let (mut cur, step) = twiddles::<V>(step);
+    let (lo, hi) = io.split_at_mut(half);
+    for i in (0..half).step_by(V::WIDTH) {
+        let a = V::load(lo.as_ptr().add(i));
+        let b = V::load(hi.as_ptr().add(i));
+        a.add(b).store(lo.as_mut_ptr().add(i));
+        a.sub(b).mul(cur).store(hi.as_mut_ptr().add(i));
+        cur = cur.mul(step);
+    }
+}
+
+// Poseidon2 on `WIDTH` independent states, with the same rounds as
+// `poseidon2::poseidon2_mix`.
+#[inline(always)]
+unsafe fn poseidon2_mix<V: Packed>(cells: &mut [V; CELLS]) {
+    multiply_by_m_ext(cells);
+    let mut round = 0;
+    for _ in 0..ROUNDS_HALF_FULL {
+        full_round(cells, round);
+        round += 1;
+    }
+    for _ in 0..ROUNDS_PARTIAL {
+        partial_round(cells, round);
+        round += 1;
+    }
+    for _ in 0..ROUNDS_HALF_FULL {
+        full_round(cells, round);
+        round += 1;
+    }
+}
+
+#[inline(always)]
+unsafe fn sbox<V: Packed>(x: V) -> V {
+    let x2 = x.mul(x);
+    let x4 = x2.mul(x2);
+    let x6 = x4.mul(x2);
+    x6.mul(x)
+}
+
+#[inline(always)]
+unsafe fn full_round<V: Packed>(cells: &mut [V; CELLS], round: usize) {
+    for (i, cell) in cells.iter_mut().enumerate() {
+        *cell = sbox(cell.add(V::splat(ROUND_CONSTANTS[round * CELLS + i])));
+    }
+    multiply_by_m_ext(cells);
+}
+
+#[inline(always)]
+unsafe fn partial_round<V: Packed>(cells: &mut [V; CELLS], round: usize) {
+    cells[0] = sbox(cells[0].add(V::splat(ROUND_CONSTANTS[round * CELLS])));
+    multiply_by_m_int(cells);
+}
+
+#[inline(always)]
+unsafe fn multiply_by_m_int<V: Packed>(cells: &mut [V; CELLS]) {
+    // Off-diagonal entries of M_INT are all 1.
+    let mut sum = cells[0];
+    for cell in cells[1..].iter() {
+        sum = sum.add(*cell);
+    }
+    for (cell, diag) in cells.iter_mut().zip(M_INT_DIAG_HZN) {
+        *cell = sum.add(V::splat(*diag).mul(*cell));
+    }
+}
+
+#[inline(always)]
+unsafe fn multiply_by_4x4_circulant<V: Packed>(x: [V; 4]) -> [V; 4] {
+    // See appendix B of Poseidon2 paper. Doubling is done by addition.
+    let t0 = x[0].add(x[1]);
+    let t1 = x[2].add(x[3]);
+    let t2 = x[1].add(x[1]).add(t1);
+    let t3 = x[3].add(x[3]).add(t0);
+    let t1_2 = t1.add(t1);
+    let t4 = t1_2.add(t1_2).add(t3);
+    let t0_2 = t0.add(t0);
+    let t5 = t0_2.add(t0_2).add(t2);
+    let t6 = t3.add(t5);
+    let t7 = t2.add(t4);
+    [t6, t5, t7, t4]
+}
+
+#[inline(always)]
+unsafe fn multiply_by_m_ext<V: Packed>(cells: &mut [V; CELLS]) {
+    let mut sums = [V::splat(BabyBearElem::ZERO); 4];
+    for chunk in cells.chunks_exact_mut(4) {
+        let out = multiply_by_4x4_circulant([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        for j in 0..4 {
+            sums[j] = sums[j].add(out[j]);
+            chunk[j] = out[j];
+        }
+    }
+    for (i, cell) in cells.iter_mut().enumerate() {
+        *cell = cell.add(sums[i % 4]);
+    }
+}
+
+// Writes the output cells of each lane to a digest.
+#[inline(always)]
+unsafe fn store_digests<V: Packed>(cells: &[V; CELLS], output: &mut [Digest]) {
+    let mut lanes = [BabyBearElem::ZERO; MAX_WIDTH];
+    for (i, cell) in cells[..CELLS_OUT].iter().enumerate() {
+        cell.store(lanes.as_mut_ptr());
+        for (digest, lane) in output.iter_mut().zip(lanes) {
+            digest.as_mut_words()[i] = lane.as_u32_montgomery();
+        }
+    }
+}
+
+// Hashes a vector of columns at a time, with each lane absorbing one column.
+// The elements of a row are contiguous, so each lane is loaded with its
+// neighbours.
+#[inline(always)]
+unsafe fn hash_rows<V: Packed>(
+    output: &mut [Digest],
+    matrix: &[BabyBearElem],
+    offset: usize,
+    row_size: usize,
+) {
+    let col_size = matrix.len() / row_size;
+    let vectors = output.len() / V::WIDTH * V::WIDTH;
+    for start in (0..vectors).step_by(V::WIDTH) {
+        let column = matrix.as_ptr().add(offset + start);
+        let mut cells = [V::splat(BabyBearElem::ZERO); CELLS];
+        let mut unmixed = 0;
+        for i in 0..col_size {
+            cells[unmixed] = V::load(column.add(i * row_size));
+            unmixed += 1;
+            if unmixed == CELLS_RATE {
+                poseidon2_mix(&mut cells);
+                unmixed = 0;
+            }
+        }
+        if unmixed != 0 || col_size == 0 {
+            // Zero pad to get a CELLS_RATE-aligned number of inputs
+            for cell in cells[unmixed..CELLS_RATE].iter_mut() {
+                *cell = V::splat(BabyBearElem::ZERO);
+            }
+            poseidon2_mix(&mut cells);
+        }
+        store_digests(&cells, &mut output[start..start + V::WIDTH]);
+    }
+    scalar_hash_rows(&mut output[vectors..], matrix, offset + vectors, row_size);
+}
+
+// Hashes a vector of pairs at a time. Digests are stored one after another, so
+// the words of each pair are gathered into lanes.
+#[inline(always)]
+unsafe fn hash_pairs<V: Packed>(output: &mut [Digest], input: &[Digest]) {
+    let vectors = output.len() / V::WIDTH * V::WIDTH;
+    let mut lanes = [BabyBearElem::ZERO; MAX_WIDTH];
+    for start in (0..vectors).step_by(V::WIDTH) {
+        let mut cells = [V::splat(BabyBearElem::ZERO); CELLS];
+        for (i, cell) in cells[..2 * DIGEST_WORDS].iter_mut().enumerate() {
+            for (lane, elem) in lanes[..V::WIDTH].iter_mut().enumerate() {
+                let digest = &input[2 * (start + lane) + i / DIGEST_WORDS];
+                *elem = BabyBearElem::new_raw(digest.as_words()[i % DIGEST_WORDS]);
+                assert!(elem.is_reduced());
+            }
+            *cell = V::load(lanes.as_ptr());
+        }
+        poseidon2_mix(&mut cells);
+        store_digests(&cells, &mut output[start..start + V::WIDTH]);
+    }
+    scalar_hash_pairs(&mut output[vectors..], &input[2 * vectors..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+    use risc0_core::field::{
+        baby_bear::{BabyBearElem, P},
+        Elem,
+    };
+
+    use super::Isa;
+    use crate::core::digest::Digest;
+
+    fn random_elems(count: usize) -> Vec<BabyBearElem> {
+        let mut rng = thread_rng();
+        (0..count).map(|_| BabyBearElem::random(&mut rng)).collect()
+    }
+
+    fn random_digests(count: usize) -> Vec<Digest> {
+        let mut rng = thread_rng();
+        (0..count)
+            .map(|_| Digest::new([(); 8].map(|_| rng.gen_range(0..P))))
+            .collect()
+    }
+
+    // Runs `f` with each supported instruction set, and checks that it returns
+    // the same as with the scalar implementation.
+    fn check<T: PartialEq + std::fmt::Debug>(f: impl Fn(Isa) -> T) {
+        let expected = f(Isa::Scalar);
+        for isa in Isa::supported() {
+            assert_eq!(f(isa), expected, "{isa:?}");
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let edges = [0, 1, 2, P - 2, P - 1].map(BabyBearElem::new);
+        let mut a = random_elems(1000);
+        let mut b = random_elems(1000);
+        for (i, x) in edges.iter().enumerate() {
+            for (j, y) in edges.iter().enumerate() {
+                a[i * edges.len() + j] = *x;
+                b[i * edges.len() + j] = *y;
+            }
+        }
+        check(|isa| {
+            let mut out = vec![BabyBearElem::ZERO; a.len()];
+            isa.add(&mut out, &a, &b);
+            out
+        });
+        check(|isa| {
+            let mut out = vec![BabyBearElem::ZERO; a.len()];
+            isa.mul(&mut out, &a, &b);
+            out
+        });
+    }
+
+    #[test]
+    fn ntt() {
+        for n in 0..=12 {
+            let input = random_elems(1 << n);
+            for expand_bits in 0..=n.min(3) {
+                check(|isa| {
+                    let mut io = input.clone();
+                    isa.evaluate_ntt(&mut io, expand_bits);
+                    io
+                });
+            }
+            check(|isa| {
+                let mut io = input.clone();
+                isa.interpolate_ntt(&mut io);
+                io
+            });
+        }
+    }
+
+    #[test]
+    fn hash_rows() {
+        for row_size in [1, 7, 8, 17, 64] {
+            for col_size in [0, 1, 16, 17, 40] {
+                let matrix = random_elems(row_size * col_size);
+                check(|isa| {
+                    let mut output = vec![Digest::ZERO; row_size];
+                    isa.hash_rows(&mut output, &matrix, 0, row_size);
+                    output
+                });
+                check(|isa| {
+                    let mut output = vec![Digest::ZERO; row_size / 2];
+                    isa.hash_rows(&mut output, &matrix, row_size / 3, row_size);
+                    output
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn hash_pairs() {
+        for count in [0, 1, 5, 16, 33] {
+            let input = random_digests(2 * count);
+            check(|isa| {
+                let mut output = vec![Digest::ZERO; count];
+                isa.hash_pairs(&mut output, &input);
+                output
+            });
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Baby Bear kernels for x86-64 CPUs with AVX2.
+
+use std::arch::x86_64::*;
+
+use risc0_core::field::baby_bear::{BabyBearElem, P};
+
+use super::{Packed, P_INV};
+use crate::core::digest::Digest;
+
+/// Eight Baby Bear elements in Montgomery form.
+#[derive(Clone, Copy)]
+struct Avx2(__m256i);
+
+impl Packed for Avx2 {
+    const WIDTH: usize = 8;
+
+    #[inline(always)]
+    unsafe fn load(src: *const BabyBearElem) -> Self {
+        Self(_mm256_loadu_si256(src.cast()))
+    }
+
+    #[inline(always)]
+    unsafe fn store(self, dst: *mut BabyBearElem) {
+        _mm256_storeu_si256(dst.cast(), self.0)
+    }
+
+    #[inline(always)]
+    unsafe fn splat(value: BabyBearElem) -> Self {
+        Self(_mm256_set1_epi32(value.as_u32_montgomery() as i32))
+    }
+
+    #[inline(always)]
+    unsafe fn add(self, rhs: Self) -> Self {
+        // Both inputs are less than P < 2^31, so the sum cannot overflow, and
+        // subtracting P wraps to a larger value if the sum is already reduced.
+        let sum = _mm256_add_epi32(self.0, rhs.0);
+        let p = _mm256_set1_epi32(P as i32);
+        Self(_mm256_min_epu32(sum, _mm256_sub_epi32(sum, p)))
+    }
+
+    #[inline(always)]
+    unsafe fn sub(self, rhs: Self) -> Self {
+        let diff = _mm256_sub_epi32(self.0, rhs.0);
+        let p = _mm256_set1_epi32(P as i32);
+        Self(_mm256_min_epu32(diff, _mm256_add_epi32(diff, p)))
+    }
+
+    #[inline(always)]
+    unsafe fn mul(self, rhs: Self) -> Self {
+        // `_mm256_mul_epu32` multiplies the even lanes into 64-bit products,
+        // so the odd lanes are shifted down and multiplied separately.
+        let evn = reduce(_mm256_mul_epu32(self.0, rhs.0));
+        let odd = reduce(_mm256_mul_epu32(
+            _mm256_srli_epi64::<32>(self.0),
+            _mm256_srli_epi64::<32>(rhs.0),
+        ));
+        let t = _mm256_blend_epi32::<0b10101010>(_mm256_srli_epi64::<32>(evn), odd);
+        let p = _mm256_set1_epi32(P as i32);
+        Self(_mm256_min_epu32(t, _mm256_add_epi32(t, p)))
+    }
+}
+
+// Montgomery reduction of 64-bit products. With q = prod * P^-1 mod 2^32, the
+// low words of prod and q * P are equal, so the high word of prod - q * P is
+// the reduced product, in (-P, P).
+#[inline(always)]
+unsafe fn reduce(prod: __m256i) -> __m256i {
+    let q = _mm256_mul_epu32(prod, _mm256_set1_epi32(P_INV as i32));
+    _mm256_sub_epi64(prod, _mm256_mul_epu32(q, _mm256_set1_epi32(P as i32)))
+}
+
+kernels!(Avx2, "avx2");
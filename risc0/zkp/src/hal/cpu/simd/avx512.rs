@@ -0,0 +1,87 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Baby Bear kernels for x86-64 CPUs with AVX-512.
+//!
+//! The AVX-512 intrinsics are not yet stable, so these kernels are only built
+//! with the `avx512` feature, which requires a nightly toolchain.
+
+use std::arch::x86_64::*;
+
+use risc0_core::field::baby_bear::{BabyBearElem, P};
+
+use super::{Packed, P_INV};
+use crate::core::digest::Digest;
+
+/// Sixteen Baby Bear elements in Montgomery form.
+#[derive(Clone, Copy)]
+struct Avx512(__m512i);
+
+impl Packed for Avx512 {
+    const WIDTH: usize = 16;
+
+    #[inline(always)]
+    unsafe fn load(src: *const BabyBearElem) -> Self {
+        Self(_mm512_loadu_si512(src.cast()))
+    }
+
+    #[inline(always)]
+    unsafe fn store(self, dst: *mut BabyBearElem) {
+        _mm512_storeu_si512(dst.cast(), self.0)
+    }
+
+    #[inline(always)]
+    unsafe fn splat(value: BabyBearElem) -> Self {
+        Self(_mm512_set1_epi32(value.as_u32_montgomery() as i32))
+    }
+
+    #[inline(always)]
+    unsafe fn add(self, rhs: Self) -> Self {
+        // Both inputs are less than P < 2^31, so the sum cannot overflow, and
+        // subtracting P wraps to a larger value if the sum is already reduced.
+        let sum = _mm512_add_epi32(self.0, rhs.0);
+        let p = _mm512_set1_epi32(P as i32);
+        Self(_mm512_min_epu32(sum, _mm512_sub_epi32(sum, p)))
+    }
+
+    #[inline(always)]
+    unsafe fn sub(self, rhs: Self) -> Self {
+        let diff = _mm512_sub_epi32(self.0, rhs.0);
+        let p = _mm512_set1_epi32(P as i32);
+        Self(_mm512_min_epu32(diff, _mm512_add_epi32(diff, p)))
+    }
+
+    #[inline(always)]
+    unsafe fn mul(self, rhs: Self) -> Self {
+        // `_mm512_mul_epu32` multiplies the even lanes into 64-bit products,
+        // so the odd lanes are shifted down and multiplied separately.
+        let evn = reduce(_mm512_mul_epu32(self.0, rhs.0));
+        let odd = reduce(_mm512_mul_epu32(
+            _mm512_srli_epi64::<32>(self.0),
+            _mm512_srli_epi64::<32>(rhs.0),
+        ));
+        let t = _mm512_mask_blend_epi32(0b1010101010101010, _mm512_srli_epi64::<32>(evn), odd);
+        let p = _mm512_set1_epi32(P as i32);
+        Self(_mm512_min_epu32(t, _mm512_add_epi32(t, p)))
+    }
+}
+
+// Montgomery reduction of 64-bit products, as in the AVX2 kernels.
+#[inline(always)]
+unsafe fn reduce(prod: __m512i) -> __m512i {
+    let q = _mm512_mul_epu32(prod, _mm512_set1_epi32(P_INV as i32));
+    _mm512_sub_epi64(prod, _mm512_mul_epu32(q, _mm512_set1_epi32(P as i32)))
+}
+
+kernels!(Avx512, "avx512f");
@@ -0,0 +1,82 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Baby Bear kernels for AArch64 CPUs with NEON.
+
+use std::arch::aarch64::*;
+
+use risc0_core::field::baby_bear::{BabyBearElem, P};
+
+use super::{Packed, P_INV};
+use crate::core::digest::Digest;
+
+/// Four Baby Bear elements in Montgomery form.
+#[derive(Clone, Copy)]
+struct Neon(uint32x4_t);
+
+impl Packed for Neon {
+    const WIDTH: usize = 4;
+
+    #[inline(always)]
+    unsafe fn load(src: *const BabyBearElem) -> Self {
+        Self(vld1q_u32(src.cast()))
+    }
+
+    #[inline(always)]
+    unsafe fn store(self, dst: *mut BabyBearElem) {
+        vst1q_u32(dst.cast(), self.0)
+    }
+
+    #[inline(always)]
+    unsafe fn splat(value: BabyBearElem) -> Self {
+        Self(vdupq_n_u32(value.as_u32_montgomery()))
+    }
+
+    #[inline(always)]
+    unsafe fn add(self, rhs: Self) -> Self {
+        // Both inputs are less than P < 2^31, so the sum cannot overflow, and
+        // subtracting P wraps to a larger value if the sum is already reduced.
+        let sum = vaddq_u32(self.0, rhs.0);
+        Self(vminq_u32(sum, vsubq_u32(sum, vdupq_n_u32(P))))
+    }
+
+    #[inline(always)]
+    unsafe fn sub(self, rhs: Self) -> Self {
+        let diff = vsubq_u32(self.0, rhs.0);
+        Self(vminq_u32(diff, vaddq_u32(diff, vdupq_n_u32(P))))
+    }
+
+    #[inline(always)]
+    unsafe fn mul(self, rhs: Self) -> Self {
+        // Montgomery multiplication. With q = prod * P^-1 mod 2^32, the low
+        // words of prod and q * P are equal, so the reduced product is the
+        // difference of their high words, in (-P, P).
+        let p = vdupq_n_u32(P);
+        let (prod_lo, prod_hi) = widening_mul(self.0, rhs.0);
+        let q = vmulq_u32(prod_lo, vdupq_n_u32(P_INV));
+        let (_, qp_hi) = widening_mul(q, p);
+        let t = vsubq_u32(prod_hi, qp_hi);
+        Self(vminq_u32(t, vaddq_u32(t, p)))
+    }
+}
+
+// Returns the low and high words of the 64-bit products of each lane.
+#[inline(always)]
+unsafe fn widening_mul(a: uint32x4_t, b: uint32x4_t) -> (uint32x4_t, uint32x4_t) {
+    let prod_01 = vreinterpretq_u32_u64(vmull_u32(vget_low_u32(a), vget_low_u32(b)));
+    let prod_23 = vreinterpretq_u32_u64(vmull_high_u32(a, b));
+    (vuzp1q_u32(prod_01, prod_23), vuzp2q_u32(prod_01, prod_23))
+}
+
+kernels!(Neon, "neon");
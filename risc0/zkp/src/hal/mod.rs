@@ -269,7 +269,8 @@ mod testutil {
         let z_pow = z.pow(H::ExtElem::EXT_SIZE);
 
         let coeffs = generate_elem(&hal, &mut rng, coeffs_size);
-        let which = hal.copy_from_u32("which", &vec![0; eval_size]);
+        let which: Vec<u32> = (0..eval_size).map(|i| (i % poly_count) as u32).collect();
+        let which = hal.copy_from_u32("which", &which);
         let xs = hal.copy_from_extelem("xs", &vec![z_pow; eval_size]);
         let out = hal.alloc_extelem("out", eval_size);
 
@@ -16,6 +16,10 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![deny(rustdoc::broken_intra_doc_links)]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+#![cfg_attr(
+    all(feature = "avx512", target_arch = "x86_64"),
+    feature(stdarch_x86_avx512, avx512_target_feature)
+)]
 
 extern crate alloc;
 
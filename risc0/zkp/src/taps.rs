@@ -66,11 +66,11 @@ pub struct TapSet<'a> {
 }
 
 impl<'a> TapSet<'a> {
-    pub fn num_groups(&self) -> usize {
+    pub const fn num_groups(&self) -> usize {
         self.group_names.len()
     }
 
-    pub fn tap_size(&self) -> usize {
+    pub const fn tap_size(&self) -> usize {
         self.group_begin[self.num_groups()]
     }
 
@@ -116,11 +116,11 @@ impl<'a> TapSet<'a> {
         self.group_names[group_id]
     }
 
-    pub fn combos_size(&self) -> usize {
+    pub const fn combos_size(&self) -> usize {
         self.combos_count
     }
 
-    pub fn reg_count(&self) -> usize {
+    pub const fn reg_count(&self) -> usize {
         self.reg_count
     }
 
@@ -12,22 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::vec::Vec;
-
 use risc0_core::field::{Elem, ExtElem, Field, RootsOfUnity};
 
 use super::Verifier;
 use crate::{
     adapter::CircuitCoreDef,
     core::{
-        hash::HashFn,
+        hash::{Rng, VerifyHashFn},
         log2_ceil,
         ntt::{bit_reverse, interpolate_ntt},
     },
     verify::{merkle::MerkleTreeVerifier, read_iop::ReadIOP, VerificationError},
-    FRI_FOLD, FRI_FOLD_PO2, FRI_MIN_DEGREE, INV_RATE, QUERIES,
+    FRI_FOLD, FRI_FOLD_PO2, FRI_MIN_DEGREE, INV_RATE, MAX_CYCLES_PO2, QUERIES,
 };
 
+/// Upper bound on the number of FRI folding rounds, reached when proving a segment of
+/// [MAX_CYCLES_PO2].
+const MAX_FRI_ROUNDS: usize =
+    (MAX_CYCLES_PO2 - FRI_MIN_DEGREE.trailing_zeros() as usize + FRI_FOLD_PO2 - 1) / FRI_FOLD_PO2;
+
 /// VerifyRoundInfo contains the data against which the queries for a particular
 /// round are checked. This includes the Merkle tree top row data, as well as
 /// the size of the domain of the polynomial, and the mixing parameter.
@@ -38,7 +41,11 @@ struct VerifyRoundInfo<'a, F: Field> {
 }
 
 impl<'a, F: Field> VerifyRoundInfo<'a, F> {
-    pub fn new(iop: &mut ReadIOP<'a, F>, hashfn: &dyn HashFn<F>, in_domain: usize) -> Self {
+    pub fn new<R: Rng<F>, H: VerifyHashFn<F> + ?Sized>(
+        iop: &mut ReadIOP<'a, F, R>,
+        hashfn: &H,
+        in_domain: usize,
+    ) -> Self {
         let domain = in_domain / FRI_FOLD;
         VerifyRoundInfo {
             domain,
@@ -54,33 +61,29 @@ impl<'a, F: Field> VerifyRoundInfo<'a, F> {
     }
 }
 
-impl<'a, F, C> Verifier<'a, F, C>
+impl<'a, F, C, H> Verifier<'a, F, C, H>
 where
     F: Field,
     C: CircuitCoreDef<F>,
+    H: VerifyHashFn<F> + ?Sized,
 {
-    fn verify_query(
+    fn verify_query<R: Rng<F>>(
         &self,
-        round: &mut VerifyRoundInfo<'a, F>,
-        iop: &mut ReadIOP<'a, F>,
+        round: &VerifyRoundInfo<'a, F>,
+        iop: &mut ReadIOP<'a, F, R>,
         pos: &mut usize,
         goal: &mut F::ExtElem,
     ) -> Result<(), VerificationError> {
         let quot = *pos / round.domain;
         let group = *pos % round.domain;
         // Get the column data
-        let data = round
-            .merkle
-            .verify(iop, self.suite.hashfn.as_ref(), group)?;
-        let mut data_ext: Vec<F::ExtElem> = (0..FRI_FOLD)
-            .map(|i| {
-                let mut inps = Vec::with_capacity(F::ExtElem::EXT_SIZE);
-                for j in 0..F::ExtElem::EXT_SIZE {
-                    inps.push(data[j * FRI_FOLD + i]);
-                }
-                F::ExtElem::from_subelems(inps)
-            })
-            .collect();
+        let data = round.merkle.verify(iop, self.hashfn, group)?;
+        let mut data_ext = [F::ExtElem::ZERO; FRI_FOLD];
+        for (i, elem) in data_ext.iter_mut().enumerate() {
+            *elem = F::ExtElem::from_subelems(
+                (0..F::ExtElem::EXT_SIZE).map(|j| data[j * FRI_FOLD + i]),
+            );
+        }
         // Check the existing goal
         if data_ext[quot] != *goal {
             return Err(VerificationError::InvalidProof);
@@ -97,36 +100,29 @@ where
         Ok(())
     }
 
-    pub fn fri_verify<InnerFn>(
+    pub fn fri_verify<R, InnerFn>(
         &self,
-        iop: &mut ReadIOP<'a, F>,
+        iop: &mut ReadIOP<'a, F, R>,
         mut degree: usize,
         mut inner: InnerFn,
     ) -> Result<(), VerificationError>
     where
-        InnerFn: FnMut(&mut ReadIOP<'a, F>, usize) -> Result<F::ExtElem, VerificationError>,
+        R: Rng<F>,
+        InnerFn: FnMut(&mut ReadIOP<'a, F, R>, usize) -> Result<F::ExtElem, VerificationError>,
     {
-        let hashfn = self.suite.hashfn.as_ref();
+        let hashfn = self.hashfn;
         let orig_domain = INV_RATE * degree;
         let mut domain = orig_domain;
         // Prep the folding verifiers
-        let rounds_capacity =
-            (log2_ceil((degree + FRI_FOLD - 1) / FRI_FOLD) + FRI_FOLD_PO2 - 1) / FRI_FOLD_PO2;
-        let mut rounds = Vec::with_capacity(rounds_capacity);
+        let mut rounds: [Option<VerifyRoundInfo<'a, F>>; MAX_FRI_ROUNDS] = Default::default();
+        let mut num_rounds = 0;
         while degree > FRI_MIN_DEGREE {
-            rounds.push(VerifyRoundInfo::new(iop, hashfn, domain));
+            // The number of rounds is bounded by the maximum po2, which the caller checks.
+            rounds[num_rounds] = Some(VerifyRoundInfo::new(iop, hashfn, domain));
+            num_rounds += 1;
             domain /= FRI_FOLD;
             degree /= FRI_FOLD;
         }
-        // We want to minimize reallocation in verify, so make sure we
-        // didn't have to reallocate.
-        assert!(
-            rounds.len() < rounds_capacity,
-            "Did not allocate enough rounds; needed {} for degree {} but only allocated {}",
-            rounds.len(),
-            degree,
-            rounds_capacity
-        );
         // Grab the final coeffs + commit
         let final_coeffs = iop.read_field_elem_slice(F::ExtElem::EXT_SIZE * degree);
         let final_digest = hashfn.hash_elem_slice(final_coeffs);
@@ -134,25 +130,25 @@ where
         // Get the generator for the final polynomial evaluations
         let gen = <F::Elem as RootsOfUnity>::ROU_FWD[log2_ceil(domain)];
         // Do queries
-        let mut poly_buf: Vec<F::ExtElem> = Vec::with_capacity(degree);
         for _ in 0..QUERIES {
             let mut pos = iop.random_bits(log2_ceil(orig_domain)) as usize;
             // Do the 'inner' verification for this index
             let mut goal = inner(iop, pos)?;
             // Verify the per-round proofs
-            for round in &mut rounds {
+            for round in rounds.iter().flatten() {
                 self.verify_query(round, iop, &mut pos, &mut goal)?;
             }
             // Do final verification
-            let x = gen.pow(pos);
-
-            poly_buf.clear();
-            poly_buf.extend((0..degree).map(|i| {
-                F::ExtElem::from_subelems(
+            let x = F::ExtElem::from_subfield(&gen.pow(pos));
+            let mut mul_x = F::ExtElem::ONE;
+            let mut fx = F::ExtElem::ZERO;
+            for i in 0..degree {
+                let coeff = F::ExtElem::from_subelems(
                     (0..F::ExtElem::EXT_SIZE).map(|j| final_coeffs[j * degree + i]),
-                )
-            }));
-            let fx = self.poly_eval(poly_buf.as_slice(), F::ExtElem::from_subfield(&x));
+                );
+                fx += coeff * mul_x;
+                mul_x *= x;
+            }
             if fx != goal {
                 return Err(VerificationError::InvalidProof);
             }
@@ -12,12 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::{boxed::Box, vec::Vec};
-
 use risc0_core::field::Field;
 
 use crate::{
-    core::{digest::Digest, hash::HashFn},
+    core::{
+        digest::Digest,
+        hash::{Rng, VerifyHashFn},
+    },
     merkle::MerkleTreeParams,
     verify::{read_iop::ReadIOP, VerificationError},
 };
@@ -37,53 +38,17 @@ pub(crate) struct MerkleTreeVerifier<'a> {
     // contains the virtual indexes [top_size..top_size*2).
     top: &'a [Digest],
 
-    // These are the rest of the tree.  These have the virtual indexes [1, top_size).
-    #[allow(clippy::vec_box)]
-    rest: Vec<Box<Digest>>,
-}
-
-// Translates from virtual indexes to indexes in the "top" and "rest" arrays.
-trait SplitMerkleIndex {
-    fn idx_to_top(&self, idx: usize) -> usize;
-    fn idx_to_rest(&self, idx: usize) -> usize;
-}
-
-impl SplitMerkleIndex for MerkleTreeParams {
-    // Returns the index into "top" of the given element
-    #[track_caller]
-    fn idx_to_top(&self, idx: usize) -> usize {
-        debug_assert!(
-            idx >= self.top_size,
-            "idx = {idx}, top_size = {}",
-            self.top_size
-        );
-        debug_assert!(
-            idx < 2 * self.top_size,
-            "idx = {idx}, top_size = {}",
-            self.top_size
-        );
-        idx - self.top_size
-    }
-
-    // Returns the index into "rest" of the given element
-    #[track_caller]
-    fn idx_to_rest(&self, idx: usize) -> usize {
-        debug_assert!(
-            idx < self.top_size,
-            "idx = {idx}, top_size = {}",
-            self.top_size
-        );
-        debug_assert!(idx >= 1, "idx = {idx}, top_size = {}", self.top_size);
-        idx - 1
-    }
+    // The root of the tree, computed from the top row. Branches are only ever checked against the
+    // top row, so the rest of the tree above it need not be retained.
+    root: Digest,
 }
 
 impl<'a> MerkleTreeVerifier<'a> {
     /// Constructs a new MerkleTreeVerifier by making the params, and then
     /// computing the root hashes from the top level hashes.
-    pub fn new<F: Field>(
-        iop: &mut ReadIOP<'a, F>,
-        hashfn: &dyn HashFn<F>,
+    pub fn new<F: Field, R: Rng<F>, H: VerifyHashFn<F> + ?Sized>(
+        iop: &mut ReadIOP<'a, F, R>,
+        hashfn: &H,
         row_size: usize,
         col_size: usize,
         queries: usize,
@@ -92,54 +57,25 @@ impl<'a> MerkleTreeVerifier<'a> {
 
         // Fill top vector with digests from IOP.
         let top = iop.read_pod_slice(params.top_size);
-        // Populate hashes up to the root of the tree.
-        let mut rest = Vec::with_capacity(params.top_size - 1);
-
-        let fill_rest = rest.spare_capacity_mut();
-
-        if !fill_rest.is_empty() {
-            for i in (params.top_size / 2..params.top_size).rev() {
-                let top_idx = params.idx_to_top(2 * i);
-                fill_rest[params.idx_to_rest(i)]
-                    .write(hashfn.hash_pair(&top[top_idx], &top[top_idx + 1]));
-            }
-        }
-        for i in (1..params.top_size / 2).rev() {
-            // SAFETY: We're working from the top down, so we will
-            // have already filled elements at upper_rest_idx.
-            let upper_rest_idx = params.idx_to_rest(i * 2);
-            fill_rest[params.idx_to_rest(i)].write(hashfn.hash_pair(
-                unsafe { fill_rest[upper_rest_idx].assume_init_ref() },
-                unsafe { fill_rest[upper_rest_idx + 1].assume_init_ref() },
-            ));
-        }
-
-        // SAFETY: These are all the elements we just filled.
-        unsafe {
-            let filled = fill_rest.len();
-            rest.set_len(filled);
-        };
+        // Hash up to the root of the tree.
+        let root = subtree_root(hashfn, top);
 
         // Commit to root (index 1).
-        let verifier = MerkleTreeVerifier { params, top, rest };
+        let verifier = MerkleTreeVerifier { params, top, root };
         iop.commit(verifier.root());
         verifier
     }
 
     /// Returns the root hash of the tree.
     pub fn root(&self) -> &Digest {
-        if self.rest.is_empty() {
-            &self.top[self.params.idx_to_top(1)]
-        } else {
-            &self.rest[self.params.idx_to_rest(1)]
-        }
+        &self.root
     }
 
     /// Verifies a branch provided by an IOP.
-    pub fn verify<F: Field>(
+    pub fn verify<F: Field, R: Rng<F>, H: VerifyHashFn<F> + ?Sized>(
         &self,
-        iop: &mut ReadIOP<'a, F>,
-        hashfn: &dyn HashFn<F>,
+        iop: &mut ReadIOP<'a, F, R>,
+        hashfn: &H,
         mut idx: usize,
     ) -> Result<&'a [F::Elem], VerificationError> {
         if idx >= self.params.row_size {
@@ -171,17 +107,24 @@ impl<'a> MerkleTreeVerifier<'a> {
                 cur = hashfn.hash_pair(&cur, other);
             }
         }
-        // Once we reduce to an index for which we have the hash, check that it's
-        // correct.
-        let present_hash: &Digest = if idx >= self.params.top_size {
-            &self.top[self.params.idx_to_top(idx)]
-        } else {
-            &self.rest[self.params.idx_to_rest(idx)]
-        };
-        if *present_hash == *cur {
+        // Once we reduce to an index in the top row, check that it's correct.
+        let present_hash: &Digest = &self.top[idx - self.params.top_size];
+        if *present_hash == cur {
             Ok(out)
         } else {
             Err(VerificationError::InvalidProof)
         }
     }
 }
+
+// Computes the root of the subtree whose leaves are the given nodes. Recurses depth-first, so
+// only a logarithmic number of digests are live at once.
+fn subtree_root<F: Field, H: VerifyHashFn<F> + ?Sized>(hashfn: &H, nodes: &[Digest]) -> Digest {
+    match nodes {
+        [node] => *node,
+        _ => {
+            let (left, right) = nodes.split_at(nodes.len() / 2);
+            hashfn.hash_pair(&subtree_root(hashfn, left), &subtree_root(hashfn, right))
+        }
+    }
+}
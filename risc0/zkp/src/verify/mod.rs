@@ -18,8 +18,8 @@ mod fri;
 mod merkle;
 mod read_iop;
 
-use alloc::{vec, vec::Vec};
-use core::{cell::RefCell, fmt, iter::zip};
+use alloc::vec;
+use core::{fmt, iter::zip};
 
 pub(crate) use merkle::MerkleTreeVerifier;
 pub use read_iop::ReadIOP;
@@ -27,10 +27,14 @@ use risc0_core::field::{Elem, ExtElem, Field, RootsOfUnity};
 
 use crate::{
     adapter::{
-        CircuitCoreDef, ProtocolInfo, PROOF_SYSTEM_INFO, REGISTER_GROUP_ACCUM, REGISTER_GROUP_CODE,
-        REGISTER_GROUP_DATA,
+        CircuitCoreDef, MixState, PolyExtStepDef, ProtocolInfo, PROOF_SYSTEM_INFO,
+        REGISTER_GROUP_ACCUM, REGISTER_GROUP_CODE, REGISTER_GROUP_DATA,
+    },
+    core::{
+        digest::Digest,
+        hash::{HashSuite, Rng, VerifyHashFn},
+        log2_ceil,
     },
-    core::{digest::Digest, hash::HashSuite, log2_ceil},
     taps::TapSet,
     INV_RATE, MAX_CYCLES_PO2, QUERIES,
 };
@@ -71,6 +75,7 @@ pub enum VerificationError {
     UnresolvedAssumption {
         digest: Digest,
     },
+    InsufficientScratch,
 }
 
 impl fmt::Debug for VerificationError {
@@ -115,6 +120,9 @@ impl fmt::Display for VerificationError {
             VerificationError::UnresolvedAssumption { digest } => {
                 write!(f, "receipt contains an unresolved assumption: {digest}")
             }
+            VerificationError::InsufficientScratch => {
+                write!(f, "scratch memory is too small to verify the seal")
+            }
         }
     }
 }
@@ -126,42 +134,112 @@ trait VerifyParams<F: Field> {
     const CHECK_SIZE: usize = INV_RATE * F::ExtElem::EXT_SIZE;
 }
 
-struct TapCache<F: Field> {
-    taps: *const TapSet<'static>,
-    mix: F::ExtElem,
-    tap_mix_pows: Vec<F::ExtElem>,
-    check_mix_pows: Vec<F::ExtElem>,
+/// Amount of scratch memory needed to verify a seal for a given circuit, in number of values of
+/// each type held by [VerifierScratch].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScratchSize {
+    /// Number of base field elements.
+    pub elems: usize,
+    /// Number of extension field elements.
+    pub exts: usize,
+    /// Number of [MixState] values.
+    pub mix_states: usize,
+}
+
+impl ScratchSize {
+    /// Compute the scratch memory needed to verify a seal for the given circuit.
+    pub fn new<F: Field, C: CircuitCoreDef<F>>(circuit: &C) -> Self {
+        Self::from_defs::<F>(circuit.get_taps(), circuit.poly_ext_def(), C::MIX_SIZE)
+    }
+
+    /// Compute the scratch memory needed to verify a seal for a circuit with the given taps,
+    /// constraint polynomial and mix size.
+    ///
+    /// Unlike [ScratchSize::new], this can be evaluated in a constant expression, e.g. to size
+    /// buffers placed in static memory.
+    pub const fn from_defs<F: Field>(
+        taps: &TapSet,
+        poly_ext: &PolyExtStepDef,
+        mix_size: usize,
+    ) -> Self {
+        let check_size = INV_RATE * F::ExtElem::EXT_SIZE;
+        let (fp_vars, mix_states) = poly_ext.scratch_size();
+
+        // Values retained from the constraint check through to the end of FRI.
+        let retained = (taps.tot_combo_backs + 1) + taps.reg_count() + check_size;
+        // The evaluated taps and constraint polynomial are only needed before FRI, and the tap
+        // totals only during FRI, so these share the remaining space.
+        let evals = taps.tap_size() + fp_vars;
+        let tots = taps.combos_size() + 1;
+        let transient = if evals > tots { evals } else { tots };
+        Self {
+            elems: mix_size,
+            exts: retained + transient,
+            mix_states,
+        }
+    }
+}
+
+/// Caller-provided memory used to hold intermediate values during verification.
+///
+/// Allows a seal to be verified in fixed memory and without heap allocations, e.g. using buffers
+/// placed in static memory. See [verify_with_scratch].
+pub struct VerifierScratch<'a, F: Field> {
+    elems: &'a mut [F::Elem],
+    exts: &'a mut [F::ExtElem],
+    mix_states: &'a mut [MixState<F::ExtElem>],
+}
+
+impl<'a, F: Field> VerifierScratch<'a, F> {
+    /// Construct a [VerifierScratch] from the given buffers. Their contents need not be
+    /// initialized to any particular value.
+    pub fn new(
+        elems: &'a mut [F::Elem],
+        exts: &'a mut [F::ExtElem],
+        mix_states: &'a mut [MixState<F::ExtElem>],
+    ) -> Self {
+        Self {
+            elems,
+            exts,
+            mix_states,
+        }
+    }
+
+    /// Returns true if this scratch memory is at least as large as the given size.
+    pub fn fits(&self, size: &ScratchSize) -> bool {
+        self.elems.len() >= size.elems
+            && self.exts.len() >= size.exts
+            && self.mix_states.len() >= size.mix_states
+    }
 }
 
-pub(crate) struct Verifier<'a, F, C>
+pub(crate) struct Verifier<'a, F, C, H>
 where
     F: Field,
+    H: ?Sized,
 {
     circuit: &'a C,
-    suite: &'a HashSuite<F>,
+    hashfn: &'a H,
     po2: u32,
     steps: usize,
     out: Option<&'a [F::Elem]>,
-    mix: Vec<F::Elem>,
-    tap_cache: RefCell<Option<TapCache<F>>>,
 }
 
-impl<'a, F: Field, C> VerifyParams<F> for Verifier<'a, F, C> {}
+impl<'a, F: Field, C, H: ?Sized> VerifyParams<F> for Verifier<'a, F, C, H> {}
 
-impl<'a, F, C> Verifier<'a, F, C>
+impl<'a, F, C, H> Verifier<'a, F, C, H>
 where
     F: Field,
     C: CircuitCoreDef<F>,
+    H: VerifyHashFn<F> + ?Sized,
 {
-    fn new(circuit: &'a C, suite: &'a HashSuite<F>) -> Self {
+    fn new(circuit: &'a C, hashfn: &'a H) -> Self {
         Self {
             circuit,
-            suite,
+            hashfn,
             po2: 0,
             steps: 0,
             out: None,
-            mix: Vec::new(),
-            tap_cache: RefCell::new(None),
         }
     }
 
@@ -170,56 +248,24 @@ where
     fn fri_eval_taps(
         &self,
         taps: &TapSet<'static>,
-        mix: F::ExtElem,
         combo_u: &[F::ExtElem],
+        tap_mix_pows: &[F::ExtElem],
+        check_mix_pows: &[F::ExtElem],
+        tot: &mut [F::ExtElem],
         check_row: &[F::Elem],
         back_one: F::Elem,
         x: F::Elem,
         z: F::ExtElem,
         rows: [&[F::Elem]; 3],
     ) -> F::ExtElem {
-        let mut tot = vec![F::ExtElem::ZERO; taps.combos_size() + 1];
         let combo_count = taps.combos_size();
         let x = F::ExtElem::from_subfield(&x);
 
-        let mut tap_cache = self.tap_cache.borrow_mut();
-        if let Some(ref c) = &mut *tap_cache {
-            if c.taps != taps || c.mix != mix {
-                // tracing::debug!("Resetting tap cache");
-                tap_cache.take();
-            }
-        }
-        if tap_cache.is_none() {
-            let mut cur_mix = F::ExtElem::ONE;
-            let mut tap_mix_pows = Vec::with_capacity(taps.reg_count());
-            for _reg in taps.regs() {
-                tap_mix_pows.push(cur_mix);
-                cur_mix *= mix;
-            }
-            assert_eq!(
-                tap_mix_pows.len(),
-                taps.reg_count(),
-                "Miscalculated capacity for tap_mix_pows"
-            );
-            let mut check_mix_pows = Vec::with_capacity(Self::CHECK_SIZE);
-            for _ in 0..Self::CHECK_SIZE {
-                check_mix_pows.push(cur_mix);
-                cur_mix *= mix;
-            }
-
-            tap_cache.replace(TapCache {
-                taps,
-                mix,
-                tap_mix_pows,
-                check_mix_pows,
-            });
-        }
-        let tap_cache = tap_cache.as_ref().unwrap();
-
-        for (reg, cur) in zip(taps.regs(), tap_cache.tap_mix_pows.iter()) {
+        tot.fill(F::ExtElem::ZERO);
+        for (reg, cur) in zip(taps.regs(), tap_mix_pows.iter()) {
             tot[reg.combo_id()] += *cur * rows[reg.group()][reg.offset()];
         }
-        for (i, cur) in zip(0..Self::CHECK_SIZE, tap_cache.check_mix_pows.iter()) {
+        for (i, cur) in zip(0..Self::CHECK_SIZE, check_mix_pows.iter()) {
             tot[combo_count] += *cur * check_row[i];
         }
         let mut ret = F::ExtElem::ZERO;
@@ -241,23 +287,35 @@ where
         ret
     }
 
-    fn verify<CheckCodeFn>(
+    fn verify<R, CheckCodeFn>(
         &mut self,
         seal: &'a [u32],
+        rng: R,
         check_code: CheckCodeFn,
+        scratch: VerifierScratch<'_, F>,
     ) -> Result<(), VerificationError>
     where
+        R: Rng<F>,
         CheckCodeFn: Fn(u32, &Digest) -> Result<(), VerificationError>,
     {
         if seal.is_empty() {
             return Err(VerificationError::ReceiptFormatError);
         }
 
+        if !scratch.fits(&ScratchSize::new(self.circuit)) {
+            return Err(VerificationError::InsufficientScratch);
+        }
+        let VerifierScratch {
+            elems,
+            exts,
+            mix_states,
+        } = scratch;
+
         let taps = self.circuit.get_taps();
-        let hashfn = self.suite.hashfn.as_ref();
+        let hashfn = self.hashfn;
 
         // Make IOP
-        let mut iop = ReadIOP::new(seal, self.suite.rng.as_ref());
+        let mut iop = ReadIOP::with_rng(seal, rng);
 
         // At the start of the protocol, seed the Fiat-Shamir transcript with context information
         // about the proof system and circuit.
@@ -299,7 +357,10 @@ where
         #[cfg(not(target_os = "zkvm"))]
         tracing::debug!("accumulate");
         // Fill in accum mix
-        self.mix = (0..C::MIX_SIZE).map(|_| iop.random_elem()).collect();
+        let accum_mix = &mut elems[..C::MIX_SIZE];
+        for elem in accum_mix.iter_mut() {
+            *elem = iop.random_elem();
+        }
 
         // Get merkle root for the accum merkle tree.
         // The accum merkle tree contains the accumulations for two permutation check
@@ -334,30 +395,42 @@ where
         // Read the U coeffs (the interpolations of the taps) + commit their hash.
         let num_taps = taps.tap_size();
         let coeff_u = iop.read_field_elem_slice(num_taps + Self::CHECK_SIZE);
-        let hash_u = self.suite.hashfn.hash_ext_elem_slice(coeff_u);
+        let hash_u = hashfn.hash_ext_elem_slice(coeff_u);
         iop.commit(&hash_u);
 
+        // Divide up the scratch space for extension field elements.
+        let (combo_u, exts) = exts.split_at_mut(taps.tot_combo_backs + 1);
+        let (tap_mix_pows, exts) = exts.split_at_mut(taps.reg_count());
+        let (check_mix_pows, transient) = exts.split_at_mut(Self::CHECK_SIZE);
+
         // Now, convert U polynomials from coefficient form to evaluation form
+        let (eval_u, fp_vars) = transient.split_at_mut(num_taps);
         let mut cur_pos = 0;
-        let mut eval_u = Vec::with_capacity(num_taps);
+        let mut eval_pos = 0;
         for reg in taps.regs() {
             for i in 0..reg.size() {
                 let x = z * back_one.pow(reg.back(i));
-                let fx = self.poly_eval(&coeff_u[cur_pos..(cur_pos + reg.size())], x);
-                eval_u.push(fx);
+                eval_u[eval_pos] = self.poly_eval(&coeff_u[cur_pos..(cur_pos + reg.size())], x);
+                eval_pos += 1;
             }
             cur_pos += reg.size();
         }
-        assert_eq!(eval_u.len(), num_taps, "Miscalculated capacity for eval_us");
+        assert_eq!(eval_pos, num_taps, "Miscalculated capacity for eval_us");
 
         // Compute the core constraint polynomial.
         // I.e. the set of all constraints mixed by poly_mix
         #[cfg(not(target_os = "zkvm"))]
         tracing::debug!("> compute_polynomial");
-        // let result = self.compute_polynomial(&eval_u, poly_mix);
         let result = self
             .circuit
-            .poly_ext(&poly_mix, &eval_u, &[self.out.unwrap(), &self.mix])
+            .poly_ext_def()
+            .step_with_scratch::<F>(
+                &poly_mix,
+                eval_u,
+                &[self.out.unwrap(), accum_mix],
+                fp_vars,
+                mix_states,
+            )
             .tot;
 
         #[cfg(not(target_os = "zkvm"))]
@@ -404,40 +477,29 @@ where
         // These columns share a denominator in the DEEP-ALI equation.
         // We group these terms together to reduce the number of inverses we
         // need to compute.
-        let mut combo_u: Vec<F::ExtElem> = vec![F::ExtElem::ZERO; taps.tot_combo_backs + 1];
+        combo_u.fill(F::ExtElem::ZERO);
         let mut cur_mix = F::ExtElem::ONE;
         cur_pos = 0;
-        let mut tap_mix_pows = Vec::with_capacity(taps.reg_count());
-        for reg in taps.regs() {
+        for (reg, tap_mix_pow) in zip(taps.regs(), tap_mix_pows.iter_mut()) {
             for i in 0..reg.size() {
                 combo_u[taps.combo_begin[reg.combo_id()] as usize + i] +=
                     cur_mix * coeff_u[cur_pos + i];
             }
-            tap_mix_pows.push(cur_mix);
+            *tap_mix_pow = cur_mix;
             cur_mix *= mix;
             cur_pos += reg.size();
         }
-        assert_eq!(
-            tap_mix_pows.len(),
-            taps.reg_count(),
-            "Miscalculated capacity for tap_mix_pows"
-        );
         // tracing::debug!("cur_mix: {cur_mix:?}, cur_pos: {cur_pos}");
         // Handle check group
-        let mut check_mix_pows = Vec::with_capacity(Self::CHECK_SIZE);
-        for _ in 0..Self::CHECK_SIZE {
+        for check_mix_pow in check_mix_pows.iter_mut() {
             combo_u[taps.tot_combo_backs] += cur_mix * coeff_u[cur_pos];
             cur_pos += 1;
-            check_mix_pows.push(cur_mix);
+            *check_mix_pow = cur_mix;
             cur_mix *= mix;
         }
-        assert_eq!(
-            check_mix_pows.len(),
-            Self::CHECK_SIZE,
-            "Miscalculated capacity for check_mix_pows"
-        );
         // tracing::debug!("cur_mix: {cur_mix:?}");
 
+        let tot = &mut transient[..taps.combos_size() + 1];
         let gen = <F::Elem as RootsOfUnity>::ROU_FWD[log2_ceil(domain)];
         // tracing::debug!("FRI-verify, size = {size}");
         self.fri_verify(&mut iop, size, |iop, idx| {
@@ -449,7 +511,18 @@ where
                 data_merkle.verify(iop, hashfn, idx)?,
             ];
             let check_row = check_merkle.verify(iop, hashfn, idx)?;
-            let ret = self.fri_eval_taps(taps, mix, &combo_u, check_row, back_one, x, z, rows);
+            let ret = self.fri_eval_taps(
+                taps,
+                combo_u,
+                tap_mix_pows,
+                check_mix_pows,
+                tot,
+                check_row,
+                back_one,
+                x,
+                z,
+                rows,
+            );
             Ok(ret)
         })?;
         iop.verify_complete();
@@ -461,16 +534,14 @@ where
     /// NOTE: The globals are the only values known to the verifier, and constitute the public
     /// statement of the prover. In many scenarios, they are the first values sent to the
     /// verifier by the prover, and therefore should be committed at the start of verification.
-    fn execute(&mut self, iop: &mut ReadIOP<'a, F>) {
+    fn execute<R: Rng<F>>(&mut self, iop: &mut ReadIOP<'a, F, R>) {
         let slice = iop.read_field_elem_slice(C::OUTPUT_SIZE + 1);
-        iop.commit(&self.suite.hashfn.hash_elem_slice(slice));
+        iop.commit(&self.hashfn.hash_elem_slice(slice));
 
         // Extract the out buffer and po2 from slice while checking sizes.
-        let (out, &[po2_elem]) = slice.split_at(C::OUTPUT_SIZE) else {
-            unreachable!()
-        };
+        let (out, po2_elem) = slice.split_at(C::OUTPUT_SIZE);
         self.out = Some(out);
-        let (&[po2], &[]) = po2_elem.to_u32_words().split_at(1) else {
+        let (&[po2], &[]) = F::Elem::as_u32_slice(po2_elem).split_at(1) else {
             panic!("po2 elem is larger than u32");
         };
         self.po2 = po2;
@@ -504,5 +575,47 @@ where
     C: CircuitCoreDef<F>,
     CheckCode: Fn(u32, &Digest) -> Result<(), VerificationError>,
 {
-    Verifier::<F, C>::new(circuit, suite).verify(seal, check_code)
+    let size = ScratchSize::new(circuit);
+    let mut elems = vec![F::Elem::ZERO; size.elems];
+    let mut exts = vec![F::ExtElem::ZERO; size.exts];
+    let zero_mix = MixState {
+        tot: F::ExtElem::ZERO,
+        mul: F::ExtElem::ZERO,
+    };
+    let mut mix_states = vec![zero_mix; size.mix_states];
+    let scratch = VerifierScratch::new(&mut elems, &mut exts, &mut mix_states);
+    verify_with_scratch(
+        circuit,
+        suite.hashfn.as_ref(),
+        suite.rng.new_rng(),
+        seal,
+        check_code,
+        scratch,
+    )
+}
+
+/// Verify a seal is valid for the given circuit, and code checking function, without allocating.
+///
+/// Intermediate values are held in the given [VerifierScratch], which must be at least as large
+/// as the [ScratchSize] for the circuit, or [VerificationError::InsufficientScratch] is returned.
+/// Hashing and Fiat-Shamir randomness are provided by
+/// `hashfn` and `rng`, which should not allocate if verification is to be allocation-free (e.g.
+/// [Poseidon2HashFn](crate::core::hash::poseidon2::Poseidon2HashFn) and
+/// [Poseidon2Rng](crate::core::hash::poseidon2::Poseidon2Rng)).
+pub fn verify_with_scratch<F, C, H, R, CheckCode>(
+    circuit: &C,
+    hashfn: &H,
+    rng: R,
+    seal: &[u32],
+    check_code: CheckCode,
+    scratch: VerifierScratch<'_, F>,
+) -> Result<(), VerificationError>
+where
+    F: Field,
+    C: CircuitCoreDef<F>,
+    H: VerifyHashFn<F> + ?Sized,
+    R: Rng<F>,
+    CheckCode: Fn(u32, &Digest) -> Result<(), VerificationError>,
+{
+    Verifier::<F, C, H>::new(circuit, hashfn).verify(seal, rng, check_code, scratch)
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::boxed::Box;
+use core::marker::PhantomData;
 
 use risc0_core::field::{Elem, Field};
 
@@ -21,16 +22,25 @@ use crate::core::{
     hash::{Rng, RngFactory},
 };
 
-pub struct ReadIOP<'a, F: Field> {
+pub struct ReadIOP<'a, F: Field, R: Rng<F> = Box<dyn Rng<F>>> {
     proof: &'a [u32],
-    rng: Box<dyn Rng<F>>,
+    rng: R,
+    phantom: PhantomData<F>,
 }
 
 impl<'a, F: Field> ReadIOP<'a, F> {
     pub fn new(proof: &'a [u32], rng: &dyn RngFactory<F>) -> Self {
+        Self::with_rng(proof, rng.new_rng())
+    }
+}
+
+impl<'a, F: Field, R: Rng<F>> ReadIOP<'a, F, R> {
+    /// Construct a [ReadIOP] driven by the given [Rng], without boxing it.
+    pub fn with_rng(proof: &'a [u32], rng: R) -> Self {
         ReadIOP {
             proof,
-            rng: rng.new_rng(),
+            rng,
+            phantom: PhantomData,
         }
     }
 
@@ -0,0 +1,179 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measures the [HardwareProfile] of this machine, for use with
+//! `Session::estimate` and `cargo risczero estimate`.
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use hotbench::{benchmark_main, BenchGroup};
+use risc0_zkp::hal::tracker;
+use risc0_zkvm::{
+    get_prover_server, ExecutorEnv, ExecutorImpl, HardwareProfile, ProverOpts, Segment, Session,
+    StepProfile, VerifierContext, RECURSION_PO2,
+};
+use risc0_zkvm_methods::FIB_ELF;
+
+const SEGMENT_PO2S: RangeInclusive<usize> = 14..=20;
+
+// Runs a benchmark of a proving step that processes `items` per iteration,
+// and returns its cost, or `None` if the benchmark is filtered out.
+fn measure<I, O>(
+    group: &mut BenchGroup,
+    name: &str,
+    items: usize,
+    mut setup: impl FnMut() -> I,
+    mut routine: impl FnMut(&mut I) -> O,
+    seal_bytes: impl Fn(&O) -> usize,
+) -> Option<StepProfile> {
+    let mut seal = 0;
+    let mut peak_memory = 0;
+    let mut ran = false;
+    group.bench(name, |b| {
+        b.iter(
+            items,
+            || {
+                let input = setup();
+                tracker().lock().unwrap().reset();
+                input
+            },
+            |input| {
+                let output = routine(input);
+                seal = seal_bytes(&output);
+                output
+            },
+        );
+        peak_memory = tracker().lock().unwrap().peak;
+        ran = true;
+    });
+
+    // hotbench reports the throughput of a step, in items per second.
+    let data = group.data().last().filter(|_| ran)?;
+    Some(StepProfile {
+        time: Duration::from_secs_f64(items as f64 / data.value.max(1) as f64),
+        peak_memory: peak_memory as u64,
+        seal_bytes: seal as u64,
+    })
+}
+
+fn execute(iterations: u32, segment_limit_po2: usize) -> Session {
+    let env = ExecutorEnv::builder()
+        .write_slice(&[iterations])
+        .segment_limit_po2(segment_limit_po2 as u32)
+        .build()
+        .unwrap();
+    ExecutorImpl::from_elf(env, FIB_ELF).unwrap().run().unwrap()
+}
+
+// Returns a segment of the given po2, which is the first of a session that
+// runs for longer than one segment.
+fn segment(po2: usize) -> Segment {
+    let session = execute(1 << po2, po2);
+    let segment = session.segments[0].resolve().unwrap();
+    assert_eq!(segment.po2(), po2);
+    segment
+}
+
+fn calibrate() {
+    let mut group = BenchGroup::from_args("calibrate");
+    let opts = ProverOpts::default();
+    let prover = get_prover_server(&opts).unwrap();
+    let ctx = VerifierContext::default();
+
+    let hal = if cfg!(feature = "cuda") {
+        "cuda"
+    } else if cfg!(feature = "metal") {
+        "metal"
+    } else {
+        "cpu"
+    };
+    let mut profile = HardwareProfile {
+        name: format!("{} {hal}", std::env::consts::ARCH),
+        ..Default::default()
+    };
+
+    for po2 in SEGMENT_PO2S {
+        let segment = segment(po2);
+        if let Some(step) = measure(
+            &mut group,
+            &format!("prove/{po2}"),
+            1 << po2,
+            || (),
+            |()| prover.prove_segment(&ctx, &segment).unwrap(),
+            |receipt| receipt.get_seal_bytes().len(),
+        ) {
+            profile.segments.insert(po2, step);
+        }
+    }
+
+    let session = execute(1 << 16, 16);
+    assert!(session.segments.len() >= 2);
+    let receipt = prover.prove_session(&ctx, &session).unwrap().receipt;
+    let composite = receipt.inner.composite().unwrap();
+    let left = prover.lift(&composite.segments[0]).unwrap();
+    let right = prover.lift(&composite.segments[1]).unwrap();
+    let succinct = prover.composite_to_succinct(composite).unwrap();
+
+    if let Some(step) = measure(
+        &mut group,
+        "lift",
+        1 << RECURSION_PO2,
+        || (),
+        |()| prover.lift(&composite.segments[0]).unwrap(),
+        |receipt| receipt.get_seal_bytes().len(),
+    ) {
+        profile.lift = step;
+    }
+    if let Some(step) = measure(
+        &mut group,
+        "join",
+        1 << RECURSION_PO2,
+        || (),
+        |()| prover.join(&left, &right).unwrap(),
+        |receipt| receipt.get_seal_bytes().len(),
+    ) {
+        profile.join = step;
+    }
+    if let Some(step) = measure(
+        &mut group,
+        "identity_p254",
+        1 << RECURSION_PO2,
+        || (),
+        |()| prover.identity_p254(&succinct).unwrap(),
+        |receipt| receipt.get_seal_bytes().len(),
+    ) {
+        profile.identity_p254 = step;
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "docker"))]
+    {
+        let seal_bytes = prover.identity_p254(&succinct).unwrap().get_seal_bytes();
+        profile.stark_to_snark = measure(
+            &mut group,
+            "stark_to_snark",
+            1 << RECURSION_PO2,
+            || (),
+            |()| risc0_zkvm::stark_to_snark(&seal_bytes).unwrap(),
+            |seal| seal.to_vec().len(),
+        );
+    }
+
+    group.summary();
+
+    let path = group.dir().join("profile.json");
+    profile.save(&path).unwrap();
+    println!("hardware profile written to {}", path.display());
+}
+
+benchmark_main!(calibrate);
@@ -14,7 +14,7 @@
 
 use std::{collections::HashMap, env};
 
-use risc0_build::{embed_methods_with_options, DockerOptions, GuestOptions};
+use risc0_build::{embed_methods_with_options, DockerOptions, GuestOptions, MemoryLayout};
 
 fn main() {
     tracing_subscriber::fmt()
@@ -42,20 +42,28 @@ fn main() {
             GuestOptions {
                 features: vec![],
                 use_docker,
+                // Keep frame pointers, to test guest panic backtraces.
+                frame_pointers: true,
+                ..Default::default()
             },
         ),
         (
             "risc0-zkvm-methods-std",
             GuestOptions {
                 features: vec!["test_feature1".to_string(), "test_feature2".to_string()],
-                use_docker: None,
+                // A smaller stack with a guard region, to test stack overflow detection.
+                memory_layout: Some(MemoryLayout {
+                    stack_size: 512 * 1024,
+                    ..Default::default()
+                }),
+                ..Default::default()
             },
         ),
         (
             "risc0-zkvm-methods-cpp-crates",
             GuestOptions {
                 features: vec![],
-                use_docker: None,
+                ..Default::default()
             },
         ),
     ]);
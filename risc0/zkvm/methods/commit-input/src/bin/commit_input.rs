@@ -0,0 +1,33 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use std::io::Read;
+
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+// Echoes standard input to the journal. The guest is built with the `commit-input` feature, so
+// everything it reads is absorbed into the committed input digest, which is checked against the
+// input digest from the host on every exit path.
+fn main() {
+    let mut input = Vec::new();
+    env::stdin().read_to_end(&mut input).unwrap();
+    env::commit_slice(&input);
+    if input.starts_with(b"exit") {
+        env::exit(0);
+    }
+}
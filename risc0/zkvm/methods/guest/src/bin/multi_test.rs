@@ -22,13 +22,14 @@ extern crate alloc;
 use alloc::{
     alloc::{alloc_zeroed, Layout},
     format, vec,
+    vec::Vec,
 };
 use core::arch::asm;
 
 use getrandom::getrandom;
 use risc0_zkp::core::hash::sha::testutil::test_sha_impl;
 use risc0_zkvm::{
-    guest::{env, memory_barrier, sha},
+    guest::{env, memory_barrier, sha, thread},
     sha::{Digest, Sha256},
     Assumption, ReceiptClaim,
 };
@@ -36,6 +37,7 @@ use risc0_zkvm_methods::multi_test::{MultiTestSpec, SYS_MULTI_TEST, SYS_MULTI_TE
 use risc0_zkvm_platform::{
     fileno,
     memory::{self, SYSTEM},
+    softfloat,
     syscall::{bigint, sys_bigint, sys_log, sys_read, sys_read_words, sys_write},
     PAGE_SIZE,
 };
@@ -54,6 +56,14 @@ fn profile_test_func2() {
     unsafe { asm!("nop") }
 }
 
+// Software implementations exported by risc0-zkvm-platform from libm.
+extern "C" {
+    fn sqrt(x: f64) -> f64;
+    fn sqrtf(x: f32) -> f32;
+    fn fma(x: f64, y: f64, z: f64) -> f64;
+    fn fmaf(x: f32, y: f32, z: f32) -> f32;
+}
+
 fn main() {
     let impl_select: MultiTestSpec = env::read();
     match impl_select {
@@ -343,5 +353,111 @@ fn main() {
                 assert_eq!(*value, 0);
             }
         }
+        MultiTestSpec::Threads => {
+            // Threads interleave deterministically, switching only when they yield or block.
+            let mut log = Vec::new();
+            let (tx, rx) = thread::channel();
+            let workers: Vec<_> = (1..=3u32)
+                .map(|i| {
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        for round in 0..2 {
+                            tx.send((i, round)).unwrap();
+                            thread::yield_now();
+                        }
+                        i * 10
+                    })
+                })
+                .collect();
+            drop(tx);
+
+            log.extend(rx.iter());
+            let results: Vec<u32> = workers.into_iter().map(|worker| worker.join()).collect();
+            assert_eq!(results, [10, 20, 30]);
+            assert_eq!(log, [(1, 0), (2, 0), (3, 0), (1, 1), (2, 1), (3, 1)]);
+
+            // Thread IDs are reused once threads are joined.
+            let worker = thread::Builder::new()
+                .stack_size(4096)
+                .spawn(thread::current_id);
+            assert_eq!(worker.id(), 1);
+            assert_eq!(worker.join(), 1);
+        }
+        MultiTestSpec::SoftFloat => {
+            // Results checked by the guest match the software implementations bit for bit.
+            let values = [
+                0.0,
+                -0.0,
+                1.0,
+                -2.5,
+                3.0,
+                0.1,
+                1e-310,
+                -5e-324,
+                1e300,
+                f64::MAX,
+                f64::MIN_POSITIVE,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ];
+            let same = |x: f64, y: f64| x.to_bits() == y.to_bits() || (x.is_nan() && y.is_nan());
+            let same32 = |x: f32, y: f32| same(x as f64, y as f64);
+            for a in values {
+                let a32 = a as f32;
+                assert!(same(softfloat::sqrt_f64(a), unsafe { sqrt(a) }));
+                assert!(same32(softfloat::sqrt_f32(a32), unsafe { sqrtf(a32) }));
+                for b in values {
+                    let b32 = b as f32;
+                    assert!(same(softfloat::add_f64(a, b), a + b));
+                    assert!(same(softfloat::sub_f64(a, b), a - b));
+                    assert!(same(softfloat::mul_f64(a, b), a * b));
+                    assert!(same(softfloat::div_f64(a, b), a / b));
+                    assert!(same(softfloat::fma_f64(a, b, 0.5), unsafe {
+                        fma(a, b, 0.5)
+                    }));
+                    assert!(same(softfloat::fma_f64(a, b, -a * b), unsafe {
+                        fma(a, b, -a * b)
+                    }));
+                    assert!(same32(softfloat::add_f32(a32, b32), a32 + b32));
+                    assert!(same32(softfloat::sub_f32(a32, b32), a32 - b32));
+                    assert!(same32(softfloat::mul_f32(a32, b32), a32 * b32));
+                    assert!(same32(softfloat::div_f32(a32, b32), a32 / b32));
+                    assert!(same32(softfloat::fma_f32(a32, b32, 0.5), unsafe {
+                        fmaf(a32, b32, 0.5)
+                    }));
+                }
+            }
+        }
+        MultiTestSpec::SoftFloatCycles => {
+            // Commits the cycles taken by a batch of each operation, first with the software
+            // implementation, then with the implementation checked by the guest.
+            use core::hint::black_box;
+            const OPS: u64 = 100;
+            fn cycles(op: impl Fn(f64, f64) -> f64) -> u64 {
+                let start = env::cycle_count();
+                for _ in 0..OPS {
+                    black_box(op(
+                        black_box(1.2345678901234567),
+                        black_box(7.654321098765432),
+                    ));
+                }
+                (env::cycle_count() - start) / OPS
+            }
+            let results: Vec<(u64, u64)> = vec![
+                (cycles(|a, b| a + b), cycles(softfloat::add_f64)),
+                (cycles(|a, b| a - b), cycles(softfloat::sub_f64)),
+                (cycles(|a, b| a * b), cycles(softfloat::mul_f64)),
+                (cycles(|a, b| a / b), cycles(softfloat::div_f64)),
+                (
+                    cycles(|a, _| unsafe { sqrt(a) }),
+                    cycles(|a, _| softfloat::sqrt_f64(a)),
+                ),
+                (
+                    cycles(|a, b| unsafe { fma(a, b, 0.5) }),
+                    cycles(|a, b| softfloat::fma_f64(a, b, 0.5)),
+                ),
+            ];
+            env::commit(&results);
+        }
     }
 }
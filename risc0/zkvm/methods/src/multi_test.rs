@@ -95,6 +95,9 @@ pub enum MultiTestSpec {
     TooManySha,
     AlignedAlloc,
     AllocZeroed,
+    Threads,
+    SoftFloat,
+    SoftFloatCycles,
 }
 
 declare_syscall!(pub SYS_MULTI_TEST);
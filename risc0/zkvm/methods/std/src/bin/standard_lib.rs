@@ -50,6 +50,32 @@ fn main() {
             let buf = reader.fill_buf().unwrap();
             risc0_zkvm::guest::env::commit_slice(&buf)
         }
+        "FS" => {
+            // Read the file named on standard input from the virtual filesystem and commit it.
+            let mut path = String::new();
+            stdin().read_to_string(&mut path).unwrap();
+            let contents = risc0_zkvm::guest::fs::read(&path).unwrap();
+            risc0_zkvm::guest::env::commit_slice(&contents);
+        }
+        "FS_DIGEST" => {
+            // Read the file named on standard input and commit the digest of the filesystem reads.
+            let mut path = String::new();
+            stdin().read_to_string(&mut path).unwrap();
+            risc0_zkvm::guest::fs::read(&path).unwrap();
+            risc0_zkvm::guest::env::commit(&risc0_zkvm::guest::fs::digest());
+        }
+        "STACK_OVERFLOW" => {
+            // Recurse until the stack runs into the guard region below it.
+            fn recurse(depth: u32) -> u32 {
+                let frame = std::hint::black_box([depth; 64]);
+                if std::hint::black_box(true) {
+                    recurse(depth + 1) + frame[0]
+                } else {
+                    frame[0]
+                }
+            }
+            risc0_zkvm::guest::env::commit(&recurse(0));
+        }
         _ => {
             panic!("Unknown test mode {test_mode}");
         }
@@ -27,6 +27,8 @@ mod getrandom;
 mod libm_extern;
 #[cfg(all(feature = "rust-runtime", target_os = "zkvm"))]
 pub mod rust_rt;
+pub mod softfloat;
+pub mod thread;
 
 /// Size of a zkVM machine word in bytes.
 /// 4 bytes (i.e. 32 bits) as the zkVM is an implementation of the rv32im ISA.
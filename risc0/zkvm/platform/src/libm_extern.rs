@@ -228,12 +228,26 @@ pub extern "C" fn floor(x: f64) -> f64 {
 
 #[no_mangle]
 pub extern "C" fn fmaf(x: f32, y: f32, z: f32) -> f32 {
-    libm::fmaf(x, y, z)
+    #[cfg(feature = "accel-float")]
+    {
+        crate::softfloat::fma_f32(x, y, z)
+    }
+    #[cfg(not(feature = "accel-float"))]
+    {
+        libm::fmaf(x, y, z)
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn fma(x: f64, y: f64, z: f64) -> f64 {
-    libm::fma(x, y, z)
+    #[cfg(feature = "accel-float")]
+    {
+        crate::softfloat::fma_f64(x, y, z)
+    }
+    #[cfg(not(feature = "accel-float"))]
+    {
+        libm::fma(x, y, z)
+    }
 }
 
 #[no_mangle]
@@ -498,12 +512,26 @@ pub extern "C" fn sin(x: f64) -> f64 {
 
 #[no_mangle]
 pub extern "C" fn sqrtf(x: f32) -> f32 {
-    libm::sqrtf(x)
+    #[cfg(feature = "accel-float")]
+    {
+        crate::softfloat::sqrt_f32(x)
+    }
+    #[cfg(not(feature = "accel-float"))]
+    {
+        libm::sqrtf(x)
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn sqrt(x: f64) -> f64 {
-    libm::sqrt(x)
+    #[cfg(feature = "accel-float")]
+    {
+        crate::softfloat::sqrt_f64(x)
+    }
+    #[cfg(not(feature = "accel-float"))]
+    {
+        libm::sqrt(x)
+    }
 }
 
 #[no_mangle]
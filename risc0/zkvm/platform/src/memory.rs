@@ -28,6 +28,22 @@ pub const SYSTEM: Region = Region::new(0x0C00_0000, mb(16));
 pub const PAGE_TABLE: Region = Region::new(0x0D00_0000, mb(16));
 pub const PRE_LOAD: Region = Region::new(0x0D70_0000, mb(9));
 
+/// Entry point of guests linked with a custom memory layout, which starts the
+/// stack at [STACK_TOP_SYMBOL] rather than at [STACK_TOP]. Guests linked with
+/// the default `_start` entry point are unaffected by it.
+pub const START_WITH_STACK_TOP_SYMBOL: &str = "_start_with_stack_top";
+/// Linker symbol for the top of the stack, used by [START_WITH_STACK_TOP_SYMBOL].
+pub const STACK_TOP_SYMBOL: &str = "__risc0_stack_top";
+/// Linker symbol marking the lowest address of the stack. Together with
+/// [STACK_GUARD_SYMBOL] it bounds a guard region just below the stack, in which
+/// the executor reports any access as a stack overflow.
+pub const STACK_BOTTOM_SYMBOL: &str = "__risc0_stack_bottom";
+/// Linker symbol marking the lowest address of the stack guard region.
+pub const STACK_GUARD_SYMBOL: &str = "__risc0_stack_guard";
+/// Linker symbol that, when defined, limits the heap to end below this address
+/// rather than at the start of [SYSTEM] memory.
+pub const HEAP_END_SYMBOL: &str = "__risc0_heap_end";
+
 pub struct Region {
     start: usize,
     len_bytes: usize,
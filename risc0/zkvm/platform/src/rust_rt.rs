@@ -78,6 +78,27 @@ mod entrypoint {
     "#,
         sym STACK_TOP
     );
+
+    // Entry point of guests linked with a custom memory layout, which starts
+    // the stack at __risc0_stack_top instead (see
+    // memory::START_WITH_STACK_TOP_SYMBOL). It is in a section of its own, so
+    // that the linker drops it from other guests, whose image IDs are then
+    // unchanged. The reference is weak so that those guests still link.
+    core::arch::global_asm!(
+        r#"
+    .section .text._start_with_stack_top
+    .globl _start_with_stack_top
+    .weak __risc0_stack_top
+    _start_with_stack_top:
+        .option push;
+        .option norelax
+        la gp, __global_pointer$
+        .option pop
+        lui sp, %hi(__risc0_stack_top)
+        addi sp, sp, %lo(__risc0_stack_top)
+        call __start;
+    "#
+    );
 }
 
 struct BumpPointerAlloc;
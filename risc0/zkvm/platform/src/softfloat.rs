@@ -0,0 +1,548 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Floating-point arithmetic computed by the host and checked by the guest.
+//!
+//! The zkVM implements rv32im, which has no floating-point instructions, so floating-point
+//! operations are normally emulated in software by `compiler_builtins` and `libm`. Division,
+//! square root and fused multiply-add are particularly expensive to emulate. The functions here
+//! instead ask the host for the result with [sys_float], and check it, in the same way that the
+//! BigInt accelerator checks a quotient provided by the host: the result is correctly rounded if
+//! the exact result lies between the midpoints separating it from its neighbours, which takes a
+//! handful of integer multiplications and comparisons to check. A host that returns any other
+//! result causes the guest to panic.
+//!
+//! Results are rounded to nearest, ties to even, as required by IEEE 754, so they are identical to
+//! those of the software implementations, except for the payloads of NaNs.
+//!
+//! With the `accel-float` feature, these functions are exported as the `compiler_builtins`
+//! intrinsics for addition, subtraction, multiplication and division of [f32] and [f64], and,
+//! together with the `export-libm` feature, as `sqrt`, `sqrtf`, `fma` and `fmaf`. No other
+//! operation is replaced: comparisons, conversions, remainders and the remaining `libm` functions
+//! still use the software implementations, although those in `libm` that are built on the
+//! arithmetic operators benefit indirectly.
+//!
+//! Checking a result costs a few wide integer multiplications, so the saving depends on the
+//! operation. Division, square root and fused multiply-add are much cheaper to check than to
+//! emulate. Addition, subtraction and multiplication are cheap to emulate already, and may gain
+//! little or nothing; the `soft_float_cycles` executor test in `risc0-zkvm` reports the cycles
+//! taken by each operation both ways.
+
+use core::cmp::Ordering;
+
+use crate::syscall::{float, sys_float};
+
+/// The parameters of an IEEE 754 binary format. Numbers are handled as their bits, widened to
+/// 64 bits.
+trait Format {
+    const FRAC_BITS: u32;
+    const EXP_BITS: u32;
+    const FLAG: u32;
+    const WORDS: usize;
+
+    const SIGN: u64 = 1 << (Self::FRAC_BITS + Self::EXP_BITS);
+    const INF: u64 = ((1 << Self::EXP_BITS) - 1) << Self::FRAC_BITS;
+    const NAN: u64 = Self::INF | 1 << (Self::FRAC_BITS - 1);
+    const BIAS: i32 = (1 << (Self::EXP_BITS - 1)) - 1;
+}
+
+struct Single;
+
+impl Format for Single {
+    const FRAC_BITS: u32 = 23;
+    const EXP_BITS: u32 = 8;
+    const FLAG: u32 = 0;
+    const WORDS: usize = 1;
+}
+
+struct Double;
+
+impl Format for Double {
+    const FRAC_BITS: u32 = 52;
+    const EXP_BITS: u32 = 11;
+    const FLAG: u32 = float::F64;
+    const WORDS: usize = 2;
+}
+
+/// Returns `a + b`.
+pub fn add_f32(a: f32, b: f32) -> f32 {
+    f32::from_bits(add::<Single>(a.to_bits() as u64, b.to_bits() as u64) as u32)
+}
+
+/// Returns `a - b`.
+pub fn sub_f32(a: f32, b: f32) -> f32 {
+    add_f32(a, f32::from_bits(b.to_bits() ^ Single::SIGN as u32))
+}
+
+/// Returns `a * b`.
+pub fn mul_f32(a: f32, b: f32) -> f32 {
+    f32::from_bits(mul::<Single>(a.to_bits() as u64, b.to_bits() as u64) as u32)
+}
+
+/// Returns `a / b`.
+pub fn div_f32(a: f32, b: f32) -> f32 {
+    f32::from_bits(div::<Single>(a.to_bits() as u64, b.to_bits() as u64) as u32)
+}
+
+/// Returns the square root of `a`.
+pub fn sqrt_f32(a: f32) -> f32 {
+    f32::from_bits(sqrt::<Single>(a.to_bits() as u64) as u32)
+}
+
+/// Returns `a * b + c`, rounded once.
+pub fn fma_f32(a: f32, b: f32, c: f32) -> f32 {
+    f32::from_bits(fma::<Single>(a.to_bits() as u64, b.to_bits() as u64, c.to_bits() as u64) as u32)
+}
+
+/// Returns `a + b`.
+pub fn add_f64(a: f64, b: f64) -> f64 {
+    f64::from_bits(add::<Double>(a.to_bits(), b.to_bits()))
+}
+
+/// Returns `a - b`.
+pub fn sub_f64(a: f64, b: f64) -> f64 {
+    add_f64(a, f64::from_bits(b.to_bits() ^ Double::SIGN))
+}
+
+/// Returns `a * b`.
+pub fn mul_f64(a: f64, b: f64) -> f64 {
+    f64::from_bits(mul::<Double>(a.to_bits(), b.to_bits()))
+}
+
+/// Returns `a / b`.
+pub fn div_f64(a: f64, b: f64) -> f64 {
+    f64::from_bits(div::<Double>(a.to_bits(), b.to_bits()))
+}
+
+/// Returns the square root of `a`.
+pub fn sqrt_f64(a: f64) -> f64 {
+    f64::from_bits(sqrt::<Double>(a.to_bits()))
+}
+
+/// Returns `a * b + c`, rounded once.
+pub fn fma_f64(a: f64, b: f64, c: f64) -> f64 {
+    f64::from_bits(fma::<Double>(a.to_bits(), b.to_bits(), c.to_bits()))
+}
+
+fn is_nan<F: Format>(x: u64) -> bool {
+    x & !F::SIGN > F::INF
+}
+
+fn is_inf<F: Format>(x: u64) -> bool {
+    x & !F::SIGN == F::INF
+}
+
+fn is_zero<F: Format>(x: u64) -> bool {
+    x & !F::SIGN == 0
+}
+
+fn add<F: Format>(a: u64, b: u64) -> u64 {
+    if is_nan::<F>(a) || is_nan::<F>(b) {
+        return F::NAN;
+    }
+    if is_inf::<F>(a) {
+        return if is_inf::<F>(b) && a != b { F::NAN } else { a };
+    }
+    if is_inf::<F>(b) {
+        return b;
+    }
+    if is_zero::<F>(a) {
+        // The sum of two zeros is only negative if both are.
+        return if is_zero::<F>(b) { a & b } else { b };
+    }
+    if is_zero::<F>(b) {
+        return a;
+    }
+
+    let (a_neg, a_mant, a_exp) = unpack::<F>(a);
+    let (b_neg, b_mant, b_exp) = unpack::<F>(b);
+    let exact = [
+        Term::new(a_neg, a_mant as u128, a_exp),
+        Term::new(b_neg, b_mant as u128, b_exp),
+    ];
+    checked::<F>(float::OP_ADD, &[a, b], |neg, mid| {
+        compare_sum(neg, &exact, mid)
+    })
+}
+
+fn mul<F: Format>(a: u64, b: u64) -> u64 {
+    if is_nan::<F>(a) || is_nan::<F>(b) {
+        return F::NAN;
+    }
+    let sign = (a ^ b) & F::SIGN;
+    if is_inf::<F>(a) || is_inf::<F>(b) {
+        return if is_zero::<F>(a) || is_zero::<F>(b) {
+            F::NAN
+        } else {
+            sign | F::INF
+        };
+    }
+    if is_zero::<F>(a) || is_zero::<F>(b) {
+        return sign;
+    }
+
+    let (a_neg, a_mant, a_exp) = unpack::<F>(a);
+    let (b_neg, b_mant, b_exp) = unpack::<F>(b);
+    let exact = [Term::new(
+        a_neg != b_neg,
+        a_mant as u128 * b_mant as u128,
+        a_exp + b_exp,
+    )];
+    checked::<F>(float::OP_MUL, &[a, b], |neg, mid| {
+        compare_sum(neg, &exact, mid)
+    })
+}
+
+fn div<F: Format>(a: u64, b: u64) -> u64 {
+    if is_nan::<F>(a) || is_nan::<F>(b) {
+        return F::NAN;
+    }
+    let sign = (a ^ b) & F::SIGN;
+    if is_inf::<F>(a) {
+        return if is_inf::<F>(b) {
+            F::NAN
+        } else {
+            sign | F::INF
+        };
+    }
+    if is_inf::<F>(b) {
+        return sign;
+    }
+    if is_zero::<F>(b) {
+        return if is_zero::<F>(a) {
+            F::NAN
+        } else {
+            sign | F::INF
+        };
+    }
+    if is_zero::<F>(a) {
+        return sign;
+    }
+
+    let (a_neg, a_mant, a_exp) = unpack::<F>(a);
+    let (b_neg, b_mant, b_exp) = unpack::<F>(b);
+    checked::<F>(float::OP_DIV, &[a, b], |neg, (mid_mant, mid_exp)| {
+        // As |b| > 0, a / b compares to the midpoint as a * sign(b) compares to midpoint * |b|.
+        sum_sign(&mut [
+            Term::new(neg != (a_neg != b_neg), a_mant as u128, a_exp),
+            Term::new(true, mid_mant as u128 * b_mant as u128, mid_exp + b_exp),
+        ])
+    })
+}
+
+fn sqrt<F: Format>(a: u64) -> u64 {
+    if is_nan::<F>(a) {
+        return F::NAN;
+    }
+    if is_zero::<F>(a) {
+        return a;
+    }
+    if a & F::SIGN != 0 {
+        return F::NAN;
+    }
+    if is_inf::<F>(a) {
+        return a;
+    }
+
+    let (_, a_mant, a_exp) = unpack::<F>(a);
+    checked::<F>(float::OP_SQRT, &[a], |neg, (mid_mant, mid_exp)| {
+        if neg {
+            return Ordering::Less;
+        }
+        // Both sides are positive, so they compare as their squares do.
+        sum_sign(&mut [
+            Term::new(false, a_mant as u128, a_exp),
+            Term::new(true, mid_mant as u128 * mid_mant as u128, 2 * mid_exp),
+        ])
+    })
+}
+
+fn fma<F: Format>(a: u64, b: u64, c: u64) -> u64 {
+    if is_nan::<F>(a) || is_nan::<F>(b) || is_nan::<F>(c) {
+        return F::NAN;
+    }
+    let sign = (a ^ b) & F::SIGN;
+    if is_inf::<F>(a) || is_inf::<F>(b) {
+        return if is_zero::<F>(a) || is_zero::<F>(b) || (is_inf::<F>(c) && c & F::SIGN != sign) {
+            F::NAN
+        } else {
+            sign | F::INF
+        };
+    }
+    if is_inf::<F>(c) {
+        return c;
+    }
+    if is_zero::<F>(a) || is_zero::<F>(b) {
+        // The product is exactly zero, so this is the sum of a signed zero and c.
+        return if is_zero::<F>(c) { sign & c } else { c };
+    }
+
+    let (a_neg, a_mant, a_exp) = unpack::<F>(a);
+    let (b_neg, b_mant, b_exp) = unpack::<F>(b);
+    let (c_neg, c_mant, c_exp) = unpack::<F>(c);
+    let exact = [
+        Term::new(
+            a_neg != b_neg,
+            a_mant as u128 * b_mant as u128,
+            a_exp + b_exp,
+        ),
+        Term::new(c_neg, c_mant as u128, c_exp),
+    ];
+    checked::<F>(float::OP_FMA, &[a, b, c], |neg, mid| {
+        compare_sum(neg, &exact, mid)
+    })
+}
+
+/// Splits a number into its sign, and an integer mantissa and exponent whose product is its
+/// magnitude. The bits of infinity are treated as the power of two following the largest finite
+/// number, which is the value it replaces when rounding.
+fn unpack<F: Format>(x: u64) -> (bool, u64, i32) {
+    let field = ((x & !F::SIGN) >> F::FRAC_BITS) as i32;
+    let frac = x & ((1 << F::FRAC_BITS) - 1);
+    if field == 0 {
+        (x & F::SIGN != 0, frac, 1 - F::BIAS - F::FRAC_BITS as i32)
+    } else {
+        (
+            x & F::SIGN != 0,
+            frac | 1 << F::FRAC_BITS,
+            field - F::BIAS - F::FRAC_BITS as i32,
+        )
+    }
+}
+
+/// Returns the midpoint between the non-negative number with bits `x` and the one after it, as
+/// an integer mantissa and exponent.
+fn midpoint<F: Format>(x: u64) -> (u64, i32) {
+    let (_, lo_mant, lo_exp) = unpack::<F>(x);
+    let (_, hi_mant, hi_exp) = unpack::<F>(x + 1);
+    (lo_mant + (hi_mant << (hi_exp - lo_exp)), lo_exp - 1)
+}
+
+/// Asks the host for the result of an operation on finite, non-zero operands, and checks it.
+///
+/// `compare` is given the sign of the result, and a non-negative midpoint, and returns how the
+/// exact result, negated if the sign is negative, compares to the midpoint.
+fn checked<F: Format>(
+    op: u32,
+    operands: &[u64],
+    compare: impl Fn(bool, (u64, i32)) -> Ordering,
+) -> u64 {
+    let mut words = [0u32; 6];
+    for (chunk, operand) in words.chunks_exact_mut(F::WORDS).zip(operands) {
+        chunk[0] = *operand as u32;
+        if F::WORDS == 2 {
+            chunk[1] = (*operand >> 32) as u32;
+        }
+    }
+    let result = unsafe { sys_float(op | F::FLAG, words.as_ptr(), operands.len() * F::WORDS) };
+    let result = result & (F::SIGN << 1).wrapping_sub(1);
+    if !is_rounded::<F>(result, compare) {
+        panic!("host returned an incorrect result for floating-point operation {op}");
+    }
+    result
+}
+
+/// Returns true if `result` is the exact result compared by `compare`, rounded to nearest, ties
+/// to even.
+fn is_rounded<F: Format>(result: u64, compare: impl Fn(bool, (u64, i32)) -> Ordering) -> bool {
+    let neg = result & F::SIGN != 0;
+    let mag = result & !F::SIGN;
+    if mag > F::INF {
+        return false;
+    }
+    if mag == F::INF {
+        // Overflow rounds to infinity, including at the midpoint, as the largest finite number
+        // has an odd mantissa.
+        return compare(neg, midpoint::<F>(mag - 1)) != Ordering::Less;
+    }
+
+    // Adjacent bit patterns are adjacent numbers, so the neighbours of the result are just below
+    // and above it. Only results with even mantissas may lie on a midpoint.
+    let even = mag & 1 == 0;
+    let above_lower = match mag {
+        // An exact zero sum is positive when rounding to nearest.
+        0 => match compare(neg, (0, 0)) {
+            Ordering::Less => false,
+            Ordering::Equal => !neg,
+            Ordering::Greater => true,
+        },
+        _ => match compare(neg, midpoint::<F>(mag - 1)) {
+            Ordering::Less => false,
+            Ordering::Equal => even,
+            Ordering::Greater => true,
+        },
+    };
+    let below_upper = match compare(neg, midpoint::<F>(mag)) {
+        Ordering::Less => true,
+        Ordering::Equal => even,
+        Ordering::Greater => false,
+    };
+    above_lower && below_upper
+}
+
+/// Compares a sum of terms, negated if `neg` is true, to a midpoint.
+fn compare_sum(neg: bool, exact: &[Term], (mid_mant, mid_exp): (u64, i32)) -> Ordering {
+    let mut terms = [Term::new(false, 0, 0); 3];
+    for (term, exact) in terms.iter_mut().zip(exact) {
+        *term = Term::new(exact.neg != neg, exact.mant, exact.exp);
+    }
+    terms[exact.len()] = Term::new(true, mid_mant as u128, mid_exp);
+    sum_sign(&mut terms[..exact.len() + 1])
+}
+
+/// A signed integer multiple of a power of two.
+#[derive(Clone, Copy)]
+struct Term {
+    neg: bool,
+    mant: u128,
+    exp: i32,
+}
+
+impl Term {
+    fn new(neg: bool, mant: u128, exp: i32) -> Self {
+        Self { neg, mant, exp }
+    }
+}
+
+// The largest gap between exponents of terms that are added exactly. Across a larger gap, the
+// smaller terms, each less than 2^128 times their power of two, cannot change the sign of a
+// non-zero sum of the larger ones.
+const MAX_GAP: u32 = 130;
+
+// Enough limbs for the sum of three terms across gaps of up to MAX_GAP bits, plus a sign bit.
+const LIMBS: usize = 14;
+
+/// Returns how the sum of at most three terms compares to zero.
+fn sum_sign(terms: &mut [Term]) -> Ordering {
+    // Sort by decreasing exponent, ignoring zeros.
+    terms.sort_unstable_by(|a, b| (b.mant != 0, b.exp).cmp(&(a.mant != 0, a.exp)));
+    let mut sum = Wide::ZERO;
+    let mut sum_exp = 0;
+    for term in terms.iter().take_while(|term| term.mant != 0) {
+        let gap = (sum_exp - term.exp) as u32;
+        if sum.is_zero() || gap > MAX_GAP {
+            if !sum.is_zero() {
+                break;
+            }
+            sum = Wide::from_term(term);
+        } else {
+            sum.shl(gap);
+            sum.add(&Wide::from_term(term));
+        }
+        sum_exp = term.exp;
+    }
+    sum.sign()
+}
+
+/// A fixed-width two's complement integer.
+struct Wide([u32; LIMBS]);
+
+impl Wide {
+    const ZERO: Self = Self([0; LIMBS]);
+
+    fn from_term(term: &Term) -> Self {
+        let mut wide = Self::ZERO;
+        for (i, limb) in wide.0.iter_mut().take(4).enumerate() {
+            *limb = (term.mant >> (32 * i)) as u32;
+        }
+        if term.neg {
+            for limb in wide.0.iter_mut() {
+                *limb = !*limb;
+            }
+            wide.add(&Self::from_term(&Term::new(false, 1, 0)));
+        }
+        wide
+    }
+
+    fn shl(&mut self, bits: u32) {
+        let words = (bits / 32) as usize;
+        let bits = bits % 32;
+        for i in (0..LIMBS).rev() {
+            let hi = if i >= words { self.0[i - words] } else { 0 };
+            let lo = if i > words { self.0[i - words - 1] } else { 0 };
+            self.0[i] = if bits == 0 {
+                hi
+            } else {
+                hi << bits | lo >> (32 - bits)
+            };
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        let mut carry = false;
+        for (limb, other) in self.0.iter_mut().zip(other.0) {
+            let (sum, carry1) = limb.overflowing_add(other);
+            let (sum, carry2) = sum.overflowing_add(carry as u32);
+            *limb = sum;
+            carry = carry1 || carry2;
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&limb| limb == 0)
+    }
+
+    fn sign(&self) -> Ordering {
+        if self.0[LIMBS - 1] >> 31 != 0 {
+            Ordering::Less
+        } else if self.is_zero() {
+            Ordering::Equal
+        } else {
+            Ordering::Greater
+        }
+    }
+}
+
+#[cfg(all(feature = "accel-float", target_os = "zkvm"))]
+mod builtins {
+    #[no_mangle]
+    pub extern "C" fn __addsf3(a: f32, b: f32) -> f32 {
+        super::add_f32(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __subsf3(a: f32, b: f32) -> f32 {
+        super::sub_f32(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __mulsf3(a: f32, b: f32) -> f32 {
+        super::mul_f32(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __divsf3(a: f32, b: f32) -> f32 {
+        super::div_f32(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __adddf3(a: f64, b: f64) -> f64 {
+        super::add_f64(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __subdf3(a: f64, b: f64) -> f64 {
+        super::sub_f64(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __muldf3(a: f64, b: f64) -> f64 {
+        super::mul_f64(a, b)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __divdf3(a: f64, b: f64) -> f64 {
+        super::div_f64(a, b)
+    }
+}
@@ -94,6 +94,75 @@ pub mod bigint {
     pub const WIDTH_WORDS: usize = WIDTH_BYTES / crate::WORD_SIZE;
 }
 
+pub mod float {
+    /// Add two numbers.
+    pub const OP_ADD: u32 = 0;
+
+    /// Multiply two numbers.
+    pub const OP_MUL: u32 = 1;
+
+    /// Divide the first number by the second.
+    pub const OP_DIV: u32 = 2;
+
+    /// Take the square root of a number.
+    pub const OP_SQRT: u32 = 3;
+
+    /// Multiply the first two numbers and add the third, with a single rounding.
+    pub const OP_FMA: u32 = 4;
+
+    /// Flag selecting 64-bit operands, each passed as its low word followed by its high word,
+    /// rather than 32-bit operands.
+    pub const F64: u32 = 1 << 8;
+}
+
+pub mod fs {
+    /// The operation succeeded.
+    pub const OK: u32 = 0;
+
+    /// No file or directory exists at the given path.
+    pub const NOT_FOUND: u32 = 1;
+
+    /// The path names a directory, but a file was expected.
+    pub const IS_DIR: u32 = 2;
+
+    /// The path is not valid UTF-8, or the host failed to access it.
+    pub const INVALID: u32 = 3;
+
+    /// The path names a regular file.
+    pub const KIND_FILE: u32 = 0;
+
+    /// The path names a directory.
+    pub const KIND_DIR: u32 = 1;
+
+    /// Number of words describing a file, as returned by `sys_fs_stat`: the kind of file, followed
+    /// by the low and high words of its length in bytes.
+    pub const STAT_WORDS: usize = 3;
+}
+
+/// System calls whose results are not absorbed into the committed input digest when the
+/// `commit-input` feature is enabled. These either carry no input from the host, or return
+/// results that the guest checks itself.
+pub const UNCOMMITTED_SYSCALLS: [SyscallName; 7] = [
+    nr::SYS_CYCLE_COUNT,
+    nr::SYS_FLOAT,
+    nr::SYS_LOG,
+    nr::SYS_PANIC,
+    nr::SYS_RANDOM,
+    nr::SYS_VERIFY_INTEGRITY,
+    nr::SYS_WRITE,
+];
+
+/// Returns true if the result of the given system call is absorbed into the committed input
+/// digest when the `commit-input` feature is enabled.
+///
+/// This includes reads from file descriptors, environment variables, arguments, the virtual
+/// filesystem and all `slice_io` channels, but not the random numbers used to seed the guest.
+pub fn is_committed_input(syscall: &str) -> bool {
+    !UNCOMMITTED_SYSCALLS
+        .iter()
+        .any(|name| name.as_str() == syscall)
+}
+
 /// A UTF-8 NUL-terminated name of a syscall with static lifetime.
 #[derive(Clone, Copy, Debug)]
 #[repr(transparent)]
@@ -130,6 +199,11 @@ pub mod nr {
     declare_syscall!(pub SYS_ARGC);
     declare_syscall!(pub SYS_ARGV);
     declare_syscall!(pub SYS_CYCLE_COUNT);
+    declare_syscall!(pub SYS_FLOAT);
+    declare_syscall!(pub SYS_FS_CLOSE);
+    declare_syscall!(pub SYS_FS_OPEN);
+    declare_syscall!(pub SYS_FS_READ);
+    declare_syscall!(pub SYS_FS_STAT);
     declare_syscall!(pub SYS_GETENV);
     declare_syscall!(pub SYS_LOG);
     declare_syscall!(pub SYS_PANIC);
@@ -231,6 +305,10 @@ macro_rules! impl_syscall {
                             )?
                           )?
                         )?);
+                #[cfg(feature = "commit-input")]
+                if $crate::syscall::is_committed_input(syscall.as_str()) {
+                    $crate::syscall::sys_commit_input(a0, a1, from_host, from_host_words);
+                }
                 Return(a0, a1)
             }
             #[cfg(not(target_os = "zkvm"))]
@@ -293,6 +371,8 @@ fn ecall_4(t0: u32, a0: u32, a1: u32, a2: u32, a3: u32, a4: u32) {
 #[inline(never)]
 #[cfg_attr(feature = "export-syscalls", no_mangle)]
 pub extern "C" fn sys_halt(user_exit: u8, out_state: *const [u32; DIGEST_WORDS]) -> ! {
+    #[cfg(feature = "commit-input")]
+    check_committed_input();
     ecall_1(
         ecall::HALT,
         halt::TERMINATE | ((user_exit as u32) << 8),
@@ -311,6 +391,8 @@ pub extern "C" fn sys_halt(user_exit: u8, out_state: *const [u32; DIGEST_WORDS])
 #[inline(never)]
 #[cfg_attr(feature = "export-syscalls", no_mangle)]
 pub unsafe extern "C" fn sys_pause(user_exit: u8, out_state: *const [u32; DIGEST_WORDS]) {
+    #[cfg(feature = "commit-input")]
+    check_committed_input();
     ecall_1(
         ecall::HALT,
         halt::PAUSE | ((user_exit as u32) << 8),
@@ -413,6 +495,30 @@ pub unsafe extern "C" fn sys_bigint(
     );
 }
 
+/// Asks the host for the result of the floating-point operation `op`, described by [float], on
+/// the given operands, and returns the bits of the result. For 32-bit operations, the result is in
+/// the low word.
+///
+/// The result is NOT checked. Users should prefer the functions in [crate::softfloat], which only
+/// ask the host for the result of operations on finite numbers, and check that it is correctly
+/// rounded.
+///
+/// # Safety
+///
+/// `operands` must be aligned and dereferenceable for `nwords` words.
+#[cfg_attr(feature = "export-syscalls", no_mangle)]
+pub unsafe extern "C" fn sys_float(op: u32, operands: *const u32, nwords: usize) -> u64 {
+    let Return(lo, hi) = syscall_3(
+        nr::SYS_FLOAT,
+        null_mut(),
+        0,
+        op,
+        operands as u32,
+        nwords as u32,
+    );
+    ((hi as u64) << 32) | lo as u64
+}
+
 /// # Safety
 ///
 /// `recv_buf` must be aligned and dereferenceable.
@@ -729,8 +835,9 @@ pub unsafe extern "C" fn sys_alloc_aligned(bytes: usize, align: usize) -> *mut u
     let ptr = heap_pos as *mut u8;
     heap_pos += bytes;
 
-    // Check to make sure heap doesn't collide with SYSTEM memory.
-    if crate::memory::SYSTEM.start() < heap_pos {
+    // Check to make sure heap doesn't collide with SYSTEM memory, or exceed
+    // the limit set when the guest was linked.
+    if heap_end() < heap_pos {
         const MSG: &[u8] = "Out of memory!".as_bytes();
         unsafe { sys_panic(MSG.as_ptr(), MSG.len()) };
     }
@@ -739,6 +846,34 @@ pub unsafe extern "C" fn sys_alloc_aligned(bytes: usize, align: usize) -> *mut u
     ptr
 }
 
+/// Returns the address below which the heap must stay.
+///
+/// This is the start of SYSTEM memory, unless the linker defines a lower
+/// `__risc0_heap_end` (see [crate::memory::HEAP_END_SYMBOL]).
+#[cfg(feature = "export-syscalls")]
+fn heap_end() -> usize {
+    #[cfg(target_os = "zkvm")]
+    {
+        let end: usize;
+        // The weak reference is resolved to zero when the symbol is
+        // undefined, which requires an absolute rather than a pc-relative
+        // address.
+        unsafe {
+            asm!(
+                ".weak __risc0_heap_end",
+                "lui {0}, %hi(__risc0_heap_end)",
+                "addi {0}, {0}, %lo(__risc0_heap_end)",
+                out(reg) end,
+                options(pure, nomem, nostack),
+            )
+        };
+        if end != 0 {
+            return end.min(crate::memory::SYSTEM.start());
+        }
+    }
+    crate::memory::SYSTEM.start()
+}
+
 /// Send a ReceiptClaim digest to the host to request verification.
 ///
 /// A cooperative prover will only return if there is a verifying proof
@@ -780,8 +915,284 @@ pub unsafe extern "C" fn sys_verify_integrity(
     }
 }
 
+// Makes a system call to the virtual filesystem, using the same protocol as `send_recv_slice`: the
+// first call sends the request and returns the length of the response, and the second receives
+// the response. The result of each call is folded into the filesystem digest.
+unsafe fn sys_fs_call(syscall: SyscallName, request: &[u8], response: &mut [u32]) -> usize {
+    let Return(nbytes, a1) = syscall_2(
+        syscall,
+        null_mut(),
+        0,
+        request.as_ptr() as u32,
+        request.len() as u32,
+    );
+    sys_fs_commit(nbytes, a1, null_mut(), 0);
+
+    let nbytes = nbytes as usize;
+    let nwords = crate::align_up(nbytes, WORD_SIZE) / WORD_SIZE;
+    if nwords > response.len() {
+        const MSG: &[u8] = "filesystem response exceeds the requested length".as_bytes();
+        sys_panic(MSG.as_ptr(), MSG.len());
+    }
+    let Return(a0, a1) = syscall_2(syscall, response.as_mut_ptr(), nwords, 0, 0);
+    sys_fs_commit(a0, a1, response.as_ptr(), nwords);
+    nbytes
+}
+
+/// Opens the file at the given path in the virtual filesystem provided by the host, and stores
+/// its file descriptor in `out_fd`.
+///
+/// Returns [fs::OK] on success, or another status from [fs] describing the error.
+///
+/// # Safety
+///
+/// `path` must be dereferenceable, and `out_fd` must be aligned and dereferenceable.
+#[cfg_attr(feature = "export-syscalls", no_mangle)]
+pub unsafe extern "C" fn sys_fs_open(path: *const u8, path_len: usize, out_fd: *mut u32) -> u32 {
+    let mut response = [0u32; 2];
+    sys_fs_call(
+        nr::SYS_FS_OPEN,
+        core::slice::from_raw_parts(path, path_len),
+        &mut response,
+    );
+    let [status, fd] = response;
+    *out_fd = fd;
+    status
+}
+
+/// Reads up to `nread` bytes from the open file `fd`, starting at `offset`, into the given buffer.
+/// Returns the number of bytes actually read, which is only less than `nread` at the end of the
+/// file.
+///
+/// # Safety
+///
+/// `recv_ptr` must be dereferenceable for `nread` bytes.
+#[cfg_attr(feature = "export-syscalls", no_mangle)]
+pub unsafe extern "C" fn sys_fs_read(
+    fd: u32,
+    offset: u64,
+    recv_ptr: *mut u8,
+    nread: usize,
+) -> usize {
+    let mut buf = [0u32; MAX_BUF_WORDS];
+    let mut nread_total = 0;
+    while nread_total < nread {
+        let chunk_offset = offset + nread_total as u64;
+        let chunk_len = min(nread - nread_total, MAX_BUF_BYTES);
+        let request = [
+            fd,
+            chunk_offset as u32,
+            (chunk_offset >> 32) as u32,
+            chunk_len as u32,
+        ];
+        let nbytes = min(
+            sys_fs_call(nr::SYS_FS_READ, words_as_bytes(&request), &mut buf),
+            chunk_len,
+        );
+        core::ptr::copy_nonoverlapping(
+            buf.as_ptr() as *const u8,
+            recv_ptr.add(nread_total),
+            nbytes,
+        );
+        nread_total += nbytes;
+        if nbytes < chunk_len {
+            break;
+        }
+    }
+    nread_total
+}
+
+/// Retrieves the kind and length of the file or directory at the given path in the virtual
+/// filesystem provided by the host, as described by [fs::STAT_WORDS].
+///
+/// Returns [fs::OK] on success, or another status from [fs] describing the error.
+///
+/// # Safety
+///
+/// `path` must be dereferenceable, and `out_stat` must be aligned and dereferenceable.
+#[cfg_attr(feature = "export-syscalls", no_mangle)]
+pub unsafe extern "C" fn sys_fs_stat(
+    path: *const u8,
+    path_len: usize,
+    out_stat: *mut [u32; fs::STAT_WORDS],
+) -> u32 {
+    let mut response = [0u32; 1 + fs::STAT_WORDS];
+    sys_fs_call(
+        nr::SYS_FS_STAT,
+        core::slice::from_raw_parts(path, path_len),
+        &mut response,
+    );
+    (*out_stat).copy_from_slice(&response[1..]);
+    response[0]
+}
+
+/// Closes a file descriptor returned by [sys_fs_open].
+#[cfg_attr(feature = "export-syscalls", no_mangle)]
+pub extern "C" fn sys_fs_close(fd: u32) {
+    unsafe { sys_fs_call(nr::SYS_FS_CLOSE, words_as_bytes(&[fd]), &mut []) };
+}
+
+fn words_as_bytes(words: &[u32]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * WORD_SIZE) }
+}
+
+// The digest of the data the guest has received from the host that is committed to as the input
+// of the execution, or None if no such data has been received.
+#[cfg(feature = "export-syscalls")]
+static mut COMMITTED_INPUT: Option<[u32; DIGEST_WORDS]> = None;
+
+// The digest of the data the guest has received from the virtual filesystem, or None if the guest
+// has not used the filesystem.
+#[cfg(feature = "export-syscalls")]
+static mut FS_DIGEST: Option<[u32; DIGEST_WORDS]> = None;
+
+// Folds the registers returned by a system call, followed by the words received from the host,
+// into the given running digest. The words are split into 64-byte blocks, with the last block
+// padded with zeros, and each block is added with the SHA-256 compression function.
+#[cfg(feature = "export-syscalls")]
+unsafe fn absorb(
+    state: &mut [u32; DIGEST_WORDS],
+    a0: u32,
+    a1: u32,
+    from_host: *const u32,
+    from_host_words: usize,
+) {
+    unsafe fn compress(state: &mut [u32; DIGEST_WORDS], block: &[u32; 2 * DIGEST_WORDS]) {
+        sys_sha_compress(
+            state,
+            state,
+            block.as_ptr() as *const [u32; DIGEST_WORDS],
+            block.as_ptr().add(DIGEST_WORDS) as *const [u32; DIGEST_WORDS],
+        );
+    }
+
+    let mut block = [0u32; 2 * DIGEST_WORDS];
+    block[0] = a0;
+    block[1] = a1;
+    let mut len = 2;
+    for i in 0..from_host_words {
+        if len == block.len() {
+            compress(state, &block);
+            len = 0;
+        }
+        block[len] = *from_host.add(i);
+        len += 1;
+    }
+    block[len..].fill(0);
+    compress(state, &block);
+}
+
+/// Folds the result of a system call, the returned registers followed by the words received from
+/// the host, into the committed input digest.
+///
+/// The digest starts as all zeros. The registers and words are split into 64-byte blocks, with
+/// the last block padded with zeros, and each block is added with the SHA-256 compression
+/// function. The host computes the same digest from the system calls it serves, and uses it as
+/// the input digest of the execution, so that when execution ends the guest can check that the
+/// data it received is the data committed to by the receipt.
+///
+/// # Safety
+///
+/// `from_host` must be aligned and dereferenceable for `from_host_words` words.
+#[cfg(feature = "export-syscalls")]
+#[no_mangle]
+pub unsafe extern "C" fn sys_commit_input(
+    a0: u32,
+    a1: u32,
+    from_host: *const u32,
+    from_host_words: usize,
+) {
+    // SAFETY: Single threaded, so nothing else can touch this while we're working.
+    let state = COMMITTED_INPUT.get_or_insert([0; DIGEST_WORDS]);
+    absorb(state, a0, a1, from_host, from_host_words);
+}
+
+/// Takes the committed input digest, storing it in `out_state`.
+///
+/// Returns false, leaving `out_state` unchanged, if the guest has not received any data that is
+/// committed to since execution started or was last resumed.
+///
+/// # Safety
+///
+/// `out_state` must be aligned and dereferenceable.
+#[cfg(feature = "export-syscalls")]
+#[no_mangle]
+pub unsafe extern "C" fn sys_take_committed_input(out_state: *mut [u32; DIGEST_WORDS]) -> bool {
+    match COMMITTED_INPUT.take() {
+        Some(state) => {
+            *out_state = state;
+            true
+        }
+        None => false,
+    }
+}
+
+// Panics unless the running hash of the data received from the host matches the input digest.
+//
+// This is called on every halt and pause, rather than only when the guest returns from main, so
+// that no exit path can produce a receipt whose input digest does not bind to the data the guest
+// actually received.
+#[cfg(feature = "commit-input")]
+fn check_committed_input() {
+    let mut committed_input = [0u32; DIGEST_WORDS];
+    // SAFETY: `committed_input` is a local array of the right size.
+    if unsafe { sys_take_committed_input(&mut committed_input) }
+        && (0..DIGEST_WORDS).any(|i| committed_input[i] != sys_input(i as u32))
+    {
+        const MSG: &[u8] = "input digest does not match the data received from the host".as_bytes();
+        // SAFETY: MSG is a valid static byte slice.
+        unsafe { sys_panic(MSG.as_ptr(), MSG.len()) };
+    }
+}
+
+/// Folds the result of a virtual filesystem call, the returned registers followed by the words
+/// received from the host, into the filesystem digest.
+///
+/// The digest is computed in the same way as the committed input digest of `sys_commit_input`,
+/// and the host computes the same digest from the filesystem calls it serves.
+///
+/// # Safety
+///
+/// `from_host` must be aligned and dereferenceable for `from_host_words` words.
+#[cfg(feature = "export-syscalls")]
+#[no_mangle]
+pub unsafe extern "C" fn sys_fs_commit(
+    a0: u32,
+    a1: u32,
+    from_host: *const u32,
+    from_host_words: usize,
+) {
+    // SAFETY: Single threaded, so nothing else can touch this while we're working.
+    let state = FS_DIGEST.get_or_insert([0; DIGEST_WORDS]);
+    absorb(state, a0, a1, from_host, from_host_words);
+}
+
+/// Stores the filesystem digest in `out_state`.
+///
+/// Returns false, leaving `out_state` unchanged, if the guest has not used the virtual
+/// filesystem.
+///
+/// # Safety
+///
+/// `out_state` must be aligned and dereferenceable.
+#[cfg(feature = "export-syscalls")]
+#[no_mangle]
+pub unsafe extern "C" fn sys_fs_digest(out_state: *mut [u32; DIGEST_WORDS]) -> bool {
+    match FS_DIGEST {
+        Some(state) => {
+            *out_state = state;
+            true
+        }
+        None => false,
+    }
+}
+
 // Make sure we only get one of these since it's stateful.
 #[cfg(not(feature = "export-syscalls"))]
 extern "C" {
     pub fn sys_alloc_aligned(nwords: usize, align: usize) -> *mut u8;
+    pub fn sys_commit_input(a0: u32, a1: u32, from_host: *const u32, from_host_words: usize);
+    pub fn sys_take_committed_input(out_state: *mut [u32; DIGEST_WORDS]) -> bool;
+    pub fn sys_fs_commit(a0: u32, a1: u32, from_host: *const u32, from_host_words: usize);
+    pub fn sys_fs_digest(out_state: *mut [u32; DIGEST_WORDS]) -> bool;
 }
@@ -0,0 +1,310 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cooperative green threads for the zkVM guest.
+//!
+//! The zkVM has a single hart, so threads never run in parallel. Instead, the running thread
+//! switches to another only when it calls [sys_thread_yield], or blocks in [sys_thread_join].
+//! Threads are scheduled round-robin in the order of their thread IDs, so the interleaving of a
+//! program's threads depends only on the program and its input, and proofs are reproducible.
+//!
+//! The standard library of the zkVM target does not use these functions, so `std::thread` remains
+//! unsupported in guests. Most guests should use `risc0_zkvm::guest::thread`, which is built on
+//! top of them. They are only defined on the zkVM target, as they switch stacks directly.
+
+/// Maximum number of threads that may exist at once, including the main thread.
+pub const MAX_THREADS: usize = 64;
+
+/// Default size of the stack of a spawned thread, in bytes.
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// Thread ID of the main thread.
+pub const MAIN_THREAD: u32 = 0;
+
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+mod imp {
+    use core::{ffi::c_void, ptr::null_mut};
+
+    use super::{DEFAULT_STACK_SIZE, MAX_THREADS};
+    use crate::syscall::{sys_alloc_aligned, sys_panic};
+
+    // Registers preserved across a call to `__risc0_thread_switch`, which are the ones that the
+    // calling convention requires the callee to save.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Context {
+        ra: u32,
+        sp: u32,
+        s: [u32; 12],
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum State {
+        Free,
+        Runnable,
+        Finished,
+    }
+
+    #[derive(Clone, Copy)]
+    struct Thread {
+        state: State,
+        detached: bool,
+        context: Context,
+        // Stacks are never deallocated, but are reused by later threads in the same slot.
+        stack: *mut u8,
+        stack_size: usize,
+    }
+
+    impl Thread {
+        const FREE: Self = Self {
+            state: State::Free,
+            detached: false,
+            context: Context {
+                ra: 0,
+                sp: 0,
+                s: [0; 12],
+            },
+            stack: null_mut(),
+            stack_size: 0,
+        };
+    }
+
+    // SAFETY: The zkVM is single threaded, and nothing here is reentrant, so these are never
+    // accessed concurrently.
+    static mut THREADS: [Thread; MAX_THREADS] = {
+        let mut threads = [Thread::FREE; MAX_THREADS];
+        threads[0].state = State::Runnable;
+        threads
+    };
+    static mut CURRENT: usize = 0;
+
+    extern "C" {
+        fn __risc0_thread_switch(from: *mut Context, to: *const Context);
+        fn __risc0_thread_start();
+    }
+
+    core::arch::global_asm!(
+        r#"
+    .section .text.__risc0_thread_switch
+    .globl __risc0_thread_switch
+    __risc0_thread_switch:
+        sw ra, 0(a0)
+        sw sp, 4(a0)
+        sw s0, 8(a0)
+        sw s1, 12(a0)
+        sw s2, 16(a0)
+        sw s3, 20(a0)
+        sw s4, 24(a0)
+        sw s5, 28(a0)
+        sw s6, 32(a0)
+        sw s7, 36(a0)
+        sw s8, 40(a0)
+        sw s9, 44(a0)
+        sw s10, 48(a0)
+        sw s11, 52(a0)
+        lw ra, 0(a1)
+        lw sp, 4(a1)
+        lw s0, 8(a1)
+        lw s1, 12(a1)
+        lw s2, 16(a1)
+        lw s3, 20(a1)
+        lw s4, 24(a1)
+        lw s5, 28(a1)
+        lw s6, 32(a1)
+        lw s7, 36(a1)
+        lw s8, 40(a1)
+        lw s9, 44(a1)
+        lw s10, 48(a1)
+        lw s11, 52(a1)
+        ret
+
+    .section .text.__risc0_thread_start
+    .globl __risc0_thread_start
+    __risc0_thread_start:
+        mv a0, s0
+        mv a1, s1
+        j {0}
+    "#,
+        sym thread_main
+    );
+
+    fn panic(msg: &str) -> ! {
+        unsafe { sys_panic(msg.as_ptr(), msg.len()) }
+    }
+
+    // Entered on the new thread's stack with the entry point and argument that were stashed in
+    // s0 and s1 by `sys_thread_spawn`.
+    unsafe extern "C" fn thread_main(entry: unsafe extern "C" fn(*mut c_void), arg: *mut c_void) {
+        entry(arg);
+
+        let thread = &mut THREADS[CURRENT];
+        thread.state = if thread.detached {
+            State::Free
+        } else {
+            State::Finished
+        };
+        if !switch_to_next() {
+            panic("no runnable threads remain");
+        }
+        unreachable!("resumed a thread that has finished");
+    }
+
+    // Switch to the next runnable thread after the current one, if there is one.
+    unsafe fn switch_to_next() -> bool {
+        let current = CURRENT;
+        let next = (1..MAX_THREADS)
+            .map(|offset| (current + offset) % MAX_THREADS)
+            .find(|&idx| THREADS[idx].state == State::Runnable);
+        let Some(next) = next else {
+            return false;
+        };
+        CURRENT = next;
+        __risc0_thread_switch(&mut THREADS[current].context, &THREADS[next].context);
+        true
+    }
+
+    pub unsafe fn spawn(
+        entry: unsafe extern "C" fn(*mut c_void),
+        arg: *mut c_void,
+        stack_size: usize,
+    ) -> u32 {
+        let Some(idx) = (0..MAX_THREADS).find(|&idx| THREADS[idx].state == State::Free) else {
+            panic("too many threads");
+        };
+        let stack_size = crate::align_up(
+            if stack_size == 0 {
+                DEFAULT_STACK_SIZE
+            } else {
+                stack_size
+            },
+            16,
+        );
+
+        let thread = &mut THREADS[idx];
+        if thread.stack_size < stack_size {
+            thread.stack = sys_alloc_aligned(stack_size, 16);
+            thread.stack_size = stack_size;
+        }
+        thread.state = State::Runnable;
+        thread.detached = false;
+        thread.context.ra = __risc0_thread_start as usize as u32;
+        thread.context.sp = thread.stack.add(thread.stack_size) as u32;
+        thread.context.s[0] = entry as usize as u32;
+        thread.context.s[1] = arg as u32;
+        idx as u32
+    }
+
+    pub unsafe fn yield_now() -> bool {
+        switch_to_next()
+    }
+
+    pub unsafe fn join(tid: u32) {
+        let tid = tid as usize;
+        if tid >= MAX_THREADS
+            || tid == CURRENT
+            || THREADS[tid].state == State::Free
+            || THREADS[tid].detached
+        {
+            panic("invalid thread to join");
+        }
+        while THREADS[tid].state != State::Finished {
+            if !switch_to_next() {
+                panic("deadlock: no other thread is runnable");
+            }
+        }
+        THREADS[tid].state = State::Free;
+    }
+
+    pub unsafe fn detach(tid: u32) {
+        let Some(thread) = THREADS.get_mut(tid as usize) else {
+            panic("invalid thread to detach");
+        };
+        match thread.state {
+            State::Finished => thread.state = State::Free,
+            State::Runnable => thread.detached = true,
+            State::Free => panic("invalid thread to detach"),
+        }
+    }
+
+    pub unsafe fn current() -> u32 {
+        CURRENT as u32
+    }
+}
+
+/// Creates a new thread that will call `entry(arg)`, and returns its thread ID.
+///
+/// The new thread does not run until the calling thread yields. A `stack_size` of zero selects
+/// [DEFAULT_STACK_SIZE]. The thread must be either joined with [sys_thread_join] or detached with
+/// [sys_thread_detach] so that its ID can be reused. Panics if [MAX_THREADS] threads already exist.
+///
+/// # Safety
+///
+/// `entry` must be safe to call with `arg` on the new thread, and must return normally.
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+#[no_mangle]
+pub unsafe extern "C" fn sys_thread_spawn(
+    entry: unsafe extern "C" fn(*mut core::ffi::c_void),
+    arg: *mut core::ffi::c_void,
+    stack_size: usize,
+) -> u32 {
+    imp::spawn(entry, arg, stack_size)
+}
+
+/// Switches to the next runnable thread, if there is one.
+///
+/// Returns false if no other thread is runnable, in which case the calling thread continues.
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+#[no_mangle]
+pub extern "C" fn sys_thread_yield() -> bool {
+    unsafe { imp::yield_now() }
+}
+
+/// Runs other threads until the thread with the given ID has finished, then frees its ID.
+///
+/// Panics if the thread cannot be joined, or if it can never finish because no other thread is
+/// runnable.
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+#[no_mangle]
+pub extern "C" fn sys_thread_join(tid: u32) {
+    unsafe { imp::join(tid) }
+}
+
+/// Marks the thread with the given ID so that its ID is freed when it finishes, instead of when
+/// it is joined.
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+#[no_mangle]
+pub extern "C" fn sys_thread_detach(tid: u32) {
+    unsafe { imp::detach(tid) }
+}
+
+/// Returns the thread ID of the calling thread.
+#[cfg(all(feature = "export-syscalls", target_os = "zkvm"))]
+#[no_mangle]
+pub extern "C" fn sys_thread_current() -> u32 {
+    unsafe { imp::current() }
+}
+
+// Make sure we only get one of these since it's stateful.
+#[cfg(not(feature = "export-syscalls"))]
+extern "C" {
+    pub fn sys_thread_spawn(
+        entry: unsafe extern "C" fn(*mut core::ffi::c_void),
+        arg: *mut core::ffi::c_void,
+        stack_size: usize,
+    ) -> u32;
+    pub fn sys_thread_yield() -> bool;
+    pub fn sys_thread_join(tid: u32);
+    pub fn sys_thread_detach(tid: u32);
+    pub fn sys_thread_current() -> u32;
+}
@@ -87,7 +87,7 @@ use crate::{
         rust_crypto::{Digest as _, Sha256},
         Digest, Digestible,
     },
-    Assumption, Assumptions, MaybePruned, Output, PrunedValueError, ReceiptClaim,
+    AggregationSet, Assumption, Assumptions, MaybePruned, Output, PrunedValueError, ReceiptClaim,
 };
 
 static mut HASHER: OnceCell<Sha256> = OnceCell::new();
@@ -314,6 +314,22 @@ pub fn verify_assumption(claim: Digest, control_root: Digest) -> Result<(), Infa
     Ok(())
 }
 
+/// Verify each of the given claims, as with [verify_integrity], and commit to the set of them.
+///
+/// The root of the [AggregationSet] of the claim digests, in order, is committed to the journal
+/// and returned. A receipt for a guest calling this function therefore attests to every claim in
+/// the set, even if they come from unrelated guests, and each claim can later be checked against
+/// the journal with an [AggregationInclusionProof][crate::AggregationInclusionProof]. See
+/// [Prover::aggregate][crate::Prover::aggregate] for the corresponding host API.
+pub fn aggregate(claims: &[ReceiptClaim]) -> Result<Digest, VerifyIntegrityError> {
+    for claim in claims {
+        verify_integrity(claim)?;
+    }
+    let root = AggregationSet::new(claims.iter().map(|claim| claim.digest()).collect()).root();
+    commit_slice(root.as_bytes());
+    Ok(root)
+}
+
 /// Exchanges slices of plain old data with the host.
 ///
 /// This makes two calls to the given syscall; the first gets the length of the
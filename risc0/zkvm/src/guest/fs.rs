@@ -0,0 +1,181 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only access to the virtual filesystem provided by the host.
+//!
+//! This module mirrors the read-only parts of [std::fs], so that code which reads files can be
+//! ported to the zkVM by replacing `std::fs` with `risc0_zkvm::guest::fs`. The standard library
+//! for the zkVM target has no filesystem, so `std::fs` itself cannot be used. The host provides
+//! the filesystem with `ExecutorEnvBuilder::virtual_fs`.
+//!
+//! Every byte the guest receives from the filesystem, including metadata, is absorbed into a
+//! running [digest]. A guest whose receipt should commit to the files it read can commit this
+//! digest to the journal when it has finished reading, and the host can compare it with the
+//! `vfs_digest` of the session.
+//!
+//! ```no_run
+//! use risc0_zkvm::guest::{env, fs};
+//!
+//! let config = fs::read_to_string("/etc/config.toml").unwrap();
+//! env::commit(&config);
+//! env::commit(&fs::digest());
+//! ```
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use risc0_zkvm_platform::syscall::{
+    fs, sys_fs_close, sys_fs_digest, sys_fs_open, sys_fs_read, sys_fs_stat, DIGEST_WORDS,
+};
+
+use crate::sha::Digest;
+
+/// An open file in the virtual filesystem.
+///
+/// The file is closed when it is dropped.
+pub struct File {
+    fd: u32,
+    pos: u64,
+    metadata: Metadata,
+}
+
+impl File {
+    /// Open the file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        let path = path.as_ref();
+        let metadata = metadata(path)?;
+        let path = path_str(path)?;
+        let mut fd = 0;
+        check(unsafe { sys_fs_open(path.as_ptr(), path.len(), &mut fd) })?;
+        Ok(File {
+            fd,
+            pos: 0,
+            metadata,
+        })
+    }
+
+    /// Returns the metadata of this file.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let nread = unsafe { sys_fs_read(self.fd, self.pos, buf.as_mut_ptr(), buf.len()) };
+        self.pos += nread as u64;
+        Ok(nread)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.metadata.len.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        sys_fs_close(self.fd);
+    }
+}
+
+/// Metadata about a file or directory in the virtual filesystem.
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    is_dir: bool,
+    len: u64,
+}
+
+impl Metadata {
+    /// Returns true if this metadata is for a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns true if this metadata is for a regular file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// Returns the size of the file, in bytes, or zero for a directory.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Returns the metadata of the file or directory at the given path.
+pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    let path = path_str(path.as_ref())?;
+    let mut stat = [0u32; fs::STAT_WORDS];
+    check(unsafe { sys_fs_stat(path.as_ptr(), path.len(), &mut stat) })?;
+    let [kind, len_lo, len_hi] = stat;
+    Ok(Metadata {
+        is_dir: kind == fs::KIND_DIR,
+        len: ((len_hi as u64) << 32) | len_lo as u64,
+    })
+}
+
+/// Read the entire contents of a file into a bytes vector.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Read the entire contents of a file into a string.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Returns the digest of all the data received from the filesystem so far, or [None] if the
+/// guest has not used the filesystem.
+pub fn digest() -> Option<Digest> {
+    let mut state = [0u32; DIGEST_WORDS];
+    unsafe { sys_fs_digest(&mut state) }.then(|| Digest::new(state))
+}
+
+fn path_str(path: &Path) -> io::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))
+}
+
+fn check(status: u32) -> io::Result<()> {
+    match status {
+        fs::OK => Ok(()),
+        fs::NOT_FOUND => Err(io::ErrorKind::NotFound.into()),
+        fs::IS_DIR => Err(io::Error::new(io::ErrorKind::Other, "is a directory")),
+        _ => Err(io::ErrorKind::InvalidInput.into()),
+    }
+}
@@ -80,6 +80,10 @@
 #![deny(missing_docs)]
 
 pub mod env;
+#[cfg(feature = "std")]
+pub mod fs;
+#[cfg(any(target_os = "zkvm", feature = "std"))]
+pub mod thread;
 pub use risc0_zkp::core::hash::sha;
 
 #[cfg(target_os = "zkvm")]
@@ -186,6 +190,27 @@ _start:
     sym STACK_TOP
 );
 
+// Entry point of guests that risc0-build links with a custom memory layout,
+// which starts the stack at __risc0_stack_top instead. It is in a section of
+// its own, so that the linker drops it from other guests, whose image IDs are
+// then unchanged. The reference is weak so that those guests still link.
+#[cfg(target_os = "zkvm")]
+core::arch::global_asm!(
+    r#"
+.section .text._start_with_stack_top;
+.globl _start_with_stack_top;
+.weak __risc0_stack_top;
+_start_with_stack_top:
+    .option push;
+    .option norelax;
+    la gp, __global_pointer$;
+    .option pop;
+    lui sp, %hi(__risc0_stack_top);
+    addi sp, sp, %lo(__risc0_stack_top);
+    call __start;
+"#
+);
+
 /// Require that accesses to behind the given pointer before the memory
 /// barrier don't get optimized away or reordered to after the memory
 /// barrier.
@@ -0,0 +1,167 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Threads backed by [std::thread], for running guest code natively.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    },
+};
+
+use risc0_zkvm_platform::thread::MAIN_THREAD;
+
+use super::{RecvError, SendError};
+
+// Thread IDs are handed out in the order threads are spawned. Threads that were not spawned
+// through this module, including the main thread, report the ID of the main thread.
+static NEXT_ID: AtomicU32 = AtomicU32::new(MAIN_THREAD + 1);
+
+thread_local! {
+    static CURRENT_ID: Cell<u32> = const { Cell::new(MAIN_THREAD) };
+}
+
+/// A handle to a spawned thread, which can be used to wait for it to finish.
+///
+/// If the handle is dropped, the thread is detached, and continues to run.
+pub struct JoinHandle<T> {
+    tid: u32,
+    inner: std::thread::JoinHandle<T>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Waits for the thread to finish, and returns the value it returned.
+    ///
+    /// If the thread panicked, the panic is resumed on the calling thread.
+    pub fn join(self) -> T {
+        self.inner
+            .join()
+            .unwrap_or_else(|err| std::panic::resume_unwind(err))
+    }
+
+    /// Returns the ID of the thread.
+    pub fn id(&self) -> u32 {
+        self.tid
+    }
+}
+
+/// Thread configuration, used to set the stack size of a new thread.
+#[derive(Default)]
+pub struct Builder {
+    stack_size: usize,
+}
+
+impl Builder {
+    /// Construct a [Builder] for a thread with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the size of the stack of the new thread, in bytes.
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Spawns a new thread that runs `f`, and returns a [JoinHandle] for it.
+    pub fn spawn<F, T>(self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let tid = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let mut builder = std::thread::Builder::new();
+        if self.stack_size != 0 {
+            builder = builder.stack_size(self.stack_size);
+        }
+        let inner = builder
+            .spawn(move || {
+                CURRENT_ID.with(|id| id.set(tid));
+                f()
+            })
+            .expect("failed to spawn thread");
+        JoinHandle { tid, inner }
+    }
+}
+
+/// Spawns a new thread with the default configuration, and returns a [JoinHandle] for it.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f)
+}
+
+/// Hints to the scheduler that another thread may run.
+pub fn yield_now() {
+    std::thread::yield_now();
+}
+
+/// Returns the ID of the current thread.
+pub fn current_id() -> u32 {
+    CURRENT_ID.with(Cell::get)
+}
+
+/// Creates a new unbounded channel, returning the sending and receiving halves.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::channel();
+    (Sender(tx), Receiver(rx))
+}
+
+/// The sending half of a channel created by [channel].
+pub struct Sender<T>(mpsc::Sender<T>);
+
+/// The receiving half of a channel created by [channel].
+pub struct Receiver<T>(mpsc::Receiver<T>);
+
+impl<T> Sender<T> {
+    /// Sends a value on the channel. This never blocks.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.0.send(value).map_err(|err| SendError(err.0))
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives a value from the channel, blocking until one is available.
+    ///
+    /// Returns an error if the channel is empty and all [Sender]s have been dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.0.recv().map_err(|_| RecvError)
+    }
+
+    /// Receives a value from the channel if one is available, without blocking.
+    ///
+    /// Returns an error if the channel is empty and all [Sender]s have been dropped.
+    pub fn try_recv(&self) -> Result<Option<T>, RecvError> {
+        match self.0.try_recv() {
+            Ok(value) => Ok(Some(value)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(RecvError),
+        }
+    }
+
+    /// Returns an iterator that receives values until all [Sender]s have been dropped.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.iter()
+    }
+}
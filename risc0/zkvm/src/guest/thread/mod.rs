@@ -0,0 +1,89 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cooperative threads for the zkVM guest.
+//!
+//! The standard library of the zkVM target does not support threads, so [std::thread::spawn]
+//! always fails in a guest. This module provides a subset of the [std::thread] and
+//! [std::sync::mpsc] APIs to use instead. In the zkVM, it runs on top of the green threads in
+//! `risc0_zkvm_platform::thread`. All threads share the zkVM's single hart, and switch only when
+//! the running thread calls [yield_now], [JoinHandle::join] or [Receiver::recv]. Threads are
+//! scheduled in a fixed order, so a program with the same input always interleaves its threads
+//! the same way, and its proofs are reproducible.
+//!
+//! On other targets, this module delegates to [std::thread] and [std::sync::mpsc], so that guest
+//! code using it can also be run and tested natively.
+//!
+//! ```
+//! use risc0_zkvm::guest::thread;
+//!
+//! let (tx, rx) = thread::channel();
+//! let workers: Vec<_> = (0..4u32)
+//!     .map(|i| {
+//!         let tx = tx.clone();
+//!         thread::spawn(move || tx.send(i * i).unwrap())
+//!     })
+//!     .collect();
+//! drop(tx);
+//!
+//! let sum: u32 = rx.iter().sum();
+//! for worker in workers {
+//!     worker.join();
+//! }
+//! assert_eq!(sum, 14);
+//! ```
+//!
+//! [std::thread::spawn]: https://doc.rust-lang.org/std/thread/fn.spawn.html
+//! [std::thread]: https://doc.rust-lang.org/std/thread/index.html
+//! [std::sync::mpsc]: https://doc.rust-lang.org/std/sync/mpsc/index.html
+
+use core::fmt;
+
+#[cfg(not(target_os = "zkvm"))]
+mod host;
+#[cfg(target_os = "zkvm")]
+mod zkvm;
+
+#[cfg(not(target_os = "zkvm"))]
+pub use self::host::*;
+#[cfg(target_os = "zkvm")]
+pub use self::zkvm::*;
+
+/// An error returned by [Sender::send] when the [Receiver] has been dropped, which contains the
+/// value that could not be sent.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// An error returned by [Receiver::recv] when the channel is empty and all [Sender]s have been
+/// dropped.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on a closed channel")
+    }
+}
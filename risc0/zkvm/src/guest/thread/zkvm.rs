@@ -0,0 +1,236 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Threads backed by the green threads of the zkVM platform.
+
+use alloc::{boxed::Box, collections::VecDeque, rc::Rc};
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+};
+
+use risc0_zkvm_platform::thread::{
+    sys_thread_current, sys_thread_detach, sys_thread_join, sys_thread_spawn, sys_thread_yield,
+};
+
+use super::{RecvError, SendError};
+
+// Threads only switch at the explicit points documented in the parent module, and never while any
+// of the `RefCell`s here are borrowed, so sharing `Rc`s between threads is sound. This allows the
+// types here to be `Send` when their contents are, as the equivalent `std` types are.
+struct Shared<T>(Rc<T>);
+
+unsafe impl<T: Send> Send for Shared<T> {}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// A handle to a spawned thread, which can be used to wait for it to finish.
+///
+/// If the handle is dropped, the thread is detached, and continues to run.
+pub struct JoinHandle<T> {
+    tid: u32,
+    result: Shared<RefCell<Option<T>>>,
+    joined: bool,
+}
+
+impl<T> JoinHandle<T> {
+    /// Waits for the thread to finish, running other threads in the meantime, and returns the
+    /// value it returned.
+    ///
+    /// Panics if there are no other runnable threads to run while waiting, as the thread can then
+    /// never finish.
+    pub fn join(mut self) -> T {
+        sys_thread_join(self.tid);
+        self.joined = true;
+        self.result
+            .0
+            .borrow_mut()
+            .take()
+            .expect("joined thread did not produce a result")
+    }
+
+    /// Returns the ID of the thread.
+    pub fn id(&self) -> u32 {
+        self.tid
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if !self.joined {
+            sys_thread_detach(self.tid);
+        }
+    }
+}
+
+/// Thread configuration, used to set the stack size of a new thread.
+#[derive(Default)]
+pub struct Builder {
+    stack_size: usize,
+}
+
+impl Builder {
+    /// Construct a [Builder] for a thread with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the size of the stack of the new thread, in bytes.
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Spawns a new thread that runs `f`, and returns a [JoinHandle] for it.
+    ///
+    /// The new thread starts running the next time the current thread yields.
+    pub fn spawn<F, T>(self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        unsafe extern "C" fn run(arg: *mut c_void) {
+            let main = Box::from_raw(arg as *mut Box<dyn FnOnce()>);
+            main();
+        }
+
+        let result = Shared(Rc::new(RefCell::new(None)));
+        let thread_result = result.clone();
+        let main: Box<dyn FnOnce()> = Box::new(move || {
+            let value = f();
+            *thread_result.0.borrow_mut() = Some(value);
+        });
+        let arg = Box::into_raw(Box::new(main)) as *mut c_void;
+        let tid = unsafe { sys_thread_spawn(run, arg, self.stack_size) };
+        JoinHandle {
+            tid,
+            result,
+            joined: false,
+        }
+    }
+}
+
+/// Spawns a new thread with the default configuration, and returns a [JoinHandle] for it.
+///
+/// The new thread starts running the next time the current thread yields.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f)
+}
+
+/// Switches to the next runnable thread, if there is one.
+pub fn yield_now() {
+    sys_thread_yield();
+}
+
+/// Returns the ID of the current thread.
+pub fn current_id() -> u32 {
+    sys_thread_current()
+}
+
+struct Channel<T> {
+    queue: RefCell<VecDeque<T>>,
+    senders: Cell<usize>,
+    receiver: Cell<bool>,
+}
+
+/// Creates a new unbounded channel, returning the sending and receiving halves.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Shared(Rc::new(Channel {
+        queue: RefCell::new(VecDeque::new()),
+        senders: Cell::new(1),
+        receiver: Cell::new(true),
+    }));
+    (Sender(channel.clone()), Receiver(channel))
+}
+
+/// The sending half of a channel created by [channel].
+pub struct Sender<T>(Shared<Channel<T>>);
+
+/// The receiving half of a channel created by [channel].
+pub struct Receiver<T>(Shared<Channel<T>>);
+
+impl<T> Sender<T> {
+    /// Sends a value on the channel. This never blocks.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let channel = &self.0 .0;
+        if !channel.receiver.get() {
+            return Err(SendError(value));
+        }
+        channel.queue.borrow_mut().push_back(value);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let channel = &self.0 .0;
+        channel.senders.set(channel.senders.get() + 1);
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let channel = &self.0 .0;
+        channel.senders.set(channel.senders.get() - 1);
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives a value from the channel, running other threads until one is available.
+    ///
+    /// Returns an error if the channel is empty and all [Sender]s have been dropped. Panics if
+    /// the channel is empty and no other thread is runnable, as no value can then be sent.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            if let Some(value) = self.try_recv()? {
+                return Ok(value);
+            }
+            if !sys_thread_yield() {
+                panic!("deadlock: receiving on an empty channel with no other runnable thread");
+            }
+        }
+    }
+
+    /// Receives a value from the channel if one is available, without blocking.
+    ///
+    /// Returns an error if the channel is empty and all [Sender]s have been dropped.
+    pub fn try_recv(&self) -> Result<Option<T>, RecvError> {
+        let channel = &self.0 .0;
+        match channel.queue.borrow_mut().pop_front() {
+            Some(value) => Ok(Some(value)),
+            None if channel.senders.get() == 0 => Err(RecvError),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns an iterator that receives values until all [Sender]s have been dropped.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        core::iter::from_fn(|| self.recv().ok())
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.0 .0.receiver.set(false);
+    }
+}
@@ -20,7 +20,7 @@ use prost::Message;
 
 use super::{
     malformed_err, pb, Asset, AssetRequest, ConnectionWrapper, Connector, ParentProcessConnector,
-    SessionInfo,
+    SessionInfo, TcpConnector,
 };
 use crate::{
     get_version,
@@ -53,6 +53,12 @@ impl Client {
         Ok(Self::with_connector(Box::new(connector)))
     }
 
+    /// Construct a [Client] that connects to an `r0vm --daemon` listening on
+    /// the specified TCP/IP address.
+    pub fn new_tcp<A: AsRef<str>>(addr: A) -> Self {
+        Self::with_connector(Box::new(TcpConnector::new(addr.as_ref())))
+    }
+
     /// Construct a [Client] based on environment variables.
     pub fn from_env() -> Result<Self> {
         Client::new_sub_process(get_r0vm_path())
@@ -461,6 +467,7 @@ impl Client {
             write_fds: env.posix_io.borrow().write_fds.keys().cloned().collect(),
             segment_limit_po2: env.segment_limit_po2,
             session_limit: env.session_limit,
+            resource_policy: Some(env.resource_policy.clone().into()),
             trace_events: (!env.trace.is_empty()).then_some(()),
             pprof_out: env
                 .pprof_out
@@ -557,6 +564,7 @@ impl Client {
                                         .exit_code
                                         .ok_or(malformed_err())?
                                         .try_into()?,
+                                    panic: session.panic.map(Into::into),
                                 }),
                                 None => Err(malformed_err()),
                             }
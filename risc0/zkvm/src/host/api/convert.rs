@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt::Debug, path::PathBuf};
+use std::{fmt::Debug, path::PathBuf, time::Duration};
 
 use anyhow::{anyhow, bail, Result};
 use prost::{Message, Name};
@@ -22,6 +22,7 @@ use serde::Serialize;
 
 use super::{malformed_err, path_to_string, pb, Asset, AssetRequest};
 use crate::{
+    host::panic_info::{GuestPanic, StackFrame},
     receipt::{
         merkle::MerkleProof, segment::decode_receipt_claim_from_seal, CompositeReceipt,
         FakeReceipt, InnerAssumptionReceipt, InnerReceipt, ReceiptMetadata, SegmentReceipt,
@@ -29,7 +30,8 @@ use crate::{
     },
     receipt_claim::Unknown,
     Assumption, Assumptions, ExitCode, Groth16Receipt, Input, Journal, MaybePruned, Output,
-    ProveInfo, ProverOpts, Receipt, ReceiptClaim, ReceiptKind, SessionStats, TraceEvent,
+    ProveInfo, ProverOpts, Receipt, ReceiptClaim, ReceiptKind, ResourcePolicy, SessionStats,
+    TraceEvent,
 };
 
 mod ver {
@@ -107,9 +109,11 @@ impl TryFrom<pb::api::Asset> for Asset {
     }
 }
 
-impl From<TraceEvent> for pb::api::TraceEvent {
-    fn from(event: TraceEvent) -> Self {
-        match event {
+impl TryFrom<TraceEvent> for pb::api::TraceEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(event: TraceEvent) -> Result<Self> {
+        Ok(match event {
             TraceEvent::InstructionStart { cycle, pc, insn } => Self {
                 kind: Some(pb::api::trace_event::Kind::InsnStart(
                     pb::api::trace_event::InstructionStart { cycle, pc, insn },
@@ -132,7 +136,13 @@ impl From<TraceEvent> for pb::api::TraceEvent {
                     },
                 )),
             },
-        }
+            TraceEvent::Paging { cycles } => Self {
+                kind: Some(pb::api::trace_event::Kind::Paging(
+                    pb::api::trace_event::Paging { cycles },
+                )),
+            },
+            event => bail!("unsupported trace event: {event:?}"),
+        })
     }
 }
 
@@ -154,6 +164,9 @@ impl TryFrom<pb::api::TraceEvent> for TraceEvent {
                 addr: event.addr,
                 region: event.region,
             },
+            pb::api::trace_event::Kind::Paging(event) => TraceEvent::Paging {
+                cycles: event.cycles,
+            },
         })
     }
 }
@@ -164,6 +177,7 @@ impl From<ExitCode> for pb::base::ExitCode {
             kind: Some(match value {
                 ExitCode::SystemSplit => pb::base::exit_code::Kind::SystemSplit(()),
                 ExitCode::SessionLimit => pb::base::exit_code::Kind::SessionLimit(()),
+                ExitCode::Fault => pb::base::exit_code::Kind::Fault(()),
                 ExitCode::Paused(code) => pb::base::exit_code::Kind::Paused(code),
                 ExitCode::Halted(code) => pb::base::exit_code::Kind::Halted(code),
             }),
@@ -180,10 +194,51 @@ impl TryFrom<pb::base::ExitCode> for ExitCode {
             pb::base::exit_code::Kind::Paused(code) => Self::Paused(code),
             pb::base::exit_code::Kind::SystemSplit(_) => Self::SystemSplit,
             pb::base::exit_code::Kind::SessionLimit(_) => Self::SessionLimit,
+            pb::base::exit_code::Kind::Fault(_) => Self::Fault,
         })
     }
 }
 
+impl From<GuestPanic> for pb::api::GuestPanic {
+    fn from(value: GuestPanic) -> Self {
+        Self {
+            message: value.message,
+            file: value.file,
+            line: value.line,
+            backtrace: value.backtrace.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<pb::api::GuestPanic> for GuestPanic {
+    fn from(value: pb::api::GuestPanic) -> Self {
+        Self::new(
+            value.message,
+            value.file,
+            value.line,
+            value.backtrace.into_iter().map(Into::into).collect(),
+        )
+    }
+}
+
+impl From<StackFrame> for pb::api::StackFrame {
+    fn from(value: StackFrame) -> Self {
+        let (file, line) = value.location.unzip();
+        Self {
+            pc: value.pc,
+            function: value.function,
+            file,
+            line,
+        }
+    }
+}
+
+impl From<pb::api::StackFrame> for StackFrame {
+    fn from(value: pb::api::StackFrame) -> Self {
+        Self::new(value.pc, value.function, value.file.zip(value.line))
+    }
+}
+
 impl From<Result<(), anyhow::Error>> for pb::api::GenericReply {
     fn from(result: Result<(), anyhow::Error>) -> Self {
         Self {
@@ -209,6 +264,32 @@ impl From<pb::api::GenericError> for anyhow::Error {
     }
 }
 
+impl From<pb::api::ResourcePolicy> for ResourcePolicy {
+    fn from(policy: pb::api::ResourcePolicy) -> Self {
+        Self {
+            wall_clock: policy.wall_clock_ms.map(Duration::from_millis),
+            pages: policy.pages,
+            journal_bytes: policy.journal_bytes,
+            input_bytes: policy.input_bytes,
+            verify_calls: policy.verify_calls,
+        }
+    }
+}
+
+impl From<ResourcePolicy> for pb::api::ResourcePolicy {
+    fn from(policy: ResourcePolicy) -> Self {
+        Self {
+            wall_clock_ms: policy
+                .wall_clock
+                .map(|limit| limit.as_millis().try_into().unwrap_or(u64::MAX)),
+            pages: policy.pages,
+            journal_bytes: policy.journal_bytes,
+            input_bytes: policy.input_bytes,
+            verify_calls: policy.verify_calls,
+        }
+    }
+}
+
 impl TryFrom<pb::api::ProverOpts> for ProverOpts {
     type Error = anyhow::Error;
 
@@ -227,6 +308,7 @@ impl TryFrom<pb::api::ProverOpts> for ProverOpts {
                 .into_iter()
                 .map(TryInto::try_into)
                 .collect::<Result<_>>()?,
+            memory_budget: opts.memory_budget.map(|budget| budget as usize),
         })
     }
 }
@@ -238,6 +320,7 @@ impl From<ProverOpts> for pb::api::ProverOpts {
             prove_guest_errors: opts.prove_guest_errors,
             receipt_kind: opts.receipt_kind as i32,
             control_ids: opts.control_ids.into_iter().map(Into::into).collect(),
+            memory_budget: opts.memory_budget.map(|budget| budget as u64),
         }
     }
 }
@@ -307,6 +390,7 @@ impl TryFrom<pb::core::ProveInfo> for ProveInfo {
         Ok(Self {
             receipt: value.receipt.ok_or(malformed_err())?.try_into()?,
             stats: value.stats.ok_or(malformed_err())?.try_into()?,
+            prover: None,
         })
     }
 }
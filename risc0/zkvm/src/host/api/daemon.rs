@@ -0,0 +1,216 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver, RecvError, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{server::Server, ConnectionWrapper, Connector, TcpConnection, ACCEPT_POLL_INTERVAL};
+use crate::ResourcePolicy;
+
+const DEFAULT_WORKERS: usize = 1;
+const DEFAULT_QUEUE_SIZE: usize = 64;
+
+// The number of provers each worker keeps, as they hold on to their HALs.
+const PROVER_CACHE_SIZE: usize = 4;
+
+/// A long-lived server that handles requests from many clients, started with
+/// `r0vm --daemon <ADDR>`.
+///
+/// As with a [Server][crate::ApiServer], each connection carries a single
+/// request. Connections are queued and handled by a fixed pool of workers.
+/// Each worker keeps the last few provers it creates, along with their HALs
+/// and the control IDs they were configured with, for later requests with the
+/// same [ProverOpts][crate::ProverOpts]. A request that panics is dropped, along
+/// with the provers of its worker, and the worker carries on with the next
+/// request.
+pub struct Daemon {
+    listener: TcpListener,
+    workers: usize,
+    queue_size: usize,
+    resource_policy: ResourcePolicy,
+    shutdown: Arc<AtomicBool>,
+}
+
+/// Stops a [Daemon] from another thread or from a signal handler.
+#[derive(Clone)]
+pub struct DaemonHandle {
+    shutdown: Arc<AtomicBool>,
+}
+
+impl DaemonHandle {
+    /// Stop accepting connections.
+    ///
+    /// Requests that are already queued or in progress are completed before
+    /// [Daemon::run] returns. A connection that is waiting for room in the
+    /// queue is closed.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Daemon {
+    /// Construct a new [Daemon] which listens on the specified TCP/IP address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            workers: DEFAULT_WORKERS,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            resource_policy: ResourcePolicy::default(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Set the number of requests handled concurrently.
+    ///
+    /// Each worker uses all available cores while proving, so more than one
+    /// worker is mostly useful for executing or for multiple GPUs.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Set the number of connections waiting for a worker, after which clients
+    /// wait to be accepted.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    /// Limit the resources used by the guest of every request, as with
+    /// [Server::with_resource_policy].
+    pub fn resource_policy(mut self, policy: ResourcePolicy) -> Self {
+        self.resource_policy = policy;
+        self
+    }
+
+    /// Returns the address that the [Daemon] is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns a handle to shut down the [Daemon].
+    pub fn handle(&self) -> DaemonHandle {
+        DaemonHandle {
+            shutdown: self.shutdown.clone(),
+        }
+    }
+
+    /// Accept and handle requests until shut down with a [DaemonHandle].
+    pub fn run(self) -> Result<()> {
+        self.listener.set_nonblocking(true)?;
+        tracing::info!("listening on {}", self.local_addr()?);
+
+        let (tx, rx) = sync_channel(self.queue_size);
+        let queue = Arc::new(Mutex::new(rx));
+        let workers = (0..self.workers)
+            .map(|idx| {
+                let connector = QueueConnector {
+                    queue: queue.clone(),
+                };
+                let resource_policy = self.resource_policy.clone();
+                thread::Builder::new()
+                    .name(format!("worker-{idx}"))
+                    .spawn(move || work(connector, resource_policy))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        while !self.shutdown.load(Ordering::Relaxed) {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    tracing::debug!("accepted client: {addr}");
+                    stream.set_nonblocking(false)?;
+                    // Waits while the queue is full, leaving further clients in the backlog.
+                    let mut stream = stream;
+                    loop {
+                        match tx.try_send(stream) {
+                            Ok(()) => break,
+                            Err(TrySendError::Full(_)) if self.shutdown.load(Ordering::Relaxed) => {
+                                tracing::debug!("closing client on shutdown: {addr}");
+                                break;
+                            }
+                            Err(TrySendError::Full(returned)) => {
+                                stream = returned;
+                                thread::sleep(ACCEPT_POLL_INTERVAL);
+                            }
+                            Err(TrySendError::Disconnected(_)) => bail!("all workers have stopped"),
+                        }
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+                Err(err) => tracing::warn!("failed to accept a connection: {err}"),
+            }
+        }
+
+        // Closing the queue stops each worker once the queued connections are handled.
+        tracing::info!("shutting down");
+        drop(tx);
+        for worker in workers {
+            worker
+                .join()
+                .map_err(|_| anyhow!("worker panicked while handling a request"))?;
+        }
+        Ok(())
+    }
+}
+
+// Hands out the connections queued by the daemon.
+#[derive(Clone)]
+struct QueueConnector {
+    queue: Arc<Mutex<Receiver<TcpStream>>>,
+}
+
+impl Connector for QueueConnector {
+    fn connect(&self) -> Result<ConnectionWrapper> {
+        let stream = self
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv()?;
+        Ok(ConnectionWrapper::new(Box::new(TcpConnection::new(stream))))
+    }
+}
+
+fn work(connector: QueueConnector, resource_policy: ResourcePolicy) {
+    loop {
+        // A panic may leave the provers in a bad state, so each panic starts over with a new
+        // server and an empty prover cache.
+        let server = Server::new(Box::new(connector.clone()))
+            .with_prover_cache(PROVER_CACHE_SIZE)
+            .with_resource_policy(resource_policy.clone());
+        loop {
+            match catch_unwind(AssertUnwindSafe(|| server.run())) {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) if err.is::<RecvError>() => return,
+                Ok(Err(err)) => tracing::warn!("request failed: {err:#}"),
+                Err(_) => {
+                    tracing::warn!("request panicked");
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -15,6 +15,8 @@
 pub(crate) mod client;
 pub(crate) mod convert;
 #[cfg(feature = "prove")]
+pub(crate) mod daemon;
+#[cfg(feature = "prove")]
 pub(crate) mod server;
 #[cfg(test)]
 #[cfg(feature = "prove")]
@@ -31,14 +33,14 @@ use std::{
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use bytes::{Buf, BufMut, Bytes};
 use prost::Message;
 
-use crate::{ExitCode, Journal};
+use crate::{host::panic_info::GuestPanic, ExitCode, Journal};
 
 mod pb {
     pub(crate) mod api {
@@ -53,6 +55,7 @@ mod pb {
 }
 
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 trait RootMessage: Message {}
 
@@ -191,7 +194,6 @@ struct TcpConnector {
 }
 
 impl TcpConnector {
-    #[cfg(feature = "prove")]
     pub(crate) fn new(addr: &str) -> Self {
         Self {
             addr: addr.to_string(),
@@ -207,6 +209,42 @@ impl Connector for TcpConnector {
     }
 }
 
+/// Accepts connections from `r0vm` workers, started with `r0vm --worker <ADDR>`, that connect to
+/// a listening socket.
+pub(crate) struct ListenerConnector {
+    listener: TcpListener,
+    timeout: Duration,
+}
+
+impl ListenerConnector {
+    pub(crate) fn new(listener: TcpListener, timeout: Duration) -> Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, timeout })
+    }
+}
+
+impl Connector for ListenerConnector {
+    fn connect(&self) -> Result<ConnectionWrapper> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    tracing::debug!("accepted worker: {addr}");
+                    stream.set_nonblocking(false)?;
+                    return Ok(ConnectionWrapper::new(Box::new(TcpConnection::new(stream))));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        bail!("timed out waiting for a worker to connect");
+                    }
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
 struct ParentProcessConnection {
     child: Child,
     stream: TcpStream,
@@ -302,6 +340,10 @@ pub struct SessionInfo {
 
     /// The [ExitCode] of the session.
     pub exit_code: ExitCode,
+
+    /// The panic that ended execution with [ExitCode::Fault], if the guest
+    /// panicked.
+    pub panic: Option<GuestPanic>,
 }
 
 /// Provides information about a segment of execution.
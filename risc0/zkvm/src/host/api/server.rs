@@ -13,9 +13,12 @@
 // limitations under the License.
 
 use std::{
+    cell::RefCell,
+    collections::VecDeque,
     error::Error as StdError,
     io::{BufReader, Error as IoError, ErrorKind as IoErrorKind, Read, Write},
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use anyhow::{anyhow, bail, Result};
@@ -26,14 +29,25 @@ use super::{malformed_err, path_to_string, pb, ConnectionWrapper, Connector, Tcp
 use crate::{
     get_prover_server, get_version,
     host::{client::slice_io::SliceIo, server::session::NullSegmentRef},
-    Assumption, ExecutorEnv, ExecutorImpl, InnerAssumptionReceipt, ProverOpts, Receipt,
-    ReceiptClaim, Segment, SegmentReceipt, SuccinctReceipt, TraceCallback, TraceEvent,
-    VerifierContext,
+    Assumption, ExecutorEnv, ExecutorImpl, InnerAssumptionReceipt, ProverOpts, ProverServer,
+    Receipt, ReceiptClaim, ResourcePolicy, Segment, SegmentReceipt, SuccinctReceipt, TraceCallback,
+    TraceEvent, VerifierContext,
 };
 
+// At most `capacity` provers, evicting the least recently used.
+struct ProverCache {
+    capacity: usize,
+    entries: VecDeque<(Vec<u8>, Rc<dyn ProverServer>)>,
+}
+
 /// A server implementation for handling requests by clients of the zkVM.
 pub struct Server {
     connector: Box<dyn Connector>,
+    // Provers kept between requests, by the serialized options they were created with, with the
+    // most recently used last.
+    provers: Option<RefCell<ProverCache>>,
+    // Limits applied to every guest, in addition to those requested by the client.
+    resource_policy: ResourcePolicy,
 }
 struct PosixIoProxy {
     fd: u32,
@@ -170,7 +184,7 @@ impl TraceCallback for TraceProxy {
         let request = pb::api::ServerReply {
             kind: Some(pb::api::server_reply::Kind::Ok(pb::api::ClientCallback {
                 kind: Some(pb::api::client_callback::Kind::Io(pb::api::OnIoRequest {
-                    kind: Some(pb::api::on_io_request::Kind::Trace(event.into())),
+                    kind: Some(pb::api::on_io_request::Kind::Trace(event.try_into()?)),
                 })),
             })),
         };
@@ -191,7 +205,11 @@ impl TraceCallback for TraceProxy {
 impl Server {
     /// Construct a new [Server] with the specified [Connector].
     pub fn new(connector: Box<dyn Connector>) -> Self {
-        Self { connector }
+        Self {
+            connector,
+            provers: None,
+            resource_policy: ResourcePolicy::default(),
+        }
     }
 
     /// Construct a new [Server] which will connect to the specified TCP/IP
@@ -201,6 +219,43 @@ impl Server {
         Self::new(Box::new(connector))
     }
 
+    /// Keep up to `capacity` of the provers created for requests, along with
+    /// their HALs, so that later requests with the same [ProverOpts] skip
+    /// setting them up again.
+    pub(crate) fn with_prover_cache(mut self, capacity: usize) -> Self {
+        self.provers = Some(RefCell::new(ProverCache {
+            capacity,
+            entries: VecDeque::new(),
+        }));
+        self
+    }
+
+    /// Limit the resources used by the guests that this server executes or
+    /// proves. Clients may request lower limits, but not higher ones.
+    pub fn with_resource_policy(mut self, policy: ResourcePolicy) -> Self {
+        self.resource_policy = policy;
+        self
+    }
+
+    fn get_prover_server(&self, opts: &ProverOpts) -> Result<Rc<dyn ProverServer>> {
+        let Some(provers) = &self.provers else {
+            return get_prover_server(opts);
+        };
+        let key = bincode::serialize(opts)?;
+        let mut provers = provers.borrow_mut();
+        let prover = match provers.entries.iter().position(|(k, _)| *k == key) {
+            Some(idx) => provers.entries.remove(idx).unwrap().1,
+            None => get_prover_server(opts)?,
+        };
+        if provers.entries.len() >= provers.capacity {
+            provers.entries.pop_front();
+        }
+        if provers.capacity > 0 {
+            provers.entries.push_back((key, prover.clone()));
+        }
+        Ok(prover)
+    }
+
     /// Start the [Server] and run until all requests are complete.
     pub fn run(&self) -> Result<()> {
         tracing::debug!("connect");
@@ -256,11 +311,12 @@ impl Server {
         request: pb::api::ExecuteRequest,
     ) -> Result<()> {
         fn inner(
+            server: &Server,
             conn: &mut ConnectionWrapper,
             request: pb::api::ExecuteRequest,
         ) -> Result<pb::api::ServerReply> {
             let env_request = request.env.ok_or(malformed_err())?;
-            let env = build_env(conn, &env_request)?;
+            let env = build_env(conn, &env_request, &server.resource_policy)?;
 
             let binary = env_request.binary.ok_or(malformed_err())?;
 
@@ -301,6 +357,10 @@ impl Server {
 
                 Ok(Box::new(NullSegmentRef))
             })?;
+            // SessionInfo only describes panics, so report any other fault as an error.
+            if let Some(fault) = session.fault {
+                bail!("{fault}");
+            }
 
             Ok(pb::api::ServerReply {
                 kind: Some(pb::api::server_reply::Kind::Ok(pb::api::ClientCallback {
@@ -310,6 +370,7 @@ impl Server {
                                 segments: session.segments.len().try_into()?,
                                 journal: session.journal.unwrap_or_default().bytes,
                                 exit_code: Some(session.exit_code.into()),
+                                panic: session.panic.map(Into::into),
                             }),
                         },
                     )),
@@ -317,7 +378,7 @@ impl Server {
             })
         }
 
-        let msg = inner(&mut conn, request).unwrap_or_else(|err| pb::api::ServerReply {
+        let msg = inner(self, &mut conn, request).unwrap_or_else(|err| pb::api::ServerReply {
             kind: Some(pb::api::server_reply::Kind::Error(pb::api::GenericError {
                 reason: err.to_string(),
             })),
@@ -329,17 +390,18 @@ impl Server {
 
     fn on_prove(&self, mut conn: ConnectionWrapper, request: pb::api::ProveRequest) -> Result<()> {
         fn inner(
+            server: &Server,
             conn: &mut ConnectionWrapper,
             request: pb::api::ProveRequest,
         ) -> Result<pb::api::ServerReply> {
             let env_request = request.env.ok_or(malformed_err())?;
-            let env = build_env(conn, &env_request)?;
+            let env = build_env(conn, &env_request, &server.resource_policy)?;
 
             let binary = env_request.binary.ok_or(malformed_err())?;
             let bytes = binary.as_bytes()?;
 
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let ctx = VerifierContext::default();
             let prove_info = prover.prove_with_ctx(env, &ctx, &bytes)?;
 
@@ -362,7 +424,7 @@ impl Server {
             })
         }
 
-        let msg = inner(&mut conn, request).unwrap_or_else(|err| pb::api::ServerReply {
+        let msg = inner(self, &mut conn, request).unwrap_or_else(|err| pb::api::ServerReply {
             kind: Some(pb::api::server_reply::Kind::Error(pb::api::GenericError {
                 reason: err.to_string(),
             })),
@@ -377,12 +439,15 @@ impl Server {
         mut conn: ConnectionWrapper,
         request: pb::api::ProveSegmentRequest,
     ) -> Result<()> {
-        fn inner(request: pb::api::ProveSegmentRequest) -> Result<pb::api::ProveSegmentReply> {
+        fn inner(
+            server: &Server,
+            request: pb::api::ProveSegmentRequest,
+        ) -> Result<pb::api::ProveSegmentReply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let segment_bytes = request.segment.ok_or(malformed_err())?.as_bytes()?;
             let segment: Segment = bincode::deserialize(&segment_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let ctx = VerifierContext::default();
             let receipt = prover.prove_segment(&ctx, &segment)?;
 
@@ -403,7 +468,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::ProveSegmentReply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::ProveSegmentReply {
             kind: Some(pb::api::prove_segment_reply::Kind::Error(
                 pb::api::GenericError {
                     reason: err.to_string(),
@@ -416,12 +481,12 @@ impl Server {
     }
 
     fn on_lift(&self, mut conn: ConnectionWrapper, request: pb::api::LiftRequest) -> Result<()> {
-        fn inner(request: pb::api::LiftRequest) -> Result<pb::api::LiftReply> {
+        fn inner(server: &Server, request: pb::api::LiftRequest) -> Result<pb::api::LiftReply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let receipt_bytes = request.receipt.ok_or(malformed_err())?.as_bytes()?;
             let segment_receipt: SegmentReceipt = bincode::deserialize(&receipt_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let receipt = prover.lift(&segment_receipt)?;
 
             let succinct_receipt_pb: pb::core::SuccinctReceipt = receipt.into();
@@ -439,7 +504,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::LiftReply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::LiftReply {
             kind: Some(pb::api::lift_reply::Kind::Error(pb::api::GenericError {
                 reason: err.to_string(),
             })),
@@ -450,7 +515,7 @@ impl Server {
     }
 
     fn on_join(&self, mut conn: ConnectionWrapper, request: pb::api::JoinRequest) -> Result<()> {
-        fn inner(request: pb::api::JoinRequest) -> Result<pb::api::JoinReply> {
+        fn inner(server: &Server, request: pb::api::JoinRequest) -> Result<pb::api::JoinReply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let left_receipt_bytes = request.left_receipt.ok_or(malformed_err())?.as_bytes()?;
             let left_succinct_receipt: SuccinctReceipt<ReceiptClaim> =
@@ -459,7 +524,7 @@ impl Server {
             let right_succinct_receipt: SuccinctReceipt<ReceiptClaim> =
                 bincode::deserialize(&right_receipt_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let receipt = prover.join(&left_succinct_receipt, &right_succinct_receipt)?;
 
             let succinct_receipt_pb: pb::core::SuccinctReceipt = receipt.into();
@@ -477,7 +542,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::JoinReply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::JoinReply {
             kind: Some(pb::api::join_reply::Kind::Error(pb::api::GenericError {
                 reason: err.to_string(),
             })),
@@ -492,7 +557,10 @@ impl Server {
         mut conn: ConnectionWrapper,
         request: pb::api::ResolveRequest,
     ) -> Result<()> {
-        fn inner(request: pb::api::ResolveRequest) -> Result<pb::api::ResolveReply> {
+        fn inner(
+            server: &Server,
+            request: pb::api::ResolveRequest,
+        ) -> Result<pb::api::ResolveReply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let conditional_receipt_bytes = request
                 .conditional_receipt
@@ -507,7 +575,7 @@ impl Server {
             let assumption_succinct_receipt: SuccinctReceipt<ReceiptClaim> =
                 bincode::deserialize(&assumption_receipt_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let receipt = prover.resolve(
                 &conditional_succinct_receipt,
                 &assumption_succinct_receipt.into_unknown(),
@@ -528,7 +596,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::ResolveReply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::ResolveReply {
             kind: Some(pb::api::resolve_reply::Kind::Error(pb::api::GenericError {
                 reason: err.to_string(),
             })),
@@ -543,13 +611,16 @@ impl Server {
         mut conn: ConnectionWrapper,
         request: pb::api::IdentityP254Request,
     ) -> Result<()> {
-        fn inner(request: pb::api::IdentityP254Request) -> Result<pb::api::IdentityP254Reply> {
+        fn inner(
+            server: &Server,
+            request: pb::api::IdentityP254Request,
+        ) -> Result<pb::api::IdentityP254Reply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let receipt_bytes = request.receipt.ok_or(malformed_err())?.as_bytes()?;
             let succinct_receipt: SuccinctReceipt<ReceiptClaim> =
                 bincode::deserialize(&receipt_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let receipt = prover.identity_p254(&succinct_receipt)?;
 
             let succinct_receipt_pb: pb::core::SuccinctReceipt = receipt.into();
@@ -569,7 +640,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::IdentityP254Reply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::IdentityP254Reply {
             kind: Some(pb::api::identity_p254_reply::Kind::Error(
                 pb::api::GenericError {
                     reason: err.to_string(),
@@ -586,12 +657,15 @@ impl Server {
         mut conn: ConnectionWrapper,
         request: pb::api::CompressRequest,
     ) -> Result<()> {
-        fn inner(request: pb::api::CompressRequest) -> Result<pb::api::CompressReply> {
+        fn inner(
+            server: &Server,
+            request: pb::api::CompressRequest,
+        ) -> Result<pb::api::CompressReply> {
             let opts: ProverOpts = request.opts.ok_or(malformed_err())?.try_into()?;
             let receipt_bytes = request.receipt.ok_or(malformed_err())?.as_bytes()?;
             let receipt: Receipt = bincode::deserialize(&receipt_bytes)?;
 
-            let prover = get_prover_server(&opts)?;
+            let prover = server.get_prover_server(&opts)?;
             let receipt = prover.compress(&opts, &receipt)?;
 
             let receipt_pb: pb::core::Receipt = receipt.into();
@@ -609,7 +683,7 @@ impl Server {
             })
         }
 
-        let msg = inner(request).unwrap_or_else(|err| pb::api::CompressReply {
+        let msg = inner(self, request).unwrap_or_else(|err| pb::api::CompressReply {
             kind: Some(pb::api::compress_reply::Kind::Error(
                 pb::api::GenericError {
                     reason: err.to_string(),
@@ -625,6 +699,7 @@ impl Server {
 fn build_env<'a>(
     conn: &ConnectionWrapper,
     request: &pb::api::ExecutorEnv,
+    resource_policy: &ResourcePolicy,
 ) -> Result<ExecutorEnv<'a>> {
     let mut env_builder = ExecutorEnv::builder();
     env_builder.env_vars(request.env_vars.clone());
@@ -646,6 +721,9 @@ fn build_env<'a>(
         env_builder.segment_limit_po2(segment_limit_po2);
     }
     env_builder.session_limit(request.session_limit);
+    let requested_policy: ResourcePolicy =
+        request.resource_policy.clone().unwrap_or_default().into();
+    env_builder.resource_policy(requested_policy.intersect(resource_policy));
     if request.trace_events.is_some() {
         let proxy = TraceProxy::new(conn.try_clone()?);
         env_builder.trace_callback(proxy);
@@ -14,9 +14,11 @@
 
 use std::{
     collections::BTreeMap,
-    net::{SocketAddr, TcpListener},
+    io::Read,
+    net::{SocketAddr, TcpListener, TcpStream},
     path::PathBuf,
     thread,
+    time::Duration,
 };
 
 use anyhow::Result;
@@ -31,8 +33,8 @@ use test_log::test;
 
 use super::{Asset, AssetRequest, ConnectionWrapper, Connector, TcpConnection};
 use crate::{
-    receipt::SuccinctReceipt, recursion::MerkleGroup, ApiClient, ApiServer, ExecutorEnv,
-    InnerReceipt, ProverOpts, Receipt, ReceiptClaim, SegmentReceipt, SessionInfo,
+    receipt::SuccinctReceipt, recursion::MerkleGroup, ApiClient, ApiDaemon, ApiServer, ExecutorEnv,
+    ExitCode, InnerReceipt, ProverOpts, Receipt, ReceiptClaim, SegmentReceipt, SessionInfo,
     SuccinctReceiptVerifierParameters, VerifierContext,
 };
 
@@ -330,7 +332,6 @@ fn lift_resolve() {
 }
 
 #[test]
-#[should_panic(expected = "MultiTestSpec::Panic invoked")]
 fn guest_error_forwarding() {
     let env = ExecutorEnv::builder()
         .write(&MultiTestSpec::Panic)
@@ -338,5 +339,68 @@ fn guest_error_forwarding() {
         .build()
         .unwrap();
     let binary = Asset::Inline(MULTI_TEST_ELF.into());
-    TestClient::new().execute(env, binary);
+    let session = TestClient::new().execute(env, binary);
+    assert_eq!(session.exit_code, ExitCode::Fault);
+
+    let panic = session.panic.unwrap();
+    assert_eq!(panic.message, "MultiTestSpec::Panic invoked");
+    assert!(!panic.backtrace.is_empty());
+}
+
+#[test]
+fn daemon() {
+    let daemon = ApiDaemon::bind("127.0.0.1:0").unwrap().workers(2);
+    let addr = daemon.local_addr().unwrap().to_string();
+    let handle = daemon.handle();
+    let server = thread::spawn(move || daemon.run().unwrap());
+
+    // Several clients can be served by the same daemon, concurrently and in turn.
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            let addr = addr.clone();
+            thread::spawn(move || {
+                let env = ExecutorEnv::builder()
+                    .write(&MultiTestSpec::DoNothing)
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                let binary = Asset::Inline(MULTI_TEST_ELF.into());
+                let work_dir = tempdir().unwrap();
+                let segments_out = AssetRequest::Path(work_dir.path().to_path_buf());
+                ApiClient::new_tcp(addr)
+                    .execute(&env, binary, segments_out, |_info, _asset| Ok(()))
+                    .unwrap()
+            })
+        })
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap().exit_code, ExitCode::Halted(0));
+    }
+
+    handle.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn daemon_shutdown_with_full_queue() {
+    let daemon = ApiDaemon::bind("127.0.0.1:0")
+        .unwrap()
+        .workers(1)
+        .queue_size(0);
+    let addr = daemon.local_addr().unwrap();
+    let handle = daemon.handle();
+    let server = thread::spawn(move || daemon.run().unwrap());
+
+    // The first client keeps the only worker busy, so the second waits for room in the queue.
+    let busy = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(500));
+    let mut waiting = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(500));
+
+    // Shutting down closes the waiting client, rather than waiting for the worker.
+    handle.shutdown();
+    assert_eq!(waiting.read(&mut [0; 1]).unwrap(), 0);
+
+    drop(busy);
+    server.join().unwrap();
 }
@@ -17,19 +17,24 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
+    fmt,
     io::{BufRead, BufReader, Cursor, Read, Write},
     mem,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::Result;
 use bytemuck::Pod;
 use bytes::Bytes;
 use risc0_zkp::core::digest::Digest;
-use risc0_zkvm_platform::{self, fileno};
-use serde::Serialize;
+use risc0_zkvm_platform::{
+    self, fileno,
+    syscall::nr::{SYS_FS_CLOSE, SYS_FS_OPEN, SYS_FS_READ, SYS_FS_STAT},
+};
+use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
 
 #[cfg(feature = "prove")]
@@ -38,6 +43,7 @@ use crate::{
     host::client::{
         posix_io::PosixIo,
         slice_io::{slice_io_from_fn, SliceIo, SliceIoTable},
+        vfs::{VfsHandler, VirtualFs},
     },
     serde::to_vec,
     AssumptionReceipt, TraceCallback,
@@ -75,6 +81,159 @@ impl SegmentPath {
     }
 }
 
+/// The format in which the profiler writes the guest's profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProfileFormat {
+    /// The pprof protobuf format, as read by `go tool pprof`.
+    #[default]
+    Pprof,
+
+    /// Folded stacks, one per line, as read by `flamegraph.pl` and `inferno-flamegraph`.
+    Folded,
+
+    /// The speedscope JSON format, as read by <https://www.speedscope.app>.
+    Speedscope,
+}
+
+impl ProfileFormat {
+    /// Infer the format from the extension of the output path: `.folded` for
+    /// [ProfileFormat::Folded], `.json` for [ProfileFormat::Speedscope], and
+    /// [ProfileFormat::Pprof] otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("folded") => Self::Folded,
+            Some("json") => Self::Speedscope,
+            _ => Self::Pprof,
+        }
+    }
+}
+
+/// Limits on the resources that a guest may use while it is executed, in
+/// addition to the cycles limited by [ExecutorEnvBuilder::session_limit].
+///
+/// When a limit is exceeded, execution stops with an error that can be
+/// downcast to a [ResourceLimitExceeded]. Each limit is unset by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ResourcePolicy {
+    /// Maximum time that execution may take. This is checked at every system
+    /// call and segment boundary, so a guest may run for up to one more
+    /// segment before it is stopped.
+    pub wall_clock: Option<Duration>,
+
+    /// Maximum number of distinct pages of guest memory that may be touched,
+    /// each of which is [crate::PAGE_SIZE] bytes. This is checked at every
+    /// segment boundary.
+    pub pages: Option<u64>,
+
+    /// Maximum size of the journal, in bytes.
+    pub journal_bytes: Option<u64>,
+
+    /// Maximum number of bytes that the guest may read from file descriptors,
+    /// [slice_io](ExecutorEnvBuilder::slice_io) channels and the
+    /// [virtual filesystem](ExecutorEnvBuilder::virtual_fs).
+    pub input_bytes: Option<u64>,
+
+    /// Maximum number of calls to `sys_verify_integrity`, which the guest
+    /// makes to verify a receipt for an assumption.
+    pub verify_calls: Option<u64>,
+}
+
+impl ResourcePolicy {
+    /// Construct a [ResourcePolicy] without any limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum time that execution may take.
+    pub fn wall_clock(mut self, limit: Duration) -> Self {
+        self.wall_clock = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of distinct pages of guest memory that may be
+    /// touched.
+    pub fn pages(mut self, limit: u64) -> Self {
+        self.pages = Some(limit);
+        self
+    }
+
+    /// Set the maximum size of the journal, in bytes.
+    pub fn journal_bytes(mut self, limit: u64) -> Self {
+        self.journal_bytes = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of bytes that the guest may read.
+    pub fn input_bytes(mut self, limit: u64) -> Self {
+        self.input_bytes = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of calls to `sys_verify_integrity`.
+    pub fn verify_calls(mut self, limit: u64) -> Self {
+        self.verify_calls = Some(limit);
+        self
+    }
+
+    /// Returns the policy that satisfies both this policy and `other`, with
+    /// the lower of the two values of each limit.
+    pub fn intersect(&self, other: &Self) -> Self {
+        fn min<T: Ord + Copy>(lhs: Option<T>, rhs: Option<T>) -> Option<T> {
+            match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) => Some(lhs.min(rhs)),
+                (lhs, rhs) => lhs.or(rhs),
+            }
+        }
+        Self {
+            wall_clock: min(self.wall_clock, other.wall_clock),
+            pages: min(self.pages, other.pages),
+            journal_bytes: min(self.journal_bytes, other.journal_bytes),
+            input_bytes: min(self.input_bytes, other.input_bytes),
+            verify_calls: min(self.verify_calls, other.verify_calls),
+        }
+    }
+}
+
+/// The error returned by the executor when the guest exceeds a limit of its
+/// [ResourcePolicy]. Each variant holds the limit that was exceeded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResourceLimitExceeded {
+    /// Execution took longer than [ResourcePolicy::wall_clock].
+    WallClock(Duration),
+
+    /// The guest touched more than [ResourcePolicy::pages] pages of memory.
+    Pages(u64),
+
+    /// The journal grew larger than [ResourcePolicy::journal_bytes].
+    JournalBytes(u64),
+
+    /// The guest read more than [ResourcePolicy::input_bytes] bytes.
+    InputBytes(u64),
+
+    /// The guest called `sys_verify_integrity` more than
+    /// [ResourcePolicy::verify_calls] times.
+    VerifyCalls(u64),
+}
+
+impl fmt::Display for ResourceLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WallClock(limit) => write!(f, "wall clock limit exceeded: {limit:?}"),
+            Self::Pages(limit) => write!(f, "page limit exceeded: {limit} pages"),
+            Self::JournalBytes(limit) => write!(f, "journal limit exceeded: {limit} bytes"),
+            Self::InputBytes(limit) => write!(f, "input limit exceeded: {limit} bytes"),
+            Self::VerifyCalls(limit) => {
+                write!(f, "sys_verify_integrity limit exceeded: {limit} calls")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceLimitExceeded {}
+
 /// The [Executor][crate::Executor] is configured from this object.
 ///
 /// The executor environment holds configuration details that inform how the
@@ -85,6 +244,7 @@ pub struct ExecutorEnv<'a> {
     pub(crate) args: Vec<String>,
     pub(crate) segment_limit_po2: Option<u32>,
     pub(crate) session_limit: Option<u64>,
+    pub(crate) resource_policy: ResourcePolicy,
     pub(crate) posix_io: Rc<RefCell<PosixIo<'a>>>,
     pub(crate) slice_io: Rc<RefCell<SliceIoTable<'a>>>,
     pub(crate) input: Vec<u8>,
@@ -92,7 +252,11 @@ pub struct ExecutorEnv<'a> {
     pub(crate) assumptions: Rc<RefCell<AssumptionReceipts>>,
     pub(crate) segment_path: Option<SegmentPath>,
     pub(crate) pprof_out: Option<PathBuf>,
+    pub(crate) profile_format: Option<ProfileFormat>,
+    pub(crate) profile_top: Option<usize>,
     pub(crate) input_digest: Option<Digest>,
+    pub(crate) vfs: Option<Rc<RefCell<VfsHandler<'a>>>>,
+    pub(crate) commit_input: bool,
 }
 
 impl<'a> ExecutorEnv<'a> {
@@ -108,6 +272,37 @@ impl<'a> ExecutorEnv<'a> {
     pub fn builder() -> ExecutorEnvBuilder<'a> {
         ExecutorEnvBuilder::default()
     }
+
+    /// Copy this environment such that it can be used for another execution of the same guest.
+    ///
+    /// Input and configuration are copied, and I/O handlers and callbacks are shared with this
+    /// environment. The record of assumptions accessed during execution is not copied.
+    pub(crate) fn shallow_clone(&self) -> Self {
+        let assumptions = AssumptionReceipts {
+            cached: self.assumptions.borrow().cached.clone(),
+            #[cfg(feature = "prove")]
+            accessed: Vec::new(),
+        };
+        Self {
+            env_vars: self.env_vars.clone(),
+            args: self.args.clone(),
+            segment_limit_po2: self.segment_limit_po2,
+            session_limit: self.session_limit,
+            resource_policy: self.resource_policy.clone(),
+            posix_io: self.posix_io.clone(),
+            slice_io: self.slice_io.clone(),
+            input: self.input.clone(),
+            trace: self.trace.clone(),
+            assumptions: Rc::new(RefCell::new(assumptions)),
+            segment_path: self.segment_path.clone(),
+            pprof_out: self.pprof_out.clone(),
+            profile_format: self.profile_format,
+            profile_top: self.profile_top,
+            input_digest: self.input_digest,
+            vfs: self.vfs.clone(),
+            commit_input: self.commit_input,
+        }
+    }
 }
 
 impl<'a> ExecutorEnvBuilder<'a> {
@@ -140,6 +335,12 @@ impl<'a> ExecutorEnvBuilder<'a> {
             }
         }
 
+        if inner.profile_top.is_none() {
+            if let Ok(env_var) = std::env::var("RISC0_PPROF_TOP") {
+                inner.profile_top = Some(env_var.parse()?);
+            }
+        }
+
         Ok(inner)
     }
 
@@ -169,6 +370,28 @@ impl<'a> ExecutorEnvBuilder<'a> {
         self
     }
 
+    /// Set limits on the resources that the guest may use, beyond its cycles.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use risc0_zkvm::{ExecutorEnv, ResourcePolicy};
+    ///
+    /// let env = ExecutorEnv::builder()
+    ///     .resource_policy(
+    ///         ResourcePolicy::new()
+    ///             .wall_clock(Duration::from_secs(60))
+    ///             .journal_bytes(1024),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn resource_policy(&mut self, policy: ResourcePolicy) -> &mut Self {
+        self.inner.resource_policy = policy;
+        self
+    }
+
     /// Add environment variables to the guest environment.
     ///
     /// # Example
@@ -331,6 +554,44 @@ impl<'a> ExecutorEnvBuilder<'a> {
         self
     }
 
+    /// Add a [VirtualFs] that the guest can read using `risc0_zkvm::guest::fs`.
+    ///
+    /// When more than one [VirtualFs] is added, paths are looked up in the most recently added
+    /// one first, falling back to earlier ones for paths that it does not contain.
+    ///
+    /// Every byte the guest reads from the filesystem is absorbed into a running digest, which
+    /// the guest can commit to its journal with `risc0_zkvm::guest::fs::digest`, and which is
+    /// reported as the `vfs_digest` of the session. The reads are also absorbed into the input
+    /// digest when [ExecutorEnvBuilder::commit_input] is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use risc0_zkvm::{ExecutorEnv, MemoryFs};
+    ///
+    /// let mut fs = MemoryFs::new();
+    /// fs.insert("/etc/config.toml", "verbose = true");
+    ///
+    /// let env = ExecutorEnv::builder()
+    ///     .virtual_fs(fs)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn virtual_fs(&mut self, fs: impl VirtualFs + 'a) -> &mut Self {
+        let vfs = self.inner.vfs.get_or_insert_with(|| {
+            let vfs = Rc::new(RefCell::new(VfsHandler::default()));
+            let mut slice_io = self.inner.slice_io.borrow_mut();
+            for syscall in [SYS_FS_CLOSE, SYS_FS_OPEN, SYS_FS_READ, SYS_FS_STAT] {
+                slice_io
+                    .inner
+                    .insert(syscall.as_str().to_string(), vfs.clone());
+            }
+            vfs
+        });
+        vfs.borrow_mut().mount(fs);
+        self
+    }
+
     /// Add an [AssumptionReceipt] to the [ExecutorEnv], for use in [composition].
     ///
     /// During execution, when the guest calls `env::verify` or `env::verify_integrity`, this
@@ -369,9 +630,47 @@ impl<'a> ExecutorEnvBuilder<'a> {
         self
     }
 
+    /// Set the format of the profile written by the profiler.
+    ///
+    /// By default, the format is inferred from the extension of the path
+    /// given to [ExecutorEnvBuilder::enable_profiler] with
+    /// [ProfileFormat::from_path].
+    pub fn profile_format(&mut self, format: ProfileFormat) -> &mut Self {
+        self.inner.profile_format = Some(format);
+        self
+    }
+
+    /// Enable the profiler and report the `top` functions that used the most
+    /// cycles in [crate::Session::profile_report] once execution ends.
+    ///
+    /// The report can also be enabled with the `RISC0_PPROF_TOP` environment
+    /// variable.
+    pub fn profile_report(&mut self, top: usize) -> &mut Self {
+        self.inner.profile_top = Some(top);
+        self
+    }
+
     /// Set the input digest.
     pub fn input_digest(&mut self, digest: Digest) -> &mut Self {
         self.inner.input_digest = Some(digest);
         self
     }
+
+    /// Commit to all data sent from the host to the guest in the input digest.
+    ///
+    /// The executor keeps a running hash of the data returned to the guest by every system call
+    /// that provides input, including reads from file descriptors, environment variables,
+    /// arguments and [slice_io](ExecutorEnvBuilder::slice_io) channels, and uses it as the input
+    /// digest of the session. The guest must be built with the `commit-input` feature of
+    /// `risc0-zkvm`, which makes it compute the same hash and check it against the input digest
+    /// every time it halts or pauses, including through `env::exit` and when it returns from
+    /// `main`. A guest whose hash does not match faults instead of halting. The `Input` field of
+    /// the resulting [crate::ReceiptClaim] then commits to exactly the data that the guest
+    /// consumed.
+    ///
+    /// This cannot be used together with [ExecutorEnvBuilder::input_digest].
+    pub fn commit_input(&mut self) -> &mut Self {
+        self.inner.commit_input = true;
+        self
+    }
 }
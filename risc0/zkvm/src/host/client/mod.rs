@@ -16,3 +16,4 @@ pub(crate) mod env;
 pub(crate) mod posix_io;
 pub(crate) mod prove;
 pub(crate) mod slice_io;
+pub(crate) mod vfs;
@@ -26,9 +26,11 @@ use crate::{
 /// An implementation of a [Prover] that runs proof workloads via Bonsai.
 ///
 /// Requires `BONSAI_API_URL` and `BONSAI_API_KEY` environment variables to
-/// submit proving sessions to Bonsai.
+/// submit proving sessions to Bonsai, unless constructed with
+/// [BonsaiProver::from_parts].
 pub struct BonsaiProver {
     name: String,
+    api: Option<(String, String)>,
 }
 
 impl BonsaiProver {
@@ -36,8 +38,25 @@ impl BonsaiProver {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            api: None,
         }
     }
+
+    /// Construct a [BonsaiProver] using the given Bonsai API URL and key,
+    /// rather than those set in the environment.
+    pub fn from_parts(name: &str, api_url: String, api_key: String) -> Self {
+        Self {
+            name: name.to_string(),
+            api: Some((api_url, api_key)),
+        }
+    }
+
+    fn client(&self) -> Result<Client> {
+        Ok(match &self.api {
+            Some((url, key)) => Client::from_parts(url.clone(), key.clone(), crate::VERSION)?,
+            None => Client::from_env(crate::VERSION)?,
+        })
+    }
 }
 
 impl Prover for BonsaiProver {
@@ -52,7 +71,7 @@ impl Prover for BonsaiProver {
         elf: &[u8],
         opts: &ProverOpts,
     ) -> Result<ProveInfo> {
-        let client = Client::from_env(crate::VERSION)?;
+        let client = self.client()?;
 
         // Compute the ImageID and upload the ELF binary
         let image_id = compute_image_id(elf)?;
@@ -120,6 +139,7 @@ impl Prover for BonsaiProver {
                         total_cycles: stats.total_cycles,
                         user_cycles: stats.cycles,
                     },
+                    prover: Some(self.name.clone()),
                 };
             } else {
                 bail!(
@@ -193,6 +213,7 @@ impl Prover for BonsaiProver {
         Ok(ProveInfo {
             receipt: groth16_receipt,
             stats: succinct_prove_info.stats,
+            prover: Some(self.name.clone()),
         })
     }
 
@@ -0,0 +1,379 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::TcpListener,
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+
+use super::{fallback::RetryPolicy, Prover, ProverOpts};
+use crate::{
+    compute_image_id,
+    host::api::{AssetRequest, ListenerConnector},
+    is_dev_mode,
+    sha::Digestible,
+    ApiClient, Asset, CompositeReceipt, ExecutorEnv, InnerReceipt, ProveInfo, Receipt,
+    ReceiptClaim, ReceiptKind, SegmentReceipt, SessionInfo, SessionStats, SuccinctReceipt,
+    VerifierContext,
+};
+
+/// An implementation of a [Prover] that spreads the proving of a session across a pool of
+/// `r0vm` worker processes.
+///
+/// Workers are started with `r0vm --worker <ADDR>`, where `ADDR` is the address of the listener
+/// given to [DistributedProver::new], and may run on other machines. Each worker connects to the
+/// listener to receive a request, and connects again once it has replied.
+///
+/// The guest is executed once, on one of the workers. Each segment is sent to the next available
+/// worker to be proven as soon as it is produced. For succinct and Groth16 receipts, the segment
+/// receipts are then lifted in parallel and joined pairwise, with each level of the join tree
+/// proven in parallel.
+///
+/// A request that fails, for example because the worker handling it exited, is retried on the
+/// next available worker according to the [RetryPolicy]. Execution is only retried if it fails
+/// before the first segment is produced. As with the [FallbackProver](crate::FallbackProver),
+/// input written to the [ExecutorEnv] is provided to every attempt, but other I/O is shared
+/// between attempts.
+pub struct DistributedProver {
+    name: String,
+    listener: TcpListener,
+    workers: usize,
+    worker_timeout: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl DistributedProver {
+    /// Construct a [DistributedProver] that accepts connections from workers on the given
+    /// listener.
+    ///
+    /// By default, one request is in flight at a time, requests wait up to a minute for a worker
+    /// to connect, and the default [RetryPolicy] is used.
+    pub fn new(name: &str, listener: TcpListener) -> Self {
+        Self {
+            name: name.to_string(),
+            listener,
+            workers: 1,
+            worker_timeout: Duration::from_secs(60),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Return [DistributedProver] with the number of requests to run concurrently set.
+    ///
+    /// This should match the number of workers in the pool.
+    pub fn with_workers(self, workers: usize) -> Self {
+        Self { workers, ..self }
+    }
+
+    /// Return [DistributedProver] with the time to wait for a worker to connect set.
+    pub fn with_worker_timeout(self, worker_timeout: Duration) -> Self {
+        Self {
+            worker_timeout,
+            ..self
+        }
+    }
+
+    /// Return [DistributedProver] with the given [RetryPolicy].
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    fn client(&self) -> Result<ApiClient> {
+        let connector = ListenerConnector::new(self.listener.try_clone()?, self.worker_timeout)?;
+        Ok(ApiClient::with_connector(Box::new(connector)))
+    }
+
+    // Run a request on the next available worker, retrying on failure as per the policy.
+    fn retry<T>(
+        &self,
+        client: &ApiClient,
+        what: &str,
+        request: impl Fn(&ApiClient) -> Result<T>,
+    ) -> Result<T> {
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        let mut attempt = 0;
+        loop {
+            match request(client) {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt + 1 < max_attempts => {
+                    tracing::warn!(
+                        "{}: {what} failed on attempt {}: {err:#}",
+                        self.name,
+                        attempt + 1
+                    );
+                    thread::sleep(self.retry_policy.backoff(attempt));
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(err.context(format!("{what} failed after {max_attempts} attempts")))
+                }
+            }
+        }
+    }
+
+    // Run `f` on each job, on up to `workers` threads that each have their own client, and
+    // return the results in the order of the jobs.
+    fn process<T: Send, U: Send>(
+        &self,
+        jobs: impl Iterator<Item = T> + Send,
+        f: impl Fn(&ApiClient, T) -> Result<U> + Sync,
+    ) -> Result<Vec<U>> {
+        let jobs = Mutex::new(jobs.enumerate());
+        let mut results = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.workers.max(1))
+                .map(|_| {
+                    scope.spawn(|| -> Result<Vec<(usize, U)>> {
+                        let client = self.client()?;
+                        let mut results = Vec::new();
+                        loop {
+                            let job = jobs.lock().unwrap().next();
+                            let Some((idx, job)) = job else {
+                                return Ok(results);
+                            };
+                            results.push((idx, f(&client, job)?));
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .map_err(|_| anyhow!("{}: prover thread panicked", self.name))?
+                })
+                .collect::<Result<Vec<_>>>()
+        })?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        results.sort_by_key(|(idx, _)| *idx);
+        Ok(results.into_iter().map(|(_, result)| result).collect())
+    }
+
+    // Execute the guest on the next available worker, sending each segment as it is produced.
+    //
+    // Execution is only retried if it fails before the first segment is produced, since the
+    // segments from a failed attempt may already be being proven.
+    fn execute(
+        &self,
+        env: &ExecutorEnv<'_>,
+        binary: Asset,
+        segments: mpsc::Sender<Asset>,
+    ) -> Result<SessionInfo> {
+        let client = self.client()?;
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        let mut attempt = 0;
+        loop {
+            let mut produced_segments = false;
+            let result = client.execute(
+                &env.shallow_clone(),
+                binary.clone(),
+                AssetRequest::Inline,
+                |_info, segment| {
+                    produced_segments = true;
+                    segments
+                        .send(segment)
+                        .map_err(|_| anyhow!("segment provers exited early"))
+                },
+            );
+            match result {
+                Err(err) if !produced_segments && attempt + 1 < max_attempts => {
+                    tracing::warn!(
+                        "{}: execute failed on attempt {}: {err:#}",
+                        self.name,
+                        attempt + 1
+                    );
+                    thread::sleep(self.retry_policy.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return result.context("execution failed"),
+            }
+        }
+    }
+
+    // Lift each segment receipt, then join adjacent pairs of receipts until one remains.
+    fn segments_to_succinct(
+        &self,
+        opts: &ProverOpts,
+        segments: Vec<SegmentReceipt>,
+    ) -> Result<SuccinctReceipt<ReceiptClaim>> {
+        let mut receipts = self.process(segments.into_iter(), |client, segment| {
+            let receipt: Asset = segment.try_into()?;
+            self.retry(client, "lift", |client| {
+                client.lift(opts, receipt.clone(), AssetRequest::Inline)
+            })
+        })?;
+
+        while receipts.len() > 1 {
+            let mut pairs = Vec::new();
+            let mut receipts_iter = receipts.into_iter();
+            while let Some(left) = receipts_iter.next() {
+                pairs.push((left, receipts_iter.next()));
+            }
+            receipts = self.process(pairs.into_iter(), |client, pair| match pair {
+                (left, Some(right)) => {
+                    let left: Asset = left.try_into()?;
+                    let right: Asset = right.try_into()?;
+                    self.retry(client, "join", |client| {
+                        client.join(opts, left.clone(), right.clone(), AssetRequest::Inline)
+                    })
+                }
+                // An odd receipt out is carried up to the next level unchanged.
+                (left, None) => Ok(left),
+            })?;
+        }
+        receipts.pop().context("session is empty")
+    }
+}
+
+impl Prover for DistributedProver {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn prove_with_ctx(
+        &self,
+        env: ExecutorEnv<'_>,
+        ctx: &VerifierContext,
+        elf: &[u8],
+        opts: &ProverOpts,
+    ) -> Result<ProveInfo> {
+        ensure!(
+            env.assumptions.borrow().cached.is_empty(),
+            "{}: assumptions are not supported",
+            self.name
+        );
+
+        let image_id = compute_image_id(elf)?;
+        let binary = Asset::Inline(elf.to_vec().into());
+
+        // Fake receipts are produced without proving segments, so the whole request goes to a
+        // single worker.
+        if is_dev_mode() {
+            let mut prove_info = self.client()?.prove(&env, opts, binary)?;
+            prove_info.prover = Some(self.name.clone());
+            return Ok(prove_info);
+        }
+
+        // Prove each segment while the rest of the session is being executed.
+        let (sender, receiver) = mpsc::channel();
+        let (session, segments) = thread::scope(|scope| {
+            let segments = scope.spawn(|| {
+                self.process(receiver.into_iter(), |client, segment: Asset| {
+                    self.retry(client, "prove_segment", |client| {
+                        client.prove_segment(opts, segment.clone(), AssetRequest::Inline)
+                    })
+                })
+            });
+
+            let session = self.execute(&env, binary, sender);
+
+            let segments = segments
+                .join()
+                .map_err(|_| anyhow!("{}: prover thread panicked", self.name));
+            (session, segments)
+        });
+        let session = session?;
+        let segments = segments??;
+
+        let stats = SessionStats {
+            segments: session.segments.len(),
+            total_cycles: session
+                .segments
+                .iter()
+                .map(|segment| 1u64 << segment.po2)
+                .sum(),
+            user_cycles: session
+                .segments
+                .iter()
+                .map(|segment| segment.cycles as u64)
+                .sum(),
+        };
+        let journal = session.journal.bytes;
+
+        let inner = match opts.receipt_kind {
+            ReceiptKind::Composite => InnerReceipt::Composite(CompositeReceipt {
+                segments,
+                assumption_receipts: Vec::new(),
+                verifier_parameters: ctx
+                    .composite_verifier_parameters()
+                    .context("composite receipt verifier parameters missing from context")?
+                    .digest(),
+            }),
+            ReceiptKind::Succinct => {
+                InnerReceipt::Succinct(self.segments_to_succinct(opts, segments)?)
+            }
+            ReceiptKind::Groth16 => {
+                let succinct = Receipt::new(
+                    InnerReceipt::Succinct(self.segments_to_succinct(opts, segments)?),
+                    journal.clone(),
+                );
+                let receipt: Asset = succinct.try_into()?;
+                let client = self.client()?;
+                self.retry(&client, "compress", |client| {
+                    client.compress(opts, receipt.clone(), AssetRequest::Inline)
+                })?
+                .inner
+            }
+        };
+
+        let receipt = Receipt::new(inner, journal);
+        if opts.prove_guest_errors {
+            receipt.verify_integrity_with_context(ctx)?;
+        } else {
+            receipt.verify_with_context(ctx, image_id)?;
+        }
+
+        Ok(ProveInfo {
+            receipt,
+            stats,
+            prover: Some(self.name.clone()),
+        })
+    }
+
+    fn compress(&self, opts: &ProverOpts, receipt: &Receipt) -> Result<Receipt> {
+        match (&receipt.inner, opts.receipt_kind) {
+            // Compression is a no-op when the requested kind is at least as large as the current.
+            (InnerReceipt::Composite(_), ReceiptKind::Composite)
+            | (InnerReceipt::Succinct(_), ReceiptKind::Composite | ReceiptKind::Succinct)
+            | (
+                InnerReceipt::Groth16(_),
+                ReceiptKind::Composite | ReceiptKind::Succinct | ReceiptKind::Groth16,
+            ) => Ok(receipt.clone()),
+            // Compression is always a no-op in dev mode
+            (InnerReceipt::Fake { .. }, _) => {
+                ensure!(
+                    is_dev_mode(),
+                    "dev mode must be enabled to compress fake receipts"
+                );
+                Ok(receipt.clone())
+            }
+            (_, _) => {
+                let receipt: Asset = receipt.clone().try_into()?;
+                let client = self.client()?;
+                self.retry(&client, "compress", |client| {
+                    client.compress(opts, receipt.clone(), AssetRequest::Inline)
+                })
+            }
+        }
+    }
+}
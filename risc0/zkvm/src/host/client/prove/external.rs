@@ -52,7 +52,8 @@ impl Prover for ExternalProver {
         let image_id = compute_image_id(elf)?;
         let client = ApiClient::new_sub_process(&self.r0vm_path)?;
         let binary = Asset::Inline(elf.to_vec().into());
-        let prove_info = client.prove(&env, opts, binary)?;
+        let mut prove_info = client.prove(&env, opts, binary)?;
+        prove_info.prover = Some(self.name.clone());
         if opts.prove_guest_errors {
             prove_info.receipt.verify_integrity_with_context(ctx)?;
         } else {
@@ -0,0 +1,332 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{rc::Rc, time::Duration};
+
+use anyhow::{anyhow, ensure, Result};
+
+use super::{Prover, ProverOpts};
+use crate::{ExecutorEnv, ProveInfo, Receipt, VerifierContext};
+
+/// Policy for retrying a failed request to a [Prover] before moving on to the next one.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// Maximum number of attempts to make with each prover, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry. The delay doubles with each further retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Make a single attempt with each prover, without retrying.
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Return [RetryPolicy] with the maximum number of attempts set.
+    pub fn with_max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Return [RetryPolicy] with the initial and maximum backoff set.
+    pub fn with_backoff(self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            initial_backoff,
+            max_backoff,
+            ..self
+        }
+    }
+
+    // Delay before the given retry, counting from zero.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts with each prover, waiting one second and then two seconds between them.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An implementation of a [Prover] that tries an ordered list of provers, falling back to the
+/// next one when a prover fails.
+///
+/// Each prover is retried according to the [RetryPolicy] before falling back. This can be used,
+/// for example, to prove locally when Bonsai is unreachable or the account is over quota. The
+/// name of the prover that produced the receipt is recorded in [ProveInfo::prover].
+///
+/// Input written to the [ExecutorEnv] is provided to every attempt. Other state, such as I/O
+/// handlers and trace callbacks, is shared between attempts, so a prover that fails part way
+/// through execution may leave a reader partially consumed.
+pub struct FallbackProver {
+    name: String,
+    provers: Vec<Rc<dyn Prover>>,
+    retry_policy: RetryPolicy,
+}
+
+impl FallbackProver {
+    /// Construct a [FallbackProver] that tries the given provers in order, using the default
+    /// [RetryPolicy].
+    pub fn new(name: &str, provers: Vec<Rc<dyn Prover>>) -> Self {
+        Self {
+            name: name.to_string(),
+            provers,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Return [FallbackProver] with the given [RetryPolicy].
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    // Run the given request against each prover in turn, retrying as per the policy, and return
+    // the first success along with the name of the prover that produced it.
+    fn try_each<T>(
+        &self,
+        mut request: impl FnMut(&dyn Prover) -> Result<T>,
+    ) -> Result<(T, String)> {
+        ensure!(
+            !self.provers.is_empty(),
+            "{}: no provers configured",
+            self.name
+        );
+
+        let mut errors = Vec::new();
+        for prover in self.provers.iter() {
+            for attempt in 0..self.retry_policy.max_attempts.max(1) {
+                if attempt > 0 {
+                    std::thread::sleep(self.retry_policy.backoff(attempt - 1));
+                }
+                match request(prover.as_ref()) {
+                    Ok(value) => return Ok((value, prover.get_name())),
+                    Err(err) => {
+                        tracing::warn!(
+                            "{}: prover {} failed on attempt {}: {err:#}",
+                            self.name,
+                            prover.get_name(),
+                            attempt + 1
+                        );
+                        errors.push(format!("{}: {err:#}", prover.get_name()));
+                    }
+                }
+            }
+        }
+        Err(anyhow!(
+            "{}: all provers failed:\n{}",
+            self.name,
+            errors.join("\n")
+        ))
+    }
+}
+
+impl Prover for FallbackProver {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn prove_with_ctx(
+        &self,
+        env: ExecutorEnv<'_>,
+        ctx: &VerifierContext,
+        elf: &[u8],
+        opts: &ProverOpts,
+    ) -> Result<ProveInfo> {
+        let (mut prove_info, name) =
+            self.try_each(|prover| prover.prove_with_ctx(env.shallow_clone(), ctx, elf, opts))?;
+        prove_info.prover = Some(name);
+        Ok(prove_info)
+    }
+
+    fn compress(&self, opts: &ProverOpts, receipt: &Receipt) -> Result<Receipt> {
+        let (receipt, _) = self.try_each(|prover| prover.compress(opts, receipt))?;
+        Ok(receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    use anyhow::{bail, Result};
+    use httpmock::prelude::*;
+    use risc0_zkvm_methods::{MULTI_TEST_ELF, MULTI_TEST_ID};
+
+    use super::{FallbackProver, RetryPolicy};
+    use crate::{
+        BonsaiProver, ExecutorEnv, FakeReceipt, InnerReceipt, ProveInfo, Prover, ProverOpts,
+        Receipt, ReceiptClaim, SessionStats, VerifierContext,
+    };
+
+    // A prover that fails a fixed number of times before producing a fake receipt.
+    struct FlakyProver {
+        name: String,
+        failures: Cell<u32>,
+        attempts: Cell<u32>,
+    }
+
+    impl FlakyProver {
+        fn new(name: &str, failures: u32) -> Rc<Self> {
+            Rc::new(Self {
+                name: name.to_string(),
+                failures: Cell::new(failures),
+                attempts: Cell::new(0),
+            })
+        }
+    }
+
+    impl Prover for FlakyProver {
+        fn get_name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn prove_with_ctx(
+            &self,
+            _env: ExecutorEnv<'_>,
+            _ctx: &VerifierContext,
+            _elf: &[u8],
+            _opts: &ProverOpts,
+        ) -> Result<ProveInfo> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                bail!("{} is unavailable", self.name);
+            }
+            let claim = ReceiptClaim::ok(MULTI_TEST_ID, Vec::new());
+            Ok(ProveInfo {
+                receipt: Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), Vec::new()),
+                stats: SessionStats {
+                    segments: 1,
+                    total_cycles: 1,
+                    user_cycles: 1,
+                },
+                prover: None,
+            })
+        }
+
+        fn compress(&self, _opts: &ProverOpts, receipt: &Receipt) -> Result<Receipt> {
+            Ok(receipt.clone())
+        }
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::default()
+            .with_max_attempts(max_attempts)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(2))
+    }
+
+    #[test]
+    fn retries_before_falling_back() {
+        let first = FlakyProver::new("first", 2);
+        let second = FlakyProver::new("second", 0);
+        let prover = FallbackProver::new("fallback", vec![first.clone(), second.clone()])
+            .with_retry_policy(fast_retries(3));
+
+        let prove_info = prover.prove(ExecutorEnv::default(), &[]).unwrap();
+        assert_eq!(prove_info.prover.as_deref(), Some("first"));
+        assert_eq!(first.attempts.get(), 3);
+        assert_eq!(second.attempts.get(), 0);
+    }
+
+    #[test]
+    fn falls_back_in_order() {
+        let first = FlakyProver::new("first", u32::MAX);
+        let second = FlakyProver::new("second", u32::MAX);
+        let third = FlakyProver::new("third", 0);
+        let prover = FallbackProver::new(
+            "fallback",
+            vec![first.clone(), second.clone(), third.clone()],
+        )
+        .with_retry_policy(fast_retries(2));
+
+        let prove_info = prover.prove(ExecutorEnv::default(), &[]).unwrap();
+        assert_eq!(prove_info.prover.as_deref(), Some("third"));
+        assert_eq!(first.attempts.get(), 2);
+        assert_eq!(second.attempts.get(), 2);
+        assert_eq!(third.attempts.get(), 1);
+    }
+
+    #[test]
+    fn reports_all_failures() {
+        let prover = FallbackProver::new(
+            "fallback",
+            vec![
+                FlakyProver::new("first", u32::MAX),
+                FlakyProver::new("second", u32::MAX),
+            ],
+        )
+        .with_retry_policy(RetryPolicy::no_retry());
+
+        let err = prover.prove(ExecutorEnv::default(), &[]).err().unwrap();
+        let msg = format!("{err:#}");
+        assert!(msg.contains("first is unavailable"), "{msg}");
+        assert!(msg.contains("second is unavailable"), "{msg}");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy =
+            RetryPolicy::default().with_backoff(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff(64), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn falls_back_from_unavailable_bonsai() {
+        // Stand-in for a Bonsai instance that is down.
+        let server = MockServer::start();
+        let unavailable = server.mock(|_, then| {
+            then.status(503).body("service unavailable");
+        });
+
+        let bonsai = BonsaiProver::from_parts(
+            "bonsai",
+            format!("http://{}", server.address()),
+            "test-key".to_string(),
+        );
+        let local = FlakyProver::new("local", 0);
+        let prover = FallbackProver::new("fallback", vec![Rc::new(bonsai), local.clone()])
+            .with_retry_policy(fast_retries(2));
+
+        let prove_info = prover
+            .prove(ExecutorEnv::default(), MULTI_TEST_ELF)
+            .unwrap();
+        assert_eq!(prove_info.prover.as_deref(), Some("local"));
+        unavailable.assert_hits(2);
+        assert_eq!(local.attempts.get(), 1);
+    }
+}
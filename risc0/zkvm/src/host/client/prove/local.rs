@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use super::{Executor, Prover, ProverOpts};
 use crate::{
@@ -43,7 +43,9 @@ impl Prover for LocalProver {
         elf: &[u8],
         opts: &ProverOpts,
     ) -> Result<ProveInfo> {
-        get_prover_server(opts)?.prove_with_ctx(env, ctx, elf)
+        let mut prove_info = get_prover_server(opts)?.prove_with_ctx(env, ctx, elf)?;
+        prove_info.prover = Some(self.name.clone());
+        Ok(prove_info)
     }
 
     fn get_name(&self) -> String {
@@ -66,10 +68,15 @@ impl Executor for LocalProver {
             });
             Ok(Box::new(NullSegmentRef))
         })?;
+        // SessionInfo only describes panics, so report any other fault as an error.
+        if let Some(fault) = session.fault {
+            bail!("{fault}");
+        }
         Ok(SessionInfo {
             segments,
             journal: session.journal.unwrap_or_default(),
             exit_code: session.exit_code,
+            panic: session.panic,
         })
     }
 }
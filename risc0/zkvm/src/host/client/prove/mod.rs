@@ -13,22 +13,26 @@
 // limitations under the License.
 
 pub(crate) mod bonsai;
+pub(crate) mod distributed;
 pub(crate) mod external;
+pub(crate) mod fallback;
 #[cfg(feature = "prove")]
 pub(crate) mod local;
 
 use std::{path::PathBuf, rc::Rc};
 
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use serde::{Deserialize, Serialize};
 
 use risc0_circuit_recursion::control_id::ALLOWED_CONTROL_IDS;
 use risc0_circuit_rv32im::control_id::SHA256_CONTROL_IDS;
 use risc0_zkp::core::digest::Digest;
 
-use self::{bonsai::BonsaiProver, external::ExternalProver};
+use self::{bonsai::BonsaiProver, external::ExternalProver, fallback::FallbackProver};
 use crate::{
-    host::prove_info::ProveInfo, is_dev_mode, ExecutorEnv, Receipt, SessionInfo, VerifierContext,
+    host::prove_info::ProveInfo, is_dev_mode, sha::Digestible, AggregationInclusionProof,
+    AggregationSet, ExecutorEnv, InnerAssumptionReceipt, Receipt, ReceiptClaim, SessionInfo,
+    SuccinctReceipt, VerifierContext,
 };
 
 /// A Prover can execute a given ELF binary and produce a
@@ -132,6 +136,50 @@ pub trait Prover {
     /// it is already succinct or Groth16 and a succinct receipt is required) this function is a
     /// no-op. As a result, it is idempotent.
     fn compress(&self, opts: &ProverOpts, receipt: &Receipt) -> Result<Receipt>;
+
+    /// Aggregate the given receipts, which may be for unrelated guests, into a single [Receipt].
+    ///
+    /// `aggregation_elf` must be a guest that reads a `Vec<ReceiptClaim>` from the input and
+    /// passes it to [env::aggregate](crate::guest::env::aggregate). Each given receipt is added as
+    /// an assumption, so the resulting receipt, compressed as requested by `opts`, is
+    /// unconditional. Its journal is the root of the [AggregationSet] of the receipt claims.
+    ///
+    /// Returns the aggregate receipt along with an [AggregationInclusionProof] for each of the
+    /// given receipts, in order, which can be used to check that receipt's claim against the
+    /// journal of the aggregate.
+    fn aggregate(
+        &self,
+        aggregation_elf: &[u8],
+        receipts: &[SuccinctReceipt<ReceiptClaim>],
+        opts: &ProverOpts,
+    ) -> Result<(Receipt, Vec<AggregationInclusionProof>)> {
+        let claims = receipts
+            .iter()
+            .map(|receipt| receipt.claim.as_value().cloned())
+            .collect::<Result<Vec<_>, _>>()
+            .context("aggregated receipt claims must not be pruned")?;
+
+        let mut env = ExecutorEnv::builder();
+        env.write(&claims)?;
+        for receipt in receipts {
+            env.add_assumption(InnerAssumptionReceipt::Succinct(
+                receipt.clone().into_unknown(),
+            ));
+        }
+        let receipt = self
+            .prove_with_opts(env.build()?, aggregation_elf, opts)?
+            .receipt;
+
+        let set = AggregationSet::new(claims.iter().map(|claim| claim.digest()).collect());
+        ensure!(
+            receipt.journal.bytes == set.root().as_bytes(),
+            "aggregation guest did not commit the root of the aggregated claims"
+        );
+        let proofs = (0..receipts.len())
+            .map(|i| set.get_proof_by_index(i as u32))
+            .collect::<Result<_>>()?;
+        Ok((receipt, proofs))
+    }
 }
 
 /// An Executor can execute a given ELF binary.
@@ -151,7 +199,9 @@ pub struct ProverOpts {
     /// When false, only prove execution sessions that end in a successful
     /// [crate::ExitCode] (i.e. `Halted(0)` or `Paused(0)`).
     /// When set to true, any completed execution session will be proven, including indicated
-    /// errors (e.g. `Halted(1)`) and sessions ending in `Fault`.
+    /// errors (e.g. `Halted(1)`) and sessions ending in `Fault`. The zkVM cannot prove that the
+    /// guest faulted, so the receipt of a session that ended in `Fault` proves the execution up
+    /// to the faulting instruction, with an exit code of `SystemSplit` and no journal.
     pub prove_guest_errors: bool,
     /// Kind of receipt to be generated by the prover.
     pub receipt_kind: ReceiptKind,
@@ -161,10 +211,20 @@ pub struct ProverOpts {
     /// programs that are allowed to run and is a key field in the
     /// [SuccinctReceiptVerifierParameters][crate::SuccinctReceiptVerifierParameters].
     pub control_ids: Vec<Digest>,
+    /// Maximum size of the buffers that the prover keeps in memory, in bytes,
+    /// or `None` to keep all of them in memory.
+    ///
+    /// When proving on the CPU, large buffers allocated once the budget is used
+    /// up are stored in temporary files instead, in the directory given by
+    /// `TMPDIR` on Unix. This is slower, but lets machines prove segments that
+    /// need more memory than they have. The budget applies to both segment and
+    /// recursion proving. GPU provers ignore the budget.
+    #[serde(default)]
+    pub memory_budget: Option<usize>,
 }
 
 /// An enumeration of receipt kinds that can be requested to be generated.
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ReceiptKind {
     /// Request that a [CompositeReceipt][crate::CompositeReceipt] be generated.
@@ -194,6 +254,7 @@ impl Default for ProverOpts {
             prove_guest_errors: false,
             receipt_kind: ReceiptKind::Composite,
             control_ids: ALLOWED_CONTROL_IDS.to_vec(),
+            memory_budget: None,
         }
     }
 }
@@ -207,6 +268,7 @@ impl ProverOpts {
             prove_guest_errors: false,
             receipt_kind: ReceiptKind::Composite,
             control_ids: SHA256_CONTROL_IDS.to_vec(),
+            memory_budget: None,
         }
     }
 
@@ -218,6 +280,7 @@ impl ProverOpts {
             prove_guest_errors: false,
             receipt_kind: ReceiptKind::Composite,
             control_ids: ALLOWED_CONTROL_IDS.to_vec(),
+            memory_budget: None,
         }
     }
 
@@ -229,6 +292,7 @@ impl ProverOpts {
             prove_guest_errors: false,
             receipt_kind: ReceiptKind::Succinct,
             control_ids: ALLOWED_CONTROL_IDS.to_vec(),
+            memory_budget: None,
         }
     }
 
@@ -242,6 +306,7 @@ impl ProverOpts {
             prove_guest_errors: false,
             receipt_kind: ReceiptKind::Groth16,
             control_ids: ALLOWED_CONTROL_IDS.to_vec(),
+            memory_budget: None,
         }
     }
 
@@ -277,6 +342,15 @@ impl ProverOpts {
         }
     }
 
+    /// Return [ProverOpts] with the memory_budget set to the given number of
+    /// bytes.
+    pub fn with_memory_budget(self, memory_budget: usize) -> Self {
+        Self {
+            memory_budget: Some(memory_budget),
+            ..self
+        }
+    }
+
     #[cfg(feature = "prove")]
     pub(crate) fn hash_suite(
         &self,
@@ -296,28 +370,45 @@ impl ProverOpts {
 /// * `ipc`: [ExternalProver] to prove using an `r0vm` sub-process. Note: `r0vm`
 ///   must be installed. To specify the path to `r0vm`, use `RISC0_SERVER_PATH`.
 ///
-/// If `RISC0_PROVER` is not specified, the following rules are used to select a
+/// `RISC0_PROVER` may also be a comma-separated list of the above (e.g.
+/// `bonsai,local`), in which case a [FallbackProver] will try each in order.
+/// Empty or unsupported entries are skipped with a warning.
+///
+/// If `RISC0_PROVER` is not specified, or names no supported prover, the following rules are used to select a
 /// [Prover]:
 /// * [BonsaiProver] if the `BONSAI_API_URL` and `BONSAI_API_KEY` environment
-///   variables are set unless `RISC0_DEV_MODE` is enabled.
+///   variables are set unless `RISC0_DEV_MODE` is enabled. If the `prove`
+///   feature flag is enabled, a [FallbackProver] is used to fall back to
+///   LocalProver when Bonsai fails.
 /// * LocalProver if the `prove` feature flag is enabled.
 /// * [ExternalProver] otherwise.
 pub fn default_prover() -> Rc<dyn Prover> {
     let explicit = std::env::var("RISC0_PROVER").unwrap_or_default();
     if !explicit.is_empty() {
-        return match explicit.to_lowercase().as_str() {
-            "bonsai" => Rc::new(BonsaiProver::new("bonsai")),
-            "ipc" => Rc::new(ExternalProver::new("ipc", get_r0vm_path())),
-            #[cfg(feature = "prove")]
-            "local" => Rc::new(self::local::LocalProver::new("local")),
-            _ => unimplemented!("Unsupported prover: {explicit}"),
-        };
+        let mut provers: Vec<_> = explicit
+            .split(',')
+            .filter_map(|name| prover_from_name(name.trim()))
+            .collect();
+        match provers.len() {
+            0 => tracing::warn!("RISC0_PROVER does not name a supported prover: {explicit:?}"),
+            1 => return provers.remove(0),
+            _ => return Rc::new(FallbackProver::new("fallback", provers)),
+        }
     }
 
     if !is_dev_mode()
         && std::env::var("BONSAI_API_URL").is_ok()
         && std::env::var("BONSAI_API_KEY").is_ok()
     {
+        #[cfg(feature = "prove")]
+        return Rc::new(FallbackProver::new(
+            "fallback",
+            vec![
+                Rc::new(BonsaiProver::new("bonsai")),
+                Rc::new(self::local::LocalProver::new("local")),
+            ],
+        ));
+        #[cfg(not(feature = "prove"))]
         return Rc::new(BonsaiProver::new("bonsai"));
     }
 
@@ -329,6 +420,20 @@ pub fn default_prover() -> Rc<dyn Prover> {
     Rc::new(ExternalProver::new("ipc", get_r0vm_path()))
 }
 
+fn prover_from_name(name: &str) -> Option<Rc<dyn Prover>> {
+    match name.to_lowercase().as_str() {
+        "bonsai" => Some(Rc::new(BonsaiProver::new("bonsai"))),
+        "ipc" => Some(Rc::new(ExternalProver::new("ipc", get_r0vm_path()))),
+        #[cfg(feature = "prove")]
+        "local" => Some(Rc::new(self::local::LocalProver::new("local"))),
+        "" => None,
+        _ => {
+            tracing::warn!("Skipping unsupported prover in RISC0_PROVER: {name}");
+            None
+        }
+    }
+}
+
 /// Return a default [Executor] based on environment variables and feature
 /// flags.
 ///
@@ -368,3 +473,15 @@ pub(crate) fn get_r0vm_path() -> PathBuf {
         .unwrap_or("r0vm".to_string())
         .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::prover_from_name;
+
+    #[test]
+    fn unsupported_prover_names_are_skipped() {
+        assert!(prover_from_name("").is_none());
+        assert!(prover_from_name("nope").is_none());
+        assert_eq!(prover_from_name("IPC").unwrap().get_name(), "ipc");
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only virtual filesystems that can be provided to the guest.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use risc0_zkvm_platform::syscall::{
+    fs,
+    nr::{SYS_FS_CLOSE, SYS_FS_OPEN, SYS_FS_READ, SYS_FS_STAT},
+};
+
+use super::slice_io::SliceIo;
+
+/// The kind and size of a file or directory in a [VirtualFs].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// True if the path names a directory.
+    pub is_dir: bool,
+
+    /// The length of the file in bytes, or zero for a directory.
+    pub len: u64,
+}
+
+impl FileMetadata {
+    fn file(len: u64) -> Self {
+        Self { is_dir: false, len }
+    }
+
+    fn dir() -> Self {
+        Self {
+            is_dir: true,
+            len: 0,
+        }
+    }
+}
+
+/// A read-only filesystem that the guest can access with `risc0_zkvm::guest::fs`.
+///
+/// Paths given to a [VirtualFs] are absolute and normalized, with no `.` or `..` components.
+pub trait VirtualFs {
+    /// Returns the metadata for the file or directory at the given path.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Returns the contents of the file at the given path.
+    fn read(&self, path: &Path) -> io::Result<Bytes>;
+}
+
+/// A [VirtualFs] backed by an in-memory map from paths to file contents.
+///
+/// Directories are implied by the paths of the files within them.
+#[derive(Clone, Default)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, Bytes>,
+}
+
+impl MemoryFs {
+    /// Construct an empty [MemoryFs].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file with the given contents, replacing any existing file at the same path.
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, contents: impl Into<Bytes>) -> &mut Self {
+        let path = normalize(&path.as_ref().to_string_lossy());
+        self.files.insert(path, contents.into());
+        self
+    }
+}
+
+impl VirtualFs for MemoryFs {
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        if let Some(contents) = self.files.get(path) {
+            return Ok(FileMetadata::file(contents.len() as u64));
+        }
+        let is_dir = self
+            .files
+            .range(path.to_path_buf()..)
+            .next()
+            .is_some_and(|(file, _)| file.starts_with(path));
+        if is_dir || path == Path::new("/") {
+            Ok(FileMetadata::dir())
+        } else {
+            Err(io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Bytes> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+/// A [VirtualFs] that exposes a directory on the host as the root of the guest's filesystem.
+///
+/// Paths are resolved within the directory, so the guest cannot name files outside of it. Symbolic
+/// links within the directory are followed only when their target is also within it; any other
+/// link is reported to the guest as an invalid path.
+pub struct DirectoryFs {
+    root: PathBuf,
+}
+
+impl DirectoryFs {
+    /// Construct a [DirectoryFs] rooted at the given directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn host_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let root = self.root.canonicalize()?;
+        let resolved = root
+            .join(path.strip_prefix("/").unwrap_or(path))
+            .canonicalize()?;
+        if !resolved.starts_with(&root) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} resolves outside of {}", path.display(), root.display()),
+            ));
+        }
+        Ok(resolved)
+    }
+}
+
+impl VirtualFs for DirectoryFs {
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = std::fs::metadata(self.host_path(path)?)?;
+        Ok(if metadata.is_dir() {
+            FileMetadata::dir()
+        } else {
+            FileMetadata::file(metadata.len())
+        })
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Bytes> {
+        Ok(std::fs::read(self.host_path(path)?)?.into())
+    }
+}
+
+/// A [VirtualFs] containing the regular files in a tar archive.
+///
+/// The archive is read into memory when the [TarFs] is constructed.
+#[derive(Clone)]
+pub struct TarFs {
+    files: MemoryFs,
+}
+
+impl TarFs {
+    /// Construct a [TarFs] from a reader of a tar archive.
+    pub fn new(reader: impl Read) -> Result<Self> {
+        let mut files = MemoryFs::new();
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            files.insert(path, contents);
+        }
+        Ok(Self { files })
+    }
+}
+
+impl VirtualFs for TarFs {
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.files.metadata(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Bytes> {
+        self.files.read(path)
+    }
+}
+
+// Resolve a path from the guest to an absolute path with no `.` or `..` components. Relative paths
+// are resolved from the root, and `..` at the root refers to the root, as in POSIX.
+fn normalize(path: &str) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    normalized
+}
+
+/// Serves the virtual filesystem syscalls from a stack of [VirtualFs] layers.
+///
+/// Each path is looked up in the most recently added layer first, so later layers overlay earlier
+/// ones. Files are read in full when they are opened.
+#[derive(Default)]
+pub(crate) struct VfsHandler<'a> {
+    layers: Vec<Box<dyn VirtualFs + 'a>>,
+    open_files: Vec<Option<Bytes>>,
+}
+
+impl<'a> VfsHandler<'a> {
+    pub(crate) fn mount(&mut self, fs: impl VirtualFs + 'a) {
+        self.layers.push(Box::new(fs));
+    }
+
+    fn lookup<T>(&self, f: impl Fn(&dyn VirtualFs) -> io::Result<T>) -> io::Result<T> {
+        for layer in self.layers.iter().rev() {
+            match f(layer.as_ref()) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                result => return result,
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    fn open(&mut self, path: &Path) -> [u32; 2] {
+        let contents = match self.lookup(|fs| fs.metadata(path)) {
+            Ok(metadata) if metadata.is_dir => return [fs::IS_DIR, 0],
+            Ok(_) => self.lookup(|fs| fs.read(path)),
+            Err(err) => Err(err),
+        };
+        match contents {
+            Ok(contents) => {
+                // Reuse the slot of a closed file, so that a guest which opens and closes files
+                // in a loop does not grow the table.
+                let fd = match self.open_files.iter().position(Option::is_none) {
+                    Some(fd) => {
+                        self.open_files[fd] = Some(contents);
+                        fd
+                    }
+                    None => {
+                        self.open_files.push(Some(contents));
+                        self.open_files.len() - 1
+                    }
+                };
+                [fs::OK, fd as u32]
+            }
+            Err(err) => [status(&err), 0],
+        }
+    }
+
+    fn stat(&self, path: &Path) -> [u32; 1 + fs::STAT_WORDS] {
+        match self.lookup(|fs| fs.metadata(path)) {
+            Ok(metadata) => {
+                let kind = if metadata.is_dir {
+                    fs::KIND_DIR
+                } else {
+                    fs::KIND_FILE
+                };
+                [
+                    fs::OK,
+                    kind,
+                    metadata.len as u32,
+                    (metadata.len >> 32) as u32,
+                ]
+            }
+            Err(err) => [status(&err), 0, 0, 0],
+        }
+    }
+
+    fn file(&self, fd: u32) -> Result<&Bytes> {
+        match self.open_files.get(fd as usize) {
+            Some(Some(contents)) => Ok(contents),
+            _ => bail!("invalid file descriptor: {fd}"),
+        }
+    }
+}
+
+fn status(err: &io::Error) -> u32 {
+    match err.kind() {
+        io::ErrorKind::NotFound => fs::NOT_FOUND,
+        _ => fs::INVALID,
+    }
+}
+
+fn words_to_bytes(words: &[u32]) -> Bytes {
+    Bytes::copy_from_slice(bytemuck::cast_slice(words))
+}
+
+impl<'a> SliceIo for VfsHandler<'a> {
+    fn handle_io(&mut self, syscall: &str, from_guest: Bytes) -> Result<Bytes> {
+        if syscall == SYS_FS_OPEN.as_str() {
+            let response = match std::str::from_utf8(&from_guest) {
+                Ok(path) => self.open(&normalize(path)),
+                Err(_) => [fs::INVALID, 0],
+            };
+            Ok(words_to_bytes(&response))
+        } else if syscall == SYS_FS_STAT.as_str() {
+            let response = match std::str::from_utf8(&from_guest) {
+                Ok(path) => self.stat(&normalize(path)),
+                Err(_) => [fs::INVALID, 0, 0, 0],
+            };
+            Ok(words_to_bytes(&response))
+        } else if syscall == SYS_FS_READ.as_str() {
+            let request: &[u32] = bytemuck::try_cast_slice(&from_guest)
+                .map_err(|err| anyhow::anyhow!("invalid SYS_FS_READ request: {err}"))?;
+            let &[fd, offset_lo, offset_hi, nbytes] = request else {
+                bail!("invalid SYS_FS_READ request length: {}", request.len());
+            };
+            let contents = self.file(fd)?;
+            let offset = ((offset_hi as u64) << 32) | offset_lo as u64;
+            let start = offset.min(contents.len() as u64) as usize;
+            let end = start + (nbytes as usize).min(contents.len() - start);
+            Ok(contents.slice(start..end))
+        } else if syscall == SYS_FS_CLOSE.as_str() {
+            let request: &[u32] = bytemuck::try_cast_slice(&from_guest)
+                .map_err(|err| anyhow::anyhow!("invalid SYS_FS_CLOSE request: {err}"))?;
+            let &[fd] = request else {
+                bail!("invalid SYS_FS_CLOSE request length: {}", request.len());
+            };
+            self.file(fd)?;
+            self.open_files[fd as usize] = None;
+            Ok(Bytes::new())
+        } else {
+            bail!("unknown filesystem syscall: {syscall}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use risc0_zkvm_platform::syscall::SyscallName;
+
+    use super::*;
+
+    fn call(handler: &mut VfsHandler, syscall: SyscallName, request: &[u8]) -> Vec<u32> {
+        let response = handler
+            .handle_io(syscall.as_str(), Bytes::copy_from_slice(request))
+            .unwrap();
+        bytemuck::pod_collect_to_vec(&response)
+    }
+
+    fn read(handler: &mut VfsHandler, fd: u32, offset: u32, nbytes: u32) -> Bytes {
+        let request = [fd, offset, 0, nbytes];
+        handler
+            .handle_io(
+                SYS_FS_READ.as_str(),
+                Bytes::copy_from_slice(bytemuck::cast_slice(&request)),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize("a/b"), Path::new("/a/b"));
+        assert_eq!(normalize("/a/./b/"), Path::new("/a/b"));
+        assert_eq!(normalize("/a/../../b"), Path::new("/b"));
+        assert_eq!(normalize(".."), Path::new("/"));
+    }
+
+    #[test]
+    fn memory_fs() {
+        let mut fs = MemoryFs::new();
+        fs.insert("dir/file", "contents");
+        assert_eq!(
+            fs.metadata(Path::new("/dir/file")).unwrap(),
+            FileMetadata::file(8)
+        );
+        assert_eq!(fs.metadata(Path::new("/dir")).unwrap(), FileMetadata::dir());
+        assert_eq!(fs.metadata(Path::new("/")).unwrap(), FileMetadata::dir());
+        assert_eq!(
+            fs.metadata(Path::new("/di")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(fs.read(Path::new("/dir/file")).unwrap(), "contents");
+    }
+
+    #[test]
+    fn tar_fs() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "etc/hello.txt", b"hello".as_slice())
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let fs = TarFs::new(archive.as_slice()).unwrap();
+        assert_eq!(fs.read(Path::new("/etc/hello.txt")).unwrap(), "hello");
+        assert!(fs.metadata(Path::new("/etc")).unwrap().is_dir);
+    }
+
+    #[test]
+    fn handler() {
+        let mut lower = MemoryFs::new();
+        lower.insert("/a", "lower a").insert("/b", "lower b");
+        let mut upper = MemoryFs::new();
+        upper.insert("/a", "upper a");
+        let mut handler = VfsHandler::default();
+        handler.mount(lower);
+        handler.mount(upper);
+
+        // Later layers take precedence, and earlier layers fill in the rest.
+        assert_eq!(call(&mut handler, SYS_FS_OPEN, b"/a"), [fs::OK, 0]);
+        assert_eq!(call(&mut handler, SYS_FS_OPEN, b"b"), [fs::OK, 1]);
+        assert_eq!(read(&mut handler, 0, 0, 100), "upper a");
+        assert_eq!(read(&mut handler, 1, 6, 100), "b");
+        assert_eq!(read(&mut handler, 1, 100, 100), "");
+
+        assert_eq!(
+            call(&mut handler, SYS_FS_STAT, b"/b"),
+            [fs::OK, fs::KIND_FILE, 7, 0]
+        );
+        assert_eq!(
+            call(&mut handler, SYS_FS_STAT, b"/c"),
+            [fs::NOT_FOUND, 0, 0, 0]
+        );
+        assert_eq!(call(&mut handler, SYS_FS_OPEN, b"/"), [fs::IS_DIR, 0]);
+
+        assert!(call(&mut handler, SYS_FS_CLOSE, bytemuck::cast_slice(&[0u32])).is_empty());
+        assert!(handler
+            .handle_io(
+                SYS_FS_READ.as_str(),
+                Bytes::copy_from_slice(bytemuck::cast_slice(&[0u32, 0, 0, 1])),
+            )
+            .is_err());
+
+        // The descriptor of a closed file is reused.
+        assert_eq!(call(&mut handler, SYS_FS_OPEN, b"/b"), [fs::OK, 0]);
+        assert_eq!(call(&mut handler, SYS_FS_OPEN, b"/a"), [fs::OK, 2]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_fs_stays_within_root() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "secret").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("file"), "file").unwrap();
+        std::os::unix::fs::symlink(root.path().join("file"), root.path().join("inside")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), root.path().join("outside"))
+            .unwrap();
+
+        let fs = DirectoryFs::new(root.path());
+        assert_eq!(fs.read(Path::new("/file")).unwrap(), "file");
+        assert_eq!(fs.read(Path::new("/inside")).unwrap(), "file");
+        assert_eq!(
+            fs.read(Path::new("/outside")).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        let mut handler = VfsHandler::default();
+        handler.mount(fs);
+        assert_eq!(
+            call(&mut handler, SYS_FS_OPEN, b"/outside"),
+            [fs::INVALID, 0]
+        );
+        assert_eq!(
+            call(&mut handler, SYS_FS_STAT, b"/outside"),
+            [fs::INVALID, 0, 0, 0]
+        );
+    }
+}
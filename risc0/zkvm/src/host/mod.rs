@@ -16,6 +16,7 @@
 pub(crate) mod api;
 #[cfg(feature = "client")]
 pub(crate) mod client;
+pub(crate) mod panic_info;
 #[cfg(any(feature = "client", feature = "prove"))]
 mod protos;
 pub(crate) mod prove_info;
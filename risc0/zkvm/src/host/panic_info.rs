@@ -0,0 +1,165 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// A panic in the guest, as reported by the guest's panic handler.
+///
+/// When the guest panics, execution ends with [crate::ExitCode::Fault] and the panic is recorded
+/// in the session, so that the host can report it without matching on the text of an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GuestPanic {
+    /// The panic message.
+    pub message: String,
+
+    /// The source file in which the guest panicked, if known.
+    pub file: Option<String>,
+
+    /// The line at which the guest panicked, if known.
+    pub line: Option<u32>,
+
+    /// The call stack of the guest when it panicked, starting with the innermost frame.
+    ///
+    /// The stack is recovered by walking frame pointers, which `risc0-build` only keeps for guests
+    /// built with `GuestOptions::frame_pointers`; otherwise only the innermost frame is reported
+    /// reliably. Frames are only symbolized if the guest was executed from an ELF binary.
+    pub backtrace: Vec<StackFrame>,
+}
+
+/// A frame of the call stack of a guest, as symbolized from the debug information and symbol
+/// table of the guest's ELF binary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StackFrame {
+    /// The address of the current instruction in the frame: the faulting instruction in the
+    /// innermost frame, and the call instruction in the others.
+    pub pc: u32,
+
+    /// The name of the function, if known.
+    pub function: Option<String>,
+
+    /// The source file and line of the instruction, if known.
+    pub location: Option<(String, u32)>,
+}
+
+impl GuestPanic {
+    /// Parse the message sent by the guest's panic handler, which is formatted as
+    /// `panicked at {file}:{line}:{column}:\n{message}` by Rust.
+    #[cfg(feature = "prove")]
+    pub(crate) fn from_message(msg: &str) -> Self {
+        let parsed = msg
+            .strip_prefix("panicked at ")
+            .and_then(|msg| msg.split_once(":\n"))
+            .and_then(|(location, message)| {
+                let mut parts = location.rsplitn(3, ':');
+                let _column = parts.next()?.parse::<u32>().ok()?;
+                let line = parts.next()?.parse().ok()?;
+                let file = parts.next()?;
+                Some((message, file, line))
+            });
+        match parsed {
+            Some((message, file, line)) => Self {
+                message: message.into(),
+                file: Some(file.into()),
+                line: Some(line),
+                backtrace: Vec::new(),
+            },
+            None => Self {
+                message: msg.into(),
+                file: None,
+                line: None,
+                backtrace: Vec::new(),
+            },
+        }
+    }
+
+    #[cfg(any(feature = "client", feature = "prove"))]
+    pub(crate) fn new(
+        message: String,
+        file: Option<String>,
+        line: Option<u32>,
+        backtrace: Vec<StackFrame>,
+    ) -> Self {
+        Self {
+            message,
+            file,
+            line,
+            backtrace,
+        }
+    }
+}
+
+impl StackFrame {
+    #[cfg(any(feature = "client", feature = "prove"))]
+    pub(crate) fn new(pc: u32, function: Option<String>, location: Option<(String, u32)>) -> Self {
+        Self {
+            pc,
+            function,
+            location,
+        }
+    }
+}
+
+impl fmt::Display for GuestPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "guest panicked at {file}:{line}:")?,
+            _ => write!(f, "guest panicked:")?,
+        }
+        write!(f, "\n{}", self.message)?;
+        if !self.backtrace.is_empty() {
+            writeln!(f)?;
+            fmt_backtrace(f, &self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GuestPanic {}
+
+/// Writes a backtrace in the same format as the backtrace of a Rust panic.
+pub(crate) fn fmt_backtrace(f: &mut fmt::Formatter<'_>, backtrace: &[StackFrame]) -> fmt::Result {
+    write!(f, "stack backtrace:")?;
+    for (idx, frame) in backtrace.iter().enumerate() {
+        match &frame.function {
+            Some(function) => write!(f, "\n{idx:>4}: {function}")?,
+            None => write!(f, "\n{idx:>4}: 0x{:08x} - <unknown>", frame.pc)?,
+        }
+        if let Some((file, line)) = &frame.location {
+            write!(f, "\n             at {file}:{line}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "prove"))]
+mod tests {
+    use super::GuestPanic;
+
+    #[test]
+    fn from_message() {
+        let panic = GuestPanic::from_message("panicked at src/main.rs:12:5:\nboom: a:b\nc");
+        assert_eq!(panic.message, "boom: a:b\nc");
+        assert_eq!(panic.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(panic.line, Some(12));
+
+        let panic = GuestPanic::from_message("unexpected");
+        assert_eq!(panic.message, "unexpected");
+        assert_eq!(panic.file, None);
+        assert_eq!(panic.line, None);
+    }
+}
@@ -300,6 +300,22 @@ pub struct ExecutorEnv {
     pub assumptions: ::prost::alloc::vec::Vec<AssumptionReceipt>,
     #[prost(string, tag = "12")]
     pub segment_path: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "13")]
+    pub resource_policy: ::core::option::Option<ResourcePolicy>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResourcePolicy {
+    #[prost(uint64, optional, tag = "1")]
+    pub wall_clock_ms: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    pub pages: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "3")]
+    pub journal_bytes: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "4")]
+    pub input_bytes: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "5")]
+    pub verify_calls: ::core::option::Option<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -331,6 +347,8 @@ pub struct ProverOpts {
     pub receipt_kind: i32,
     #[prost(message, repeated, tag = "4")]
     pub control_ids: ::prost::alloc::vec::Vec<super::base::Digest>,
+    #[prost(uint64, optional, tag = "5")]
+    pub memory_budget: ::core::option::Option<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -341,6 +359,32 @@ pub struct SessionInfo {
     pub journal: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, optional, tag = "3")]
     pub exit_code: ::core::option::Option<super::base::ExitCode>,
+    #[prost(message, optional, tag = "4")]
+    pub panic: ::core::option::Option<GuestPanic>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GuestPanic {
+    #[prost(string, tag = "1")]
+    pub message: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "2")]
+    pub file: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint32, optional, tag = "3")]
+    pub line: ::core::option::Option<u32>,
+    #[prost(message, repeated, tag = "4")]
+    pub backtrace: ::prost::alloc::vec::Vec<StackFrame>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StackFrame {
+    #[prost(uint32, tag = "1")]
+    pub pc: u32,
+    #[prost(string, optional, tag = "2")]
+    pub function: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub file: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint32, optional, tag = "4")]
+    pub line: ::core::option::Option<u32>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -487,7 +531,7 @@ pub mod posix_cmd {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct TraceEvent {
-    #[prost(oneof = "trace_event::Kind", tags = "1, 2, 3")]
+    #[prost(oneof = "trace_event::Kind", tags = "1, 2, 3, 4")]
     pub kind: ::core::option::Option<trace_event::Kind>,
 }
 /// Nested message and enum types in `TraceEvent`.
@@ -521,6 +565,12 @@ pub mod trace_event {
         pub region: ::prost::alloc::vec::Vec<u8>,
     }
     #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Paging {
+        #[prost(uint64, tag = "1")]
+        pub cycles: u64,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Kind {
         #[prost(message, tag = "1")]
@@ -529,6 +579,8 @@ pub mod trace_event {
         RegisterSet(RegisterSet),
         #[prost(message, tag = "3")]
         MemorySet(MemorySet),
+        #[prost(message, tag = "4")]
+        Paging(Paging),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
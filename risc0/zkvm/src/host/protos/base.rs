@@ -28,7 +28,7 @@ pub struct Digest {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExitCode {
-    #[prost(oneof = "exit_code::Kind", tags = "1, 2, 3, 4")]
+    #[prost(oneof = "exit_code::Kind", tags = "1, 2, 3, 4, 6")]
     pub kind: ::core::option::Option<exit_code::Kind>,
 }
 /// Nested message and enum types in `ExitCode`.
@@ -44,5 +44,7 @@ pub mod exit_code {
         SystemSplit(()),
         #[prost(message, tag = "4")]
         SessionLimit(()),
+        #[prost(message, tag = "6")]
+        Fault(()),
     }
 }
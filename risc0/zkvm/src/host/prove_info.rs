@@ -14,14 +14,30 @@
 
 //! Struct containing information about a prover's execution including the receipt.
 
+use alloc::string::String;
+
 use crate::Receipt;
 
 /// Information returned by the prover including receipt as well as other information useful for debugging
+#[non_exhaustive]
 pub struct ProveInfo {
     /// receipt from the computation
     pub receipt: Receipt,
     /// stats about cycle counts of the execution
     pub stats: SessionStats,
+    /// name of the [Prover](crate::Prover) that produced the receipt, if known
+    pub prover: Option<String>,
+}
+
+impl ProveInfo {
+    /// Construct a [ProveInfo] for a receipt produced by an unnamed prover.
+    pub fn new(receipt: Receipt, stats: SessionStats) -> Self {
+        Self {
+            receipt,
+            stats,
+            prover: None,
+        }
+    }
 }
 
 /// Struct containing information about a prover's cycle count after running the guest program
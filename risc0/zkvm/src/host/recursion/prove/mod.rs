@@ -293,7 +293,11 @@ pub struct Prover {
 impl Prover {
     fn new(program: Program, control_id: Digest, opts: ProverOpts) -> Self {
         Self {
-            prover: risc0_circuit_recursion::prove::Prover::new(program, &opts.hashfn),
+            prover: risc0_circuit_recursion::prove::Prover::new_with_memory_budget(
+                program,
+                &opts.hashfn,
+                opts.memory_budget,
+            ),
             control_id,
         }
     }
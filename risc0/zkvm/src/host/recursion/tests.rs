@@ -12,14 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::VecDeque;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::VecDeque,
+};
 
 use risc0_binfmt::tagged_struct;
-use risc0_circuit_recursion::CircuitImpl;
+use risc0_circuit_recursion::{CircuitImpl, CIRCUIT};
 use risc0_zkp::{
     adapter::{CircuitInfo, PROOF_SYSTEM_INFO},
-    core::digest::{digest, Digest, DIGEST_WORDS},
+    core::{
+        digest::{digest, Digest, DIGEST_WORDS},
+        hash::poseidon2::{Poseidon2HashFn, Poseidon2Rng},
+    },
     field::baby_bear::BabyBearElem,
+    verify::{VerificationError, VerifierScratch},
 };
 use risc0_zkvm_methods::{multi_test::MultiTestSpec, MULTI_TEST_ELF, MULTI_TEST_ID};
 use test_log::test;
@@ -30,11 +38,39 @@ use crate::{
     receipt_claim::{MaybePruned, Unknown},
     sha::{self, Digestible},
     ExecutorEnv, ExecutorImpl, InnerReceipt, ProverOpts, Receipt, SegmentReceipt, Session,
-    SuccinctReceipt, SuccinctReceiptVerifierParameters, VerifierContext, ALLOWED_CONTROL_ROOT,
-    RECURSION_PO2,
+    SuccinctReceipt, SuccinctReceiptVerifierParameters, SuccinctReceiptVerifierScratch,
+    VerifierContext, ALLOWED_CONTROL_ROOT, RECURSION_PO2,
 };
 use risc0_circuit_recursion::prove::{poseidon254_hal_pair, poseidon2_hal_pair};
 
+// Counts the allocations made by each thread, so that tests can check that a code path does not
+// allocate.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
 // Failure on older mac minis in the lab with Intel UHD 630 graphics:
 // (signal: 11, SIGSEGV: invalid memory reference)
 #[cfg_attr(
@@ -145,6 +181,48 @@ fn test_recursion_poseidon2() {
     succinct_receipt
         .verify_integrity_with_context(&ctx)
         .unwrap();
+
+    // The allocation-free verifier must agree with the default one, without allocating.
+    let mut scratch = SuccinctReceiptVerifierScratch::new();
+    let (result, allocations) = count_allocations(|| {
+        succinct_receipt.verify_integrity_with_scratch(
+            ctx.succinct_verifier_parameters.as_ref().unwrap(),
+            &mut scratch,
+        )
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(allocations, 0);
+
+    // Scratch memory that is too small is reported as an error.
+    let result = risc0_zkp::verify::verify_with_scratch(
+        &CIRCUIT,
+        &Poseidon2HashFn,
+        Poseidon2Rng::new(),
+        &succinct_receipt.seal,
+        |_, _| Ok(()),
+        VerifierScratch::new(&mut [], &mut [], &mut []),
+    );
+    assert_eq!(result, Err(VerificationError::InsufficientScratch));
+
+    // Batch verification reports a result for each receipt, in order.
+    let mut bad_claim = succinct_receipt.clone();
+    bad_claim.claim = MaybePruned::Pruned(digest1);
+    let mut bad_hashfn = succinct_receipt.clone();
+    bad_hashfn.hashfn = "sha-256".to_string();
+    let results =
+        SuccinctReceipt::verify_batch(&[bad_claim, succinct_receipt.clone(), bad_hashfn], &ctx);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_err());
+    assert_eq!(results[1], Ok(()));
+    assert_eq!(results[2], Err(VerificationError::InvalidHashSuite));
+
+    // Custom hash suites registered in the context are used, as when verifying one receipt.
+    let mut ctx = ctx;
+    ctx.suites.insert("custom".to_string(), suite.clone());
+    let mut custom = succinct_receipt.clone();
+    custom.hashfn = "custom".to_string();
+    custom.verify_integrity_with_context(&ctx).unwrap();
+    assert_eq!(SuccinctReceipt::verify_batch(&[custom], &ctx), [Ok(())]);
 }
 
 #[cfg_attr(
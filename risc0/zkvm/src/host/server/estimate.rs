@@ -0,0 +1,298 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimates of the cost of proving a [Session] on a given machine.
+//!
+//! Proving cost is dominated by the segments, whose cost depends only on
+//! their po2, and by the fixed-size recursion programs that compress segment
+//! receipts into a succinct receipt. A [HardwareProfile] records how long each
+//! of these steps takes on a machine, which is enough to predict the cost of
+//! proving any session there without proving it.
+
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{AssumptionReceipt, InnerAssumptionReceipt, ReceiptKind, Session};
+
+/// The measured cost of one proving step on a machine.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepProfile {
+    /// Wall-clock time taken by the step.
+    pub time: Duration,
+
+    /// Peak memory allocated by the prover during the step, in bytes.
+    pub peak_memory: u64,
+
+    /// Size of the seal produced by the step, in bytes.
+    pub seal_bytes: u64,
+}
+
+/// The cost of each proving step on a machine, used by [Session::estimate].
+///
+/// Profiles are measured by the `calibrate` benchmark of this crate, which
+/// writes the profile of the machine it runs on to
+/// `target/hotbench/calibrate/profile.json`:
+///
+/// ```bash
+/// cargo bench -p risc0-zkvm --bench calibrate
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HardwareProfile {
+    /// A description of the machine that the profile was measured on.
+    pub name: String,
+
+    /// Cost of proving a segment, keyed by the po2 of the segment.
+    pub segments: BTreeMap<usize, StepProfile>,
+
+    /// Cost of lifting a segment receipt into a succinct receipt.
+    pub lift: StepProfile,
+
+    /// Cost of joining two succinct receipts. Resolving an assumption runs a
+    /// recursion program of the same size, and is assumed to cost the same.
+    pub join: StepProfile,
+
+    /// Cost of converting a succinct receipt to one using Poseidon254.
+    pub identity_p254: StepProfile,
+
+    /// Cost of proving the Groth16 seal, or `None` if the profile was
+    /// measured on a machine that cannot run the Groth16 prover.
+    pub stark_to_snark: Option<StepProfile>,
+}
+
+/// The predicted cost of proving a [Session] into a receipt of a given kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// The kind of receipt that is proven.
+    pub receipt_kind: ReceiptKind,
+
+    /// Wall-clock time of proving the session, including all recursion steps.
+    pub proving_time: Duration,
+
+    /// Peak memory allocated by the prover, in bytes.
+    pub peak_memory: u64,
+
+    /// Size of the seals and journal of the receipt, in bytes.
+    pub receipt_size: u64,
+}
+
+impl HardwareProfile {
+    /// Read a profile from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read(path)
+            .with_context(|| format!("failed to read hardware profile {}", path.display()))?;
+        serde_json::from_slice(&json)
+            .with_context(|| format!("failed to parse hardware profile {}", path.display()))
+    }
+
+    /// Write this profile to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the cost of proving a segment of the given po2.
+    ///
+    /// Sizes that were not measured are scaled from the closest measured po2:
+    /// time and memory are linear in the number of cycles, while the seal
+    /// grows with the depth of its Merkle trees, which is linear in the po2.
+    pub fn segment(&self, po2: usize) -> Result<StepProfile> {
+        let Some((&measured, step)) = self
+            .segments
+            .iter()
+            .min_by_key(|(measured, _)| measured.abs_diff(po2))
+        else {
+            bail!("hardware profile has no segment measurements");
+        };
+        let scale = |value: f64| {
+            if po2 >= measured {
+                value * (1u64 << (po2 - measured)) as f64
+            } else {
+                value / (1u64 << (measured - po2)) as f64
+            }
+        };
+        Ok(StepProfile {
+            time: Duration::from_secs_f64(scale(step.time.as_secs_f64())),
+            peak_memory: scale(step.peak_memory as f64) as u64,
+            seal_bytes: step.seal_bytes * po2 as u64 / measured.max(1) as u64,
+        })
+    }
+}
+
+// The number of recursion programs run to compress a session into a single
+// succinct receipt.
+#[derive(Default)]
+struct RecursionSteps {
+    lifts: u32,
+    joins: u32,
+}
+
+impl RecursionSteps {
+    fn add_segments(&mut self, count: usize) {
+        self.lifts += count as u32;
+        self.joins += count.saturating_sub(1) as u32;
+    }
+
+    // Counts the steps that compress an assumption, and the join that resolves it.
+    fn add_assumption(&mut self, assumption: &InnerAssumptionReceipt) -> Result<()> {
+        match assumption {
+            InnerAssumptionReceipt::Succinct(_) => (),
+            InnerAssumptionReceipt::Composite(receipt) => {
+                self.add_segments(receipt.segments.len());
+                for assumption in receipt.assumption_receipts.iter() {
+                    self.add_assumption(assumption)?;
+                }
+            }
+            InnerAssumptionReceipt::Fake(_) => {
+                bail!(
+                    "compressing composite receipts with fake receipt assumptions is not supported"
+                )
+            }
+            InnerAssumptionReceipt::Groth16(_) => bail!(
+                "compressing composite receipts with Groth16 receipt assumptions is not supported"
+            ),
+        }
+        self.joins += 1;
+        Ok(())
+    }
+
+    fn time(&self, profile: &HardwareProfile) -> Duration {
+        profile.lift.time * self.lifts + profile.join.time * self.joins
+    }
+}
+
+impl Session {
+    /// Predict the cost of proving this [Session] on the machine described by
+    /// `profile`, for each kind of receipt.
+    ///
+    /// Steps run one after another, so proving time is the sum of the time of
+    /// each segment and recursion step, and peak memory is that of the most
+    /// expensive step. Estimates are returned for [ReceiptKind::Composite],
+    /// [ReceiptKind::Succinct] unless an assumption cannot be compressed, and
+    /// [ReceiptKind::Groth16] if the profile has Groth16 measurements.
+    pub fn estimate(&self, profile: &HardwareProfile) -> Result<Vec<CostEstimate>> {
+        self.ensure_provable()?;
+
+        let mut assumptions = Vec::new();
+        for (assumption, receipt) in self.assumptions.iter() {
+            match receipt {
+                AssumptionReceipt::Proven(receipt) => assumptions.push(receipt),
+                AssumptionReceipt::Unresolved(_) => bail!(
+                    "cannot estimate a session with an unresolved assumption for claim {}",
+                    assumption.claim
+                ),
+            }
+        }
+
+        let journal_bytes = self
+            .journal
+            .as_ref()
+            .map_or(0, |journal| journal.bytes.len() as u64);
+
+        let mut composite = CostEstimate {
+            receipt_kind: ReceiptKind::Composite,
+            proving_time: Duration::ZERO,
+            peak_memory: 0,
+            receipt_size: journal_bytes,
+        };
+        for po2 in self.segment_po2s()? {
+            let step = profile.segment(po2)?;
+            composite.proving_time += step.time;
+            composite.peak_memory = composite.peak_memory.max(step.peak_memory);
+            composite.receipt_size += step.seal_bytes;
+        }
+        for receipt in assumptions.iter() {
+            composite.receipt_size += bincode::serialized_size(receipt)?;
+        }
+        let mut estimates = vec![composite.clone()];
+
+        let mut steps = RecursionSteps::default();
+        steps.add_segments(self.segments.len());
+        if assumptions
+            .iter()
+            .try_for_each(|receipt| steps.add_assumption(receipt))
+            .is_err()
+        {
+            return Ok(estimates);
+        }
+        let succinct = CostEstimate {
+            receipt_kind: ReceiptKind::Succinct,
+            proving_time: composite.proving_time + steps.time(profile),
+            peak_memory: composite
+                .peak_memory
+                .max(profile.lift.peak_memory)
+                .max(profile.join.peak_memory),
+            receipt_size: profile.lift.seal_bytes + journal_bytes,
+        };
+        estimates.push(succinct.clone());
+
+        if let Some(stark_to_snark) = &profile.stark_to_snark {
+            estimates.push(CostEstimate {
+                receipt_kind: ReceiptKind::Groth16,
+                proving_time: succinct.proving_time
+                    + profile.identity_p254.time
+                    + stark_to_snark.time,
+                peak_memory: succinct
+                    .peak_memory
+                    .max(profile.identity_p254.peak_memory)
+                    .max(stark_to_snark.peak_memory),
+                receipt_size: stark_to_snark.seal_bytes + journal_bytes,
+            });
+        }
+        Ok(estimates)
+    }
+
+    // Returns the po2 of each segment, resolving the segments if the executor
+    // did not record them.
+    fn segment_po2s(&self) -> Result<Vec<usize>> {
+        if self.segment_po2s.len() == self.segments.len() {
+            return Ok(self.segment_po2s.clone());
+        }
+        self.segments
+            .iter()
+            .map(|segment| Ok(segment.resolve()?.po2()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{HardwareProfile, StepProfile};
+
+    fn step(secs: u64, peak_memory: u64, seal_bytes: u64) -> StepProfile {
+        StepProfile {
+            time: Duration::from_secs(secs),
+            peak_memory,
+            seal_bytes,
+        }
+    }
+
+    #[test]
+    fn segment_scaling() {
+        let profile = HardwareProfile {
+            segments: [(16, step(2, 1000, 160)), (20, step(40, 20000, 200))].into(),
+            ..Default::default()
+        };
+        assert_eq!(profile.segment(16).unwrap(), step(2, 1000, 160));
+        assert_eq!(profile.segment(17).unwrap(), step(4, 2000, 170));
+        assert_eq!(profile.segment(21).unwrap(), step(80, 40000, 210));
+        assert_eq!(profile.segment(15).unwrap(), step(1, 500, 150));
+        assert!(HardwareProfile::default().segment(20).is_err());
+    }
+}
@@ -12,27 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cell::RefCell, io::Write, mem, rc::Rc, sync::Arc, time::Instant};
+use std::{cell::RefCell, collections::BTreeSet, io::Write, mem, rc::Rc, sync::Arc, time::Instant};
 
 use anyhow::{Context as _, Result};
 use risc0_binfmt::{MemoryImage, Program};
-use risc0_circuit_rv32im::prove::emu::{
-    addr::ByteAddr,
-    exec::{
-        Executor, Syscall as NewSyscall, SyscallContext as NewSyscallContext,
-        DEFAULT_SEGMENT_LIMIT_PO2,
+use risc0_circuit_rv32im::prove::{
+    emu::{
+        addr::ByteAddr,
+        exec::{
+            Executor, Syscall as NewSyscall, SyscallContext as NewSyscallContext, SyscallFault,
+            DEFAULT_SEGMENT_LIMIT_PO2,
+        },
     },
+    segment::Segment as CircuitSegment,
+};
+use risc0_zkp::core::{
+    digest::{Digest, DIGEST_WORDS},
+    hash::sha::{self, Sha256},
+};
+use risc0_zkvm_platform::{
+    fileno,
+    memory::GUEST_MAX_MEM,
+    syscall::{
+        is_committed_input,
+        nr::{SYS_FS_CLOSE, SYS_FS_OPEN, SYS_FS_READ, SYS_FS_STAT, SYS_READ, SYS_VERIFY_INTEGRITY},
+    },
+    PAGE_SIZE, WORD_SIZE,
 };
-use risc0_zkp::core::digest::Digest;
-use risc0_zkvm_platform::{fileno, memory::GUEST_MAX_MEM, PAGE_SIZE};
 use tempfile::tempdir;
 
 use crate::{
-    host::client::env::SegmentPath, Assumptions, ExecutorEnv, FileSegmentRef, Output, Segment,
-    SegmentRef, Session,
+    host::{client::env::SegmentPath, panic_info::GuestPanic},
+    Assumptions, ExecutorEnv, FileSegmentRef, Output, ProfileFormat, ResourceLimitExceeded,
+    Segment, SegmentRef, Session,
 };
 
 use super::{
+    fault::{backtrace_enabled, stack_guard, symbolize, walk_frame_pointers, CallStack, FaultInfo},
     profiler::Profiler,
     syscall::{SyscallContext, SyscallTable},
 };
@@ -45,6 +61,20 @@ pub struct ExecutorImpl<'a> {
     image: MemoryImage,
     pub(crate) syscall_table: SyscallTable<'a>,
     profiler: Option<Rc<RefCell<Profiler>>>,
+    // The guest's ELF binary, if known, used to symbolize backtraces when the guest faults.
+    elf: Option<Vec<u8>>,
+    // Name of the last syscall made by the guest, reported when the guest faults.
+    last_syscall: RefCell<Option<String>>,
+    // The panic reported by the guest, if any, with the addresses of the guest's stack frames.
+    panic: RefCell<Option<(GuestPanic, Vec<u32>)>>,
+    // Running hash of the data returned by syscalls whose results the guest commits to, if any
+    // have been made in the current run.
+    committed_input: RefCell<Option<Digest>>,
+    // Running hash of the data returned by the virtual filesystem, which like the guest's copy
+    // persists across pauses.
+    vfs_digest: RefCell<Option<Digest>>,
+    // Resources used by the guest in the current run, checked against the env's resource policy.
+    usage: RefCell<ResourceUsage>,
 }
 
 impl<'a> ExecutorImpl<'a> {
@@ -77,9 +107,10 @@ impl<'a> ExecutorImpl<'a> {
     /// ```
     pub fn from_elf(mut env: ExecutorEnv<'a>, elf: &[u8]) -> Result<Self> {
         let program = Program::load_elf(elf, GUEST_MAX_MEM as u32)?;
-        let image = MemoryImage::new(&program, PAGE_SIZE as u32)?;
+        let mut image = MemoryImage::new(&program, PAGE_SIZE as u32)?;
+        image.stack_guard = stack_guard(elf);
 
-        let profiler = if env.pprof_out.is_some() {
+        let profiler = if env.pprof_out.is_some() || env.profile_top.is_some() {
             let profiler = Rc::new(RefCell::new(Profiler::new(elf, None)?));
             env.trace.push(profiler.clone());
             Some(profiler)
@@ -87,7 +118,9 @@ impl<'a> ExecutorImpl<'a> {
             None
         };
 
-        Self::with_details(env, image, profiler)
+        let mut exec = Self::with_details(env, image, profiler)?;
+        exec.elf = Some(elf.to_vec());
+        Ok(exec)
     }
 
     fn with_details(
@@ -101,6 +134,12 @@ impl<'a> ExecutorImpl<'a> {
             image,
             syscall_table,
             profiler,
+            elf: None,
+            last_syscall: RefCell::new(None),
+            panic: RefCell::new(None),
+            committed_input: RefCell::new(None),
+            vfs_digest: RefCell::new(None),
+            usage: RefCell::new(ResourceUsage::new(Journal::default())),
         })
     }
 
@@ -117,6 +156,11 @@ impl<'a> ExecutorImpl<'a> {
 
     /// Run the executor until [crate::ExitCode::Halted] or
     /// [crate::ExitCode::Paused] is reached, producing a [Session] as a result.
+    ///
+    /// If the guest panics, the [Session] ends with [crate::ExitCode::Fault] and its
+    /// [Session::panic] describes the panic. If the guest faults in any other way, e.g. by
+    /// accessing memory outside of its address space, the [Session] also ends with
+    /// [crate::ExitCode::Fault], and its [Session::fault] describes the fault.
     pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<Session>
     where
         F: FnMut(Segment) -> Result<Box<dyn SegmentRef>>,
@@ -134,16 +178,33 @@ impl<'a> ExecutorImpl<'a> {
             .segment_limit_po2
             .unwrap_or(DEFAULT_SEGMENT_LIMIT_PO2 as u32) as usize;
 
+        // When the input digest is committed to by the guest, it is not known until execution
+        // ends, so segments are held back until it can be filled in.
+        let defer_segments = self.env.commit_input;
+        self.committed_input.replace(None);
+        self.last_syscall.replace(None);
+        self.panic.replace(None);
+        self.usage.replace(ResourceUsage::new(journal.clone()));
+
+        let call_stack = backtrace_enabled().then(|| Rc::new(RefCell::new(CallStack::default())));
+        let mut trace = self.env.trace.clone();
+        if let Some(call_stack) = &call_stack {
+            trace.push(call_stack.clone());
+        }
+
         let mut refs = Vec::new();
-        let mut exec = Executor::new(
-            self.image.clone(),
-            self,
-            self.env.input_digest,
-            self.env.trace.clone(),
-        );
+        let mut deferred = Vec::new();
+        let mut segment_po2s = Vec::new();
+        let stack_guard = self.image.stack_guard.clone();
+        let mut exec = Executor::new(self.image.clone(), self, self.env.input_digest, trace);
+        if let Some(stack_guard) = stack_guard {
+            exec = exec.with_stack_guard(stack_guard);
+        }
 
         let start_time = Instant::now();
         let result = exec.run(segment_limit_po2, self.env.session_limit, |inner| {
+            self.record_segment(&inner)?;
+            segment_po2s.push(inner.po2);
             let output = inner
                 .exit_code
                 .expects_output()
@@ -177,12 +238,40 @@ impl<'a> ExecutorImpl<'a> {
                 inner,
                 output,
             };
-            let segment_ref = callback(segment)?;
-            refs.push(segment_ref);
+            if defer_segments {
+                deferred.push(segment);
+            } else {
+                refs.push(callback(segment)?);
+            }
             Ok(())
-        })?;
+        });
+        let result = result?;
         let elapsed = start_time.elapsed();
 
+        let fault = result.trap.as_ref().map(|trap| {
+            FaultInfo::new(
+                trap,
+                self.last_syscall.take(),
+                call_stack
+                    .as_ref()
+                    .map(|call_stack| call_stack.borrow())
+                    .as_deref(),
+                self.elf.as_deref(),
+            )
+        });
+
+        let panic = self.panic.take().map(|(mut panic, pcs)| {
+            panic.backtrace = symbolize(self.elf.as_deref(), pcs);
+            panic
+        });
+
+        let committed_input = self.committed_input.take();
+        let input_digest = committed_input.or(self.env.input_digest);
+        for mut segment in deferred {
+            segment.inner.input_digest = input_digest.unwrap_or_default();
+            refs.push(callback(segment)?);
+        }
+
         // Set the session_journal to the committed data iff the guest set a non-zero output.
         let session_journal = result
             .output_digest
@@ -199,16 +288,26 @@ impl<'a> ExecutorImpl<'a> {
         // Leave the assumptions cache so it can be used if execution is resumed from pause.
         let assumptions = mem::take(&mut self.env.assumptions.borrow_mut().accessed);
 
+        let mut profile_report = None;
         if let Some(profiler) = self.profiler.take() {
-            let report = profiler.borrow_mut().finalize_to_vec();
-            std::fs::write(self.env.pprof_out.as_ref().unwrap(), report)?;
+            let mut profiler = profiler.borrow_mut();
+            if let Some(top) = self.env.profile_top {
+                profile_report = Some(profiler.report(top));
+            }
+            if let Some(path) = &self.env.pprof_out {
+                let format = self
+                    .env
+                    .profile_format
+                    .unwrap_or_else(|| ProfileFormat::from_path(path));
+                std::fs::write(path, profiler.finalize_to_format(format))?;
+            }
         }
 
         self.image = result.post_image.clone();
 
-        let session = Session::new(
+        let mut session = Session::new(
             refs,
-            self.env.input_digest.unwrap_or_default(),
+            input_digest.unwrap_or_default(),
             session_journal,
             result.exit_code,
             result.post_image,
@@ -218,6 +317,11 @@ impl<'a> ExecutorImpl<'a> {
             result.pre_state,
             result.post_state,
         );
+        session.panic = panic;
+        session.fault = fault;
+        session.profile_report = profile_report;
+        session.vfs_digest = *self.vfs_digest.borrow();
+        session.segment_po2s = segment_po2s;
 
         tracing::info_span!("executor").in_scope(|| {
             tracing::info!("execution time: {elapsed:?}");
@@ -255,11 +359,142 @@ impl<'a> NewSyscall for ExecutorImpl<'a> {
         into_guest: &mut [u32],
     ) -> Result<(u32, u32)> {
         let mut ctx = ContextAdapter { ctx };
-        self.syscall_table
+        self.last_syscall.replace(Some(syscall.to_string()));
+        let result = self
+            .syscall_table
             .get_syscall(syscall)
             .context(format!("Unknown syscall: {syscall:?}"))?
             .borrow_mut()
-            .syscall(syscall, &mut ctx, into_guest)
+            .syscall(syscall, &mut ctx, into_guest);
+        let result = match result.map_err(|err| err.downcast::<GuestPanic>()) {
+            Ok(result) => result,
+            Err(Ok(panic)) => {
+                // End the session at the panic rather than aborting execution.
+                self.panic
+                    .replace(Some((panic, walk_frame_pointers(ctx.ctx))));
+                return Err(SyscallFault.into());
+            }
+            Err(Err(err)) => return Err(err),
+        };
+        if is_vfs_syscall(syscall) {
+            absorb(&mut self.vfs_digest.borrow_mut(), result, into_guest);
+        }
+        if self.env.commit_input && is_committed_input(syscall) {
+            absorb(&mut self.committed_input.borrow_mut(), result, into_guest);
+        }
+        self.record_syscall(syscall, result, into_guest)?;
+        Ok(result)
+    }
+
+    fn input_digest(&self) -> Option<Digest> {
+        *self.committed_input.borrow()
+    }
+}
+
+// Returns true for the virtual filesystem syscalls, whose results are always absorbed into the
+// filesystem digest by the guest.
+fn is_vfs_syscall(syscall: &str) -> bool {
+    [SYS_FS_CLOSE, SYS_FS_OPEN, SYS_FS_READ, SYS_FS_STAT]
+        .iter()
+        .any(|name| name.as_str() == syscall)
+}
+
+// Absorb the result of a syscall into a running digest, in the same way as `sys_commit_input` and
+// `sys_fs_commit` do in the guest.
+fn absorb(digest: &mut Option<Digest>, (a0, a1): (u32, u32), into_guest: &[u32]) {
+    let state = digest.get_or_insert(Digest::ZERO);
+    for block in [&[a0, a1], into_guest].concat().chunks(2 * DIGEST_WORDS) {
+        let mut words = [0u32; 2 * DIGEST_WORDS];
+        words[..block.len()].copy_from_slice(block);
+        *state = *sha::Impl::compress(
+            state,
+            &Digest::from(<[u32; DIGEST_WORDS]>::try_from(&words[..DIGEST_WORDS]).unwrap()),
+            &Digest::from(<[u32; DIGEST_WORDS]>::try_from(&words[DIGEST_WORDS..]).unwrap()),
+        );
+    }
+}
+
+// The resources used by the guest during a run.
+struct ResourceUsage {
+    start_time: Instant,
+    journal: Journal,
+    pages: BTreeSet<u32>,
+    input_bytes: u64,
+    verify_calls: u64,
+}
+
+impl ResourceUsage {
+    fn new(journal: Journal) -> Self {
+        Self {
+            start_time: Instant::now(),
+            journal,
+            pages: BTreeSet::new(),
+            input_bytes: 0,
+            verify_calls: 0,
+        }
+    }
+}
+
+impl<'a> ExecutorImpl<'a> {
+    // Account for the data returned to the guest by a syscall, and stop execution if that exceeds
+    // a limit of the resource policy.
+    fn record_syscall(&self, syscall: &str, (a0, _): (u32, u32), into_guest: &[u32]) -> Result<()> {
+        let mut usage = self.usage.borrow_mut();
+        if syscall == SYS_READ.as_str() {
+            // The number of bytes read is returned in a0.
+            usage.input_bytes += a0 as u64;
+        } else if syscall == SYS_VERIFY_INTEGRITY.as_str() {
+            usage.verify_calls += 1;
+        } else if is_vfs_syscall(syscall) || self.env.slice_io.borrow().inner.contains_key(syscall)
+        {
+            // Files read from the virtual filesystem count as input too, however its syscalls are
+            // dispatched.
+            usage.input_bytes += (into_guest.len() * WORD_SIZE) as u64;
+        }
+        self.check_resources(&usage)
+    }
+
+    // Account for the pages of memory touched in a segment, and stop execution if that exceeds a
+    // limit of the resource policy.
+    fn record_segment(&self, segment: &CircuitSegment) -> Result<()> {
+        let mut usage = self.usage.borrow_mut();
+        if self.env.resource_policy.pages.is_some() {
+            let info = &segment.partial_image.info;
+            usage.pages.extend(
+                segment
+                    .partial_image
+                    .pages()
+                    .iter()
+                    .map(|(page_idx, _)| page_idx)
+                    .filter(|&page_idx| info.get_page_addr(page_idx) < GUEST_MAX_MEM as u32),
+            );
+        }
+        self.check_resources(&usage)
+    }
+
+    fn check_resources(&self, usage: &ResourceUsage) -> Result<()> {
+        let policy = &self.env.resource_policy;
+        let exceeded = |limit: Option<u64>, used: u64| limit.filter(|&limit| used > limit);
+        if let Some(limit) = policy
+            .wall_clock
+            .filter(|&limit| usage.start_time.elapsed() > limit)
+        {
+            return Err(ResourceLimitExceeded::WallClock(limit).into());
+        }
+        if let Some(limit) = exceeded(policy.pages, usage.pages.len() as u64) {
+            return Err(ResourceLimitExceeded::Pages(limit).into());
+        }
+        let journal_bytes = usage.journal.buf.borrow().len() as u64;
+        if let Some(limit) = exceeded(policy.journal_bytes, journal_bytes) {
+            return Err(ResourceLimitExceeded::JournalBytes(limit).into());
+        }
+        if let Some(limit) = exceeded(policy.input_bytes, usage.input_bytes) {
+            return Err(ResourceLimitExceeded::InputBytes(limit).into());
+        }
+        if let Some(limit) = exceeded(policy.verify_calls, usage.verify_calls) {
+            return Err(ResourceLimitExceeded::VerifyCalls(limit).into());
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,270 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Diagnostics for guests that fault.
+
+use std::{fmt, ops::Range};
+
+use addr2line::{object::File, ObjectContext};
+use anyhow::Result;
+use elf::{abi::STT_FUNC, endian::LittleEndian, ElfBytes};
+use risc0_circuit_rv32im::prove::emu::{
+    addr::ByteAddr,
+    exec::{SyscallContext as NewSyscallContext, TrapError},
+    rv32im::TrapCause,
+};
+use risc0_zkvm_platform::{
+    memory::{STACK_BOTTOM_SYMBOL, STACK_GUARD_SYMBOL},
+    syscall::reg_abi::REG_FP,
+    WORD_SIZE,
+};
+use rustc_demangle::demangle;
+
+use super::profiler::{extract_call_stack_op, lookup_pc, CallStackOp};
+use crate::{
+    host::panic_info::{fmt_backtrace, StackFrame},
+    TraceCallback, TraceEvent,
+};
+
+/// Diagnostics for a guest that faulted, e.g. by accessing memory outside of the guest's address
+/// space or executing an illegal instruction.
+///
+/// When the guest faults, [crate::ExecutorImpl::run] returns an error that can be downcast to a
+/// [FaultInfo]. It is displayed like the backtrace of a Rust panic.
+///
+/// Only the faulting function is included in the backtrace, unless the `RUST_BACKTRACE`
+/// environment variable is set on the host, in which case the executor tracks the guest's calls so
+/// that the full call stack can be reported. Tracking calls slows down execution.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FaultInfo {
+    /// The cause of the fault.
+    pub cause: TrapCause,
+
+    /// The address of the faulting instruction.
+    pub pc: u32,
+
+    /// The name of the last system call made by the guest before the fault, if any.
+    pub last_syscall: Option<String>,
+
+    /// The call stack at the fault, starting with the faulting function.
+    pub backtrace: Vec<StackFrame>,
+
+    /// Whether the backtrace includes the callers of the faulting function.
+    pub full_backtrace: bool,
+}
+
+impl FaultInfo {
+    pub(crate) fn new(
+        trap: &TrapError,
+        last_syscall: Option<String>,
+        call_stack: Option<&CallStack>,
+        elf: Option<&[u8]>,
+    ) -> Self {
+        let mut pcs = vec![trap.pc.0];
+        if let Some(call_stack) = call_stack {
+            pcs.extend(call_stack.call_sites.iter().rev());
+        }
+        Self {
+            cause: trap.cause.clone(),
+            pc: trap.pc.0,
+            last_syscall,
+            backtrace: symbolize(elf, pcs),
+            full_backtrace: call_stack.is_some(),
+        }
+    }
+}
+
+impl fmt::Display for FaultInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.cause {
+            TrapCause::StackOverflow(addr) => writeln!(
+                f,
+                "guest overflowed its stack at pc 0x{:08x}: access to 0x{:08x} in the stack guard",
+                self.pc, addr.0
+            )?,
+            cause => writeln!(f, "guest faulted at pc 0x{:08x}: {cause:08x?}", self.pc)?,
+        }
+        if let Some(syscall) = &self.last_syscall {
+            writeln!(f, "last syscall: {syscall}")?;
+        }
+        fmt_backtrace(f, &self.backtrace)?;
+        if !self.full_backtrace {
+            write!(
+                f,
+                "\nnote: run with `RUST_BACKTRACE=1` environment variable to display a full backtrace."
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FaultInfo {}
+
+/// Returns true if the host asked for full backtraces, in the same way as for Rust panics.
+pub(crate) fn backtrace_enabled() -> bool {
+    std::env::var("RUST_BACKTRACE").is_ok_and(|value| value != "0")
+}
+
+/// Tracks the addresses of the call instructions that led to the current function.
+///
+/// This follows the same rules for calls and returns as the [super::profiler::Profiler].
+#[derive(Default)]
+pub(crate) struct CallStack {
+    pc: u32,
+    insn: u32,
+    call_sites: Vec<u32>,
+}
+
+impl TraceCallback for CallStack {
+    fn trace_callback(&mut self, event: TraceEvent) -> Result<()> {
+        if let TraceEvent::InstructionStart { pc, insn, .. } = event {
+            match extract_call_stack_op(self.insn) {
+                Some(CallStackOp::Push) => self.call_sites.push(self.pc),
+                Some(CallStackOp::Pop) => self.pop(pc),
+                Some(CallStackOp::PopPush) => {
+                    self.pop(pc);
+                    self.call_sites.push(self.pc);
+                }
+                None => (),
+            }
+            self.pc = pc;
+            self.insn = insn;
+        }
+        Ok(())
+    }
+}
+
+impl CallStack {
+    // Unwind to the call that returns to `pc`, or the bottom of the stack if there is none.
+    fn pop(&mut self, pc: u32) {
+        while let Some(call_site) = self.call_sites.pop() {
+            if call_site == pc.wrapping_sub(4) {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns the stack of a guest that panicked, by walking the frame pointers from the `ecall` made
+/// by the panic handler.
+///
+/// In each frame, the frame pointer points just past the return address and the caller's frame
+/// pointer. The walk stops at the first frame pointer that does not point into the stack of a
+/// caller, which is how the outermost frame is recognized.
+pub(crate) fn walk_frame_pointers(ctx: &mut dyn NewSyscallContext) -> Vec<u32> {
+    // Limit the walk in case the frame pointers are corrupt.
+    const MAX_FRAMES: usize = 256;
+
+    let mut pcs = vec![ctx.peek_pc().0];
+    let Ok(mut fp) = ctx.peek_register(REG_FP) else {
+        return pcs;
+    };
+    while pcs.len() < MAX_FRAMES && fp % WORD_SIZE as u32 == 0 {
+        let (Ok(ra), Ok(caller_fp)) = (
+            ctx.peek_u32(ByteAddr(fp.wrapping_sub(4))),
+            ctx.peek_u32(ByteAddr(fp.wrapping_sub(8))),
+        ) else {
+            break;
+        };
+        if ra < WORD_SIZE as u32 {
+            break;
+        }
+        // Report the call instruction rather than the instruction after it.
+        pcs.push(ra - WORD_SIZE as u32);
+        // The stack grows down, so callers' frames are at higher addresses.
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+    pcs
+}
+
+/// Returns the stack guard region of the guest, if it was linked with one.
+///
+/// The region is bounded by linker symbols that risc0-build defines when a guest is built with a
+/// custom memory layout.
+pub(crate) fn stack_guard(elf: &[u8]) -> Option<Range<u32>> {
+    let elf = ElfBytes::<LittleEndian>::minimal_parse(elf).ok()?;
+    let (symtab, strtab) = elf.symbol_table().ok()??;
+    let lookup = |name: &str| {
+        symtab
+            .iter()
+            .find(|sym| {
+                strtab
+                    .get(sym.st_name as usize)
+                    .is_ok_and(|sym_name| sym_name == name)
+            })
+            .map(|sym| sym.st_value as u32)
+    };
+    let guard = lookup(STACK_GUARD_SYMBOL)?..lookup(STACK_BOTTOM_SYMBOL)?;
+    (!guard.is_empty()).then_some(guard)
+}
+
+/// Symbolizes the given addresses, innermost first, using the guest's ELF binary if it is known.
+pub(crate) fn symbolize(elf: Option<&[u8]>, pcs: Vec<u32>) -> Vec<StackFrame> {
+    let symbolizer = elf.and_then(|elf| Symbolizer::new(elf).ok());
+    pcs.into_iter()
+        .flat_map(|pc| match &symbolizer {
+            Some(symbolizer) => symbolizer.frames(pc),
+            None => vec![StackFrame::new(pc, None, None)],
+        })
+        .collect()
+}
+
+struct Symbolizer<'a> {
+    ctx: ObjectContext,
+    elf: ElfBytes<'a, LittleEndian>,
+}
+
+impl<'a> Symbolizer<'a> {
+    fn new(elf: &'a [u8]) -> Result<Self> {
+        let ctx = ObjectContext::new(&File::parse(elf)?)?;
+        let elf = ElfBytes::<LittleEndian>::minimal_parse(elf)?;
+        Ok(Self { ctx, elf })
+    }
+
+    // Returns the frames at the given address, innermost first, of which there is more than one
+    // if functions were inlined.
+    fn frames(&self, pc: u32) -> Vec<StackFrame> {
+        let frames: Vec<_> = lookup_pc(pc, &self.ctx)
+            .into_iter()
+            .map(|frame| {
+                StackFrame::new(
+                    pc,
+                    Some(frame.name),
+                    Some((frame.filename, frame.lineno as u32)),
+                )
+            })
+            .collect();
+        if !frames.is_empty() {
+            return frames;
+        }
+
+        // Without debug information, fall back to the function in the symbol table.
+        vec![StackFrame::new(pc, self.function(pc), None)]
+    }
+
+    fn function(&self, pc: u32) -> Option<String> {
+        let (symtab, strtab) = self.elf.symbol_table().ok()??;
+        let sym = symtab.iter().find(|sym| {
+            sym.st_symtype() == STT_FUNC
+                && sym.st_value <= pc as u64
+                && (pc as u64) < sym.st_value + sym.st_size
+        })?;
+        let name = strtab.get(sym.st_name as usize).ok()?;
+        Some(format!("{:#}", demangle(name)))
+    }
+}
@@ -18,10 +18,18 @@
 //! guest.  It does not trace full stack traces, but only provides the
 //! top level stack frame.  (More than one stack frame may show up
 //! in the case of inlined functions).
+//!
+//! Cycles spent paging memory in and out, and in system calls, are
+//! attributed to the `[paging]` and `[syscall]` pseudo-frames, called
+//! from the function that caused them.
+//!
+//! The profile can be written as pprof protobuf, as folded stacks for
+//! flamegraphs, or as speedscope JSON, and summarized as a report of
+//! the functions that used the most cycles.
 
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Write,
     hash::{Hash, Hasher},
     rc::Rc,
@@ -39,13 +47,17 @@ use prost::Message;
 use risc0_zkvm_platform::memory::TEXT_START;
 use rrs_lib::instruction_formats::{IType, JType, OPCODE_JAL, OPCODE_JALR};
 use rustc_demangle::demangle;
+use serde_json::json;
 
 use super::proto;
-use crate::{TraceCallback, TraceEvent};
+use crate::{ProfileFormat, TraceCallback, TraceEvent};
+
+/// The encoding of the `ecall` instruction.
+const ECALL: u32 = 0x00000073;
 
 /// Operations effecting the function call stack.
 #[derive(Debug)]
-enum CallStackOp {
+pub(crate) enum CallStackOp {
     Push,
     Pop,
     PopPush,
@@ -59,7 +71,7 @@ enum CallStackOp {
 /// return, or neither.
 ///
 /// [RISC-V ISA manual]: https://riscv.org/wp-content/uploads/2017/05/riscv-spec-v2.2.pdf
-fn extract_call_stack_op(insn: u32) -> Option<CallStackOp> {
+pub(crate) fn extract_call_stack_op(insn: u32) -> Option<CallStackOp> {
     let opcode: u32 = insn & 0x7f;
 
     match opcode {
@@ -98,6 +110,34 @@ struct CallNode {
 
     /// Nodes representing further calls from this context.
     pub(crate) calls: HashMap<u32, Rc<RefCell<CallNode>>>,
+
+    /// Overhead cycles by program counter with the current call stack.
+    pub(crate) overhead: HashMap<(u32, Overhead), usize>,
+}
+
+/// Cycles that are not spent executing the instructions of a function, and are attributed to a
+/// pseudo-frame called by the function instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Overhead {
+    /// Cycles spent paging memory in and out for the function's instructions.
+    Paging,
+
+    /// Cycles spent in system calls made by the function, including accelerators.
+    Syscall,
+}
+
+impl Overhead {
+    fn frame(self) -> Frame {
+        let name = match self {
+            Overhead::Paging => "[paging]",
+            Overhead::Syscall => "[syscall]",
+        };
+        Frame {
+            name: name.to_string(),
+            lineno: 0,
+            filename: "unknown".to_string(),
+        }
+    }
 }
 
 impl CallNode {
@@ -156,10 +196,13 @@ pub struct Profiler {
     ctx: ObjectContext,
 
     profile: ProfileBuilder,
+
+    // Name of the profiled binary
+    name: String,
 }
 
 /// Represents a frame.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Frame {
     /// Function name
     pub name: String,
@@ -171,6 +214,19 @@ pub struct Frame {
     pub filename: String,
 }
 
+/// A call stack, outermost frame first, and the cycles attributed to it.
+#[derive(Clone, Debug)]
+pub(crate) struct StackSample {
+    /// Program counter of the innermost function
+    pub pc: u32,
+
+    /// Frames of the call stack, outermost first
+    pub frames: Vec<Frame>,
+
+    /// Number of cycles attributed to the call stack
+    pub cycles: u64,
+}
+
 fn decode_frame(fr: addr2line::Frame<EndianRcSlice<RunTimeEndian>>) -> Option<Frame> {
     Some(Frame {
         name: fr.function.as_ref()?.demangle().ok()?.to_string(),
@@ -179,7 +235,7 @@ fn decode_frame(fr: addr2line::Frame<EndianRcSlice<RunTimeEndian>>) -> Option<Fr
     })
 }
 
-fn lookup_pc(pc: u32, ctx: &ObjectContext) -> Vec<Frame> {
+pub(crate) fn lookup_pc(pc: u32, ctx: &ObjectContext) -> Vec<Frame> {
     let frames = match ctx.find_frames(pc as u64) {
         LookupResult::Output(result) => result.unwrap(),
         LookupResult::Load {
@@ -219,6 +275,7 @@ impl Profiler {
             call_stack_path: Vec::new(),
             ctx,
             profile: ProfileBuilder::new(),
+            name: filename.unwrap_or("guest").to_string(),
         };
 
         // Save the main binary name
@@ -291,24 +348,59 @@ impl Profiler {
         frames
     }
 
-    /// Walk the profile tree rooted at node_ref, adding all call stacks in the profile to the
-    /// profile under construction. All call stacks encountered build on top of the base_stack.
-    fn walk_stacks(&mut self, node_ref: Rc<RefCell<CallNode>>, base_stack: Vec<Frame>) {
+    /// Walk the profile tree rooted at node_ref, adding all call stacks in the profile to
+    /// samples. All call stacks encountered build on top of the base_stack.
+    fn walk_stacks(
+        &self,
+        node_ref: Rc<RefCell<CallNode>>,
+        base_stack: Vec<Frame>,
+        samples: &mut Vec<StackSample>,
+    ) {
         let node = node_ref.borrow();
         for (&pc, count) in &node.counts {
             let mut new_stack = base_stack.clone();
-            let frames = self.lookup_pc(pc.into());
-            if !frames.is_empty() {
-                new_stack.extend(frames);
+            new_stack.extend(self.lookup_pc(pc.into()));
+            samples.push(StackSample {
+                pc,
+                frames: new_stack.clone(),
+                cycles: *count as u64,
+            });
+
+            if let Some(next_node_ref) = node.calls.get(&pc) {
+                self.walk_stacks(next_node_ref.clone(), new_stack, samples);
             }
+        }
+        for (&(pc, overhead), count) in &node.overhead {
+            let mut new_stack = base_stack.clone();
+            new_stack.extend(self.lookup_pc(pc.into()));
+            new_stack.push(overhead.frame());
+            samples.push(StackSample {
+                pc,
+                frames: new_stack,
+                cycles: *count as u64,
+            });
+        }
+    }
+
+    /// Returns the call stacks in the profile and the cycles attributed to each.
+    pub(crate) fn samples(&self) -> Vec<StackSample> {
+        tracing::debug!("{}", self.root.borrow().fmt(0, self));
+        let mut samples = Vec::new();
+        self.walk_stacks(Rc::clone(&self.root), Vec::new(), &mut samples);
+        samples
+    }
 
-            let location_ids: Vec<_> = new_stack
+    /// Add all call stacks in the profile to the profile protobuf under construction.
+    fn build_profile(&mut self) {
+        for stack in self.samples() {
+            let location_ids: Vec<_> = stack
+                .frames
                 .iter()
                 .rev()
                 .map(|fr| {
                     let func_id = self.profile.get_function(&fr.name, &fr.filename);
                     let loc = proto::Location {
-                        address: pc as u64,
+                        address: stack.pc as u64,
                         line: vec![proto::Line {
                             function_id: func_id,
                             line: fr.lineno,
@@ -320,37 +412,72 @@ impl Profiler {
                 .collect();
             let sample = proto::Sample {
                 location_id: location_ids,
-                value: vec![*count as i64],
+                value: vec![stack.cycles as i64],
                 ..Default::default()
             };
 
             if !sample.location_id.is_empty() {
                 self.profile.add_sample(sample);
             }
-
-            if let Some(next_node_ref) = node.calls.get(&pc) {
-                self.walk_stacks(next_node_ref.clone(), new_stack);
-            }
         }
     }
 
     /// Count and save the profiling samples, write the results to `output_path`.
     #[cfg(test)]
     pub(crate) fn finalize(mut self) -> ProfileBuilder {
-        let root_ref = Rc::clone(&self.root);
-        tracing::debug!("{}", self.root.borrow().fmt(0, &self));
-        self.walk_stacks(root_ref, Vec::new());
+        self.build_profile();
         self.profile
     }
 
     /// Count and save the profiling samples, consuming the profiler and
     /// returning the compiled profile protobuf, encoded as bytes.
     pub fn finalize_to_vec(&mut self) -> Vec<u8> {
-        let root_ref = Rc::clone(&self.root);
-        tracing::debug!("{}", self.root.borrow().fmt(0, self));
-        self.walk_stacks(root_ref, Vec::new());
+        self.build_profile();
         self.profile.profile.encode_to_vec()
     }
+
+    /// Count and save the profiling samples, returning the profile encoded
+    /// in the given format.
+    pub fn finalize_to_format(&mut self, format: ProfileFormat) -> Vec<u8> {
+        match format {
+            ProfileFormat::Pprof => self.finalize_to_vec(),
+            ProfileFormat::Folded => to_folded(&self.samples()).into_bytes(),
+            ProfileFormat::Speedscope => to_speedscope(&self.name, &self.samples()),
+        }
+    }
+
+    /// Returns a report of the `top` functions that used the most cycles,
+    /// including the cycles of the functions they called, for display in a
+    /// terminal.
+    pub fn report(&self, top: usize) -> String {
+        to_report(&self.samples(), top)
+    }
+
+    // Attribute cycles to the function at the top of the current call stack.
+    fn add_cycles(&mut self, overhead: Option<Overhead>, cycles: usize) {
+        if self.call_stack_path.is_empty() {
+            return;
+        }
+        let current_node = self
+            .current_node
+            .as_ref()
+            .expect("current_node should always be Some after initialization");
+        let mut current_node_borrowed = current_node.borrow_mut();
+        match overhead {
+            Some(overhead) => {
+                *current_node_borrowed
+                    .overhead
+                    .entry((self.current_key, overhead))
+                    .or_default() += cycles
+            }
+            None => {
+                *current_node_borrowed
+                    .counts
+                    .entry(self.current_key)
+                    .or_default() += cycles
+            }
+        }
+    }
 }
 
 impl TraceCallback for Profiler {
@@ -362,18 +489,8 @@ impl TraceCallback for Profiler {
                 let orig_pc = self.pc;
                 let orig_insn = self.insn;
 
-                if !self.call_stack_path.is_empty() {
-                    let current_node = self
-                        .current_node
-                        .as_ref()
-                        .expect("current_node should always be Some after initialization");
-                    let mut current_node_borrowed = current_node.borrow_mut();
-                    current_node_borrowed
-                        .counts
-                        .entry(self.current_key)
-                        .and_modify(|e| *e += cycles as usize)
-                        .or_insert(cycles as usize);
-                }
+                let overhead = (orig_insn == ECALL).then_some(Overhead::Syscall);
+                self.add_cycles(overhead, cycles as usize);
 
                 if let Some(op) = extract_call_stack_op(orig_insn) {
                     match op {
@@ -434,8 +551,10 @@ impl TraceCallback for Profiler {
                 self.insn = insn;
                 self.cycle = cycle;
             }
-            TraceEvent::RegisterSet { .. } => (),
-            TraceEvent::MemorySet { .. } => (),
+            TraceEvent::Paging { cycles } => {
+                self.add_cycles(Some(Overhead::Paging), cycles as usize)
+            }
+            _ => (),
         }
         Ok(())
     }
@@ -532,29 +651,42 @@ impl ProfileBuilder {
     }
 
     /// Dereferences strings, etc. in the protobuf for testing purposes.
-    /// Returns a tuple of (frames, program counter, cycles)
+    /// Returns a tuple of (frames, program counter, cycles), skipping samples
+    /// attributed to overhead pseudo-frames.
     #[cfg(test)]
     pub(crate) fn iter(&self) -> impl Iterator<Item = (Vec<Frame>, usize, usize)> + '_ {
-        self.profile.sample.iter().flat_map(move |sample| {
-            sample.location_id.iter().map(move |id| {
-                let loc = &self.profile.location[*id as usize - 1];
-                (
-                    loc.line
-                        .iter()
-                        .map(|line| {
-                            let func = &self.profile.function[line.function_id as usize - 1];
-                            Frame {
-                                name: self.profile.string_table[func.name as usize].clone(),
-                                lineno: line.line,
-                                filename: self.profile.string_table[func.filename as usize].clone(),
-                            }
-                        })
-                        .collect(),
-                    loc.address as usize,
-                    sample.value[0] as usize,
-                )
+        let is_overhead = |sample: &&proto::Sample| {
+            let loc = &self.profile.location[sample.location_id[0] as usize - 1];
+            loc.line.iter().any(|line| {
+                let func = &self.profile.function[line.function_id as usize - 1];
+                self.profile.string_table[func.name as usize].starts_with('[')
+            })
+        };
+        self.profile
+            .sample
+            .iter()
+            .filter(move |sample| !is_overhead(sample))
+            .flat_map(move |sample| {
+                sample.location_id.iter().map(move |id| {
+                    let loc = &self.profile.location[*id as usize - 1];
+                    (
+                        loc.line
+                            .iter()
+                            .map(|line| {
+                                let func = &self.profile.function[line.function_id as usize - 1];
+                                Frame {
+                                    name: self.profile.string_table[func.name as usize].clone(),
+                                    lineno: line.line,
+                                    filename: self.profile.string_table[func.filename as usize]
+                                        .clone(),
+                                }
+                            })
+                            .collect(),
+                        loc.address as usize,
+                        sample.value[0] as usize,
+                    )
+                })
             })
-        })
     }
 }
 
@@ -580,3 +712,116 @@ impl PartialEq for LocationKey {
 }
 
 impl Eq for LocationKey {}
+
+/// Encodes call stacks as folded stacks, one per line, as read by `flamegraph.pl` and
+/// `inferno-flamegraph`.
+fn to_folded(samples: &[StackSample]) -> String {
+    let mut stacks = BTreeMap::<String, u64>::new();
+    for sample in samples.iter().filter(|sample| !sample.frames.is_empty()) {
+        let stack = sample
+            .frames
+            .iter()
+            .map(|fr| fr.name.replace(';', ":"))
+            .collect::<Vec<_>>()
+            .join(";");
+        *stacks.entry(stack).or_default() += sample.cycles;
+    }
+
+    let mut output = String::new();
+    for (stack, cycles) in stacks {
+        writeln!(output, "{stack} {cycles}").unwrap();
+    }
+    output
+}
+
+/// Encodes call stacks in the speedscope file format, as read by <https://www.speedscope.app>.
+fn to_speedscope(name: &str, samples: &[StackSample]) -> Vec<u8> {
+    let mut frame_ids = HashMap::<&Frame, usize>::new();
+    let mut frames = Vec::new();
+    let mut stacks = Vec::new();
+    let mut weights = Vec::new();
+    for sample in samples.iter().filter(|sample| !sample.frames.is_empty()) {
+        let stack: Vec<_> = sample
+            .frames
+            .iter()
+            .map(|fr| {
+                *frame_ids.entry(fr).or_insert_with(|| {
+                    frames.push(json!({
+                        "name": fr.name,
+                        "file": fr.filename,
+                        "line": fr.lineno,
+                    }));
+                    frames.len() - 1
+                })
+            })
+            .collect();
+        stacks.push(stack);
+        weights.push(sample.cycles);
+    }
+
+    let total: u64 = weights.iter().sum();
+    let profile = json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "name": name,
+        "exporter": "risc0-zkvm",
+        "shared": { "frames": frames },
+        "profiles": [{
+            "type": "sampled",
+            "name": name,
+            "unit": "none",
+            "startValue": 0,
+            "endValue": total,
+            "samples": stacks,
+            "weights": weights,
+        }],
+    });
+    serde_json::to_vec(&profile).unwrap()
+}
+
+/// Formats a table of the `top` functions that used the most cycles, including the cycles of the
+/// functions they called.
+fn to_report(samples: &[StackSample], top: usize) -> String {
+    // Cycles spent by each function itself, and including the functions it called.
+    let mut functions = HashMap::<&str, (u64, u64)>::new();
+    for sample in samples {
+        let mut seen = HashSet::new();
+        for fr in &sample.frames {
+            // Count recursive calls once per call stack.
+            if seen.insert(fr.name.as_str()) {
+                functions.entry(&fr.name).or_default().1 += sample.cycles;
+            }
+        }
+        if let Some(fr) = sample.frames.last() {
+            functions.entry(&fr.name).or_default().0 += sample.cycles;
+        }
+    }
+    let total: u64 = samples.iter().map(|sample| sample.cycles).sum();
+    let mut functions: Vec<_> = functions.into_iter().collect();
+    functions.sort_by(|a, b| (b.1 .1, b.1 .0, a.0).cmp(&(a.1 .1, a.1 .0, b.0)));
+
+    let percent = |cycles: u64| 100.0 * cycles as f64 / total.max(1) as f64;
+    let mut output = String::new();
+    writeln!(
+        output,
+        "top {} of {} functions by cycles ({total} cycles in total):",
+        top.min(functions.len()),
+        functions.len()
+    )
+    .unwrap();
+    writeln!(
+        output,
+        "{:>12} {:>7} {:>12} {:>7}  function",
+        "total", "total%", "self", "self%"
+    )
+    .unwrap();
+    for (name, (self_cycles, total_cycles)) in functions.into_iter().take(top) {
+        writeln!(
+            output,
+            "{total_cycles:>12} {:>6.2}% {self_cycles:>12} {:>6.2}%  {name}",
+            percent(total_cycles),
+            percent(self_cycles)
+        )
+        .unwrap();
+    }
+    output
+}
@@ -21,9 +21,10 @@ use bytes::Bytes;
 use risc0_zkvm_platform::{
     fileno,
     syscall::{
+        float,
         nr::{
-            SYS_ARGC, SYS_ARGV, SYS_CYCLE_COUNT, SYS_GETENV, SYS_LOG, SYS_PANIC, SYS_RANDOM,
-            SYS_READ, SYS_VERIFY_INTEGRITY, SYS_WRITE,
+            SYS_ARGC, SYS_ARGV, SYS_CYCLE_COUNT, SYS_FLOAT, SYS_GETENV, SYS_LOG, SYS_PANIC,
+            SYS_RANDOM, SYS_READ, SYS_VERIFY_INTEGRITY, SYS_WRITE,
         },
         reg_abi::{REG_A3, REG_A4, REG_A5},
         SyscallName,
@@ -32,10 +33,13 @@ use risc0_zkvm_platform::{
 };
 
 use crate::{
-    host::client::{
-        env::{AssumptionReceipts, ExecutorEnv},
-        posix_io::PosixIo,
-        slice_io::SliceIo,
+    host::{
+        client::{
+            env::{AssumptionReceipts, ExecutorEnv},
+            posix_io::PosixIo,
+            slice_io::SliceIo,
+        },
+        panic_info::GuestPanic,
     },
     receipt::AssumptionReceipt,
     sha::{Digest, DIGEST_BYTES},
@@ -89,6 +93,7 @@ impl<'a> SyscallTable<'a> {
 
         let posix_io = env.posix_io.clone();
         this.with_syscall(SYS_CYCLE_COUNT, SysCycleCount)
+            .with_syscall(SYS_FLOAT, SysFloat)
             .with_syscall(SYS_LOG, posix_io.clone())
             .with_syscall(SYS_PANIC, SysPanic)
             .with_syscall(SYS_RANDOM, SysRandom)
@@ -137,6 +142,53 @@ impl Syscall for SysCycleCount {
     }
 }
 
+pub(crate) struct SysFloat;
+impl Syscall for SysFloat {
+    fn syscall(
+        &mut self,
+        _syscall: &str,
+        ctx: &mut dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        let op = ctx.load_register(REG_A3);
+        let buf_ptr = ctx.load_register(REG_A4);
+        let buf_words = ctx.load_register(REG_A5);
+        let from_guest = ctx.load_region(buf_ptr, buf_words * WORD_SIZE as u32)?;
+
+        // The guest checks that the result is correctly rounded, which the results of the host's
+        // native floating-point operations are.
+        macro_rules! apply {
+            ($x:expr) => {
+                match (op & !float::F64, $x.as_slice()) {
+                    (float::OP_ADD, [a, b]) => a + b,
+                    (float::OP_MUL, [a, b]) => a * b,
+                    (float::OP_DIV, [a, b]) => a / b,
+                    (float::OP_SQRT, [a]) => a.sqrt(),
+                    (float::OP_FMA, [a, b, c]) => a.mul_add(*b, *c),
+                    _ => bail!(
+                        "Invalid floating-point operation {op} on {} words",
+                        buf_words
+                    ),
+                }
+            };
+        }
+        let result = if op & float::F64 != 0 {
+            let operands: Vec<f64> = from_guest
+                .chunks_exact(2 * WORD_SIZE)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect();
+            apply!(operands).to_bits()
+        } else {
+            let operands: Vec<f32> = from_guest
+                .chunks_exact(WORD_SIZE)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect();
+            apply!(operands).to_bits() as u64
+        };
+        Ok((result as u32, (result >> 32) as u32))
+    }
+}
+
 pub(crate) struct SysGetenv(pub HashMap<String, String>);
 impl Syscall for SysGetenv {
     fn syscall(
@@ -174,7 +226,8 @@ impl Syscall for SysPanic {
         let buf_len = ctx.load_register(REG_A4);
         let from_guest = ctx.load_region(buf_ptr, buf_len)?;
         let msg = from_utf8(&from_guest)?;
-        bail!("Guest panicked: {msg}");
+        // The executor ends the session when it receives this error, and records the panic.
+        Err(GuestPanic::from_message(msg).into())
     }
 }
 
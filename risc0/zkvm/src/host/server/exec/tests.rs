@@ -17,30 +17,40 @@ use std::{
     io::Cursor,
     str::from_utf8,
     sync::Mutex,
+    time::Duration,
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bytes::Bytes;
 use risc0_binfmt::{MemoryImage, Program};
 use risc0_zkvm_methods::{
     multi_test::{MultiTestSpec, SYS_MULTI_TEST, SYS_MULTI_TEST_WORDS},
-    BLST_ELF, HELLO_COMMIT_ELF, MULTI_TEST_ELF, RAND_ELF, SLICE_IO_ELF, STANDARD_LIB_ELF,
+    BLST_ELF, COMMIT_INPUT_ELF, HELLO_COMMIT_ELF, MULTI_TEST_ELF, RAND_ELF, SLICE_IO_ELF,
+    STANDARD_LIB_ELF,
+};
+use risc0_zkvm_platform::{
+    fileno,
+    memory::GUEST_MAX_MEM,
+    syscall::nr::{SYS_FLOAT, SYS_RANDOM},
+    PAGE_SIZE, WORD_SIZE,
 };
-use risc0_zkvm_platform::{fileno, syscall::nr::SYS_RANDOM, PAGE_SIZE, WORD_SIZE};
 use sha2::{Digest as _, Sha256};
+use tempfile::tempdir;
 use test_log::test;
 
 use crate::{
     host::server::{
         exec::{
+            fault::stack_guard,
             profiler::{Frame, Profiler},
-            syscall::{Syscall, SyscallContext},
+            syscall::{SysFloat, Syscall, SyscallContext},
         },
         testutils,
     },
     serde::to_vec,
     sha::{Digest, Digestible},
-    ExecutorEnv, ExecutorImpl, ExitCode,
+    ExecutorEnv, ExecutorImpl, ExitCode, HardwareProfile, MemoryFs, ProfileFormat, ReceiptKind,
+    ResourceLimitExceeded, ResourcePolicy, Session, StepProfile, TrapCause,
 };
 
 fn run_test(spec: MultiTestSpec) {
@@ -716,15 +726,16 @@ mod sys_verify {
             .unwrap();
 
         // Result of execution should be a guest panic resulting from the pruned input.
-        let err = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
             .unwrap()
             .run()
-            .map(|_| ())
-            .unwrap_err();
+            .unwrap();
+        assert_eq!(session.exit_code, ExitCode::Fault);
 
-        tracing::debug!("err: {err}");
-        assert!(err
-            .to_string()
+        let panic = session.panic.unwrap();
+        tracing::debug!("panic: {panic}");
+        assert!(panic
+            .message
             .contains("env::verify_integrity returned error"));
     }
 }
@@ -851,6 +862,134 @@ fn buf_read() {
     assert_eq!(output, input);
 }
 
+#[test]
+fn commit_input() {
+    let run = |args: &[&str]| {
+        let env = ExecutorEnv::builder()
+            .env_var("TEST_MODE", "ARGS")
+            .args(args)
+            .commit_input()
+            .build()
+            .unwrap();
+        let session = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF)
+            .unwrap()
+            .run()
+            .unwrap();
+        for segment in session.segments.iter() {
+            assert_eq!(segment.resolve().unwrap().inner.input_digest, session.input);
+        }
+        session.input
+    };
+
+    // The input digest commits to the arguments and environment variables read by the guest.
+    let input1 = run(&["grep", "foo"]);
+    let input2 = run(&["grep", "bar"]);
+    assert_ne!(input1, Digest::ZERO);
+    assert_ne!(input1, input2);
+    assert_eq!(input1, run(&["grep", "foo"]));
+}
+
+#[test]
+fn commit_input_checked_by_guest() {
+    let run = |input: &[u8], commit_input: bool| {
+        let mut builder = ExecutorEnv::builder();
+        builder.stdin(input);
+        if commit_input {
+            builder.commit_input();
+        } else {
+            builder.input_digest(Digest::from([1u32; 8]));
+        }
+        ExecutorImpl::from_elf(builder.build().unwrap(), COMMIT_INPUT_ELF)
+            .unwrap()
+            .run()
+            .unwrap()
+    };
+
+    for input in [b"hello".as_slice(), b"exit"] {
+        let session = run(input, true);
+        assert_eq!(session.exit_code, ExitCode::Halted(0));
+        assert_eq!(session.journal.unwrap().bytes, input);
+
+        // A guest built with the `commit-input` feature refuses to halt when the input digest
+        // does not match the data it received, whether it returns from main or exits early.
+        let session = run(input, false);
+        assert_eq!(session.exit_code, ExitCode::Fault);
+        assert!(session
+            .panic
+            .unwrap()
+            .message
+            .contains("input digest does not match"));
+    }
+}
+
+#[test]
+fn virtual_fs() {
+    let run = |contents: &'static [u8]| {
+        let mut fs = MemoryFs::new();
+        fs.insert("/data/input.txt", contents);
+        let env = ExecutorEnv::builder()
+            .env_var("TEST_MODE", "FS")
+            .stdin("data/../data/input.txt".as_bytes())
+            .virtual_fs(fs)
+            .build()
+            .unwrap();
+        let session = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF)
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(session.journal.unwrap().bytes, contents);
+        for segment in session.segments.iter() {
+            assert_eq!(segment.resolve().unwrap().inner.input_digest, session.input);
+        }
+        session.vfs_digest.unwrap()
+    };
+
+    // The filesystem digest commits to the contents of the file that was read.
+    let digest1 = run(b"first contents");
+    let digest2 = run(b"other contents");
+    assert_ne!(digest1, digest2);
+    assert_eq!(digest1, run(b"first contents"));
+}
+
+#[test]
+fn virtual_fs_digest_matches_guest() {
+    let mut fs = MemoryFs::new();
+    fs.insert("/input.txt", "contents");
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "FS_DIGEST")
+        .stdin("/input.txt".as_bytes())
+        .virtual_fs(fs)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    let guest_digest: Option<Digest> = session.journal.as_ref().unwrap().decode().unwrap();
+    assert_eq!(guest_digest, session.vfs_digest);
+    assert!(guest_digest.is_some());
+}
+
+#[test]
+fn virtual_fs_with_input_digest() {
+    let mut fs = MemoryFs::new();
+    fs.insert("/input.txt", "contents");
+    let input = Digest::from([1u32; 8]);
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "FS")
+        .stdin("/input.txt".as_bytes())
+        .virtual_fs(fs)
+        .input_digest(input)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    assert_eq!(session.input, input);
+    assert!(session.vfs_digest.is_some());
+}
+
 #[test]
 fn commit_hello_world() {
     ExecutorImpl::from_elf(ExecutorEnv::default(), HELLO_COMMIT_ELF)
@@ -859,19 +998,220 @@ fn commit_hello_world() {
         .unwrap();
 }
 
+fn run_with_policy(elf: &[u8], policy: ResourcePolicy, stdin: &[u8]) -> Result<Session> {
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "STDIO")
+        .stdin(stdin)
+        .resource_policy(policy)
+        .build()
+        .unwrap();
+    ExecutorImpl::from_elf(env, elf).unwrap().run()
+}
+
+#[test]
+fn resource_policy() {
+    // Within every limit.
+    let policy = ResourcePolicy::new()
+        .wall_clock(Duration::from_secs(600))
+        .pages(1 << 16)
+        .journal_bytes(11)
+        .input_bytes(5)
+        .verify_calls(0);
+    run_with_policy(HELLO_COMMIT_ELF, policy.clone(), b"").unwrap();
+    run_with_policy(STANDARD_LIB_ELF, policy, b"12345").unwrap();
+
+    let exceeded = |elf: &[u8], policy: ResourcePolicy, stdin: &[u8]| {
+        run_with_policy(elf, policy, stdin)
+            .err()
+            .unwrap()
+            .downcast::<ResourceLimitExceeded>()
+            .unwrap()
+    };
+    assert_eq!(
+        exceeded(
+            HELLO_COMMIT_ELF,
+            ResourcePolicy::new().journal_bytes(10),
+            b""
+        ),
+        ResourceLimitExceeded::JournalBytes(10)
+    );
+    assert_eq!(
+        exceeded(HELLO_COMMIT_ELF, ResourcePolicy::new().pages(1), b""),
+        ResourceLimitExceeded::Pages(1)
+    );
+    assert_eq!(
+        exceeded(
+            STANDARD_LIB_ELF,
+            ResourcePolicy::new().input_bytes(4),
+            b"12345"
+        ),
+        ResourceLimitExceeded::InputBytes(4)
+    );
+
+    // Files read from the virtual filesystem count as input.
+    let mut fs = MemoryFs::new();
+    fs.insert("/input.txt", vec![0u8; 1024]);
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "FS")
+        .stdin("/input.txt".as_bytes())
+        .virtual_fs(fs)
+        .resource_policy(ResourcePolicy::new().input_bytes(512))
+        .build()
+        .unwrap();
+    let err = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF)
+        .unwrap()
+        .run()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.downcast::<ResourceLimitExceeded>().unwrap(),
+        ResourceLimitExceeded::InputBytes(512)
+    );
+    assert_eq!(
+        exceeded(
+            HELLO_COMMIT_ELF,
+            ResourcePolicy::new().wall_clock(Duration::ZERO),
+            b""
+        ),
+        ResourceLimitExceeded::WallClock(Duration::ZERO)
+    );
+}
+
+#[test]
+fn estimate() {
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::BusyLoop { cycles: 1 << 16 })
+        .unwrap()
+        .segment_limit_po2(14)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    let po2s: Vec<_> = session
+        .segments
+        .iter()
+        .map(|segment| segment.resolve().unwrap().po2())
+        .collect();
+    assert_eq!(session.segment_po2s, po2s);
+    let segments = po2s.len() as u64;
+    assert!(segments > 1);
+
+    let step = |secs, peak_memory, seal_bytes| StepProfile {
+        time: Duration::from_secs(secs),
+        peak_memory,
+        seal_bytes,
+    };
+    let profile = HardwareProfile {
+        // Every segment costs the same, whatever its po2.
+        segments: (0..=14).map(|po2| (po2, step(1, 100, 1000))).collect(),
+        lift: step(2, 500, 200),
+        join: step(3, 50, 200),
+        ..Default::default()
+    };
+    let estimates = session.estimate(&profile).unwrap();
+    assert_eq!(estimates.len(), 2);
+
+    let composite = &estimates[0];
+    assert_eq!(composite.receipt_kind, ReceiptKind::Composite);
+    assert_eq!(composite.proving_time, Duration::from_secs(segments));
+    assert_eq!(composite.peak_memory, 100);
+    assert_eq!(composite.receipt_size, 1000 * segments);
+
+    let succinct = &estimates[1];
+    assert_eq!(succinct.receipt_kind, ReceiptKind::Succinct);
+    assert_eq!(
+        succinct.proving_time,
+        Duration::from_secs(segments * 3 + (segments - 1) * 3)
+    );
+    assert_eq!(succinct.peak_memory, 500);
+    assert_eq!(succinct.receipt_size, 200);
+}
+
+#[test]
+fn threads() {
+    run_test(MultiTestSpec::Threads);
+}
+
+#[test]
+fn soft_float() {
+    run_test(MultiTestSpec::SoftFloat);
+}
+
+#[test]
+fn soft_float_cycles() {
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::SoftFloatCycles)
+        .unwrap()
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    let results: Vec<(u64, u64)> = session.journal.unwrap().decode().unwrap();
+    let ops = ["add", "sub", "mul", "div", "sqrt", "fma"];
+    for (op, (soft, checked)) in ops.iter().zip(&results) {
+        tracing::info!("f64 {op}: {soft} cycles in software, {checked} cycles checked");
+    }
+
+    // Division, square root and fused multiply-add are expensive to emulate, so checking a
+    // result provided by the host must be cheaper than computing it.
+    for (op, (soft, checked)) in ops.iter().zip(&results).skip(3) {
+        assert!(
+            checked < soft,
+            "f64 {op}: {checked} cycles checked is not cheaper than {soft} cycles in software"
+        );
+    }
+}
+
+#[test]
+fn soft_float_incorrect_host() {
+    // Replacement syscall for sys_float that rounds every result in the wrong direction.
+    struct OffByOne;
+    impl Syscall for OffByOne {
+        fn syscall(
+            &mut self,
+            syscall: &str,
+            ctx: &mut dyn SyscallContext,
+            to_guest: &mut [u32],
+        ) -> Result<(u32, u32)> {
+            let (lo, hi) = SysFloat.syscall(syscall, ctx, to_guest)?;
+            Ok((lo ^ 1, hi))
+        }
+    }
+
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::SoftFloat)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut exec = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap();
+    exec.syscall_table.with_syscall(SYS_FLOAT, OffByOne);
+    let panic = exec.run().unwrap().panic.unwrap();
+    assert!(panic
+        .message
+        .contains("host returned an incorrect result for floating-point operation"));
+}
+
 #[test]
 fn random() {
     run_test(MultiTestSpec::DoRandom);
 }
 
 #[test]
-#[should_panic(expected = "WARNING: `getrandom()` called from guest.")]
 fn getrandom_panic() {
     let env = ExecutorEnv::builder().build().unwrap();
-    let _session = ExecutorImpl::from_elf(env, RAND_ELF)
+    let session = ExecutorImpl::from_elf(env, RAND_ELF)
         .unwrap()
         .run()
         .unwrap();
+    assert!(session
+        .panic
+        .unwrap()
+        .message
+        .contains("WARNING: `getrandom()` called from guest."));
 }
 
 #[test]
@@ -892,7 +1232,7 @@ fn slice_io() {
     run(b"0000");
 }
 
-// Check that a compliant host will return an error on panic.
+// Check that a compliant host will end the session with a fault on panic.
 #[test]
 fn panic() {
     let env = ExecutorEnv::builder()
@@ -901,7 +1241,20 @@ fn panic() {
         .build()
         .unwrap();
     let mut exec = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap();
-    let err = exec.run().err().unwrap();
+    let session = exec.run().unwrap();
+    assert_eq!(session.exit_code, ExitCode::Fault);
+    assert!(session.journal.is_none());
+
+    let panic = session.panic.as_ref().unwrap();
+    assert_eq!(panic.message, "MultiTestSpec::Panic invoked");
+    assert!(panic.file.as_ref().unwrap().ends_with("multi_test.rs"));
+    assert!(panic.line.is_some());
+    assert!(panic.backtrace.iter().any(|frame| frame
+        .function
+        .as_ref()
+        .is_some_and(|function| function.contains("multi_test"))));
+
+    let err = session.claim().unwrap_err();
     assert!(err.to_string().contains("MultiTestSpec::Panic invoked"));
 }
 
@@ -913,10 +1266,59 @@ fn fault() {
         .build()
         .unwrap();
     let mut exec = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap();
-    let err = exec.run().err().unwrap();
+    let session = exec.run().unwrap();
+    assert_eq!(session.exit_code, ExitCode::Fault);
+    assert!(session.journal.is_none());
+    assert!(session.panic.is_none());
+
+    let fault = session.fault.as_ref().unwrap();
+    assert!(fault.to_string().contains("StoreAccessFault"));
+    assert_eq!(fault.cause, TrapCause::StoreAccessFault);
+    assert!(fault.last_syscall.is_some());
+    let frame = &fault.backtrace[0];
+    assert_eq!(frame.pc, fault.pc);
+    assert!(frame.function.as_ref().unwrap().contains("multi_test"));
+    assert_eq!(session.post_state.pc, fault.pc);
+
+    let err = session.claim().unwrap_err();
     assert!(err.to_string().contains("StoreAccessFault"));
 }
 
+#[test]
+fn stack_overflow() {
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "STACK_OVERFLOW")
+        .build()
+        .unwrap();
+    let mut exec = ExecutorImpl::from_elf(env, STANDARD_LIB_ELF).unwrap();
+    let session = exec.run().unwrap();
+    assert_eq!(session.exit_code, ExitCode::Fault);
+
+    let fault = session.fault.as_ref().unwrap();
+    assert!(fault.to_string().contains("overflowed its stack"));
+    assert!(matches!(fault.cause, TrapCause::StackOverflow(_)));
+    let frame = &fault.backtrace[0];
+    assert_eq!(frame.pc, fault.pc);
+    assert!(frame.function.as_ref().unwrap().contains("recurse"));
+}
+
+#[test]
+fn stack_overflow_from_image() {
+    // The stack guard is carried in the image, so it is checked without the ELF.
+    let program = Program::load_elf(STANDARD_LIB_ELF, GUEST_MAX_MEM as u32).unwrap();
+    let mut image = MemoryImage::new(&program, PAGE_SIZE as u32).unwrap();
+    image.stack_guard = stack_guard(STANDARD_LIB_ELF);
+    assert!(image.stack_guard.is_some());
+
+    let env = ExecutorEnv::builder()
+        .env_var("TEST_MODE", "STACK_OVERFLOW")
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::new(env, image).unwrap().run().unwrap();
+    let fault = session.fault.unwrap();
+    assert!(matches!(fault.cause, TrapCause::StackOverflow(_)));
+}
+
 #[test]
 fn profiler() {
     let mut profiler = Profiler::new(MULTI_TEST_ELF, Some("multi_test.elf")).unwrap();
@@ -1004,6 +1406,84 @@ fn profiler() {
     assert!(check(&fr, addr), "{fr:#?} {addr}");
 }
 
+#[test]
+fn profiler_formats() {
+    let tmp = tempdir().unwrap();
+    for (name, format) in [
+        ("profile.folded", ProfileFormat::Folded),
+        ("profile.json", ProfileFormat::Speedscope),
+    ] {
+        let path = tmp.path().join(name);
+        let env = ExecutorEnv::builder()
+            .write(&MultiTestSpec::Profiler)
+            .unwrap()
+            .enable_profiler(&path)
+            .build()
+            .unwrap();
+        assert_eq!(ProfileFormat::from_path(&path), format);
+        ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        match format {
+            ProfileFormat::Folded => {
+                assert!(output
+                    .lines()
+                    .any(|line| line.contains("profile_test_func1;profile_test_func2 ")));
+                assert!(output.lines().any(|line| line.contains(";[paging] ")));
+            }
+            _ => {
+                let profile: serde_json::Value = serde_json::from_str(&output).unwrap();
+                let frames = profile["shared"]["frames"].as_array().unwrap();
+                assert!(frames.iter().any(|fr| fr["name"] == "profile_test_func2"));
+                assert!(frames.iter().any(|fr| fr["name"] == "[syscall]"));
+                let weights = profile["profiles"][0]["weights"].as_array().unwrap();
+                let samples = profile["profiles"][0]["samples"].as_array().unwrap();
+                assert_eq!(weights.len(), samples.len());
+            }
+        }
+    }
+}
+
+#[test]
+fn profile_report() {
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::Profiler)
+        .unwrap()
+        .profile_report(1000)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+
+    let report = session.profile_report.unwrap();
+    let mut lines = report.lines();
+    assert!(lines.next().unwrap().starts_with("top "), "{report}");
+    assert!(lines.next().unwrap().ends_with("function"), "{report}");
+    let functions: Vec<_> = lines
+        .map(|line| line.split_whitespace().last().unwrap())
+        .collect();
+    assert!(functions.contains(&"profile_test_func2"), "{report}");
+    assert!(functions.contains(&"[paging]"), "{report}");
+
+    // Only the requested number of functions are reported.
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::Profiler)
+        .unwrap()
+        .profile_report(3)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    assert_eq!(session.profile_report.unwrap().lines().count(), 2 + 3);
+}
+
 #[test]
 fn oom() {
     let env = ExecutorEnv::builder()
@@ -1012,8 +1492,8 @@ fn oom() {
         .build()
         .unwrap();
     let mut exec = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap();
-    let err = exec.run().err().unwrap();
-    assert!(err.to_string().contains("Out of memory"), "{err:?}");
+    let panic = exec.run().unwrap().panic.unwrap();
+    assert!(panic.message.contains("Out of memory"), "{panic:?}");
 }
 
 #[test]
@@ -1027,6 +1507,9 @@ fn memory_access() {
             .build()
             .unwrap();
         let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap().run()?;
+        if let Some(fault) = session.fault {
+            bail!("{fault}");
+        }
         Ok(session.exit_code)
     }
 
@@ -1154,6 +1637,7 @@ mod docker {
                 .run()
                 .unwrap();
         }
+        events.retain(|event| !matches!(event, TraceEvent::Paging { .. }));
         let occurrences = events
             .windows(4)
             .filter_map(|window| {
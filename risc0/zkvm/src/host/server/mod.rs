@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub(crate) mod estimate;
 pub(crate) mod exec;
 #[cfg(feature = "prove")]
 pub(crate) mod prove;
@@ -67,6 +67,7 @@ impl ProverServer for DevModeProver {
         Ok(ProveInfo {
             receipt,
             stats: session.stats(),
+            prover: None,
         })
     }
 
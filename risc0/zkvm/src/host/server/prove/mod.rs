@@ -22,7 +22,7 @@ mod tests;
 use std::rc::Rc;
 
 use anyhow::{anyhow, bail, ensure, Result};
-use risc0_circuit_rv32im::prove::segment_prover;
+use risc0_circuit_rv32im::prove::segment_prover_with_memory_budget;
 use risc0_core::field::baby_bear::{BabyBear, Elem, ExtElem};
 use risc0_zkp::hal::{CircuitHal, Hal};
 
@@ -237,6 +237,6 @@ pub fn get_prover_server(opts: &ProverOpts) -> Result<Rc<dyn ProverServer>> {
         return Ok(Rc::new(DevModeProver));
     }
 
-    let prover = segment_prover(&opts.hashfn)?;
+    let prover = segment_prover_with_memory_budget(&opts.hashfn, opts.memory_budget)?;
     Ok(Rc::new(ProverImpl::new(opts.clone(), prover)))
 }
@@ -54,6 +54,11 @@ impl ProverServer for ProverImpl {
             session.journal.as_ref().map(hex::encode),
             session.segments.len()
         );
+        if !self.opts.prove_guest_errors {
+            session.ensure_provable()?;
+        }
+        let session_claim = session.claim_with_guest_errors()?;
+
         let mut segments = Vec::new();
         for segment_ref in session.segments.iter() {
             let segment = segment_ref.resolve()?;
@@ -111,13 +116,13 @@ impl ProverServer for ProverImpl {
 
         // Verify the receipt to catch if something is broken in the proving process.
         composite_receipt.verify_integrity_with_context(ctx)?;
-        if composite_receipt.claim()?.digest() != session.claim()?.digest() {
+        if composite_receipt.claim()?.digest() != session_claim.digest() {
             tracing::debug!("composite receipt and session claim do not match");
             tracing::debug!("composite receipt claim: {:#?}", composite_receipt.claim()?);
-            tracing::debug!("session claim: {:#?}", session.claim()?);
+            tracing::debug!("session claim: {session_claim:#?}");
             bail!(
                 "session and composite receipt claim do not match: session {}, receipt {}",
-                hex::encode(session.claim()?.digest()),
+                hex::encode(session_claim.digest()),
                 hex::encode(composite_receipt.claim()?.digest())
             );
         }
@@ -147,13 +152,13 @@ impl ProverServer for ProverImpl {
 
         // Verify the receipt to catch if something is broken in the proving process.
         receipt.verify_integrity_with_context(ctx)?;
-        if receipt.claim()?.digest() != session.claim()?.digest() {
+        if receipt.claim()?.digest() != session_claim.digest() {
             tracing::debug!("receipt and session claim do not match");
             tracing::debug!("receipt claim: {:#?}", receipt.claim()?);
-            tracing::debug!("session claim: {:#?}", session.claim()?);
+            tracing::debug!("session claim: {session_claim:#?}");
             bail!(
                 "session and receipt claim do not match: session {}, receipt {}",
-                hex::encode(session.claim()?.digest()),
+                hex::encode(session_claim.digest()),
                 hex::encode(receipt.claim()?.digest())
             );
         }
@@ -161,6 +166,7 @@ impl ProverServer for ProverImpl {
         Ok(ProveInfo {
             receipt,
             stats: session.stats(),
+            prover: None,
         })
     }
 
@@ -83,6 +83,40 @@ fn hashfn_poseidon2() {
 //     prover.prove(env, MULTI_TEST_ELF).unwrap();
 // }
 
+// Proving a po2 20 segment on the CPU with the default options needs more
+// than 16 GiB for its buffers. With a memory budget, the large buffers past the
+// budget are stored in temporary files instead. This takes several minutes and
+// reads the process-wide memory tracker, so run it on its own:
+// `cargo test -F prove -- --ignored prove_po2_20_within_memory_budget`.
+#[test]
+#[ignore]
+fn prove_po2_20_within_memory_budget() {
+    const MEMORY_BUDGET: usize = 8 << 30;
+    const MEMORY_LIMIT: usize = 16 << 30;
+
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::BusyLoop { cycles: 1 << 20 })
+        .unwrap()
+        .segment_limit_po2(20)
+        .build()
+        .unwrap();
+    let session = ExecutorImpl::from_elf(env, MULTI_TEST_ELF)
+        .unwrap()
+        .run()
+        .unwrap();
+    let segment = session.segments[0].resolve().unwrap();
+    assert_eq!(segment.po2(), 20);
+
+    let opts = ProverOpts::default().with_memory_budget(MEMORY_BUDGET);
+    let prover = get_prover_server(&opts).unwrap();
+    risc0_zkp::hal::tracker().lock().unwrap().reset();
+    prover
+        .prove_segment(&VerifierContext::default(), &segment)
+        .unwrap();
+    let peak = risc0_zkp::hal::tracker().lock().unwrap().peak;
+    assert!(peak < MEMORY_LIMIT, "peak buffer memory {peak} bytes");
+}
+
 #[test]
 fn receipt_serde() {
     let receipt = prove_nothing("sha-256").unwrap().receipt;
@@ -476,6 +510,39 @@ fn sys_input() {
     prove_session_fast(&session);
 }
 
+#[test]
+fn prove_fault() {
+    let env = ExecutorEnv::builder()
+        .write(&MultiTestSpec::Fault)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut exec = ExecutorImpl::from_elf(env, MULTI_TEST_ELF).unwrap();
+    let session = exec.run().unwrap();
+    assert_eq!(session.exit_code, ExitCode::Fault);
+
+    let err = get_prover_server(&ProverOpts::fast())
+        .unwrap()
+        .prove_session(&VerifierContext::default(), &session)
+        .unwrap_err();
+    assert!(err.to_string().contains("StoreAccessFault"), "{err}");
+
+    let opts = ProverOpts::fast().with_prove_guest_errors(true);
+    let receipt = get_prover_server(&opts)
+        .unwrap()
+        .prove_session(&VerifierContext::default(), &session)
+        .unwrap()
+        .receipt;
+    receipt
+        .verify_integrity_with_context(&Default::default())
+        .unwrap();
+    let claim = receipt.claim().unwrap();
+    let claim = claim.as_value().unwrap();
+    assert_eq!(claim.exit_code, ExitCode::SystemSplit);
+    assert_eq!(claim.post.as_value().unwrap().pc, session.post_state.pc);
+    assert!(claim.output.as_value().unwrap().is_none());
+}
+
 #[cfg(feature = "docker")]
 #[cfg(target_arch = "x86_64")]
 mod docker {
@@ -628,9 +695,13 @@ mod docker {
 mod sys_verify {
     use std::sync::OnceLock;
 
-    use risc0_zkp::core::{digest::digest, hash::poseidon2::Poseidon2HashSuite};
+    use risc0_zkp::core::{
+        digest::{digest, Digest},
+        hash::poseidon2::Poseidon2HashSuite,
+    };
     use risc0_zkvm_methods::{
-        multi_test::MultiTestSpec, HELLO_COMMIT_ELF, HELLO_COMMIT_ID, MULTI_TEST_ELF, MULTI_TEST_ID,
+        multi_test::MultiTestSpec, AGGREGATE_ELF, AGGREGATE_ID, HELLO_COMMIT_ELF, HELLO_COMMIT_ID,
+        MULTI_TEST_ELF, MULTI_TEST_ID,
     };
     use test_log::test;
 
@@ -640,8 +711,8 @@ mod sys_verify {
         recursion::{prove::zkr, MerkleGroup},
         serde::to_vec,
         sha::Digestible,
-        Assumption, ExecutorEnv, ExecutorEnvBuilder, ExitCode, ProverOpts, Receipt,
-        SuccinctReceipt, RECURSION_PO2,
+        Assumption, ExecutorEnv, ExecutorEnvBuilder, ExitCode, LocalProver, Prover, ProverOpts,
+        Receipt, SuccinctReceipt, RECURSION_PO2,
     };
 
     fn prove_hello_commit() -> Receipt {
@@ -703,6 +774,47 @@ mod sys_verify {
         ONCE.get_or_init(|| prove_hello_commit())
     }
 
+    #[test]
+    fn aggregate() {
+        let opts = ProverOpts::succinct();
+        let prove_succinct = |env, elf| {
+            get_prover_server(&opts)
+                .unwrap()
+                .prove(env, elf)
+                .unwrap()
+                .receipt
+                .inner
+                .succinct()
+                .unwrap()
+                .clone()
+        };
+        let env = ExecutorEnv::builder()
+            .write(&MultiTestSpec::DoNothing)
+            .unwrap()
+            .build()
+            .unwrap();
+        let receipts = [
+            prove_succinct(ExecutorEnv::default(), HELLO_COMMIT_ELF),
+            prove_succinct(env, MULTI_TEST_ELF),
+        ];
+
+        let (aggregate, proofs) = LocalProver::new("local")
+            .aggregate(AGGREGATE_ELF, &receipts, &opts)
+            .unwrap();
+        aggregate.verify(AGGREGATE_ID).unwrap();
+        aggregate.inner.succinct().unwrap();
+
+        // Each claim can be checked against the aggregate on its own.
+        let root = Digest::try_from(aggregate.journal.bytes.as_slice()).unwrap();
+        assert_eq!(proofs.len(), receipts.len());
+        for (receipt, proof) in receipts.iter().zip(&proofs) {
+            proof.verify(&receipt.claim.digest(), &root).unwrap();
+        }
+        assert!(proofs[0]
+            .verify(&receipts[1].claim.digest(), &root)
+            .is_err());
+    }
+
     #[test]
     fn sys_verify_1() {
         let spec = MultiTestSpec::SysVerify(vec![(
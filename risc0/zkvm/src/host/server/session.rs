@@ -17,13 +17,16 @@
 
 use std::{collections::BTreeSet, fs, path::PathBuf};
 
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Result};
 use risc0_binfmt::{MemoryImage, SystemState};
 use risc0_circuit_rv32im::prove::segment::Segment as CircuitSegment;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    host::{client::env::SegmentPath, prove_info::SessionStats},
+    host::{
+        client::env::SegmentPath, panic_info::GuestPanic, prove_info::SessionStats,
+        server::exec::fault::FaultInfo,
+    },
     sha::Digest,
     Assumption, AssumptionReceipt, Assumptions, ExitCode, Journal, MaybePruned, Output,
     ReceiptClaim,
@@ -45,8 +48,8 @@ pub struct PageFaults {
 pub struct Session {
     /// The constituent [Segment]s of the Session. The final [Segment] will have
     /// an [ExitCode] of [Halted](ExitCode::Halted), [Paused](ExitCode::Paused),
-    /// or [SessionLimit](ExitCode::SessionLimit), and all other [Segment]s (if
-    /// any) will have [ExitCode::SystemSplit].
+    /// [SessionLimit](ExitCode::SessionLimit) or [Fault](ExitCode::Fault), and
+    /// all other [Segment]s (if any) will have [ExitCode::SystemSplit].
     pub segments: Vec<Box<dyn SegmentRef>>,
 
     /// The input digest.
@@ -80,6 +83,28 @@ pub struct Session {
 
     /// The system state of the final [MemoryImage] at the end of execution.
     pub post_state: SystemState,
+
+    /// The panic that ended execution with [ExitCode::Fault], if the guest
+    /// panicked.
+    pub panic: Option<GuestPanic>,
+
+    /// The fault that ended execution with [ExitCode::Fault], if the guest
+    /// faulted other than by panicking, e.g. by accessing memory outside of
+    /// its address space.
+    pub fault: Option<FaultInfo>,
+
+    /// The report of the functions that used the most cycles, if requested
+    /// with [crate::ExecutorEnvBuilder::profile_report].
+    pub profile_report: Option<String>,
+
+    /// The digest of the data the guest received from the virtual filesystem,
+    /// or [None] if the guest did not use it. This is the value returned by
+    /// `risc0_zkvm::guest::fs::digest` at the end of execution.
+    pub vfs_digest: Option<Digest>,
+
+    /// The po2 of each segment, recorded by the executor so that estimating
+    /// the cost of proving does not need to resolve the segments.
+    pub(crate) segment_po2s: Vec<usize>,
 }
 
 /// The execution trace of a portion of a program.
@@ -156,7 +181,47 @@ impl Session {
             total_cycles,
             pre_state,
             post_state,
+            panic: None,
+            fault: None,
+            profile_report: None,
+            vfs_digest: None,
+            segment_po2s: Vec::new(),
+        }
+    }
+
+    /// Returns an error if the session ended in a way that cannot be proven,
+    /// such as a guest panic.
+    pub(crate) fn ensure_provable(&self) -> Result<()> {
+        if self.exit_code == ExitCode::Fault {
+            match (&self.panic, &self.fault) {
+                (Some(panic), _) => bail!("cannot prove a session that ended in a fault: {panic}"),
+                (None, Some(fault)) => {
+                    bail!("cannot prove a session that ended in a fault: {fault}")
+                }
+                (None, None) => bail!("cannot prove a session that ended in a fault"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the claim proven by proving this session with
+    /// [ProverOpts::prove_guest_errors](crate::ProverOpts::prove_guest_errors).
+    ///
+    /// The zkVM cannot prove that the guest faulted, so when the session ended
+    /// in a fault, the receipt instead proves the execution up to the
+    /// faulting instruction, ending in [ExitCode::SystemSplit] with no output.
+    /// For any other session, this is the same as [Session::claim].
+    pub(crate) fn claim_with_guest_errors(&self) -> Result<ReceiptClaim> {
+        if self.exit_code != ExitCode::Fault {
+            return self.claim();
         }
+        Ok(ReceiptClaim {
+            pre: self.pre_state.clone().into(),
+            post: self.post_state.clone().into(),
+            exit_code: ExitCode::SystemSplit,
+            input: MaybePruned::Pruned(self.input),
+            output: None.into(),
+        })
     }
 
     /// Add a hook to be called during the proving phase.
@@ -168,6 +233,8 @@ impl Session {
     /// [ReceiptClaim] is the claim that will be proven if this [Session]
     /// is passed to the [crate::Prover].
     pub fn claim(&self) -> Result<ReceiptClaim> {
+        self.ensure_provable()?;
+
         // Construct the Output struct for the session, checking internal consistency.
         // NOTE: The Session output is distinct from the final Segment output because in the
         // Session output any proven assumptions are not included.
@@ -52,6 +52,7 @@
 //! | Feature          | Target(s)         | Implies    | Description                                                                                                                                                  |
 //! | ---------------- | ----------------- | ---------- | ------------------------------------------------------------------------------------------------------------------------------------------------------------ |
 //! | client           | all except rv32im | std        | Enables the client API.                                                                                                                                      |
+//! | commit-input     | rv32im            |            | Makes the guest absorb all data received from the host into the input digest of the receipt. Requires `ExecutorEnvBuilder::commit_input` on the host.        |
 //! | cuda             |                   | prove, std | Enables CUDA GPU acceleration for the prover. Requires CUDA toolkit to be installed.                                                                         |
 //! | disable-dev-mode | all except rv32im |            | Disables dev mode so that proving and verifying may not be faked. Used to prevent a misplaced `RISC0_DEV_MODE` from breaking security in production systems. |
 //! | metal            | macos             | prove, std | Enables Metal GPU acceleration for the prover.                                                                                                               |
@@ -96,11 +97,15 @@ pub use self::receipt_claim::{
 #[cfg(all(not(target_os = "zkvm"), feature = "prove",))]
 pub use {
     self::host::{
-        api::server::Server as ApiServer,
+        api::{
+            daemon::{Daemon as ApiDaemon, DaemonHandle as ApiDaemonHandle},
+            server::Server as ApiServer,
+        },
         client::prove::local::LocalProver,
         recursion::RECURSION_PO2,
         server::{
-            exec::executor::ExecutorImpl,
+            estimate::{CostEstimate, HardwareProfile, StepProfile},
+            exec::{executor::ExecutorImpl, fault::FaultInfo},
             prove::{get_prover_server, HalPair, ProverServer},
             session::{
                 FileSegmentRef, NullSegmentRef, Segment, SegmentRef, Session, SessionEvents,
@@ -108,7 +113,7 @@ pub use {
             },
         },
     },
-    risc0_circuit_rv32im::prove::engine::loader::Loader,
+    risc0_circuit_rv32im::prove::{emu::rv32im::TrapCause, engine::loader::Loader},
     risc0_groth16::{
         docker::stark_to_snark, to_json as seal_to_json, ProofJson as Groth16ProofJson,
     },
@@ -120,11 +125,19 @@ pub use {
             client::Client as ApiClient, Asset, AssetRequest, Connector, SegmentInfo, SessionInfo,
         },
         client::{
-            env::{ExecutorEnv, ExecutorEnvBuilder},
+            env::{
+                ExecutorEnv, ExecutorEnvBuilder, ProfileFormat, ResourceLimitExceeded,
+                ResourcePolicy,
+            },
             prove::{
-                bonsai::BonsaiProver, default_executor, default_prover, external::ExternalProver,
+                bonsai::BonsaiProver,
+                default_executor, default_prover,
+                distributed::DistributedProver,
+                external::ExternalProver,
+                fallback::{FallbackProver, RetryPolicy},
                 Executor, Prover, ProverOpts, ReceiptKind,
             },
+            vfs::{DirectoryFs, FileMetadata, MemoryFs, TarFs, VirtualFs},
         },
     },
     risc0_circuit_rv32im::trace::{TraceCallback, TraceEvent},
@@ -132,6 +145,7 @@ pub use {
 #[cfg(not(target_os = "zkvm"))]
 pub use {
     self::host::{
+        panic_info::{GuestPanic, StackFrame},
         prove_info::{ProveInfo, SessionStats},
         recursion::{ALLOWED_CONTROL_IDS, ALLOWED_CONTROL_ROOT},
     },
@@ -141,10 +155,10 @@ pub use {
 };
 
 pub use receipt::{
-    AssumptionReceipt, CompositeReceipt, CompositeReceiptVerifierParameters, FakeReceipt,
-    InnerAssumptionReceipt, InnerReceipt, Journal, Receipt, ReceiptMetadata, SegmentReceipt,
-    SegmentReceiptVerifierParameters, SuccinctReceipt, SuccinctReceiptVerifierParameters,
-    VerifierContext,
+    AggregationInclusionProof, AggregationSet, AssumptionReceipt, CompositeReceipt,
+    CompositeReceiptVerifierParameters, FakeReceipt, InnerAssumptionReceipt, InnerReceipt, Journal,
+    Receipt, ReceiptMetadata, SegmentReceipt, SegmentReceiptVerifierParameters, SuccinctReceipt,
+    SuccinctReceiptVerifierParameters, SuccinctReceiptVerifierScratch, VerifierContext,
 };
 //#[cfg(any(not(target_os = "zkvm"), feature = "std"))]
 pub use receipt::{Groth16Receipt, Groth16ReceiptVerifierParameters};
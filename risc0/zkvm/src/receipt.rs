@@ -14,6 +14,7 @@
 
 //! Manages the output and cryptographic data for a proven computation.
 
+pub(crate) mod aggregation;
 pub(crate) mod composite;
 pub(crate) mod groth16;
 pub(crate) mod merkle;
@@ -48,9 +49,12 @@ use crate::{
 pub use self::groth16::{Groth16Receipt, Groth16ReceiptVerifierParameters};
 
 pub use self::{
+    aggregation::{AggregationInclusionProof, AggregationSet},
     composite::{CompositeReceipt, CompositeReceiptVerifierParameters},
     segment::{SegmentReceipt, SegmentReceiptVerifierParameters},
-    succinct::{SuccinctReceipt, SuccinctReceiptVerifierParameters},
+    succinct::{
+        SuccinctReceipt, SuccinctReceiptVerifierParameters, SuccinctReceiptVerifierScratch,
+    },
 };
 
 /// A receipt attesting to the execution of a guest program.
@@ -0,0 +1,245 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Commitments to a set of receipt claims, used to aggregate unrelated receipts into one.
+
+use alloc::{vec, vec::Vec};
+use core::iter;
+
+use anyhow::{bail, ensure, Result};
+use risc0_binfmt::tagged_struct;
+use risc0_zkp::{core::digest::Digest, verify::VerificationError};
+use serde::{Deserialize, Serialize};
+
+use crate::sha;
+
+/// An ordered set of claim digests, committed to as the root of a SHA-256 Merkle tree.
+///
+/// An aggregation guest verifies each claim in the set, and commits the [AggregationSet::root] as
+/// its journal. A receipt for that guest then attests to every claim in the set, and an
+/// [AggregationInclusionProof] can be used to check any one claim against the aggregate on its own.
+///
+/// Leaves and internal nodes are hashed with different tags, and the tree is padded up to a power
+/// of two with a tagged empty leaf, so that neither an internal node nor the padding can be passed
+/// off as a claim in the set.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "Vec<Digest>", into = "Vec<Digest>")]
+pub struct AggregationSet {
+    claims: Vec<Digest>,
+    // Nodes of the tree, level by level, from the leaves up to the root.
+    levels: Vec<Vec<Digest>>,
+}
+
+impl AggregationSet {
+    /// Create a new [AggregationSet] from the given claim digests.
+    pub fn new(claims: Vec<Digest>) -> Self {
+        let width = claims.len().next_power_of_two();
+        let mut level: Vec<Digest> = claims
+            .iter()
+            .map(hash_leaf)
+            .chain(iter::repeat(hash_empty()).take(width - claims.len()))
+            .collect();
+        let mut levels = vec![];
+        while level.len() > 1 {
+            let parents = level
+                .chunks_exact(2)
+                .map(|pair| hash_node(&pair[0], &pair[1]))
+                .collect();
+            levels.push(core::mem::replace(&mut level, parents));
+        }
+        levels.push(level);
+        Self { claims, levels }
+    }
+
+    /// Ordered list of claim digests in the set.
+    pub fn claims(&self) -> &[Digest] {
+        &self.claims
+    }
+
+    /// Calculate the root of the [AggregationSet].
+    pub fn root(&self) -> Digest {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Calculate and return an [AggregationInclusionProof] for the given claim digest.
+    /// Will return an error if the given claim is not in the set.
+    pub fn get_proof(&self, claim: &Digest) -> Result<AggregationInclusionProof> {
+        let Some(index) = self.claims.iter().position(|elem| elem == claim) else {
+            bail!("Unable to find {claim:?} in aggregation set");
+        };
+        self.get_proof_by_index(index as u32)
+    }
+
+    /// Calculate and return an [AggregationInclusionProof] for the claim at the given index.
+    /// Will return an error if the given index is out of the range of claims.
+    pub fn get_proof_by_index(&self, index: u32) -> Result<AggregationInclusionProof> {
+        ensure!(
+            (index as usize) < self.claims.len(),
+            "index {index} is out of range for an aggregation set of {} claims",
+            self.claims.len()
+        );
+        let digests = self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(depth, level)| level[(index as usize >> depth) ^ 1])
+            .collect();
+        Ok(AggregationInclusionProof { index, digests })
+    }
+}
+
+impl From<Vec<Digest>> for AggregationSet {
+    fn from(claims: Vec<Digest>) -> Self {
+        Self::new(claims)
+    }
+}
+
+impl From<AggregationSet> for Vec<Digest> {
+    fn from(set: AggregationSet) -> Self {
+        set.claims
+    }
+}
+
+/// An inclusion proof for a claim in an [AggregationSet].
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AggregationInclusionProof {
+    /// Index of the claim for which inclusion is being proven.
+    pub index: u32,
+    /// Sibling digests on the path from the claim to the root.
+    pub digests: Vec<Digest>,
+}
+
+impl AggregationInclusionProof {
+    /// Verify the inclusion proof for the given claim digest against the given root.
+    pub fn verify(&self, claim: &Digest, root: &Digest) -> Result<(), VerificationError> {
+        let received = self.root(claim);
+        if received != *root {
+            return Err(VerificationError::ClaimDigestMismatch {
+                expected: *root,
+                received,
+            });
+        }
+        Ok(())
+    }
+
+    /// Calculate the root of this branch by iteratively hashing, starting from the claim.
+    pub fn root(&self, claim: &Digest) -> Digest {
+        let mut cur = hash_leaf(claim);
+        let mut cur_index = self.index;
+        for sibling in &self.digests {
+            cur = if cur_index & 1 == 0 {
+                hash_node(&cur, sibling)
+            } else {
+                hash_node(sibling, &cur)
+            };
+            cur_index >>= 1;
+        }
+        cur
+    }
+}
+
+fn hash_leaf(claim: &Digest) -> Digest {
+    tagged_struct::<sha::Impl>("risc0.AggregationLeaf", &[*claim], &[])
+}
+
+fn hash_empty() -> Digest {
+    tagged_struct::<sha::Impl>("risc0.AggregationEmpty", &[] as &[Digest], &[])
+}
+
+fn hash_node(left: &Digest, right: &Digest) -> Digest {
+    tagged_struct::<sha::Impl>("risc0.AggregationNode", &[*left, *right], &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(n: u32) -> Vec<Digest> {
+        (0..n)
+            .map(|i| Digest::new([i + 1, 0, 0, 0, 0, 0, 0, i]))
+            .collect()
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for n in [1, 2, 3, 5, 8] {
+            let set = AggregationSet::new(claims(n));
+            let root = set.root();
+            for claim in set.claims() {
+                let proof = set.get_proof(claim).unwrap();
+                assert_eq!(
+                    proof.digests.len(),
+                    n.next_power_of_two().trailing_zeros() as usize
+                );
+                proof.verify(claim, &root).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn proofs_reject_other_claims() {
+        let set = AggregationSet::new(claims(3));
+        let root = set.root();
+        let proof = set.get_proof_by_index(0).unwrap();
+        assert!(proof.verify(&set.claims()[1], &root).is_err());
+        assert!(proof.verify(&Digest::ZERO, &root).is_err());
+        assert!(set.get_proof(&Digest::ZERO).is_err());
+
+        // A set with a different order of claims has a different root.
+        let mut reversed = set.claims().to_vec();
+        reversed.reverse();
+        assert_ne!(AggregationSet::new(reversed).root(), root);
+    }
+
+    #[test]
+    fn proof_index_out_of_range() {
+        let set = AggregationSet::new(claims(3));
+        assert!(set.get_proof_by_index(2).is_ok());
+        assert!(set.get_proof_by_index(3).is_err());
+        assert!(AggregationSet::new(vec![]).get_proof_by_index(0).is_err());
+    }
+
+    #[test]
+    fn nodes_and_padding_are_not_claims() {
+        let set = AggregationSet::new(claims(3));
+        let root = set.root();
+
+        // An internal node, with the path from it to the root.
+        let node = hash_node(&hash_leaf(&set.claims()[0]), &hash_leaf(&set.claims()[1]));
+        let sibling = hash_node(&hash_leaf(&set.claims()[2]), &hash_empty());
+        assert_eq!(hash_node(&node, &sibling), root);
+        let proof = AggregationInclusionProof {
+            index: 0,
+            digests: vec![sibling],
+        };
+        assert!(proof.verify(&node, &root).is_err());
+
+        // The padding leaf, with the path from it to the root.
+        let proof = AggregationInclusionProof {
+            index: 3,
+            digests: vec![hash_leaf(&set.claims()[2]), node],
+        };
+        assert!(proof.verify(&Digest::ZERO, &root).is_err());
+        assert!(proof.verify(&hash_empty(), &root).is_err());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let set = AggregationSet::new(claims(5));
+        let bytes = bincode::serialize(&set).unwrap();
+        let decoded: AggregationSet = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.claims(), set.claims());
+        assert_eq!(decoded.root(), set.root());
+    }
+}
@@ -15,7 +15,7 @@
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use risc0_binfmt::{tagged_struct, Digestible};
 use risc0_circuit_recursion::control_id::{ALLOWED_CONTROL_ROOT, BN254_IDENTITY_CONTROL_ID};
 use risc0_groth16::{fr_from_hex_string, split_digest, Seal, Verifier, VerifyingKey};
@@ -27,7 +27,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     receipt::VerifierContext,
     receipt_claim::{MaybePruned, Unknown},
-    sha,
+    sha, ReceiptClaim,
 };
 
 /// A receipt composed of a Groth16 over the BN_254 curve
@@ -115,6 +115,131 @@ where
     }
 }
 
+// Size in bytes of a word in the Solidity ABI encoding.
+const ABI_WORD_SIZE: usize = 32;
+
+// Size in bytes of the selector prefixed to the seal in ABI calldata.
+const ABI_SELECTOR_SIZE: usize = 4;
+
+impl Groth16Receipt<ReceiptClaim> {
+    /// Encode this receipt as Solidity ABI calldata for an on-chain verifier.
+    ///
+    /// The result is the ABI encoding of `(bytes seal, bytes32 imageId, bytes32 journalDigest)`,
+    /// the arguments to the verifier's `verify` function, without the function selector. The
+    /// encoded `seal` is the Groth16 seal prefixed with a 4-byte selector, taken as the first
+    /// bytes of [Groth16Receipt::verifier_parameters], which the on-chain verifier uses to select
+    /// the matching verifying key.
+    ///
+    /// The claim must be that of a successful execution (i.e. as constructed by
+    /// [ReceiptClaim::ok]), and the image ID and journal digest must not be pruned, since only
+    /// those values are passed on-chain.
+    pub fn to_abi_calldata(&self) -> Result<Vec<u8>> {
+        let claim = self.claim.as_value().map_err(anyhow::Error::msg)?;
+        let image_id = claim.pre.digest::<sha::Impl>();
+        let journal_digest = claim
+            .output
+            .as_value()
+            .map_err(anyhow::Error::msg)?
+            .as_ref()
+            .ok_or_else(|| anyhow!("receipt claim has no output"))?
+            .journal
+            .digest::<sha::Impl>();
+        ensure!(
+            claim.digest::<sha::Impl>()
+                == ReceiptClaim::ok(image_id, MaybePruned::Pruned(journal_digest))
+                    .digest::<sha::Impl>(),
+            "receipt claim is not for a successful execution and cannot be verified on-chain"
+        );
+
+        let seal_len = ABI_SELECTOR_SIZE + self.seal.len();
+        let padded_seal_len = seal_len.div_ceil(ABI_WORD_SIZE) * ABI_WORD_SIZE;
+        let mut calldata = Vec::with_capacity(4 * ABI_WORD_SIZE + padded_seal_len);
+
+        // Head: offset of the seal bytes, followed by the two static arguments.
+        calldata.extend_from_slice(&abi_encode_usize(3 * ABI_WORD_SIZE));
+        calldata.extend_from_slice(image_id.as_bytes());
+        calldata.extend_from_slice(journal_digest.as_bytes());
+
+        // Tail: length of the seal, followed by its zero-padded contents.
+        calldata.extend_from_slice(&abi_encode_usize(seal_len));
+        calldata.extend_from_slice(&self.verifier_parameters.as_bytes()[..ABI_SELECTOR_SIZE]);
+        calldata.extend_from_slice(&self.seal);
+        calldata.resize(4 * ABI_WORD_SIZE + padded_seal_len, 0);
+
+        Ok(calldata)
+    }
+
+    /// Decode a receipt from Solidity ABI calldata, as produced by
+    /// [Groth16Receipt::to_abi_calldata].
+    ///
+    /// The selector on the encoded seal must match the given verifier parameters digest. The
+    /// claim of the returned receipt is reconstructed from the image ID and journal digest, with
+    /// the journal pruned.
+    pub fn from_abi_calldata(calldata: &[u8], verifier_parameters: Digest) -> Result<Self> {
+        let word = |i: usize| -> Result<&[u8]> {
+            calldata
+                .get(i * ABI_WORD_SIZE..(i + 1) * ABI_WORD_SIZE)
+                .ok_or_else(|| anyhow!("ABI calldata is too short"))
+        };
+
+        ensure!(
+            abi_decode_usize(word(0)?)? == 3 * ABI_WORD_SIZE,
+            "ABI calldata has an unexpected offset for the seal"
+        );
+        let image_id = Digest::try_from(word(1)?).map_err(anyhow::Error::msg)?;
+        let journal_digest = Digest::try_from(word(2)?).map_err(anyhow::Error::msg)?;
+        let seal_len = abi_decode_usize(word(3)?)?;
+        ensure!(
+            seal_len >= ABI_SELECTOR_SIZE,
+            "ABI calldata seal is too short to contain a selector"
+        );
+
+        let padded_seal_len = seal_len
+            .checked_next_multiple_of(ABI_WORD_SIZE)
+            .ok_or_else(|| anyhow!("ABI calldata seal length is out of range"))?;
+        ensure!(
+            calldata.len() == 4 * ABI_WORD_SIZE + padded_seal_len,
+            "ABI calldata length does not match the encoded seal length"
+        );
+        let (seal, padding) = calldata[4 * ABI_WORD_SIZE..].split_at(seal_len);
+        ensure!(
+            padding.iter().all(|&b| b == 0),
+            "ABI calldata seal padding is not zero"
+        );
+
+        let (selector, seal) = seal.split_at(ABI_SELECTOR_SIZE);
+        ensure!(
+            selector == &verifier_parameters.as_bytes()[..ABI_SELECTOR_SIZE],
+            "ABI calldata seal selector {} does not match verifier parameters {verifier_parameters}",
+            hex::encode(selector)
+        );
+
+        Ok(Self::new(
+            seal.to_vec(),
+            ReceiptClaim::ok(image_id, MaybePruned::Pruned(journal_digest)).into(),
+            verifier_parameters,
+        ))
+    }
+}
+
+// Encode the given value as a big-endian ABI uint256 word.
+fn abi_encode_usize(value: usize) -> [u8; ABI_WORD_SIZE] {
+    let mut word = [0u8; ABI_WORD_SIZE];
+    word[ABI_WORD_SIZE - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+// Decode a big-endian ABI uint256 word, requiring that it fits in a usize.
+fn abi_decode_usize(word: &[u8]) -> Result<usize> {
+    let (high, low) = word.split_at(ABI_WORD_SIZE - 8);
+    ensure!(
+        high.iter().all(|&b| b == 0),
+        "ABI calldata integer is out of range"
+    );
+    let value = u64::from_be_bytes(low.try_into().map_err(anyhow::Error::msg)?);
+    usize::try_from(value).map_err(anyhow::Error::msg)
+}
+
 /// Verifier parameters used to verify a [Groth16Receipt].
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Groth16ReceiptVerifierParameters {
@@ -155,9 +280,9 @@ impl Default for Groth16ReceiptVerifierParameters {
 
 #[cfg(test)]
 mod tests {
-    use super::Groth16ReceiptVerifierParameters;
-    use crate::sha::Digestible;
-    use risc0_zkp::core::digest::digest;
+    use super::{Groth16Receipt, Groth16ReceiptVerifierParameters};
+    use crate::{sha::Digestible, ReceiptClaim};
+    use risc0_zkp::core::digest::{digest, Digest};
 
     // Check that the verifier parameters has a stable digest (and therefore a stable value). This
     // struct encodes parameters used in verification, and so this value should be updated if and
@@ -170,4 +295,75 @@ mod tests {
             digest!("c9faac19ced5230976543ca0fa51dd224a325cca56b1e1c9512cdf3025d53bb9")
         );
     }
+
+    #[test]
+    fn groth16_receipt_abi_calldata_round_trip() {
+        let image_id = digest!("9cf2d1ffd4a0e4bc7ae9bd6ecb6d3f0e3ac6f8b4ee1b7d6d2b9a0d1c6f0e3a21");
+        let verifier_parameters = Groth16ReceiptVerifierParameters::default().digest();
+        let receipt = Groth16Receipt::new(
+            (0..=255).collect(),
+            ReceiptClaim::ok(image_id, b"hello".to_vec()).into(),
+            verifier_parameters,
+        );
+
+        // Head words: seal offset, image ID, and journal digest. Tail words: seal length, then the
+        // selector and seal, zero-padded to a whole number of words.
+        let expected = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000060",
+            "9cf2d1ffd4a0e4bc7ae9bd6ecb6d3f0e3ac6f8b4ee1b7d6d2b9a0d1c6f0e3a21",
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            "0000000000000000000000000000000000000000000000000000000000000104",
+            "c9faac19000102030405060708090a0b0c0d0e0f101112131415161718191a1b",
+            "1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b",
+            "3c3d3e3f404142434445464748494a4b4c4d4e4f505152535455565758595a5b",
+            "5c5d5e5f606162636465666768696a6b6c6d6e6f707172737475767778797a7b",
+            "7c7d7e7f808182838485868788898a8b8c8d8e8f909192939495969798999a9b",
+            "9c9d9e9fa0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babb",
+            "bcbdbebfc0c1c2c3c4c5c6c7c8c9cacbcccdcecfd0d1d2d3d4d5d6d7d8d9dadb",
+            "dcdddedfe0e1e2e3e4e5e6e7e8e9eaebecedeeeff0f1f2f3f4f5f6f7f8f9fafb",
+            "fcfdfeff00000000000000000000000000000000000000000000000000000000",
+        ))
+        .unwrap();
+
+        let calldata = receipt.to_abi_calldata().unwrap();
+        assert_eq!(hex::encode(&calldata), hex::encode(expected));
+
+        let decoded = Groth16Receipt::from_abi_calldata(&calldata, verifier_parameters).unwrap();
+        assert_eq!(decoded.seal, receipt.seal);
+        assert_eq!(decoded.verifier_parameters, verifier_parameters);
+        assert_eq!(decoded.claim.digest(), receipt.claim.digest());
+        assert_eq!(decoded.to_abi_calldata().unwrap(), calldata);
+    }
+
+    #[test]
+    fn groth16_receipt_abi_calldata_rejects_invalid() {
+        let verifier_parameters = Groth16ReceiptVerifierParameters::default().digest();
+        let receipt = Groth16Receipt::new(
+            vec![7; 256],
+            ReceiptClaim::ok(Digest::ZERO, b"hello".to_vec()).into(),
+            verifier_parameters,
+        );
+        let calldata = receipt.to_abi_calldata().unwrap();
+
+        // Selector for a different set of verifier parameters.
+        assert!(Groth16Receipt::from_abi_calldata(&calldata, Digest::ZERO).is_err());
+
+        // Truncated and non-zero padding.
+        assert!(Groth16Receipt::from_abi_calldata(
+            &calldata[..calldata.len() - 1],
+            verifier_parameters
+        )
+        .is_err());
+        let mut padded = calldata.clone();
+        *padded.last_mut().unwrap() = 1;
+        assert!(Groth16Receipt::from_abi_calldata(&padded, verifier_parameters).is_err());
+
+        // Claims other than a successful execution cannot be verified on-chain.
+        let paused = Groth16Receipt::new(
+            vec![7; 256],
+            ReceiptClaim::paused(Digest::ZERO, b"hello".to_vec()).into(),
+            verifier_parameters,
+        );
+        assert!(paused.to_abi_calldata().is_err());
+    }
 }
@@ -30,7 +30,9 @@ use alloc::vec::Vec;
 
 use anyhow::{ensure, Result};
 use risc0_core::field::baby_bear::BabyBear;
-use risc0_zkp::core::{digest::Digest, hash::HashFn};
+#[cfg(feature = "prove")]
+use risc0_zkp::core::hash::HashFn;
+use risc0_zkp::core::{digest::Digest, hash::VerifyHashFn};
 use serde::{Deserialize, Serialize};
 
 /// Merkle tree implementation used in the recursion system to commit to a set of recursion
@@ -136,11 +138,11 @@ impl MerkleGroup {
 
 impl MerkleProof {
     /// Verify the Merkle inclusion proof against the given leaf and root.
-    pub fn verify(
+    pub fn verify<H: VerifyHashFn<BabyBear> + ?Sized>(
         &self,
         leaf: &Digest,
         root: &Digest,
-        hashfn: &dyn HashFn<BabyBear>,
+        hashfn: &H,
     ) -> Result<()> {
         ensure!(
             self.root(leaf, hashfn) == *root,
@@ -150,14 +152,14 @@ impl MerkleProof {
     }
 
     /// Calculate the root of this branch by iteratively hashing, starting from the leaf.
-    pub fn root(&self, leaf: &Digest, hashfn: &dyn HashFn<BabyBear>) -> Digest {
+    pub fn root<H: VerifyHashFn<BabyBear> + ?Sized>(&self, leaf: &Digest, hashfn: &H) -> Digest {
         let mut cur = *leaf;
         let mut cur_index = self.index;
         for sibling in &self.digests {
             cur = if cur_index & 1 == 0 {
-                *hashfn.hash_pair(&cur, sibling)
+                hashfn.hash_pair(&cur, sibling)
             } else {
-                *hashfn.hash_pair(sibling, &cur)
+                hashfn.hash_pair(sibling, &cur)
             };
             cur_index >>= 1;
         }
@@ -12,16 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::{collections::VecDeque, string::String, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 use core::fmt::Debug;
 
-use risc0_binfmt::{read_sha_halfs, tagged_struct, Digestible};
-use risc0_circuit_recursion::{control_id::ALLOWED_CONTROL_ROOT, CircuitImpl, CIRCUIT};
-use risc0_core::field::baby_bear::BabyBearElem;
+use risc0_binfmt::{tagged_struct, Digestible};
+use risc0_circuit_recursion::{
+    control_id::ALLOWED_CONTROL_ROOT, CircuitImpl, CIRCUIT, SCRATCH_SIZE,
+};
+use risc0_core::field::{
+    baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
+    Elem,
+};
 use risc0_zkp::{
-    adapter::{CircuitInfo, ProtocolInfo, PROOF_SYSTEM_INFO},
-    core::{digest::Digest, hash::sha::Sha256},
-    verify::VerificationError,
+    adapter::{CircuitInfo, MixState, ProtocolInfo, PROOF_SYSTEM_INFO},
+    core::{
+        digest::Digest,
+        hash::{
+            poseidon2::{Poseidon2HashFn, Poseidon2Rng},
+            sha::Sha256,
+            HashSuite, VerifyHashFn,
+        },
+    },
+    verify::{VerificationError, VerifierScratch},
 };
 use serde::{Deserialize, Serialize};
 
@@ -93,66 +105,203 @@ where
             .succinct_verifier_parameters
             .as_ref()
             .ok_or(VerificationError::VerifierParametersMissing)?;
-
-        // Check that the proof system and circuit info strings match what is implemented by this
-        // function. Info strings are used a version identifiers, and this verify implementation
-        // supports exactly one proof systema and circuit version at a time.
-        if params.proof_system_info != PROOF_SYSTEM_INFO {
-            return Err(VerificationError::ProofSystemInfoMismatch {
-                expected: PROOF_SYSTEM_INFO,
-                received: params.proof_system_info,
-            });
-        }
-        if params.circuit_info != CircuitImpl::CIRCUIT_INFO {
-            return Err(VerificationError::CircuitInfoMismatch {
-                expected: CircuitImpl::CIRCUIT_INFO,
-                received: params.circuit_info,
-            });
-        }
+        check_verifier_parameters(params)?;
 
         let suite = ctx
             .suites
             .get(&self.hashfn)
             .ok_or(VerificationError::InvalidHashSuite)?;
+        self.verify_with_suite(params, suite, None)
+    }
+
+    /// Verify the integrity of each of the given receipts, returning a result for each, in order.
+    ///
+    /// Results are the same as calling [SuccinctReceipt::verify_integrity_with_context] on each
+    /// receipt. The verifier parameters are checked once, control inclusion proofs shared by
+    /// multiple receipts are only checked once, and, with the `client` feature enabled, seals are
+    /// verified in parallel.
+    pub fn verify_batch(
+        receipts: &[Self],
+        ctx: &VerifierContext,
+    ) -> Vec<Result<(), VerificationError>>
+    where
+        Claim: Sync,
+    {
+        let params = match ctx.succinct_verifier_parameters.as_ref() {
+            Some(params) => params,
+            None => {
+                return receipts
+                    .iter()
+                    .map(|_| Err(VerificationError::VerifierParametersMissing))
+                    .collect()
+            }
+        };
+        if check_verifier_parameters(params).is_err() {
+            return receipts
+                .iter()
+                .map(|_| check_verifier_parameters(params))
+                .collect();
+        }
+
+        // Check each distinct control inclusion proof once. Receipts from the same prover will
+        // almost always share a small number of proofs.
+        let mut inclusion_results = BTreeMap::new();
+        for receipt in receipts {
+            let Some(suite) = ctx.suites.get(&receipt.hashfn) else {
+                continue;
+            };
+            inclusion_results
+                .entry(receipt.control_inclusion_key())
+                .or_insert_with(|| {
+                    receipt
+                        .verify_control_inclusion(
+                            &receipt.control_id,
+                            params,
+                            suite.hashfn.as_ref(),
+                        )
+                        .is_ok()
+                });
+        }
 
-        let check_code = |_, control_id: &Digest| -> Result<(), VerificationError> {
-            self.control_inclusion_proof
-                .verify(control_id, &params.control_root, suite.hashfn.as_ref())
-                .map_err(|_| {
-                    tracing::debug!(
-                        "failed to verify control inclusion proof for {control_id} against root {} with {}",
-                        params.control_root,
-                        suite.name,
-                    );
-                    VerificationError::ControlVerificationError {
-                        control_id: *control_id,
-                    }
+        let verify = |receipt: &Self, suite: Option<&HashSuite<BabyBear>>| {
+            let suite = suite.ok_or(VerificationError::InvalidHashSuite)?;
+            let included = inclusion_results
+                .get(&receipt.control_inclusion_key())
+                .copied();
+            receipt.verify_with_suite(params, suite, included)
+        };
+
+        #[cfg(feature = "client")]
+        {
+            use rayon::prelude::*;
+
+            receipts
+                .par_iter()
+                .map(|receipt| verify(receipt, ctx.suites.get(&receipt.hashfn)))
+                .collect()
+        }
+        #[cfg(not(feature = "client"))]
+        receipts
+            .iter()
+            .map(|receipt| verify(receipt, ctx.suites.get(&receipt.hashfn)))
+            .collect()
+    }
+
+    // Verify the seal with the given hash suite, then check its outputs. If the result of checking
+    // the control inclusion proof for self.control_id is already known, it can be provided.
+    fn verify_with_suite(
+        &self,
+        params: &SuccinctReceiptVerifierParameters,
+        suite: &HashSuite<BabyBear>,
+        control_id_included: Option<bool>,
+    ) -> Result<(), VerificationError> {
+        let check_code = |_, control_id: &Digest| match control_id_included {
+            Some(true) if *control_id == self.control_id => Ok(()),
+            Some(false) if *control_id == self.control_id => {
+                Err(VerificationError::ControlVerificationError {
+                    control_id: *control_id,
                 })
+            }
+            _ => self.verify_control_inclusion(control_id, params, suite.hashfn.as_ref()),
         };
 
         // Verify the receipt itself is correct, and therefore the encoded globals are
         // reliable.
         risc0_zkp::verify::verify(&CIRCUIT, suite, &self.seal, check_code)?;
 
+        self.verify_output(params)
+    }
+
+    fn control_inclusion_key(&self) -> (&str, Digest, u32, &[Digest]) {
+        (
+            &self.hashfn,
+            self.control_id,
+            self.control_inclusion_proof.index,
+            &self.control_inclusion_proof.digests,
+        )
+    }
+
+    /// Verify the integrity of this receipt, ensuring the claim is attested to by the seal,
+    /// without allocating.
+    ///
+    /// All intermediate values are held in the given scratch memory, so verification makes no heap
+    /// allocations. Note that this crate still links `alloc` for its other APIs, so the target must
+    /// provide a global allocator, although it is never called here. Only receipts using the
+    /// "poseidon2" hash function are supported.
+    pub fn verify_integrity_with_scratch(
+        &self,
+        params: &SuccinctReceiptVerifierParameters,
+        scratch: &mut SuccinctReceiptVerifierScratch,
+    ) -> Result<(), VerificationError> {
+        check_verifier_parameters(params)?;
+
+        if self.hashfn != "poseidon2" {
+            return Err(VerificationError::InvalidHashSuite);
+        }
+        let hashfn = &Poseidon2HashFn;
+        let check_code =
+            |_, control_id: &Digest| self.verify_control_inclusion(control_id, params, hashfn);
+
+        let scratch = VerifierScratch::new(
+            &mut scratch.elems,
+            &mut scratch.exts,
+            &mut scratch.mix_states,
+        );
+        risc0_zkp::verify::verify_with_scratch(
+            &CIRCUIT,
+            hashfn,
+            Poseidon2Rng::new(),
+            &self.seal,
+            check_code,
+            scratch,
+        )?;
+
+        self.verify_output(params)
+    }
+
+    fn verify_control_inclusion<H: VerifyHashFn<BabyBear> + ?Sized>(
+        &self,
+        control_id: &Digest,
+        params: &SuccinctReceiptVerifierParameters,
+        hashfn: &H,
+    ) -> Result<(), VerificationError> {
+        if self.control_inclusion_proof.root(control_id, hashfn) != params.control_root {
+            tracing::debug!(
+                "failed to verify control inclusion proof for {control_id} against root {} with {}",
+                params.control_root,
+                self.hashfn,
+            );
+            return Err(VerificationError::ControlVerificationError {
+                control_id: *control_id,
+            });
+        }
+        Ok(())
+    }
+
+    // Check the globals written by the recursion program, which is the control root and the claim
+    // digest, against the expected values. Should only be called on a verified seal.
+    fn verify_output(
+        &self,
+        params: &SuccinctReceiptVerifierParameters,
+    ) -> Result<(), VerificationError> {
         // Extract the globals from the seal
         let output_elems: &[BabyBearElem] =
             bytemuck::checked::cast_slice(&self.seal[..CircuitImpl::OUTPUT_SIZE]);
-        let mut seal_claim = VecDeque::new();
-        for elem in output_elems {
-            seal_claim.push_back(elem.as_u32())
-        }
+        let mut seal_claim = output_elems.iter().map(|elem| elem.as_u32());
 
         // Read the Poseidon2 control root digest from the first 16 words of the output.
         // NOTE: Implemented recursion programs have two output slots, each of size 16 elems.
         // A SHA2 digest is encoded as 16 half words. Poseidon digests are encoded in 8 elems,
         // but are interspersed with padding to fill out the whole 16 elems.
-        let control_root: Digest = seal_claim
-            .drain(0..16)
-            .enumerate()
-            .filter_map(|(i, word)| (i & 1 == 0).then_some(word))
-            .collect::<Vec<_>>()
-            .try_into()
-            .map_err(|_| VerificationError::ReceiptFormatError)?;
+        let mut control_root = Digest::ZERO;
+        for word in control_root.as_mut_words() {
+            *word = seal_claim
+                .next()
+                .ok_or(VerificationError::ReceiptFormatError)?;
+            seal_claim
+                .next()
+                .ok_or(VerificationError::ReceiptFormatError)?;
+        }
 
         if control_root != params.inner_control_root.unwrap_or(params.control_root) {
             tracing::debug!(
@@ -166,8 +315,16 @@ where
         }
 
         // Verify the output hash matches that data
-        let output_hash =
-            read_sha_halfs(&mut seal_claim).map_err(|_| VerificationError::ReceiptFormatError)?;
+        let mut output_hash = Digest::ZERO;
+        for bytes in output_hash.as_mut_bytes().chunks_exact_mut(2) {
+            let half = seal_claim
+                .next()
+                .ok_or(VerificationError::ReceiptFormatError)?;
+            bytes[0] = half as u8;
+            bytes[1] = (half >> 8)
+                .try_into()
+                .map_err(|_| VerificationError::ReceiptFormatError)?;
+        }
         if output_hash != self.claim.digest::<sha::Impl>() {
             tracing::debug!(
                 "succinct receipt claim does not match the output digest: claim: {:#?}, digest expected: {output_hash:?}",
@@ -207,6 +364,58 @@ where
     }
 }
 
+// Check that the proof system and circuit info strings match what is implemented by this
+// verifier. Info strings are used a version identifiers, and this verify implementation supports
+// exactly one proof systema and circuit version at a time.
+fn check_verifier_parameters(
+    params: &SuccinctReceiptVerifierParameters,
+) -> Result<(), VerificationError> {
+    if params.proof_system_info != PROOF_SYSTEM_INFO {
+        return Err(VerificationError::ProofSystemInfoMismatch {
+            expected: PROOF_SYSTEM_INFO,
+            received: params.proof_system_info,
+        });
+    }
+    if params.circuit_info != CircuitImpl::CIRCUIT_INFO {
+        return Err(VerificationError::CircuitInfoMismatch {
+            expected: CircuitImpl::CIRCUIT_INFO,
+            received: params.circuit_info,
+        });
+    }
+    Ok(())
+}
+
+/// Fixed-size memory used to verify a [SuccinctReceipt] without an allocator.
+///
+/// See [SuccinctReceipt::verify_integrity_with_scratch]. This struct is large (a few hundred
+/// KiB), so on constrained targets it is best placed in static memory rather than on the stack.
+pub struct SuccinctReceiptVerifierScratch {
+    elems: [BabyBearElem; SCRATCH_SIZE.elems],
+    exts: [BabyBearExtElem; SCRATCH_SIZE.exts],
+    mix_states: [MixState<BabyBearExtElem>; SCRATCH_SIZE.mix_states],
+}
+
+impl SuccinctReceiptVerifierScratch {
+    /// Construct a new [SuccinctReceiptVerifierScratch].
+    pub const fn new() -> Self {
+        const ZERO_MIX: MixState<BabyBearExtElem> = MixState {
+            tot: BabyBearExtElem::ZERO,
+            mul: BabyBearExtElem::ZERO,
+        };
+        Self {
+            elems: [BabyBearElem::ZERO; SCRATCH_SIZE.elems],
+            exts: [BabyBearExtElem::ZERO; SCRATCH_SIZE.exts],
+            mix_states: [ZERO_MIX; SCRATCH_SIZE.mix_states],
+        }
+    }
+}
+
+impl Default for SuccinctReceiptVerifierScratch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Verifier parameters used to verify a [SuccinctReceipt].
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct SuccinctReceiptVerifierParameters {
@@ -254,9 +463,28 @@ impl Default for SuccinctReceiptVerifierParameters {
 
 #[cfg(test)]
 mod tests {
-    use super::SuccinctReceiptVerifierParameters;
+    use super::{SuccinctReceiptVerifierParameters, SuccinctReceiptVerifierScratch};
     use crate::sha::Digestible;
-    use risc0_zkp::core::digest::digest;
+    use risc0_circuit_recursion::{CIRCUIT, SCRATCH_SIZE};
+    use risc0_zkp::{core::digest::digest, verify::ScratchSize};
+
+    // Memory budget for verifying a succinct receipt without an allocator. Embedded targets size
+    // their static memory around this, so it should only grow deliberately.
+    const SCRATCH_BUDGET_BYTES: usize = 256 * 1024;
+
+    #[test]
+    fn succinct_receipt_verifier_scratch_fits_circuit() {
+        assert_eq!(ScratchSize::new(&CIRCUIT), SCRATCH_SIZE);
+    }
+
+    #[test]
+    fn succinct_receipt_verifier_scratch_within_budget() {
+        let size = core::mem::size_of::<SuccinctReceiptVerifierScratch>();
+        assert!(
+            size <= SCRATCH_BUDGET_BYTES,
+            "scratch is {size} bytes; budget is {SCRATCH_BUDGET_BYTES} bytes"
+        );
+    }
 
     // Check that the verifier parameters has a stable digest (and therefore a stable value). This
     // struct encodes parameters used in verification, and so this value should be updated if and
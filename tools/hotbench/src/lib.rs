@@ -90,6 +90,14 @@ impl BenchGroup {
         }
     }
 
+    pub fn data(&self) -> &[BenchmarkData] {
+        &self.data
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.opts.target_dir.join(&self.name)
+    }
+
     fn filter_matches(&self, name: &str) -> bool {
         let filter = self.opts.filter.clone().unwrap_or_default();
         name.contains(&filter)